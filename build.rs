@@ -0,0 +1,1282 @@
+//! Generates `$OUT_DIR/messages.rs` from the Kafka message schemas vendored
+//! in `schema/`: a module per request and response, holding a struct for the
+//! message and each structure nested in it, decoded and encoded in every
+//! valid version with the helpers of `protocol::primitives`.
+
+use std::{collections::HashSet, env, fmt::Write, fs, path::Path};
+
+const SCHEMA_DIR: &str = "schema";
+
+fn main() {
+    println!("cargo:rerun-if-changed={}", SCHEMA_DIR);
+
+    let mut paths = fs::read_dir(SCHEMA_DIR)
+        .expect("failed to read the schema directory")
+        .map(|entry| entry.expect("failed to read a schema entry").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    let mut out =
+        String::from("// Generated by build.rs from the schemas in schema/. Do not edit.\n");
+    let mut flexible_versions = Vec::new();
+    for path in paths {
+        let text = fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("failed to read {}: {}", path.display(), e));
+        let json = Parser::new(&text)
+            .parse_document()
+            .unwrap_or_else(|e| panic!("failed to parse {}: {}", path.display(), e));
+        let message = Message::from_json(&json)
+            .unwrap_or_else(|e| panic!("invalid schema {}: {}", path.display(), e));
+        message.generate(&mut out);
+        if let (Some(api), Some(_)) = (message.name.strip_suffix("Request"), message.api_key) {
+            flexible_versions.push((api.to_string(), message.flexible_versions));
+        }
+    }
+    generate_first_flexible_version(&mut out, &flexible_versions);
+
+    let dest = Path::new(&env::var("OUT_DIR").expect("OUT_DIR is not set")).join("messages.rs");
+    fs::write(&dest, out).unwrap_or_else(|e| panic!("failed to write {}: {}", dest.display(), e));
+}
+
+/// Generates `ApiKey::first_flexible_version` from the flexible versions of
+/// each request, naming the `ApiKey` variant after the request.
+fn generate_first_flexible_version(out: &mut String, flexible_versions: &[(String, Versions)]) {
+    let _ = writeln!(
+        out,
+        "\nimpl super::primitives::ApiKey {{\n    /// The first version of this API using compact strings and arrays and\n    /// carrying tagged fields.\n    pub(crate) fn first_flexible_version(&self) -> i16 {{\n        match self {{"
+    );
+    for (api, versions) in flexible_versions {
+        // An API that is never flexible gets a version no client sends.
+        let first = if versions.is_empty() {
+            i16::MAX
+        } else {
+            versions.min
+        };
+        let _ = writeln!(out, "            Self::{} => {},", api, first);
+    }
+    let _ = writeln!(out, "        }}\n    }}\n}}");
+}
+
+/// A JSON value. Numbers are kept as written since they only end up in
+/// generated source.
+#[derive(Debug)]
+enum Json {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// A scalar as the text the schema means by it, e.g. a default of `false`
+    /// or `"false"`.
+    fn as_scalar(&self) -> Option<String> {
+        match self {
+            Json::Null => Some("null".to_string()),
+            Json::Bool(b) => Some(b.to_string()),
+            Json::Number(n) => Some(n.clone()),
+            Json::String(s) => Some(s.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// Parses the JSON the Kafka schemas are written in, which allows `//` line
+/// comments.
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Self {
+        Self {
+            input: input.as_bytes(),
+            pos: 0,
+        }
+    }
+
+    fn parse_document(&mut self) -> Result<Json, String> {
+        let value = self.parse_value()?;
+        self.skip_whitespace();
+        if self.pos != self.input.len() {
+            return Err(format!("trailing characters at byte {}", self.pos));
+        }
+
+        Ok(value)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.input.len() {
+            match self.input[self.pos] {
+                b' ' | b'\t' | b'\r' | b'\n' => self.pos += 1,
+                b'/' if self.input.get(self.pos + 1) == Some(&b'/') => {
+                    while self.pos < self.input.len() && self.input[self.pos] != b'\n' {
+                        self.pos += 1;
+                    }
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.input.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), String> {
+        if self.peek() != Some(byte) {
+            return Err(format!("expected '{}' at byte {}", byte as char, self.pos));
+        }
+        self.pos += 1;
+
+        Ok(())
+    }
+
+    fn parse_value(&mut self) -> Result<Json, String> {
+        match self.peek() {
+            Some(b'{') => self.parse_object(),
+            Some(b'[') => self.parse_array(),
+            Some(b'"') => self.parse_string().map(Json::String),
+            Some(b't') => self.parse_literal("true", Json::Bool(true)),
+            Some(b'f') => self.parse_literal("false", Json::Bool(false)),
+            Some(b'n') => self.parse_literal("null", Json::Null),
+            Some(b'-' | b'0'..=b'9') => {
+                let start = self.pos;
+                while self.pos < self.input.len()
+                    && matches!(
+                        self.input[self.pos],
+                        b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9'
+                    )
+                {
+                    self.pos += 1;
+                }
+                Ok(Json::Number(
+                    String::from_utf8_lossy(&self.input[start..self.pos]).into_owned(),
+                ))
+            }
+            _ => Err(format!("unexpected character at byte {}", self.pos)),
+        }
+    }
+
+    fn parse_literal(&mut self, literal: &str, value: Json) -> Result<Json, String> {
+        if !self.input[self.pos..].starts_with(literal.as_bytes()) {
+            return Err(format!("expected {} at byte {}", literal, self.pos));
+        }
+        self.pos += literal.len();
+
+        Ok(value)
+    }
+
+    fn parse_object(&mut self) -> Result<Json, String> {
+        self.expect(b'{')?;
+        let mut members = Vec::new();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return Ok(Json::Object(members));
+        }
+        loop {
+            if self.peek() != Some(b'"') {
+                return Err(format!("expected a key at byte {}", self.pos));
+            }
+            let key = self.parse_string()?;
+            self.expect(b':')?;
+            members.push((key, self.parse_value()?));
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(Json::Object(members));
+                }
+                _ => return Err(format!("expected ',' or '}}' at byte {}", self.pos)),
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<Json, String> {
+        self.expect(b'[')?;
+        let mut items = Vec::new();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(Json::Array(items));
+        }
+        loop {
+            items.push(self.parse_value()?);
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(Json::Array(items));
+                }
+                _ => return Err(format!("expected ',' or ']' at byte {}", self.pos)),
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        self.expect(b'"')?;
+        let mut bytes = Vec::new();
+        loop {
+            let byte = *self
+                .input
+                .get(self.pos)
+                .ok_or_else(|| "unterminated string".to_string())?;
+            self.pos += 1;
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let escaped = *self
+                        .input
+                        .get(self.pos)
+                        .ok_or_else(|| "unterminated escape".to_string())?;
+                    self.pos += 1;
+                    match escaped {
+                        b'n' => bytes.push(b'\n'),
+                        b't' => bytes.push(b'\t'),
+                        b'r' => bytes.push(b'\r'),
+                        b'u' => {
+                            let hex = self
+                                .input
+                                .get(self.pos..self.pos + 4)
+                                .ok_or_else(|| "truncated \\u escape".to_string())?;
+                            let code = u32::from_str_radix(&String::from_utf8_lossy(hex), 16)
+                                .map_err(|e| format!("invalid \\u escape: {}", e))?;
+                            let c = char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER);
+                            bytes.extend_from_slice(c.to_string().as_bytes());
+                            self.pos += 4;
+                        }
+                        other => bytes.push(other),
+                    }
+                }
+                other => bytes.push(other),
+            }
+        }
+
+        String::from_utf8(bytes).map_err(|e| format!("invalid UTF-8 in string: {}", e))
+    }
+}
+
+/// An inclusive range of versions; `min > max` when empty.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Versions {
+    min: i16,
+    max: i16,
+}
+
+impl Versions {
+    const NONE: Versions = Versions { min: 0, max: -1 };
+
+    /// Parses `"none"`, `"3"`, `"3+"` or `"3-7"`.
+    fn parse(spec: &str) -> Result<Self, String> {
+        let spec = spec.trim();
+        let number = |s: &str| {
+            s.trim()
+                .parse::<i16>()
+                .map_err(|e| format!("invalid version {:?}: {}", spec, e))
+        };
+        if spec == "none" {
+            Ok(Self::NONE)
+        } else if let Some(min) = spec.strip_suffix('+') {
+            Ok(Versions {
+                min: number(min)?,
+                max: i16::MAX,
+            })
+        } else if let Some((min, max)) = spec.split_once('-') {
+            Ok(Versions {
+                min: number(min)?,
+                max: number(max)?,
+            })
+        } else {
+            let version = number(spec)?;
+            Ok(Versions {
+                min: version,
+                max: version,
+            })
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.min > self.max
+    }
+
+    fn intersect(&self, other: Versions) -> Versions {
+        Versions {
+            min: self.min.max(other.min),
+            max: self.max.min(other.max),
+        }
+    }
+
+    /// The condition on `version` that holds for these versions, among the
+    /// `within` ones; `None` when it always holds.
+    fn condition(&self, within: Versions) -> Option<String> {
+        let versions = self.intersect(within);
+        if versions.is_empty() {
+            Some("false".to_string())
+        } else if versions == within {
+            None
+        } else if versions.min == versions.max {
+            Some(format!("version == {}", versions.min))
+        } else if versions.max >= within.max {
+            Some(format!("version >= {}", versions.min))
+        } else if versions.min <= within.min {
+            Some(format!("version <= {}", versions.max))
+        } else {
+            Some(format!(
+                "({}..={}).contains(&version)",
+                versions.min, versions.max
+            ))
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Type {
+    Bool,
+    Int8,
+    Int16,
+    Int32,
+    Int64,
+    Uint16,
+    Uint32,
+    Float64,
+    Uuid,
+    String,
+    Bytes,
+    Records,
+    Struct(String),
+    Array(Box<Type>),
+}
+
+impl Type {
+    fn parse(name: &str) -> Type {
+        match name {
+            "bool" => Type::Bool,
+            "int8" => Type::Int8,
+            "int16" => Type::Int16,
+            "int32" => Type::Int32,
+            "int64" => Type::Int64,
+            "uint16" => Type::Uint16,
+            "uint32" => Type::Uint32,
+            "float64" => Type::Float64,
+            "uuid" => Type::Uuid,
+            "string" => Type::String,
+            "bytes" => Type::Bytes,
+            "records" => Type::Records,
+            _ => match name.strip_prefix("[]") {
+                Some(element) => Type::Array(Box::new(Type::parse(element))),
+                None => Type::Struct(name.to_string()),
+            },
+        }
+    }
+
+    fn rust(&self) -> String {
+        match self {
+            Type::Bool => "bool".to_string(),
+            Type::Int8 => "i8".to_string(),
+            Type::Int16 => "i16".to_string(),
+            Type::Int32 => "i32".to_string(),
+            Type::Int64 => "i64".to_string(),
+            Type::Uint16 => "u16".to_string(),
+            Type::Uint32 => "u32".to_string(),
+            Type::Float64 => "f64".to_string(),
+            Type::Uuid => "Uuid".to_string(),
+            Type::String => "String".to_string(),
+            Type::Bytes | Type::Records => "Bytes".to_string(),
+            Type::Struct(name) => name.clone(),
+            Type::Array(element) => format!("Vec<{}>", element.rust()),
+        }
+    }
+
+    /// The `Buf::try_get_*` and `BufMut::put_*` suffix of a number.
+    fn number_suffix(&self) -> Option<&'static str> {
+        match self {
+            Type::Int8 => Some("i8"),
+            Type::Int16 => Some("i16"),
+            Type::Int32 => Some("i32"),
+            Type::Int64 => Some("i64"),
+            Type::Uint16 => Some("u16"),
+            Type::Uint32 => Some("u32"),
+            Type::Float64 => Some("f64"),
+            _ => None,
+        }
+    }
+}
+
+/// When a field may be null.
+#[derive(Debug, Clone, PartialEq)]
+enum Nullable {
+    Never,
+    Always,
+    /// Only in the versions where the condition holds.
+    When(String),
+}
+
+#[derive(Debug)]
+struct Field {
+    name: String,
+    ty: Type,
+    versions: Versions,
+    nullable_versions: Versions,
+    tag: Option<u32>,
+    tagged_versions: Versions,
+    /// Overrides the flexible versions of the message for this field.
+    flexible_versions: Option<Versions>,
+    default: Option<String>,
+    about: Option<String>,
+    /// The fields of the structure this field holds, when declared inline.
+    fields: Vec<Field>,
+}
+
+impl Field {
+    fn from_json(json: &Json) -> Result<Field, String> {
+        let name = json
+            .get("name")
+            .and_then(Json::as_str)
+            .ok_or("field without a name")?
+            .to_string();
+        let versions_of = |key: &str| -> Result<Versions, String> {
+            match json.get(key).and_then(Json::as_str) {
+                Some(spec) => Versions::parse(spec),
+                None => Ok(Versions::NONE),
+            }
+        };
+        let tag = match json.get("tag") {
+            Some(Json::Number(n)) => Some(
+                n.parse::<u32>()
+                    .map_err(|e| format!("invalid tag of {}: {}", name, e))?,
+            ),
+            _ => None,
+        };
+        let fields = match json.get("fields") {
+            Some(Json::Array(fields)) => fields
+                .iter()
+                .map(Field::from_json)
+                .collect::<Result<_, _>>()?,
+            _ => Vec::new(),
+        };
+
+        Ok(Field {
+            ty: Type::parse(
+                json.get("type")
+                    .and_then(Json::as_str)
+                    .ok_or_else(|| format!("field {} without a type", name))?,
+            ),
+            versions: Versions::parse(
+                json.get("versions")
+                    .and_then(Json::as_str)
+                    .ok_or_else(|| format!("field {} without versions", name))?,
+            )?,
+            nullable_versions: versions_of("nullableVersions")?,
+            tag,
+            tagged_versions: versions_of("taggedVersions")?,
+            flexible_versions: match json.get("flexibleVersions").and_then(Json::as_str) {
+                Some(spec) => Some(Versions::parse(spec)?),
+                None => None,
+            },
+            default: json.get("default").and_then(Json::as_scalar),
+            about: json.get("about").and_then(Json::as_str).map(str::to_string),
+            fields,
+            name,
+        })
+    }
+
+    fn ident(&self) -> String {
+        let snake = snake_case(&self.name);
+        match snake.as_str() {
+            "type" | "match" | "ref" | "mod" | "use" | "struct" | "enum" | "fn" | "impl"
+            | "self" | "static" | "const" | "loop" | "move" | "where" | "async" | "await" => {
+                format!("r#{}", snake)
+            }
+            _ => snake,
+        }
+    }
+
+    /// When the field may be null, among the `within` versions it is in.
+    fn nullable(&self, within: Versions) -> Nullable {
+        let within = self.versions.intersect(within);
+        if self.nullable_versions.intersect(within).is_empty() {
+            Nullable::Never
+        } else {
+            match self.nullable_versions.condition(within) {
+                None => Nullable::Always,
+                Some(condition) => Nullable::When(condition),
+            }
+        }
+    }
+
+    fn is_optional(&self) -> bool {
+        !self.nullable_versions.is_empty()
+            && matches!(
+                self.ty,
+                Type::String | Type::Bytes | Type::Records | Type::Array(_) | Type::Struct(_)
+            )
+    }
+
+    fn rust_type(&self) -> String {
+        if self.is_optional() {
+            format!("Option<{}>", self.ty.rust())
+        } else {
+            self.ty.rust()
+        }
+    }
+
+    fn default_value(&self) -> String {
+        let default = self.default.as_deref().filter(|d| !d.is_empty());
+        if self.is_optional() && default == Some("null") {
+            return "None".to_string();
+        }
+
+        let value = match &self.ty {
+            Type::Bool => default.unwrap_or("false").to_string(),
+            Type::Float64 => {
+                let value = default.unwrap_or("0");
+                if value.contains('.') {
+                    value.to_string()
+                } else {
+                    format!("{}.0", value)
+                }
+            }
+            Type::Int8 | Type::Int16 | Type::Int32 | Type::Int64 | Type::Uint16 | Type::Uint32 => {
+                default.unwrap_or("0").to_string()
+            }
+            Type::Uuid => "Uuid::nil()".to_string(),
+            Type::String => match default {
+                Some(value) => format!("{:?}.to_string()", value),
+                None => "String::new()".to_string(),
+            },
+            Type::Bytes | Type::Records => "Bytes::new()".to_string(),
+            Type::Struct(name) => format!("{}::default()", name),
+            Type::Array(_) => "Vec::new()".to_string(),
+        };
+        if self.is_optional() {
+            format!("Some({})", value)
+        } else {
+            value
+        }
+    }
+
+    /// The flexibility of the field's encoding, among the versions `within`.
+    fn flexible(&self, within: Versions) -> String {
+        match self.flexible_versions {
+            Some(versions) => versions
+                .condition(within)
+                .unwrap_or_else(|| "true".to_string()),
+            None => "flexible".to_string(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Struct {
+    name: String,
+    about: Option<String>,
+    versions: Versions,
+    fields: Vec<Field>,
+}
+
+#[derive(Debug)]
+struct Message {
+    name: String,
+    api_key: Option<i16>,
+    valid_versions: Versions,
+    flexible_versions: Versions,
+    fields: Vec<Field>,
+    common_structs: Vec<Struct>,
+}
+
+impl Message {
+    fn from_json(json: &Json) -> Result<Message, String> {
+        let name = json
+            .get("name")
+            .and_then(Json::as_str)
+            .ok_or("message without a name")?
+            .to_string();
+        let fields_of = |json: &Json| -> Result<Vec<Field>, String> {
+            match json.get("fields") {
+                Some(Json::Array(fields)) => fields.iter().map(Field::from_json).collect(),
+                _ => Ok(Vec::new()),
+            }
+        };
+        let valid_versions = Versions::parse(
+            json.get("validVersions")
+                .and_then(Json::as_str)
+                .ok_or("message without validVersions")?,
+        )?;
+        let common_structs = match json.get("commonStructs") {
+            Some(Json::Array(structs)) => structs
+                .iter()
+                .map(|common| {
+                    Ok(Struct {
+                        name: common
+                            .get("name")
+                            .and_then(Json::as_str)
+                            .ok_or("common struct without a name")?
+                            .to_string(),
+                        about: None,
+                        versions: match common.get("versions").and_then(Json::as_str) {
+                            Some(spec) => Versions::parse(spec)?.intersect(valid_versions),
+                            None => valid_versions,
+                        },
+                        fields: fields_of(common)?,
+                    })
+                })
+                .collect::<Result<_, String>>()?,
+            _ => Vec::new(),
+        };
+
+        Ok(Message {
+            api_key: match json.get("apiKey") {
+                Some(Json::Number(n)) => Some(
+                    n.parse::<i16>()
+                        .map_err(|e| format!("invalid apiKey: {}", e))?,
+                ),
+                _ => None,
+            },
+            valid_versions,
+            flexible_versions: Versions::parse(
+                json.get("flexibleVersions")
+                    .and_then(Json::as_str)
+                    .unwrap_or("none"),
+            )?,
+            fields: fields_of(json)?,
+            common_structs,
+            name,
+        })
+    }
+
+    fn generate(&self, out: &mut String) {
+        let module = snake_case(&self.name);
+        let _ = writeln!(out, "\npub mod {} {{\n    use super::*;", module);
+
+        let mut generated = HashSet::new();
+        let top = Struct {
+            name: self.name.clone(),
+            about: None,
+            versions: self.valid_versions,
+            fields: Vec::new(),
+        };
+        self.generate_struct(out, &top, &self.fields, true);
+        generated.insert(self.name.clone());
+        self.generate_nested(out, &self.fields, self.valid_versions, &mut generated);
+        for common in &self.common_structs {
+            if generated.insert(common.name.clone()) {
+                self.generate_struct(out, common, &common.fields, false);
+                self.generate_nested(out, &common.fields, common.versions, &mut generated);
+            }
+        }
+
+        let _ = writeln!(out, "}}\npub use {}::{};", module, self.name);
+    }
+
+    /// Generates the structures declared inline in `fields`.
+    fn generate_nested(
+        &self,
+        out: &mut String,
+        fields: &[Field],
+        within: Versions,
+        generated: &mut HashSet<String>,
+    ) {
+        for field in fields.iter().filter(|field| !field.fields.is_empty()) {
+            let name = match &field.ty {
+                Type::Struct(name) => name,
+                Type::Array(element) => match element.as_ref() {
+                    Type::Struct(name) => name,
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            if !generated.insert(name.clone()) {
+                continue;
+            }
+            let nested = Struct {
+                name: name.clone(),
+                about: field.about.clone(),
+                versions: field.versions.intersect(within),
+                fields: Vec::new(),
+            };
+            self.generate_struct(out, &nested, &field.fields, false);
+            self.generate_nested(out, &field.fields, nested.versions, generated);
+        }
+    }
+
+    fn generate_struct(&self, out: &mut String, st: &Struct, fields: &[Field], top: bool) {
+        // Fields outside the versions of the message are never on the wire.
+        let fields = fields
+            .iter()
+            .filter(|field| !field.versions.intersect(st.versions).is_empty())
+            .collect::<Vec<_>>();
+
+        if top {
+            let _ = writeln!(
+                out,
+                "\n    /// {}, versions {}-{}.",
+                match self.api_key {
+                    Some(api_key) => format!("The {} of API key {}", self.name, api_key),
+                    None => format!("The {}", self.name),
+                },
+                self.valid_versions.min,
+                self.valid_versions.max
+            );
+        } else if let Some(about) = &st.about {
+            let _ = writeln!(out, "\n    /// {}", about);
+        } else {
+            out.push('\n');
+        }
+        let _ = writeln!(
+            out,
+            "    #[derive(Debug, Clone, PartialEq)]\n    pub struct {} {{",
+            st.name
+        );
+        for field in &fields {
+            if let Some(about) = &field.about {
+                let _ = writeln!(out, "        /// {}", about);
+            }
+            let _ = writeln!(out, "        pub {}: {},", field.ident(), field.rust_type());
+        }
+        let _ = writeln!(
+            out,
+            "        /// Tagged fields this schema doesn't know.\n        pub tag: TaggedFields,\n    }}"
+        );
+
+        if top {
+            let _ = writeln!(out, "\n    #[cfg(test)]\n    impl {} {{", st.name);
+            let _ = writeln!(
+                out,
+                "        pub const MIN_VERSION: i16 = {};\n        pub const MAX_VERSION: i16 = {};\n    }}",
+                self.valid_versions.min, self.valid_versions.max
+            );
+        }
+
+        let _ = writeln!(
+            out,
+            "\n    impl Default for {} {{\n        fn default() -> Self {{\n            Self {{",
+            st.name
+        );
+        for field in &fields {
+            let _ = writeln!(
+                out,
+                "                {}: {},",
+                field.ident(),
+                field.default_value()
+            );
+        }
+        let _ = writeln!(
+            out,
+            "                tag: TaggedFields::new(),\n            }}\n        }}\n    }}"
+        );
+
+        let flexible = self
+            .flexible_versions
+            .condition(st.versions)
+            .unwrap_or_else(|| "true".to_string());
+        let tagged_within = st.versions.intersect(self.flexible_versions);
+
+        // Decoding: each field is read into a local of its name, in wire
+        // order, and the structure is built from them at the end.
+        let tagged = fields
+            .iter()
+            .filter(|field| field.tag.is_some())
+            .collect::<Vec<_>>();
+        let mut body = String::new();
+        for field in fields.iter().filter(|field| field.tag.is_none()) {
+            let read = read_value(
+                &field.ty,
+                &field.nullable(st.versions),
+                &field.flexible(st.versions),
+                &field.ident(),
+            );
+            let read = match &field.ty {
+                // A partially nullable field is read into its option.
+                Type::String | Type::Bytes | Type::Records | Type::Struct(_) | Type::Array(_)
+                    if field.is_optional() && field.nullable(st.versions) == Nullable::Never =>
+                {
+                    format!("Some({})", read)
+                }
+                _ => read,
+            };
+            let read = match field.versions.condition(st.versions) {
+                Some(condition) => format!(
+                    "if {} {{\n{}\n}} else {{\n    {}\n}}",
+                    condition,
+                    indent(&read, 1),
+                    field.default_value()
+                ),
+                None => read,
+            };
+            push_conditional(
+                &mut body,
+                None,
+                &format!("let {} = {};", field.ident(), read),
+                3,
+            );
+        }
+        if tagged_within.is_empty() {
+            body.push_str("            let tag = TaggedFields::new();\n");
+        } else {
+            let _ = writeln!(
+                body,
+                "            let {}tag = if flexible {{\n                TaggedFields::from_be_bytes(buf)\n                    .map_err(|e| anyhow::anyhow!(\"failed to parse tagged fields: {{}}\", e))?\n            }} else {{\n                TaggedFields::new()\n            }};",
+                if tagged.is_empty() { "" } else { "mut " }
+            );
+            for field in &tagged {
+                let within = field.tagged_versions.intersect(tagged_within);
+                let read = read_value(&field.ty, &field.nullable(within), "true", &field.ident());
+                let read = if field.is_optional() && field.nullable(within) == Nullable::Never {
+                    format!("Some({})", read)
+                } else {
+                    read
+                };
+                // The tagged fields are empty unless the version is flexible.
+                let take = match field.tagged_versions.condition(tagged_within) {
+                    Some(versions) => format!(
+                        "if {} {{ tag.take({}) }} else {{ None }}",
+                        versions,
+                        field.tag.unwrap_or_default()
+                    ),
+                    None => format!("tag.take({})", field.tag.unwrap_or_default()),
+                };
+                let take = format!(
+                    "let {} = match {} {{\n    Some(mut data) => {{\n        let buf = &mut data;\n{}\n    }}\n    None => {},\n}};",
+                    field.ident(),
+                    take,
+                    indent(&read, 2),
+                    field.default_value()
+                );
+                push_conditional(&mut body, None, &take, 3);
+            }
+        }
+        let _ = writeln!(body, "            Ok(Self {{");
+        for field in &fields {
+            let _ = writeln!(body, "                {},", field.ident());
+        }
+        let _ = writeln!(body, "                tag,\n            }})");
+        let body = declare_flexible(&flexible, &body) + &body;
+        let _ = writeln!(
+            out,
+            "\n    impl FromVersionedBytes for {} {{\n        fn from_versioned_be_bytes<B: Buf>({}: &mut B, {}: i16) -> Result<Self> {{\n{}        }}\n    }}",
+            st.name,
+            used("buf", &body),
+            used("version", &body),
+            body
+        );
+
+        // Encoding.
+        let mut body = String::new();
+        for field in fields.iter().filter(|field| field.tag.is_none()) {
+            let write = write_value(
+                &field.ty,
+                &field.nullable(st.versions),
+                &field.flexible(st.versions),
+                &format!("self.{}", field.ident()),
+                false,
+                field.is_optional(),
+            );
+            push_conditional(&mut body, field.versions.condition(st.versions), &write, 3);
+        }
+        if !tagged_within.is_empty() && tagged.is_empty() {
+            let _ = writeln!(
+                body,
+                "            if flexible {{\n                buf.put_slice(&self.tag.to_be_bytes());\n            }}"
+            );
+        } else if !tagged_within.is_empty() {
+            let _ = writeln!(
+                body,
+                "            if flexible {{\n                let mut tag = self.tag.clone();"
+            );
+            for field in &tagged {
+                let within = field.tagged_versions.intersect(tagged_within);
+                let write = write_value(
+                    &field.ty,
+                    &field.nullable(within),
+                    "true",
+                    &format!("self.{}", field.ident()),
+                    false,
+                    field.is_optional(),
+                );
+                let changed = match field.default_value().as_str() {
+                    "false" => format!("self.{}", field.ident()),
+                    "true" => format!("!self.{}", field.ident()),
+                    "None" => format!("self.{}.is_some()", field.ident()),
+                    default => format!("self.{} != {}", field.ident(), default),
+                };
+                let condition = match field.tagged_versions.condition(tagged_within) {
+                    Some(versions) => format!("{} && {}", versions, changed),
+                    None => changed,
+                };
+                let insert = format!(
+                    "let mut data = BytesMut::new();\n{{\n    let buf = &mut data;\n{}\n}}\ntag.insert({}, data.freeze());",
+                    indent(&write, 1),
+                    field.tag.unwrap_or_default()
+                );
+                push_conditional(&mut body, Some(condition), &insert, 4);
+            }
+            let _ = writeln!(
+                body,
+                "                buf.put_slice(&tag.to_be_bytes());\n            }}"
+            );
+        }
+        let body = if body.is_empty() {
+            "            Bytes::new()\n".to_string()
+        } else {
+            format!(
+                "{}            let mut out = BytesMut::new();\n            let buf = &mut out;\n{}            out.freeze()\n",
+                declare_flexible(&flexible, &body),
+                body
+            )
+        };
+        let _ = writeln!(
+            out,
+            "\n    impl ToVersionedBytes for {} {{\n        fn to_versioned_be_bytes(&self, {}: i16) -> Bytes {{\n{}        }}\n    }}",
+            st.name,
+            used("version", &body),
+            body
+        );
+
+        // A value setting every field that is on the wire in a version, for
+        // the round-trip tests.
+        let mut body = String::new();
+        for field in &fields {
+            let versions = match field.tag {
+                Some(_) => field
+                    .versions
+                    .intersect(field.tagged_versions)
+                    .intersect(self.flexible_versions),
+                None => field.versions,
+            };
+            let default = field.default_value();
+            let sample = match sample_value(&field.ty) {
+                sample if sample == default => "2".to_string(),
+                sample if field.is_optional() => format!("Some({})", sample),
+                sample => sample,
+            };
+            let value = match (versions.condition(st.versions), &field.ty) {
+                (Some(condition), Type::Bool) if default == "false" => condition,
+                (Some(condition), Type::Bool) => negate(&condition),
+                (Some(condition), _) => format!(
+                    "if {} {{\n    {}\n}} else {{\n    {}\n}}",
+                    condition, sample, default
+                ),
+                (None, Type::Bool) => (default == "false").to_string(),
+                (None, _) => sample,
+            };
+            push_conditional(
+                &mut body,
+                None,
+                &format!("{}: {},", field.ident(), value),
+                4,
+            );
+        }
+        let _ = writeln!(
+            out,
+            "\n    #[cfg(test)]\n    impl Sample for {} {{\n        fn sample({}: i16) -> Self {{\n            Self {{\n{}                tag: TaggedFields::new(),\n            }}\n        }}\n    }}",
+            st.name,
+            used("version", &body),
+            body
+        );
+    }
+}
+
+/// The negation of a condition made by [`Versions::condition`].
+fn negate(condition: &str) -> String {
+    for (op, negated) in [(" == ", " != "), (" >= ", " < "), (" <= ", " > ")] {
+        if condition.contains(op) {
+            return condition.replace(op, negated);
+        }
+    }
+    match condition {
+        "false" => "true".to_string(),
+        range => format!("!{}", range),
+    }
+}
+
+/// An expression for a value of `ty` other than its default.
+fn sample_value(ty: &Type) -> String {
+    match ty {
+        Type::Bool => "true".to_string(),
+        Type::Float64 => "1.5".to_string(),
+        Type::Uuid => "Uuid::from_u128(1)".to_string(),
+        Type::String => "\"a\".to_string()".to_string(),
+        Type::Bytes | Type::Records => "Bytes::from_static(b\"a\")".to_string(),
+        Type::Struct(name) => format!("{}::sample(version)", name),
+        Type::Array(element) => format!("vec![{}]", sample_value(element)),
+        _ => "1".to_string(),
+    }
+}
+
+/// `ident` as a parameter of generated code in `body`, prefixed with an
+/// underscore when `body` doesn't use it.
+fn used(ident: &str, body: &str) -> String {
+    if uses(ident, body) {
+        ident.to_string()
+    } else {
+        format!("_{}", ident)
+    }
+}
+
+/// Whether `body` refers to `ident` as a whole word.
+fn uses(ident: &str, body: &str) -> bool {
+    let is_ident = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    body.match_indices(ident).any(|(at, _)| {
+        !body[..at].ends_with(is_ident) && !body[at + ident.len()..].starts_with(is_ident)
+    })
+}
+
+/// The `flexible` local of generated code in `body`, if it uses it.
+fn declare_flexible(flexible: &str, body: &str) -> String {
+    if uses("flexible", body) {
+        format!("            let flexible = {};\n", flexible)
+    } else {
+        String::new()
+    }
+}
+
+/// Appends `code` at `level` of indentation, inside `if condition` when
+/// there is one.
+fn push_conditional(out: &mut String, condition: Option<String>, code: &str, level: usize) {
+    match condition {
+        Some(condition) => {
+            let _ = writeln!(out, "{}if {} {{", "    ".repeat(level), condition);
+            let _ = writeln!(out, "{}", indent(code, level + 1));
+            let _ = writeln!(out, "{}}}", "    ".repeat(level));
+        }
+        None => {
+            let _ = writeln!(out, "{}", indent(code, level));
+        }
+    }
+}
+
+fn indent(code: &str, level: usize) -> String {
+    code.lines()
+        .map(|line| format!("{}{}", "    ".repeat(level), line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Wraps a fallible read of the field `label` so that its error names it.
+fn try_read(read: &str, label: &str) -> String {
+    format!(
+        "{}.map_err(|e| anyhow::anyhow!(\"failed to parse {}: {{}}\", e))?",
+        read, label
+    )
+}
+
+/// An expression reading a value of `ty` from `buf`.
+fn read_value(ty: &Type, nullable: &Nullable, flexible: &str, label: &str) -> String {
+    let (nullable_read, read) = match ty {
+        Type::Bool => return format!("{} != 0", try_read("buf.try_get_u8()", label)),
+        Type::Uuid => return format!("Uuid::from_u128({})", try_read("buf.try_get_u128()", label)),
+        Type::String => (
+            try_read(&format!("get_nullable_string(buf, {})", flexible), label),
+            try_read(&format!("get_string(buf, {})", flexible), label),
+        ),
+        Type::Bytes | Type::Records => (
+            try_read(&format!("get_nullable_bytes(buf, {})", flexible), label),
+            try_read(&format!("get_bytes(buf, {})", flexible), label),
+        ),
+        Type::Struct(name) => {
+            let read = try_read(
+                &format!("{}::from_versioned_be_bytes(buf, version)", name),
+                label,
+            );
+            let marker = try_read("buf.try_get_i8()", label);
+            return match nullable {
+                Nullable::Never => read,
+                Nullable::Always => {
+                    format!("if {} < 0 {{ None }} else {{ Some({}) }}", marker, read)
+                }
+                Nullable::When(condition) => format!(
+                    "if {} && {} < 0 {{ None }} else {{ Some({}) }}",
+                    condition, marker, read
+                ),
+            };
+        }
+        Type::Array(element) => {
+            let len = try_read(&format!("get_array_len(buf, {})", flexible), label);
+            let element = read_value(element, &Nullable::Never, flexible, label);
+            let items = format!(
+                "let mut items = Vec::with_capacity(len);\nfor _ in 0..len {{\n    items.push({});\n}}",
+                element
+            );
+            return match nullable {
+                Nullable::Never => format!(
+                    "{{\n    let len = {}\n        .ok_or_else(|| anyhow::anyhow!(\"failed to parse {}: unexpected null array\"))?;\n{}\n    items\n}}",
+                    len,
+                    label,
+                    indent(&items, 1)
+                ),
+                Nullable::Always | Nullable::When(_) => format!(
+                    "match {} {{\n    Some(len) => {{\n{}\n        Some(items)\n    }}\n    None => None,\n}}",
+                    len,
+                    indent(&items, 2)
+                ),
+            };
+        }
+        number => {
+            return try_read(
+                &format!("buf.try_get_{}()", number.number_suffix().unwrap_or("i8")),
+                label,
+            )
+        }
+    };
+
+    match nullable {
+        Nullable::Never => read,
+        Nullable::Always => nullable_read,
+        Nullable::When(condition) => format!(
+            "if {} {{ {} }} else {{ Some({}) }}",
+            condition, nullable_read, read
+        ),
+    }
+}
+
+/// Statements writing `value`, of `ty`, to `buf`. `by_ref` is whether
+/// `value` is a reference, and `optional` whether it is held in an option.
+fn write_value(
+    ty: &Type,
+    nullable: &Nullable,
+    flexible: &str,
+    value: &str,
+    by_ref: bool,
+    optional: bool,
+) -> String {
+    let copied = if by_ref {
+        format!("*{}", value)
+    } else {
+        value.to_string()
+    };
+    let borrowed = if by_ref {
+        value.to_string()
+    } else {
+        format!("&{}", value)
+    };
+
+    match ty {
+        Type::Bool => format!("buf.put_u8({} as u8);", copied),
+        Type::Uuid => format!("buf.put_u128({}.as_u128());", value),
+        Type::String | Type::Bytes | Type::Records => {
+            let (put, put_nullable) = match ty {
+                Type::String => ("put_string", "put_nullable_string"),
+                _ => ("put_bytes", "put_nullable_bytes"),
+            };
+            if !optional {
+                return format!("{}(buf, {}, {});", put, borrowed, flexible);
+            }
+            let nullable_write =
+                format!("{}(buf, {}.as_deref(), {});", put_nullable, value, flexible);
+            let write = format!(
+                "{}(buf, {}.as_deref().unwrap_or_default(), {});",
+                put, value, flexible
+            );
+            match nullable {
+                Nullable::Never => write,
+                Nullable::Always => nullable_write,
+                Nullable::When(condition) => format!(
+                    "if {} {{\n    {}\n}} else {{\n    {}\n}}",
+                    condition, nullable_write, write
+                ),
+            }
+        }
+        Type::Struct(name) => {
+            if !optional {
+                return format!("buf.put_slice(&{}.to_versioned_be_bytes(version));", value);
+            }
+            let (marker, null) = match nullable {
+                Nullable::Never => (
+                    String::new(),
+                    format!(
+                        "buf.put_slice(&{}::default().to_versioned_be_bytes(version))",
+                        name
+                    ),
+                ),
+                Nullable::Always => ("buf.put_i8(1);\n        ".to_string(), "buf.put_i8(-1)".to_string()),
+                Nullable::When(condition) => (
+                    format!("if {} {{\n            buf.put_i8(1);\n        }}\n        ", condition),
+                    format!(
+                        "if {} {{\n            buf.put_i8(-1)\n        }} else {{\n            buf.put_slice(&{}::default().to_versioned_be_bytes(version))\n        }}",
+                        condition, name
+                    ),
+                ),
+            };
+            format!(
+                "match &{} {{\n    Some(value) => {{\n        {}buf.put_slice(&value.to_versioned_be_bytes(version));\n    }}\n    None => {{\n        {}\n    }}\n}}",
+                value, marker, null
+            )
+        }
+        Type::Array(element) => {
+            let element = write_value(element, &Nullable::Never, flexible, "item", true, false);
+            if !optional {
+                return format!(
+                    "put_array_len(buf, Some({}.len()), {});\nfor item in {}.iter() {{\n{}\n}}",
+                    value,
+                    flexible,
+                    value,
+                    indent(&element, 1)
+                );
+            }
+            let len = match nullable {
+                Nullable::Never => format!(
+                    "put_array_len(buf, Some({}.as_ref().map_or(0, Vec::len)), {});",
+                    value, flexible
+                ),
+                Nullable::Always => format!(
+                    "put_array_len(buf, {}.as_ref().map(Vec::len), {});",
+                    value, flexible
+                ),
+                Nullable::When(condition) => format!(
+                    "if {} {{\n    put_array_len(buf, {}.as_ref().map(Vec::len), {});\n}} else {{\n    put_array_len(buf, Some({}.as_ref().map_or(0, Vec::len)), {});\n}}",
+                    condition, value, flexible, value, flexible
+                ),
+            };
+            format!(
+                "{}\nfor item in {}.iter().flatten() {{\n{}\n}}",
+                len,
+                value,
+                indent(&element, 1)
+            )
+        }
+        number => format!(
+            "buf.put_{}({});",
+            number.number_suffix().unwrap_or("i8"),
+            copied
+        ),
+    }
+}
+
+/// `FetchRequest` -> `fetch_request`, `timeoutMs` -> `timeout_ms`.
+fn snake_case(name: &str) -> String {
+    let chars = name.chars().collect::<Vec<_>>();
+    let mut snake = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        if c.is_ascii_uppercase() && i > 0 {
+            let prev = chars[i - 1];
+            let next_lower = chars.get(i + 1).is_some_and(|n| n.is_ascii_lowercase());
+            if prev.is_ascii_lowercase()
+                || prev.is_ascii_digit()
+                || (prev.is_ascii_uppercase() && next_lower)
+            {
+                snake.push('_');
+            }
+        }
+        snake.push(c.to_ascii_lowercase());
+    }
+
+    snake
+}
@@ -0,0 +1,246 @@
+use super::ConfigType;
+
+/// A broker config that supplies the value of a topic config when the
+/// topic doesn't override it.
+#[derive(Debug)]
+pub(crate) struct BrokerSynonym {
+    pub(crate) name: &'static str,
+    /// Factor converting a value of this config to the topic config's unit,
+    /// e.g. hours to milliseconds.
+    pub(crate) multiplier: i64,
+    pub(crate) default: Option<&'static str>,
+}
+
+#[derive(Debug)]
+pub(crate) struct TopicConfigDef {
+    pub(crate) name: &'static str,
+    pub(crate) config_type: ConfigType,
+    pub(crate) default: &'static str,
+    /// Broker configs in order of precedence.
+    pub(crate) synonyms: &'static [BrokerSynonym],
+    /// Accepted values of a string, or of each element of a list; empty
+    /// when any value of the type is accepted.
+    pub(crate) valid_values: &'static [&'static str],
+    pub(crate) documentation: &'static str,
+}
+
+const fn synonym(name: &'static str, default: Option<&'static str>) -> BrokerSynonym {
+    BrokerSynonym {
+        name,
+        multiplier: 1,
+        default,
+    }
+}
+
+const MINUTE_MS: i64 = 60 * 1000;
+const HOUR_MS: i64 = 60 * MINUTE_MS;
+
+pub(crate) const TOPIC_CONFIGS: &[TopicConfigDef] = &[
+    TopicConfigDef {
+        name: "cleanup.policy",
+        config_type: ConfigType::List,
+        default: "delete",
+        synonyms: &[synonym("log.cleanup.policy", Some("delete"))],
+        valid_values: &["delete", "compact"],
+        documentation: "The retention policy to use on log segments: delete, compact or both.",
+    },
+    TopicConfigDef {
+        name: "compression.type",
+        config_type: ConfigType::String,
+        default: "producer",
+        synonyms: &[synonym("compression.type", Some("producer"))],
+        valid_values: &["uncompressed", "zstd", "lz4", "snappy", "gzip", "producer"],
+        documentation: "The final compression type for the topic.",
+    },
+    TopicConfigDef {
+        name: "delete.retention.ms",
+        config_type: ConfigType::Long,
+        default: "86400000",
+        synonyms: &[synonym("log.cleaner.delete.retention.ms", Some("86400000"))],
+        valid_values: &[],
+        documentation: "How long delete tombstones are retained for compacted topics.",
+    },
+    TopicConfigDef {
+        name: "file.delete.delay.ms",
+        config_type: ConfigType::Long,
+        default: "60000",
+        synonyms: &[synonym("log.segment.delete.delay.ms", Some("60000"))],
+        valid_values: &[],
+        documentation: "The time to wait before deleting a file from the filesystem.",
+    },
+    TopicConfigDef {
+        name: "flush.messages",
+        config_type: ConfigType::Long,
+        default: "9223372036854775807",
+        synonyms: &[synonym(
+            "log.flush.interval.messages",
+            Some("9223372036854775807"),
+        )],
+        valid_values: &[],
+        documentation: "The number of messages written to a log before it is fsynced.",
+    },
+    TopicConfigDef {
+        name: "flush.ms",
+        config_type: ConfigType::Long,
+        default: "9223372036854775807",
+        synonyms: &[synonym("log.flush.interval.ms", None)],
+        valid_values: &[],
+        documentation: "The time after which a log is fsynced.",
+    },
+    TopicConfigDef {
+        name: "index.interval.bytes",
+        config_type: ConfigType::Int,
+        default: "4096",
+        synonyms: &[synonym("log.index.interval.bytes", Some("4096"))],
+        valid_values: &[],
+        documentation: "How frequently an entry is added to the offset index.",
+    },
+    TopicConfigDef {
+        name: "max.message.bytes",
+        config_type: ConfigType::Int,
+        default: "1048588",
+        synonyms: &[synonym("message.max.bytes", Some("1048588"))],
+        valid_values: &[],
+        documentation: "The largest record batch size allowed.",
+    },
+    TopicConfigDef {
+        name: "message.timestamp.type",
+        config_type: ConfigType::String,
+        default: "CreateTime",
+        synonyms: &[synonym("log.message.timestamp.type", Some("CreateTime"))],
+        valid_values: &["CreateTime", "LogAppendTime"],
+        documentation: "Whether record timestamps are the create time or the log append time.",
+    },
+    TopicConfigDef {
+        name: "min.cleanable.dirty.ratio",
+        config_type: ConfigType::Double,
+        default: "0.5",
+        synonyms: &[synonym("log.cleaner.min.cleanable.ratio", Some("0.5"))],
+        valid_values: &[],
+        documentation: "How frequently the log compactor attempts to clean the log.",
+    },
+    TopicConfigDef {
+        name: "min.compaction.lag.ms",
+        config_type: ConfigType::Long,
+        default: "0",
+        synonyms: &[synonym("log.cleaner.min.compaction.lag.ms", Some("0"))],
+        valid_values: &[],
+        documentation: "The minimum time a message remains uncompacted in the log.",
+    },
+    TopicConfigDef {
+        name: "min.insync.replicas",
+        config_type: ConfigType::Int,
+        default: "1",
+        synonyms: &[synonym("min.insync.replicas", Some("1"))],
+        valid_values: &[],
+        documentation:
+            "The minimum number of replicas that must acknowledge a write with acks=all.",
+    },
+    TopicConfigDef {
+        name: "preallocate",
+        config_type: ConfigType::Boolean,
+        default: "false",
+        synonyms: &[synonym("log.preallocate", Some("false"))],
+        valid_values: &[],
+        documentation: "Whether the file is preallocated when a new log segment is created.",
+    },
+    TopicConfigDef {
+        name: "retention.bytes",
+        config_type: ConfigType::Long,
+        default: "-1",
+        synonyms: &[synonym("log.retention.bytes", Some("-1"))],
+        valid_values: &[],
+        documentation:
+            "The maximum size a partition can grow to before old segments are discarded.",
+    },
+    TopicConfigDef {
+        name: "retention.ms",
+        config_type: ConfigType::Long,
+        default: "604800000",
+        synonyms: &[
+            synonym("log.retention.ms", None),
+            BrokerSynonym {
+                name: "log.retention.minutes",
+                multiplier: MINUTE_MS,
+                default: None,
+            },
+            BrokerSynonym {
+                name: "log.retention.hours",
+                multiplier: HOUR_MS,
+                default: Some("168"),
+            },
+        ],
+        valid_values: &[],
+        documentation: "The maximum time a log is retained before old segments are discarded.",
+    },
+    TopicConfigDef {
+        name: "segment.bytes",
+        config_type: ConfigType::Int,
+        default: "1073741824",
+        synonyms: &[synonym("log.segment.bytes", Some("1073741824"))],
+        valid_values: &[],
+        documentation: "The segment file size for the log.",
+    },
+    TopicConfigDef {
+        name: "segment.index.bytes",
+        config_type: ConfigType::Int,
+        default: "10485760",
+        synonyms: &[synonym("log.index.size.max.bytes", Some("10485760"))],
+        valid_values: &[],
+        documentation: "The size of the index that maps offsets to file positions.",
+    },
+    TopicConfigDef {
+        name: "segment.jitter.ms",
+        config_type: ConfigType::Long,
+        default: "0",
+        synonyms: &[
+            synonym("log.roll.jitter.ms", None),
+            BrokerSynonym {
+                name: "log.roll.jitter.hours",
+                multiplier: HOUR_MS,
+                default: Some("0"),
+            },
+        ],
+        valid_values: &[],
+        documentation: "The maximum random jitter subtracted from the segment roll time.",
+    },
+    TopicConfigDef {
+        name: "segment.ms",
+        config_type: ConfigType::Long,
+        default: "604800000",
+        synonyms: &[
+            synonym("log.roll.ms", None),
+            BrokerSynonym {
+                name: "log.roll.hours",
+                multiplier: HOUR_MS,
+                default: Some("168"),
+            },
+        ],
+        valid_values: &[],
+        documentation: "The time after which the log is rolled even if the segment isn't full.",
+    },
+    TopicConfigDef {
+        name: "unclean.leader.election.enable",
+        config_type: ConfigType::Boolean,
+        default: "false",
+        synonyms: &[synonym("unclean.leader.election.enable", Some("false"))],
+        valid_values: &[],
+        documentation: "Whether replicas not in the ISR may be elected as leader.",
+    },
+];
+
+pub(crate) fn topic_config(name: &str) -> Option<&'static TopicConfigDef> {
+    TOPIC_CONFIGS.iter().find(|def| def.name == name)
+}
+
+/// The broker config `name` along with the topic config it backs.
+pub(crate) fn broker_config(
+    name: &str,
+) -> Option<(&'static TopicConfigDef, &'static BrokerSynonym)> {
+    TOPIC_CONFIGS.iter().find_map(|def| {
+        def.synonyms
+            .iter()
+            .find(|synonym| synonym.name == name)
+            .map(|synonym| (def, synonym))
+    })
+}
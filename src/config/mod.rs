@@ -0,0 +1,315 @@
+pub(crate) mod definitions;
+pub(crate) mod properties;
+
+use std::collections::BTreeMap;
+
+use definitions::{broker_config, topic_config, BrokerSynonym, TopicConfigDef, TOPIC_CONFIGS};
+pub use properties::ServerProperties;
+
+/// Where the value of a config comes from, as reported by DescribeConfigs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ConfigSource {
+    DynamicTopic = 1,
+    DynamicBroker = 3,
+    DynamicDefaultBroker = 4,
+    StaticBroker = 5,
+    Default = 6,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ConfigType {
+    Unknown = 0,
+    Boolean = 1,
+    String = 2,
+    Int = 3,
+    Long = 5,
+    Double = 6,
+    List = 7,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct ConfigSynonym {
+    pub(crate) name: String,
+    pub(crate) value: Option<String>,
+    pub(crate) source: ConfigSource,
+}
+
+/// A config with its effective value and every value that could supply it,
+/// highest precedence first.
+#[derive(Debug, Clone)]
+pub(crate) struct ConfigEntry {
+    pub(crate) name: String,
+    pub(crate) value: Option<String>,
+    pub(crate) source: ConfigSource,
+    pub(crate) read_only: bool,
+    pub(crate) config_type: ConfigType,
+    pub(crate) documentation: Option<&'static str>,
+    pub(crate) synonyms: Vec<ConfigSynonym>,
+}
+
+/// Dynamic configs stored in the metadata log, by config name.
+#[derive(Debug, Default)]
+pub(crate) struct DynamicConfigs {
+    pub(crate) topic: BTreeMap<String, String>,
+    pub(crate) broker: BTreeMap<String, String>,
+    pub(crate) cluster_default: BTreeMap<String, String>,
+}
+
+/// Resolves every topic config from `configs` and `properties`.
+pub(crate) fn topic_configs(
+    configs: &DynamicConfigs,
+    properties: &ServerProperties,
+) -> Vec<ConfigEntry> {
+    TOPIC_CONFIGS
+        .iter()
+        .map(|def| {
+            let mut synonyms = Vec::new();
+
+            if let Some(value) = configs.topic.get(def.name) {
+                synonyms.push(ConfigSynonym {
+                    name: def.name.to_string(),
+                    value: Some(value.clone()),
+                    source: ConfigSource::DynamicTopic,
+                });
+            }
+
+            for synonym in def.synonyms {
+                synonyms.extend(
+                    broker_synonyms(synonym, configs, properties)
+                        .into_iter()
+                        .filter(|entry| entry.value.is_some()),
+                );
+            }
+
+            let (value, source) = match synonyms.first() {
+                Some(first) => (
+                    convert(def, &first.name, first.value.as_deref()),
+                    first.source,
+                ),
+                None => (Some(def.default.to_string()), ConfigSource::Default),
+            };
+
+            ConfigEntry {
+                name: def.name.to_string(),
+                value,
+                source,
+                read_only: false,
+                config_type: def.config_type,
+                documentation: Some(def.documentation),
+                synonyms,
+            }
+        })
+        .collect()
+}
+
+/// Resolves the broker configs of this broker. Settings from
+/// `server.properties` that can't be changed dynamically are read-only.
+pub(crate) fn broker_configs(
+    configs: &DynamicConfigs,
+    properties: &ServerProperties,
+) -> Vec<ConfigEntry> {
+    let mut entries = BTreeMap::new();
+
+    for def in TOPIC_CONFIGS {
+        for synonym in def.synonyms {
+            let synonyms = broker_synonyms(synonym, configs, properties)
+                .into_iter()
+                .filter(|entry| entry.value.is_some() || entry.source == ConfigSource::Default)
+                .collect::<Vec<ConfigSynonym>>();
+            let first = synonyms
+                .first()
+                .cloned()
+                .expect("the default is always present");
+
+            entries.insert(
+                synonym.name,
+                ConfigEntry {
+                    name: synonym.name.to_string(),
+                    value: first.value,
+                    source: first.source,
+                    read_only: false,
+                    config_type: def.config_type,
+                    documentation: Some(def.documentation),
+                    synonyms,
+                },
+            );
+        }
+    }
+
+    for (name, value) in properties.iter() {
+        entries.entry(name).or_insert_with(|| ConfigEntry {
+            name: name.to_string(),
+            value: Some(value.to_string()),
+            source: ConfigSource::StaticBroker,
+            read_only: true,
+            config_type: ConfigType::Unknown,
+            documentation: None,
+            synonyms: vec![ConfigSynonym {
+                name: name.to_string(),
+                value: Some(value.to_string()),
+                source: ConfigSource::StaticBroker,
+            }],
+        });
+    }
+
+    entries.into_values().collect()
+}
+
+/// The cluster-wide dynamic defaults, described through the broker
+/// resource with an empty name.
+pub(crate) fn cluster_default_configs(configs: &DynamicConfigs) -> Vec<ConfigEntry> {
+    configs
+        .cluster_default
+        .iter()
+        .map(|(name, value)| {
+            let synonym = ConfigSynonym {
+                name: name.clone(),
+                value: Some(value.clone()),
+                source: ConfigSource::DynamicDefaultBroker,
+            };
+
+            ConfigEntry {
+                name: name.clone(),
+                value: Some(value.clone()),
+                source: ConfigSource::DynamicDefaultBroker,
+                read_only: false,
+                config_type: broker_config(name)
+                    .map(|(def, _)| def.config_type)
+                    .unwrap_or(ConfigType::Unknown),
+                documentation: broker_config(name).map(|(def, _)| def.documentation),
+                synonyms: vec![synonym],
+            }
+        })
+        .collect()
+}
+
+/// Checks that `value` is acceptable for the topic config `name`.
+pub(crate) fn validate_topic_config(name: &str, value: &str) -> Result<(), String> {
+    let def = topic_config(name).ok_or_else(|| format!("Unknown topic config name: {}", name))?;
+
+    validate_value(def, name, value)
+}
+
+/// Checks that the broker config `name` can be set dynamically to `value`.
+pub(crate) fn validate_broker_config(name: &str, value: &str) -> Result<(), String> {
+    let (def, _) = broker_config(name)
+        .ok_or_else(|| format!("Cannot update these configs dynamically: {}", name))?;
+
+    validate_value(def, name, value)
+}
+
+/// The list elements of `value`, as used by APPEND and SUBTRACT.
+pub(crate) fn list_elements(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|element| !element.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+pub(crate) fn config_type(name: &str) -> ConfigType {
+    topic_config(name)
+        .or_else(|| broker_config(name).map(|(def, _)| def))
+        .map(|def| def.config_type)
+        .unwrap_or(ConfigType::Unknown)
+}
+
+fn validate_value(def: &TopicConfigDef, name: &str, value: &str) -> Result<(), String> {
+    let invalid = || {
+        format!(
+            "Invalid value {} for configuration {}: expected a value of type {:?}",
+            value, name, def.config_type
+        )
+    };
+
+    match def.config_type {
+        ConfigType::Boolean => {
+            if !value.eq_ignore_ascii_case("true") && !value.eq_ignore_ascii_case("false") {
+                return Err(invalid());
+            }
+        }
+        ConfigType::Int => {
+            value.trim().parse::<i32>().map_err(|_| invalid())?;
+        }
+        ConfigType::Long => {
+            value.trim().parse::<i64>().map_err(|_| invalid())?;
+        }
+        ConfigType::Double => {
+            value.trim().parse::<f64>().map_err(|_| invalid())?;
+        }
+        ConfigType::String | ConfigType::List | ConfigType::Unknown => {}
+    }
+
+    let elements = match def.config_type {
+        ConfigType::List => list_elements(value),
+        _ => vec![value.to_string()],
+    };
+
+    if let Some(element) = elements.iter().find(|element| {
+        !def.valid_values.is_empty() && !def.valid_values.contains(&element.as_str())
+    }) {
+        return Err(format!(
+            "Invalid value {} for configuration {}: String must be one of: {}",
+            element,
+            name,
+            def.valid_values.join(", ")
+        ));
+    }
+
+    Ok(())
+}
+
+/// The values `synonym` takes in each place it can be set, highest
+/// precedence first, ending with its default.
+fn broker_synonyms(
+    synonym: &BrokerSynonym,
+    configs: &DynamicConfigs,
+    properties: &ServerProperties,
+) -> Vec<ConfigSynonym> {
+    let entry = |value: Option<&str>, source| ConfigSynonym {
+        name: synonym.name.to_string(),
+        value: value.map(str::to_string),
+        source,
+    };
+
+    vec![
+        entry(
+            configs.broker.get(synonym.name).map(String::as_str),
+            ConfigSource::DynamicBroker,
+        ),
+        entry(
+            configs
+                .cluster_default
+                .get(synonym.name)
+                .map(String::as_str),
+            ConfigSource::DynamicDefaultBroker,
+        ),
+        entry(properties.get(synonym.name), ConfigSource::StaticBroker),
+        entry(synonym.default, ConfigSource::Default),
+    ]
+}
+
+/// Converts the value of the config `name`, which backs `def`, to the unit of
+/// `def`.
+fn convert(def: &TopicConfigDef, name: &str, value: Option<&str>) -> Option<String> {
+    let value = value?;
+    let multiplier = def
+        .synonyms
+        .iter()
+        .find(|synonym| synonym.name == name)
+        .map(|synonym| synonym.multiplier)
+        .unwrap_or(1);
+
+    if multiplier == 1 {
+        return Some(value.to_string());
+    }
+
+    Some(
+        value
+            .trim()
+            .parse::<i64>()
+            .map(|value| value.saturating_mul(multiplier).to_string())
+            .unwrap_or_else(|_| value.to_string()),
+    )
+}
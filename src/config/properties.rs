@@ -0,0 +1,68 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::storage::LOG_DIR;
+
+/// The `node.id` of a broker whose properties don't set one.
+const DEFAULT_NODE_ID: i32 = 1;
+
+/// The static broker configuration read from `server.properties`.
+#[derive(Debug, Default, Clone)]
+pub struct ServerProperties {
+    values: BTreeMap<String, String>,
+}
+
+impl ServerProperties {
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+
+        let values = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with('!'))
+            .filter_map(|line| line.split_once(['=', ':']))
+            .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+            .collect();
+
+        Ok(Self { values })
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(String::as_str)
+    }
+
+    /// The directories of `log.dirs`, or else `log.dir`. The first one also
+    /// holds the metadata log.
+    pub(crate) fn log_dirs(&self) -> Vec<PathBuf> {
+        let dirs = self
+            .get("log.dirs")
+            .or_else(|| self.get("log.dir"))
+            .unwrap_or(LOG_DIR)
+            .split(',')
+            .map(str::trim)
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .collect::<Vec<PathBuf>>();
+
+        if dirs.is_empty() {
+            vec![PathBuf::from(LOG_DIR)]
+        } else {
+            dirs
+        }
+    }
+
+    pub(crate) fn node_id(&self) -> i32 {
+        self.get("node.id")
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_NODE_ID)
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.values
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+}
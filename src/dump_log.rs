@@ -0,0 +1,114 @@
+use std::{fs, path::Path};
+
+use bytes::{Buf, Bytes};
+
+use crate::{
+    protocol::{
+        bytes::FromBytes,
+        cluster_metadata::{Batch, RecordValue},
+        control_record::{ControlRecord, ControlRecordValue},
+    },
+    Result,
+};
+
+/// Prints every batch of a log segment, in the spirit of `kafka-dump-log.sh`.
+/// Record values of `__cluster_metadata` segments are decoded as metadata
+/// records; other values are only summarized.
+pub fn dump_log(path: &Path) -> Result<()> {
+    let decode_metadata = path
+        .parent()
+        .and_then(|dir| dir.file_name())
+        .and_then(|name| name.to_str())
+        .map(|name| name.starts_with("__cluster_metadata"))
+        .unwrap_or(false);
+
+    let contents = Bytes::from(fs::read(path)?);
+    let mut bytes = contents.clone();
+
+    println!("Dumping {}", path.display());
+
+    while bytes.has_remaining() {
+        let position = contents.len() - bytes.remaining();
+        let batch = match Batch::<Bytes>::try_from(&mut bytes) {
+            Ok(batch) => batch,
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        };
+        let size = contents.len() - bytes.remaining() - position;
+
+        println!(
+            "baseOffset: {} lastOffset: {} count: {} baseSequence: {} producerId: {} \
+             producerEpoch: {} partitionLeaderEpoch: {} isTransactional: {} isControl: {} \
+             position: {} CreateTime: {} size: {} crc: {}",
+            batch.base_offset(),
+            batch.last_offset(),
+            batch.record_count(),
+            batch.base_sequence(),
+            batch.producer_id(),
+            batch.producer_epoch(),
+            batch.partition_leader_epoch(),
+            batch.is_transactional(),
+            batch.is_control(),
+            position,
+            batch.base_timestamp(),
+            size,
+            batch.crc(),
+        );
+
+        for record in batch.control_records() {
+            let offset = batch.base_offset() + record.offset_delta() as i64;
+            println!("| offset: {} {}", offset, describe_control_record(record));
+        }
+
+        for record in batch.records() {
+            let offset = batch.base_offset() + record.offset_delta() as i64;
+            let key_size = record.key().map(|key| key.len() as i64).unwrap_or(-1);
+            let mut value = record.record_value().clone();
+
+            if decode_metadata {
+                let payload = RecordValue::from_be_bytes(&mut value)
+                    .map(|value| format!("{:?}", value.value()))
+                    .unwrap_or_else(|e| format!("<undecodable: {}>", e));
+                println!("| offset: {} payload: {}", offset, payload);
+            } else {
+                println!(
+                    "| offset: {} keySize: {} valueSize: {}",
+                    offset,
+                    key_size,
+                    value.len()
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn describe_control_record(record: &ControlRecord) -> String {
+    match record.value() {
+        ControlRecordValue::Abort(marker) => format!(
+            "endTxnMarker: ABORT coordinatorEpoch: {}",
+            marker.coordinator_epoch()
+        ),
+        ControlRecordValue::Commit(marker) => format!(
+            "endTxnMarker: COMMIT coordinatorEpoch: {}",
+            marker.coordinator_epoch()
+        ),
+        ControlRecordValue::LeaderChange(message) => {
+            format!("LeaderChange: leaderId: {}", message.leader_id())
+        }
+        ControlRecordValue::SnapshotHeader(header) => format!(
+            "SnapshotHeader: lastContainedLogTimestamp: {}",
+            header.last_contained_log_timestamp()
+        ),
+        ControlRecordValue::SnapshotFooter(_) => "SnapshotFooter".to_string(),
+        ControlRecordValue::KRaftVersion(version) => {
+            format!("KRaftVersion: kraftVersion: {}", version.kraft_version())
+        }
+        ControlRecordValue::Unknown { record_type, value } => format!(
+            "controlType: {} valueSize: {}",
+            i16::from(*record_type),
+            value.len()
+        ),
+    }
+}
@@ -0,0 +1,116 @@
+use std::{
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    config::ServerProperties,
+    metadata::{
+        controller::{
+            NewTopic, CLUSTER_METADATA_PARTITION, CLUSTER_METADATA_TOPIC, METADATA_VERSION_FEATURE,
+        },
+        Controller,
+    },
+    protocol::cluster_metadata::{
+        Batch, FeatureRecordValue, Record, RecordValue, RecordValueByType,
+    },
+    storage::{
+        meta_properties::{
+            decode_uuid, encode_uuid, random_uuid, MetaProperties, META_PROPERTIES_FILE,
+        },
+        LogDir, LogManager,
+    },
+    Result,
+};
+
+/// `metadata.version` written to the bootstrap metadata log, 3.9-IV0.
+const BOOTSTRAP_METADATA_VERSION: i16 = 21;
+
+/// A new random cluster id, as printed by `kafka-storage.sh random-uuid`.
+pub fn random_cluster_id() -> String {
+    encode_uuid(random_uuid())
+}
+
+/// Formats the log directories of `properties` in the spirit of
+/// `kafka-storage.sh format`: each gets a `meta.properties`, and the
+/// metadata log is bootstrapped with `metadata.version` and `topics`, given
+/// as names with partition counts. Returns the cluster id, which is generated
+/// when `cluster_id` is `None`.
+pub fn format_storage(
+    properties: &ServerProperties,
+    cluster_id: Option<&str>,
+    topics: &[(String, i32)],
+) -> Result<String> {
+    let cluster_id = match cluster_id {
+        Some(cluster_id) => {
+            decode_uuid(cluster_id).ok_or_else(|| {
+                format!(
+                    "Cluster ID string {} does not appear to be a valid UUID",
+                    cluster_id
+                )
+            })?;
+            cluster_id.to_string()
+        }
+        None => random_cluster_id(),
+    };
+
+    let log_dirs = properties.log_dirs();
+    if let Some(dir) = log_dirs
+        .iter()
+        .find(|dir| dir.join(META_PROPERTIES_FILE).exists())
+    {
+        return Err(format!("Log directory {} is already formatted", dir.display()).into());
+    }
+
+    let log_dirs = log_dirs
+        .into_iter()
+        .map(|path| {
+            let id = random_uuid();
+            MetaProperties::new(&cluster_id, properties.node_id(), id)
+                .write(&path)
+                .map(|_| LogDir { path, id })
+        })
+        .collect::<std::io::Result<Vec<LogDir>>>()?;
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default();
+    let logs = Arc::new(LogManager::new(log_dirs));
+    let bootstrap = Batch::new(
+        0,
+        -1,
+        -1,
+        -1,
+        timestamp,
+        vec![Record::new(
+            0,
+            None,
+            RecordValue::new(RecordValueByType::Feature(FeatureRecordValue::new(
+                METADATA_VERSION_FEATURE,
+                BOOTSTRAP_METADATA_VERSION,
+            ))),
+        )],
+    );
+    logs.with_log(CLUSTER_METADATA_TOPIC, CLUSTER_METADATA_PARTITION, |log| {
+        log.append(bootstrap)
+    })?;
+
+    // Topics go through the controller so they are validated and laid out
+    // exactly as if they had been created through CreateTopics.
+    let controller = Controller::load(logs, properties.clone(), cluster_id.clone())?;
+    for (name, num_partitions) in topics {
+        let topic = NewTopic {
+            name: name.clone(),
+            num_partitions: *num_partitions,
+            replication_factor: -1,
+            assignments: Vec::new(),
+            configs: Vec::new(),
+        };
+        controller
+            .create_topic(&topic, false)
+            .map_err(|e| format!("failed to create topic {}: {}", name, e.message))?;
+    }
+
+    Ok(cluster_id)
+}
@@ -0,0 +1,37 @@
+use crate::protocol::{
+    messages::AddOffsetsToTxnResponse,
+    primitives::ApiKey,
+    request::RequestV0,
+    response::{ErrorCode, ResponseBody},
+};
+
+use super::{transaction_error, BrokerState, RequestContext, RequestHandler};
+
+pub(crate) struct AddOffsetsToTxnHandler;
+
+impl RequestHandler<BrokerState> for AddOffsetsToTxnHandler {
+    fn api_key(&self) -> ApiKey {
+        ApiKey::AddOffsetsToTxn
+    }
+
+    fn versions(&self) -> (i16, i16) {
+        (0, 3)
+    }
+
+    fn handle(&self, ctx: &RequestContext<'_, BrokerState>, request: &RequestV0) -> ResponseBody {
+        let error_code = match request.body().as_add_offsets_to_txn_request() {
+            Some(body) => ctx.state.transaction_coordinator.add_offsets(
+                &body.transactional_id,
+                body.producer_id,
+                body.producer_epoch,
+            ),
+            None => ErrorCode::UnknownServerError,
+        };
+
+        ResponseBody::AddOffsetsToTxn(AddOffsetsToTxnResponse {
+            error_code: transaction_error(error_code, request.header().request_api_version())
+                as i16,
+            ..Default::default()
+        })
+    }
+}
@@ -0,0 +1,97 @@
+use crate::protocol::{
+    messages::{
+        add_partitions_to_txn_response::{
+            AddPartitionsToTxnPartitionResult, AddPartitionsToTxnTopicResult,
+        },
+        AddPartitionsToTxnResponse,
+    },
+    primitives::ApiKey,
+    request::RequestV0,
+    response::{ErrorCode, ResponseBody},
+};
+
+use super::{transaction_error, BrokerState, RequestContext, RequestHandler};
+
+pub(crate) struct AddPartitionsToTxnHandler;
+
+impl RequestHandler<BrokerState> for AddPartitionsToTxnHandler {
+    fn api_key(&self) -> ApiKey {
+        ApiKey::AddPartitionsToTxn
+    }
+
+    fn versions(&self) -> (i16, i16) {
+        (0, 3)
+    }
+
+    fn handle(&self, ctx: &RequestContext<'_, BrokerState>, request: &RequestV0) -> ResponseBody {
+        let Some(body) = request.body().as_add_partitions_to_txn_request() else {
+            return ResponseBody::AddPartitionsToTxn(AddPartitionsToTxnResponse::default());
+        };
+
+        let requested = body
+            .v3_and_below_topics
+            .iter()
+            .flat_map(|topic| {
+                topic
+                    .partitions
+                    .iter()
+                    .map(|partition| (topic.name.clone(), *partition))
+            })
+            .collect::<Vec<(String, i32)>>();
+
+        // Kafka adds either all partitions or none of them, so a single
+        // unknown partition fails the request.
+        let unknown = requested
+            .iter()
+            .filter(|(topic, partition)| !ctx.partition_exists(topic, *partition))
+            .cloned()
+            .collect::<Vec<(String, i32)>>();
+
+        let error_code = if unknown.is_empty() {
+            transaction_error(
+                ctx.state.transaction_coordinator.add_partitions(
+                    &body.v3_and_below_transactional_id,
+                    body.v3_and_below_producer_id,
+                    body.v3_and_below_producer_epoch,
+                    &requested,
+                ),
+                request.header().request_api_version(),
+            )
+        } else {
+            ErrorCode::OperationNotAttempted
+        };
+
+        let results = body
+            .v3_and_below_topics
+            .iter()
+            .map(|topic| AddPartitionsToTxnTopicResult {
+                name: topic.name.clone(),
+                results_by_partition: topic
+                    .partitions
+                    .iter()
+                    .map(|&partition| {
+                        let partition_error_code = if unknown
+                            .iter()
+                            .any(|(t, p)| *t == topic.name && *p == partition)
+                        {
+                            ErrorCode::UnknownTopicOrPartition
+                        } else {
+                            error_code
+                        };
+                        AddPartitionsToTxnPartitionResult {
+                            partition_index: partition,
+                            partition_error_code: partition_error_code as i16,
+                            ..Default::default()
+                        }
+                    })
+                    .collect(),
+                ..Default::default()
+            })
+            .collect();
+
+        ResponseBody::AddPartitionsToTxn(AddPartitionsToTxnResponse {
+            results_by_topic_v3_and_below: results,
+            ..Default::default()
+        })
+    }
+}
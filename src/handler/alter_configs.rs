@@ -0,0 +1,74 @@
+use crate::metadata::controller::ControllerError;
+use crate::protocol::{
+    messages::{alter_configs_response::AlterConfigsResourceResponse, AlterConfigsResponse},
+    primitives::ApiKey,
+    request::RequestV0,
+    response::{ErrorCode, ResponseBody},
+};
+
+use super::{BrokerState, RequestContext, RequestHandler};
+
+pub(crate) struct AlterConfigsHandler;
+
+impl RequestHandler<BrokerState> for AlterConfigsHandler {
+    fn api_key(&self) -> ApiKey {
+        ApiKey::AlterConfigs
+    }
+
+    fn versions(&self) -> (i16, i16) {
+        (0, 2)
+    }
+
+    fn handle(&self, ctx: &RequestContext<'_, BrokerState>, request: &RequestV0) -> ResponseBody {
+        let Some(body) = request.body().as_alter_configs_request() else {
+            return ResponseBody::AlterConfigs(AlterConfigsResponse::default());
+        };
+
+        let responses = body
+            .resources
+            .iter()
+            .map(|resource| {
+                let configs = resource
+                    .configs
+                    .iter()
+                    .map(|config| (config.name.clone(), config.value.clone()))
+                    .collect::<Vec<(String, Option<String>)>>();
+
+                let result = ctx.state.controller.alter_configs(
+                    resource.resource_type,
+                    &resource.resource_name,
+                    &configs,
+                    body.validate_only,
+                );
+
+                let (error_code, error_message) = error_and_message(result);
+                AlterConfigsResourceResponse {
+                    error_code: error_code as i16,
+                    error_message,
+                    resource_type: resource.resource_type,
+                    resource_name: resource.resource_name.clone(),
+                    ..Default::default()
+                }
+            })
+            .collect();
+
+        ResponseBody::AlterConfigs(AlterConfigsResponse {
+            responses,
+            ..Default::default()
+        })
+    }
+}
+
+/// The error code and message a resource is answered with, shared with
+/// IncrementalAlterConfigs.
+pub(super) fn error_and_message(
+    result: std::result::Result<(), ControllerError>,
+) -> (ErrorCode, Option<String>) {
+    match result {
+        Ok(()) => (ErrorCode::None, None),
+        Err(ControllerError {
+            error_code,
+            message,
+        }) => (error_code, Some(message)),
+    }
+}
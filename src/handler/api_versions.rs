@@ -0,0 +1,185 @@
+use bytes::Buf;
+
+use crate::metadata::controller::SUPPORTED_FEATURES;
+use crate::protocol::{
+    bytes::FromVersionedBytes,
+    messages::{
+        api_versions_response::{ApiVersion, FinalizedFeatureKey, SupportedFeatureKey},
+        ApiVersionsRequest, ApiVersionsResponse,
+    },
+    primitives::ApiKey,
+    request::{RequestBody, RequestV0},
+    response::{ErrorCode, ResponseBody, ResponseHeader, ResponseV0},
+};
+
+use super::{BrokerState, RequestContext, RequestHandler};
+
+use crate::Result;
+
+pub(crate) struct ApiVersionsHandler;
+
+/// The oldest and newest ApiVersions the broker answers in.
+const VERSIONS: (i16, i16) = (0, 4);
+
+/// A version the broker doesn't know isn't decoded, as the client only needs
+/// the supported versions back to negotiate.
+fn decode_request(mut buf: &mut dyn Buf, version: i16) -> Result<RequestBody> {
+    if !(VERSIONS.0..=VERSIONS.1).contains(&version) {
+        return Ok(RequestBody::ApiVersions(ApiVersionsRequest::default()));
+    }
+
+    Ok(RequestBody::ApiVersions(
+        ApiVersionsRequest::from_versioned_be_bytes(&mut buf, version)
+            .map_err(|e| anyhow::anyhow!("failed to parse ApiVersionsRequest: {}", e))?,
+    ))
+}
+
+/// Answers ApiVersions `version` with `api_versions`. A version missing from
+/// the ApiVersions entry is answered with UNSUPPORTED_VERSION, which
+/// [`encode_response`] encodes in v0 for any client to read before retrying
+/// with the newest version both sides support.
+fn negotiate(version: i16, api_versions: Vec<ApiVersion>) -> ApiVersionsResponse {
+    let supported = api_versions.iter().any(|api_version| {
+        api_version.api_key == ApiKey::ApiVersions as i16
+            && (api_version.min_version..=api_version.max_version).contains(&version)
+    });
+
+    ApiVersionsResponse {
+        error_code: match supported {
+            true => ErrorCode::None,
+            false => ErrorCode::UnsupportedVersion,
+        } as i16,
+        api_keys: api_versions,
+        ..Default::default()
+    }
+}
+
+fn encode_response(request: &RequestV0, body: ResponseBody) -> ResponseV0 {
+    let header = request.header();
+    let version = match &body {
+        ResponseBody::ApiVersions(response)
+            if response.error_code == ErrorCode::UnsupportedVersion as i16 =>
+        {
+            0
+        }
+        _ => header.request_api_version(),
+    };
+
+    ResponseV0::new(
+        ResponseHeader::new(ApiKey::ApiVersions, version, header.correlation_id()),
+        version,
+        body,
+    )
+}
+
+impl RequestHandler<BrokerState> for ApiVersionsHandler {
+    fn api_key(&self) -> ApiKey {
+        ApiKey::ApiVersions
+    }
+
+    fn versions(&self) -> (i16, i16) {
+        VERSIONS
+    }
+
+    fn decode(&self, buf: &mut dyn Buf, version: i16) -> Result<RequestBody> {
+        decode_request(buf, version)
+    }
+
+    /// Lists the registered APIs and, from v3 on, the features this broker
+    /// supports and the levels the cluster has finalized.
+    fn handle(&self, ctx: &RequestContext<'_, BrokerState>, request: &RequestV0) -> ResponseBody {
+        let version = request.header().request_api_version();
+        let body = negotiate(version, ctx.registry.api_versions());
+        if body.error_code != ErrorCode::None as i16 {
+            return ResponseBody::ApiVersions(body);
+        }
+
+        let image = ctx.state.controller.image();
+        let supported_features = SUPPORTED_FEATURES
+            .iter()
+            .map(|(name, min_level, max_level)| SupportedFeatureKey {
+                name: name.to_string(),
+                min_version: *min_level,
+                max_version: *max_level,
+                ..Default::default()
+            })
+            .collect();
+        // Finalized features are versioned by the offset of the last record
+        // applied to the image.
+        let finalized_features = image
+            .features()
+            .iter()
+            .map(|(name, level)| FinalizedFeatureKey {
+                name: name.clone(),
+                max_version_level: *level,
+                min_version_level: *level,
+                ..Default::default()
+            })
+            .collect();
+
+        ResponseBody::ApiVersions(ApiVersionsResponse {
+            supported_features,
+            finalized_features_epoch: image.next_offset() - 1,
+            finalized_features,
+            ..body
+        })
+    }
+
+    fn encode(&self, request: &RequestV0, body: ResponseBody) -> ResponseV0 {
+        encode_response(request, body)
+    }
+}
+
+/// Serves a server without cluster state, which has no features to list.
+impl RequestHandler<()> for ApiVersionsHandler {
+    fn api_key(&self) -> ApiKey {
+        ApiKey::ApiVersions
+    }
+
+    fn versions(&self) -> (i16, i16) {
+        VERSIONS
+    }
+
+    fn decode(&self, buf: &mut dyn Buf, version: i16) -> Result<RequestBody> {
+        decode_request(buf, version)
+    }
+
+    fn handle(&self, ctx: &RequestContext<'_, ()>, request: &RequestV0) -> ResponseBody {
+        ResponseBody::ApiVersions(negotiate(
+            request.header().request_api_version(),
+            ctx.registry.api_versions(),
+        ))
+    }
+
+    fn encode(&self, request: &RequestV0, body: ResponseBody) -> ResponseV0 {
+        encode_response(request, body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handler::tests::TestBroker;
+
+    #[test]
+    fn unsupported_version_is_answered_and_decoded_in_v0() {
+        let broker = TestBroker::new();
+
+        let response = broker
+            .send(
+                VERSIONS.1 + 1,
+                RequestBody::ApiVersions(ApiVersionsRequest::default()),
+            )
+            .unwrap();
+
+        assert_eq!(response.api_version(), 0);
+        let ResponseBody::ApiVersions(body) = response.body() else {
+            panic!("unexpected response {:?}", response);
+        };
+        assert_eq!(body.error_code, ErrorCode::UnsupportedVersion as i16);
+        assert!(body.api_keys.iter().any(|api_version| {
+            api_version.api_key == ApiKey::ApiVersions as i16
+                && (api_version.min_version, api_version.max_version) == VERSIONS
+        }));
+    }
+}
@@ -0,0 +1,91 @@
+use std::collections::HashSet;
+
+use crate::metadata::controller::ControllerError;
+use crate::protocol::{
+    messages::{create_partitions_response::CreatePartitionsTopicResult, CreatePartitionsResponse},
+    primitives::ApiKey,
+    request::RequestV0,
+    response::{ErrorCode, ResponseBody},
+};
+
+use super::{BrokerState, RequestContext, RequestHandler};
+
+pub(crate) struct CreatePartitionsHandler;
+
+impl RequestHandler<BrokerState> for CreatePartitionsHandler {
+    fn api_key(&self) -> ApiKey {
+        ApiKey::CreatePartitions
+    }
+
+    fn versions(&self) -> (i16, i16) {
+        (0, 3)
+    }
+
+    fn handle(&self, ctx: &RequestContext<'_, BrokerState>, request: &RequestV0) -> ResponseBody {
+        let Some(body) = request.body().as_create_partitions_request() else {
+            return ResponseBody::CreatePartitions(CreatePartitionsResponse::default());
+        };
+
+        let mut seen = HashSet::new();
+        let duplicates = body
+            .topics
+            .iter()
+            .filter(|topic| !seen.insert(topic.name.as_str()))
+            .map(|topic| topic.name.as_str())
+            .collect::<HashSet<&str>>();
+
+        let results = body
+            .topics
+            .iter()
+            .map(|topic| {
+                if duplicates.contains(topic.name.as_str()) {
+                    return topic_result(
+                        &topic.name,
+                        ErrorCode::InvalidRequest,
+                        Some("Duplicate topic in request.".to_string()),
+                    );
+                }
+
+                // The replicas of each new partition; none when the request
+                // leaves placement to the broker.
+                let assignments = topic
+                    .assignments
+                    .iter()
+                    .flatten()
+                    .map(|assignment| assignment.broker_ids.clone())
+                    .collect::<Vec<Vec<i32>>>();
+
+                match ctx.state.controller.create_partitions(
+                    &topic.name,
+                    topic.count,
+                    &assignments,
+                    body.validate_only,
+                ) {
+                    Ok(()) => topic_result(&topic.name, ErrorCode::None, None),
+                    Err(ControllerError {
+                        error_code,
+                        message,
+                    }) => topic_result(&topic.name, error_code, Some(message)),
+                }
+            })
+            .collect();
+
+        ResponseBody::CreatePartitions(CreatePartitionsResponse {
+            results,
+            ..Default::default()
+        })
+    }
+}
+
+fn topic_result(
+    name: &str,
+    error_code: ErrorCode,
+    error_message: Option<String>,
+) -> CreatePartitionsTopicResult {
+    CreatePartitionsTopicResult {
+        name: name.to_string(),
+        error_code: error_code as i16,
+        error_message,
+        ..Default::default()
+    }
+}
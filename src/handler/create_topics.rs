@@ -0,0 +1,188 @@
+use std::collections::HashSet;
+
+use crate::metadata::controller::{ControllerError, NewTopic};
+use crate::protocol::{
+    messages::{
+        create_topics_response::{CreatableTopicConfigs, CreatableTopicResult},
+        CreateTopicsResponse,
+    },
+    primitives::ApiKey,
+    request::RequestV0,
+    response::{ErrorCode, ResponseBody},
+};
+
+use super::{BrokerState, RequestContext, RequestHandler};
+
+pub(crate) struct CreateTopicsHandler;
+
+impl RequestHandler<BrokerState> for CreateTopicsHandler {
+    fn api_key(&self) -> ApiKey {
+        ApiKey::CreateTopics
+    }
+
+    fn versions(&self) -> (i16, i16) {
+        (0, 7)
+    }
+
+    fn handle(&self, ctx: &RequestContext<'_, BrokerState>, request: &RequestV0) -> ResponseBody {
+        let Some(body) = request.body().as_create_topics_request() else {
+            return ResponseBody::CreateTopics(CreateTopicsResponse::default());
+        };
+
+        let mut seen = HashSet::new();
+        let duplicates = body
+            .topics
+            .iter()
+            .filter(|topic| !seen.insert(topic.name.as_str()))
+            .map(|topic| topic.name.as_str())
+            .collect::<HashSet<&str>>();
+
+        let topics = body
+            .topics
+            .iter()
+            .map(|topic| {
+                if duplicates.contains(topic.name.as_str()) {
+                    return topic_error(
+                        &topic.name,
+                        ErrorCode::InvalidRequest,
+                        format!(
+                            "Create topics request contains multiple entries for topic {}",
+                            topic.name
+                        ),
+                    );
+                }
+
+                let new_topic = NewTopic {
+                    name: topic.name.clone(),
+                    num_partitions: topic.num_partitions,
+                    replication_factor: topic.replication_factor,
+                    assignments: topic
+                        .assignments
+                        .iter()
+                        .map(|assignment| {
+                            (assignment.partition_index, assignment.broker_ids.clone())
+                        })
+                        .collect(),
+                    configs: topic
+                        .configs
+                        .iter()
+                        .map(|config| (config.name.clone(), config.value.clone()))
+                        .collect(),
+                };
+
+                match ctx
+                    .state
+                    .controller
+                    .create_topic(&new_topic, body.validate_only)
+                {
+                    Ok(created) => CreatableTopicResult {
+                        name: topic.name.clone(),
+                        topic_id: created.topic_id,
+                        error_code: ErrorCode::None as i16,
+                        error_message: None,
+                        num_partitions: created.num_partitions,
+                        replication_factor: created.replication_factor,
+                        configs: Some(
+                            created
+                                .configs
+                                .iter()
+                                .map(|entry| CreatableTopicConfigs {
+                                    name: entry.name.clone(),
+                                    value: entry.value.clone(),
+                                    read_only: entry.read_only,
+                                    config_source: entry.source as i8,
+                                    is_sensitive: false,
+                                    ..Default::default()
+                                })
+                                .collect(),
+                        ),
+                        ..Default::default()
+                    },
+                    Err(ControllerError {
+                        error_code,
+                        message,
+                    }) => topic_error(&topic.name, error_code, message),
+                }
+            })
+            .collect();
+
+        ResponseBody::CreateTopics(CreateTopicsResponse {
+            topics,
+            ..Default::default()
+        })
+    }
+}
+
+fn topic_error(name: &str, error_code: ErrorCode, error_message: String) -> CreatableTopicResult {
+    CreatableTopicResult {
+        name: name.to_string(),
+        error_code: error_code as i16,
+        error_message: Some(error_message),
+        num_partitions: -1,
+        replication_factor: -1,
+        configs: Some(Vec::new()),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handler::tests::TestBroker;
+    use crate::protocol::{
+        messages::{create_topics_request::CreatableTopic, CreateTopicsRequest},
+        request::RequestBody,
+    };
+    use crate::transaction::coordinator::{CONSUMER_OFFSETS_TOPIC, TRANSACTION_STATE_TOPIC};
+
+    #[test]
+    fn internal_topics_can_not_be_created() {
+        let broker = TestBroker::new();
+
+        let response = broker
+            .send(
+                7,
+                RequestBody::CreateTopics(CreateTopicsRequest {
+                    topics: [TRANSACTION_STATE_TOPIC, CONSUMER_OFFSETS_TOPIC, "foo"]
+                        .into_iter()
+                        .map(|name| CreatableTopic {
+                            name: name.to_string(),
+                            num_partitions: 1,
+                            replication_factor: -1,
+                            ..Default::default()
+                        })
+                        .collect(),
+                    ..Default::default()
+                }),
+            )
+            .unwrap();
+        let ResponseBody::CreateTopics(body) = response.body() else {
+            panic!("unexpected response {:?}", response);
+        };
+        let results = body
+            .topics
+            .iter()
+            .map(|topic| (topic.name.as_str(), topic.error_code))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            results,
+            [
+                (
+                    TRANSACTION_STATE_TOPIC,
+                    ErrorCode::InvalidTopicException as i16
+                ),
+                (
+                    CONSUMER_OFFSETS_TOPIC,
+                    ErrorCode::InvalidTopicException as i16
+                ),
+                ("foo", ErrorCode::None as i16),
+            ]
+        );
+        assert!(broker
+            .state
+            .controller
+            .image()
+            .topic(TRANSACTION_STATE_TOPIC)
+            .is_none());
+    }
+}
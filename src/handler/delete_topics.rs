@@ -0,0 +1,174 @@
+use uuid::Uuid;
+
+use crate::metadata::controller::ControllerError;
+use crate::protocol::{
+    messages::{delete_topics_response::DeletableTopicResult, DeleteTopicsResponse},
+    primitives::ApiKey,
+    request::RequestV0,
+    response::{ErrorCode, ResponseBody},
+};
+
+use super::{BrokerState, RequestContext, RequestHandler};
+
+pub(crate) struct DeleteTopicsHandler;
+
+impl RequestHandler<BrokerState> for DeleteTopicsHandler {
+    fn api_key(&self) -> ApiKey {
+        ApiKey::DeleteTopics
+    }
+
+    fn versions(&self) -> (i16, i16) {
+        (0, 6)
+    }
+
+    fn handle(&self, ctx: &RequestContext<'_, BrokerState>, request: &RequestV0) -> ResponseBody {
+        let Some(body) = request.body().as_delete_topics_request() else {
+            return ResponseBody::DeleteTopics(DeleteTopicsResponse::default());
+        };
+
+        // Versions before 6 name the topics in `topic_names`, later ones
+        // name or identify them in `topics`.
+        let requested = body
+            .topic_names
+            .iter()
+            .map(|name| (Some(name.clone()), Uuid::nil()))
+            .chain(
+                body.topics
+                    .iter()
+                    .map(|topic| (topic.name.clone(), topic.topic_id)),
+            );
+
+        let responses = requested
+            .map(|(name, topic_id)| {
+                match ctx.state.controller.delete_topic(name.as_deref(), topic_id) {
+                    Ok((name, topic_id)) => DeletableTopicResult {
+                        name: Some(name),
+                        topic_id,
+                        error_code: ErrorCode::None as i16,
+                        error_message: None,
+                        ..Default::default()
+                    },
+                    Err(ControllerError {
+                        error_code,
+                        message,
+                    }) => DeletableTopicResult {
+                        name,
+                        topic_id,
+                        error_code: error_code as i16,
+                        error_message: Some(message),
+                        ..Default::default()
+                    },
+                }
+            })
+            .collect();
+
+        ResponseBody::DeleteTopics(DeleteTopicsResponse {
+            responses,
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handler::tests::TestBroker;
+    use crate::protocol::{
+        messages::{delete_topics_request::DeleteTopicState, DeleteTopicsRequest},
+        request::RequestBody,
+    };
+    use crate::transaction::coordinator::{CONSUMER_OFFSETS_TOPIC, TRANSACTION_STATE_TOPIC};
+
+    #[test]
+    fn deletes_topics_by_name_before_version_6_and_by_state_after() {
+        let broker = TestBroker::new();
+        broker.create_topic("foo", 1);
+        broker.create_topic("bar", 1);
+
+        let response = broker
+            .send(
+                0,
+                RequestBody::DeleteTopics(DeleteTopicsRequest {
+                    topic_names: vec!["foo".to_string(), "baz".to_string()],
+                    ..Default::default()
+                }),
+            )
+            .unwrap();
+        let ResponseBody::DeleteTopics(body) = response.body() else {
+            panic!("unexpected response {:?}", response);
+        };
+        let results = body
+            .responses
+            .iter()
+            .map(|result| (result.name.as_deref(), result.error_code))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            results,
+            [
+                (Some("foo"), ErrorCode::None as i16),
+                (Some("baz"), ErrorCode::UnknownTopicOrPartition as i16),
+            ]
+        );
+
+        let bar = broker.state.controller.image().topic("bar").unwrap().id();
+        let response = broker
+            .send(
+                6,
+                RequestBody::DeleteTopics(DeleteTopicsRequest {
+                    topics: vec![DeleteTopicState {
+                        name: None,
+                        topic_id: bar,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }),
+            )
+            .unwrap();
+        let ResponseBody::DeleteTopics(body) = response.body() else {
+            panic!("unexpected response {:?}", response);
+        };
+        assert_eq!(body.responses.len(), 1);
+        assert_eq!(body.responses[0].name.as_deref(), Some("bar"));
+        assert_eq!(body.responses[0].error_code, ErrorCode::None as i16);
+    }
+
+    #[test]
+    fn internal_topics_can_not_be_deleted() {
+        let broker = TestBroker::new();
+        broker
+            .state
+            .transaction_coordinator
+            .init_producer_id(Some("txn"), 60_000, -1, -1)
+            .unwrap();
+
+        let response = broker
+            .send(
+                6,
+                RequestBody::DeleteTopics(DeleteTopicsRequest {
+                    topics: [TRANSACTION_STATE_TOPIC, CONSUMER_OFFSETS_TOPIC]
+                        .into_iter()
+                        .map(|name| DeleteTopicState {
+                            name: Some(name.to_string()),
+                            ..Default::default()
+                        })
+                        .collect(),
+                    ..Default::default()
+                }),
+            )
+            .unwrap();
+        let ResponseBody::DeleteTopics(body) = response.body() else {
+            panic!("unexpected response {:?}", response);
+        };
+        assert!(body
+            .responses
+            .iter()
+            .all(|result| result.error_code == ErrorCode::InvalidRequest as i16));
+
+        let transaction_log = broker
+            .state
+            .logs
+            .with_log(TRANSACTION_STATE_TOPIC, 0, |log| log.read_all())
+            .unwrap();
+        assert!(!transaction_log.is_empty());
+    }
+}
@@ -0,0 +1,65 @@
+use crate::protocol::{
+    messages::{describe_cluster_response::DescribeClusterBroker, DescribeClusterResponse},
+    primitives::ApiKey,
+    request::RequestV0,
+    response::{ErrorCode, ResponseBody},
+};
+
+use super::{BrokerState, RequestContext, RequestHandler};
+
+/// The DescribeCluster endpoint type asking for brokers rather than
+/// controllers.
+const BROKER_ENDPOINT_TYPE: i8 = 1;
+
+pub(crate) struct DescribeClusterHandler;
+
+impl RequestHandler<BrokerState> for DescribeClusterHandler {
+    fn api_key(&self) -> ApiKey {
+        ApiKey::DescribeCluster
+    }
+
+    fn versions(&self) -> (i16, i16) {
+        (0, 1)
+    }
+
+    fn handle(&self, ctx: &RequestContext<'_, BrokerState>, request: &RequestV0) -> ResponseBody {
+        let endpoint_type = request
+            .body()
+            .as_describe_cluster_request()
+            .map(|body| body.endpoint_type)
+            .unwrap_or(BROKER_ENDPOINT_TYPE);
+
+        // This node only listens for clients, so it can't describe the
+        // controller quorum.
+        if endpoint_type != BROKER_ENDPOINT_TYPE {
+            return ResponseBody::DescribeCluster(DescribeClusterResponse {
+                error_code: ErrorCode::UnsupportedEndpointType as i16,
+                error_message: Some(format!(
+                    "The request was sent to a broker endpoint, but endpoint type {} was \
+                     requested",
+                    endpoint_type
+                )),
+                endpoint_type,
+                controller_id: -1,
+                ..Default::default()
+            });
+        }
+
+        let broker = ctx.broker();
+        ResponseBody::DescribeCluster(DescribeClusterResponse {
+            error_code: ErrorCode::None as i16,
+            error_message: None,
+            endpoint_type,
+            cluster_id: ctx.state.controller.cluster_id().to_string(),
+            controller_id: ctx.state.controller.node_id(),
+            brokers: vec![DescribeClusterBroker {
+                broker_id: broker.node_id,
+                host: broker.host,
+                port: broker.port,
+                rack: broker.rack,
+                ..Default::default()
+            }],
+            ..Default::default()
+        })
+    }
+}
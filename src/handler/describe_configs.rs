@@ -0,0 +1,109 @@
+use crate::config::ConfigSource;
+use crate::metadata::controller::ControllerError;
+use crate::protocol::{
+    messages::{
+        describe_configs_response::{
+            DescribeConfigsResourceResult, DescribeConfigsResult, DescribeConfigsSynonym,
+        },
+        DescribeConfigsResponse,
+    },
+    primitives::ApiKey,
+    request::RequestV0,
+    response::{ErrorCode, ResponseBody},
+};
+
+use super::{BrokerState, RequestContext, RequestHandler};
+
+pub(crate) struct DescribeConfigsHandler;
+
+impl RequestHandler<BrokerState> for DescribeConfigsHandler {
+    fn api_key(&self) -> ApiKey {
+        ApiKey::DescribeConfigs
+    }
+
+    fn versions(&self) -> (i16, i16) {
+        (0, 4)
+    }
+
+    fn handle(&self, ctx: &RequestContext<'_, BrokerState>, request: &RequestV0) -> ResponseBody {
+        let Some(body) = request.body().as_describe_configs_request() else {
+            return ResponseBody::DescribeConfigs(DescribeConfigsResponse::default());
+        };
+
+        let results = body
+            .resources
+            .iter()
+            .map(|resource| {
+                // A null and an empty array both describe every config.
+                let keys = resource
+                    .configuration_keys
+                    .as_deref()
+                    .filter(|keys| !keys.is_empty());
+
+                match ctx.state.controller.describe_configs(
+                    resource.resource_type,
+                    &resource.resource_name,
+                    keys,
+                ) {
+                    Ok(entries) => DescribeConfigsResult {
+                        error_code: ErrorCode::None as i16,
+                        error_message: None,
+                        resource_type: resource.resource_type,
+                        resource_name: resource.resource_name.clone(),
+                        configs: entries
+                            .iter()
+                            .map(|entry| {
+                                let synonyms = if body.include_synonyms {
+                                    entry
+                                        .synonyms
+                                        .iter()
+                                        .map(|synonym| DescribeConfigsSynonym {
+                                            name: synonym.name.clone(),
+                                            value: synonym.value.clone(),
+                                            source: synonym.source as i8,
+                                            ..Default::default()
+                                        })
+                                        .collect()
+                                } else {
+                                    Vec::new()
+                                };
+
+                                DescribeConfigsResourceResult {
+                                    name: entry.name.clone(),
+                                    value: entry.value.clone(),
+                                    read_only: entry.read_only,
+                                    is_default: entry.source == ConfigSource::Default,
+                                    config_source: entry.source as i8,
+                                    is_sensitive: false,
+                                    synonyms,
+                                    config_type: entry.config_type as i8,
+                                    documentation: entry
+                                        .documentation
+                                        .filter(|_| body.include_documentation)
+                                        .map(str::to_string),
+                                    ..Default::default()
+                                }
+                            })
+                            .collect(),
+                        ..Default::default()
+                    },
+                    Err(ControllerError {
+                        error_code,
+                        message,
+                    }) => DescribeConfigsResult {
+                        error_code: error_code as i16,
+                        error_message: Some(message),
+                        resource_type: resource.resource_type,
+                        resource_name: resource.resource_name.clone(),
+                        ..Default::default()
+                    },
+                }
+            })
+            .collect();
+
+        ResponseBody::DescribeConfigs(DescribeConfigsResponse {
+            results,
+            ..Default::default()
+        })
+    }
+}
@@ -0,0 +1,95 @@
+use crate::protocol::{
+    messages::{
+        describe_log_dirs_response::{
+            DescribeLogDirsPartition, DescribeLogDirsResult, DescribeLogDirsTopic,
+        },
+        DescribeLogDirsResponse,
+    },
+    primitives::ApiKey,
+    request::RequestV0,
+    response::{ErrorCode, ResponseBody},
+};
+
+use super::{BrokerState, RequestContext, RequestHandler};
+
+pub(crate) struct DescribeLogDirsHandler;
+
+impl RequestHandler<BrokerState> for DescribeLogDirsHandler {
+    fn api_key(&self) -> ApiKey {
+        ApiKey::DescribeLogDirs
+    }
+
+    fn versions(&self) -> (i16, i16) {
+        (0, 4)
+    }
+
+    fn handle(&self, ctx: &RequestContext<'_, BrokerState>, request: &RequestV0) -> ResponseBody {
+        let requested = request
+            .body()
+            .as_describe_log_dirs_request()
+            .and_then(|body| body.topics.as_ref());
+        let is_requested = |topic: &str, partition: i32| match requested {
+            None => true,
+            Some(topics) => topics
+                .iter()
+                .any(|t| t.topic == topic && t.partitions.contains(&partition)),
+        };
+
+        let results = ctx
+            .state
+            .logs
+            .log_dirs()
+            .iter()
+            .map(|log_dir| {
+                let log_dir_name = log_dir.path.to_string_lossy();
+                let partitions = match ctx.state.logs.partition_sizes(log_dir) {
+                    Ok(partitions) => partitions,
+                    Err(e) => {
+                        eprintln!("failed to describe log dir {}: {}", log_dir_name, e);
+                        return DescribeLogDirsResult {
+                            error_code: ErrorCode::KafkaStorageError as i16,
+                            log_dir: log_dir_name.to_string(),
+                            ..Default::default()
+                        };
+                    }
+                };
+
+                // Partitions come sorted by topic, so each topic is one run.
+                let mut topics: Vec<(String, Vec<DescribeLogDirsPartition>)> = Vec::new();
+                for partition in partitions
+                    .into_iter()
+                    .filter(|p| is_requested(&p.topic, p.partition))
+                {
+                    let entry = DescribeLogDirsPartition {
+                        partition_index: partition.partition,
+                        partition_size: partition.size as i64,
+                        ..Default::default()
+                    };
+                    match topics.last_mut() {
+                        Some((topic, entries)) if *topic == partition.topic => entries.push(entry),
+                        _ => topics.push((partition.topic, vec![entry])),
+                    }
+                }
+
+                DescribeLogDirsResult {
+                    error_code: ErrorCode::None as i16,
+                    log_dir: log_dir_name.to_string(),
+                    topics: topics
+                        .into_iter()
+                        .map(|(name, partitions)| DescribeLogDirsTopic {
+                            name,
+                            partitions,
+                            ..Default::default()
+                        })
+                        .collect(),
+                    ..Default::default()
+                }
+            })
+            .collect();
+
+        ResponseBody::DescribeLogDirs(DescribeLogDirsResponse {
+            results,
+            ..Default::default()
+        })
+    }
+}
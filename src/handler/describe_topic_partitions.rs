@@ -0,0 +1,186 @@
+use crate::metadata::image::PartitionImage;
+use crate::protocol::{
+    messages::{
+        describe_topic_partitions_response::{
+            Cursor, DescribeTopicPartitionsResponsePartition, DescribeTopicPartitionsResponseTopic,
+        },
+        DescribeTopicPartitionsRequest, DescribeTopicPartitionsResponse,
+    },
+    primitives::ApiKey,
+    request::RequestV0,
+    response::{ErrorCode, ResponseBody},
+};
+
+use super::{BrokerState, RequestContext, RequestHandler};
+
+/// Most partitions a single DescribeTopicPartitions response describes,
+/// whatever the client asks for.
+const MAX_REQUEST_PARTITION_SIZE_LIMIT: i32 = 2000;
+
+pub(crate) struct DescribeTopicPartitionsHandler;
+
+impl RequestHandler<BrokerState> for DescribeTopicPartitionsHandler {
+    fn api_key(&self) -> ApiKey {
+        ApiKey::DescribeTopicPartitions
+    }
+
+    fn versions(&self) -> (i16, i16) {
+        (0, 0)
+    }
+
+    /// Describes the requested topics in name order, returning at most
+    /// `response_partition_limit` partitions and a cursor to the rest. An
+    /// empty topic list describes every topic.
+    fn handle(&self, ctx: &RequestContext<'_, BrokerState>, request: &RequestV0) -> ResponseBody {
+        let default = DescribeTopicPartitionsRequest::default();
+        let body = request
+            .body()
+            .as_describe_topic_partitions_request()
+            .unwrap_or(&default);
+        let cursor = body.cursor.as_ref();
+
+        let image = ctx.state.controller.image();
+
+        let mut topic_names = body
+            .topics
+            .iter()
+            .map(|topic| topic.name.clone())
+            .collect::<Vec<String>>();
+        if topic_names.is_empty() {
+            topic_names = image
+                .topics()
+                .map(|topic| topic.name().to_string())
+                .collect();
+        }
+        topic_names.sort();
+        topic_names.dedup();
+        // Topics before the cursor were returned in earlier pages.
+        topic_names.retain(|name| cursor.map_or(true, |cursor| *name >= cursor.topic_name));
+
+        // A limit of zero or less would page forever without moving the
+        // cursor, so it gets the maximum instead.
+        let mut remaining = match body.response_partition_limit {
+            ..=0 => MAX_REQUEST_PARTITION_SIZE_LIMIT,
+            limit => limit.min(MAX_REQUEST_PARTITION_SIZE_LIMIT),
+        } as usize;
+        let mut topics = Vec::new();
+        let mut next_cursor = None;
+        for topic_name in topic_names {
+            let Some(topic) = image.topic(&topic_name) else {
+                topics.push(DescribeTopicPartitionsResponseTopic {
+                    error_code: ErrorCode::UnknownTopicOrPartition as i16,
+                    name: Some(topic_name),
+                    topic_authorized_operations: 0,
+                    ..Default::default()
+                });
+                continue;
+            };
+
+            let first_partition = cursor
+                .filter(|cursor| cursor.topic_name == topic_name)
+                .map_or(0, |cursor| cursor.partition_index);
+            let mut partitions = topic
+                .partitions()
+                .filter(|partition| partition.partition_id() >= first_partition)
+                .collect::<Vec<&PartitionImage>>();
+
+            if remaining == 0 {
+                next_cursor = Some(cursor_at(&topic_name, first_partition));
+                break;
+            }
+            if partitions.len() > remaining {
+                next_cursor = Some(cursor_at(&topic_name, partitions[remaining].partition_id()));
+                partitions.truncate(remaining);
+            }
+            remaining -= partitions.len();
+
+            topics.push(DescribeTopicPartitionsResponseTopic {
+                error_code: ErrorCode::None as i16,
+                name: Some(topic_name),
+                topic_id: topic.id(),
+                is_internal: topic.is_internal(),
+                partitions: partitions
+                    .into_iter()
+                    .map(|partition| {
+                        describe_partition(partition, ctx.offline_replicas(&image, partition))
+                    })
+                    .collect(),
+                topic_authorized_operations: 0,
+                ..Default::default()
+            });
+            if next_cursor.is_some() {
+                break;
+            }
+        }
+
+        ResponseBody::DescribeTopicPartitions(DescribeTopicPartitionsResponse {
+            topics,
+            next_cursor,
+            ..Default::default()
+        })
+    }
+}
+
+fn cursor_at(topic_name: &str, partition_index: i32) -> Cursor {
+    Cursor {
+        topic_name: topic_name.to_string(),
+        partition_index,
+        ..Default::default()
+    }
+}
+
+fn describe_partition(
+    partition: &PartitionImage,
+    offline_replicas: Vec<i32>,
+) -> DescribeTopicPartitionsResponsePartition {
+    DescribeTopicPartitionsResponsePartition {
+        error_code: ErrorCode::None as i16,
+        partition_index: partition.partition_id(),
+        leader_id: partition.leader(),
+        leader_epoch: partition.leader_epoch(),
+        replica_nodes: partition.replicas().to_vec(),
+        isr_nodes: partition.isr().to_vec(),
+        eligible_leader_replicas: Some(partition.eligible_leader_replicas().to_vec()),
+        last_known_elr: Some(partition.last_known_elr().to_vec()),
+        offline_replicas,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handler::tests::TestBroker;
+    use crate::protocol::{
+        messages::describe_topic_partitions_request::TopicRequest, request::RequestBody,
+    };
+
+    #[test]
+    fn non_positive_limit_describes_up_to_the_maximum() {
+        let broker = TestBroker::new();
+        broker.create_topic("foo", 3);
+
+        for response_partition_limit in [0, -1, i32::MIN] {
+            let response = broker
+                .send(
+                    0,
+                    RequestBody::DescribeTopicPartitions(DescribeTopicPartitionsRequest {
+                        topics: vec![TopicRequest {
+                            name: "foo".to_string(),
+                            ..Default::default()
+                        }],
+                        response_partition_limit,
+                        ..Default::default()
+                    }),
+                )
+                .unwrap();
+
+            let ResponseBody::DescribeTopicPartitions(body) = response.body() else {
+                panic!("unexpected response {:?}", response);
+            };
+            assert_eq!(body.topics.len(), 1);
+            assert_eq!(body.topics[0].partitions.len(), 3);
+            assert_eq!(body.next_cursor, None);
+        }
+    }
+}
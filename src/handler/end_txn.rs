@@ -0,0 +1,38 @@
+use crate::protocol::{
+    messages::EndTxnResponse,
+    primitives::ApiKey,
+    request::RequestV0,
+    response::{ErrorCode, ResponseBody},
+};
+
+use super::{transaction_error, BrokerState, RequestContext, RequestHandler};
+
+pub(crate) struct EndTxnHandler;
+
+impl RequestHandler<BrokerState> for EndTxnHandler {
+    fn api_key(&self) -> ApiKey {
+        ApiKey::EndTxn
+    }
+
+    fn versions(&self) -> (i16, i16) {
+        (0, 3)
+    }
+
+    fn handle(&self, ctx: &RequestContext<'_, BrokerState>, request: &RequestV0) -> ResponseBody {
+        let error_code = match request.body().as_end_txn_request() {
+            Some(body) => ctx.state.transaction_coordinator.end_transaction(
+                &body.transactional_id,
+                body.producer_id,
+                body.producer_epoch,
+                body.committed,
+            ),
+            None => ErrorCode::UnknownServerError,
+        };
+
+        ResponseBody::EndTxn(EndTxnResponse {
+            error_code: transaction_error(error_code, request.header().request_api_version())
+                as i16,
+            ..Default::default()
+        })
+    }
+}
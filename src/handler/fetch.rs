@@ -0,0 +1,338 @@
+use std::collections::HashMap;
+
+use bytes::Bytes;
+
+use crate::protocol::{
+    messages::{
+        fetch_request::FetchTopic,
+        fetch_response::{
+            AbortedTransaction, FetchableTopicResponse, LeaderIdAndEpoch, PartitionData,
+        },
+        FetchResponse,
+    },
+    primitives::ApiKey,
+    request::RequestV0,
+    response::{ErrorCode, ResponseBody},
+};
+use crate::storage::partition_log::IsolationLevel;
+
+use super::{BrokerState, RequestContext, RequestHandler};
+
+pub(crate) struct FetchHandler;
+
+impl RequestHandler<BrokerState> for FetchHandler {
+    fn api_key(&self) -> ApiKey {
+        ApiKey::Fetch
+    }
+
+    fn versions(&self) -> (i16, i16) {
+        (4, 16)
+    }
+
+    fn handle(&self, ctx: &RequestContext<'_, BrokerState>, request: &RequestV0) -> ResponseBody {
+        let version = request.header().request_api_version();
+        let Some(body) = request.body().as_fetch_request() else {
+            return ResponseBody::Fetch(FetchResponse::default());
+        };
+        let isolation_level = IsolationLevel::from(body.isolation_level);
+        let mut budget = FetchBudget {
+            remaining: body.max_bytes.max(0) as usize,
+            min_one_batch: true,
+        };
+
+        let responses = body
+            .topics
+            .iter()
+            .map(|topic| fetch_topic(ctx, topic, version, isolation_level, &mut budget))
+            .collect();
+
+        ResponseBody::Fetch(FetchResponse {
+            responses,
+            ..Default::default()
+        })
+    }
+}
+
+/// What is left of the request's `max_bytes`. Until some partition returns
+/// records, the first batch is returned even when it exceeds the limits so the
+/// consumer can make progress (KIP-74).
+struct FetchBudget {
+    remaining: usize,
+    min_one_batch: bool,
+}
+
+/// Reads the requested partitions of `topic`, which is named up to v12 and
+/// identified by id from v13 on. A nil id matches no topic and is answered
+/// with UNKNOWN_TOPIC_ID.
+fn fetch_topic(
+    ctx: &RequestContext<'_, BrokerState>,
+    topic: &FetchTopic,
+    version: i16,
+    isolation_level: IsolationLevel,
+    budget: &mut FetchBudget,
+) -> FetchableTopicResponse {
+    let topic_id = topic.topic_id;
+    let image = ctx.state.controller.image();
+    let (found, unknown_topic) = match version {
+        13.. => (image.topic_by_id(topic_id), ErrorCode::UnknownTopic),
+        _ => (
+            image.topic(&topic.topic),
+            ErrorCode::UnknownTopicOrPartition,
+        ),
+    };
+    let (topic_name, leaders) = match found {
+        Some(topic) => (
+            Some(topic.name().to_string()),
+            topic
+                .partitions()
+                .map(|partition| {
+                    (
+                        partition.partition_id(),
+                        (partition.leader(), partition.leader_epoch()),
+                    )
+                })
+                .collect::<HashMap<i32, (i32, i32)>>(),
+        ),
+        None => (None, HashMap::new()),
+    };
+    drop(image);
+
+    let partitions = topic
+        .partitions
+        .iter()
+        .map(|partition| {
+            let partition_index = partition.partition;
+
+            let Some(topic_name) = &topic_name else {
+                return error_partition(partition_index, unknown_topic, version);
+            };
+
+            let Some(&(leader_id, leader_epoch)) = leaders.get(&partition_index) else {
+                return error_partition(
+                    partition_index,
+                    ErrorCode::UnknownTopicOrPartition,
+                    version,
+                );
+            };
+
+            // Clients from v12 on learn the leader from the error itself.
+            if leader_id != ctx.state.controller.node_id() {
+                return PartitionData {
+                    high_watermark: -1,
+                    last_stable_offset: -1,
+                    log_start_offset: -1,
+                    current_leader: LeaderIdAndEpoch {
+                        leader_id,
+                        leader_epoch,
+                        ..Default::default()
+                    },
+                    ..error_partition(partition_index, ErrorCode::NotLeaderOrFollower, version)
+                };
+            }
+
+            let fetched = ctx.state.logs.with_log(topic_name, partition_index, |log| {
+                log.read(
+                    partition.fetch_offset,
+                    isolation_level,
+                    budget
+                        .remaining
+                        .min(partition.partition_max_bytes.max(0) as usize),
+                    budget.min_one_batch,
+                )
+            });
+
+            let data = match fetched {
+                Ok(data) => data,
+                Err(e) => {
+                    eprintln!(
+                        "Failed to read log for topic: {}, partition: {}, error: {}",
+                        topic_name, partition_index, e
+                    );
+                    return error_partition(partition_index, ErrorCode::KafkaStorageError, version);
+                }
+            };
+
+            if partition.fetch_offset < data.log_start_offset
+                || partition.fetch_offset > data.high_watermark
+            {
+                return error_partition(partition_index, ErrorCode::OffsetOutOfRange, version);
+            }
+
+            if !data.records.is_empty() {
+                budget.remaining = budget.remaining.saturating_sub(data.records.len());
+                budget.min_one_batch = false;
+            }
+
+            PartitionData {
+                partition_index,
+                error_code: ErrorCode::None as i16,
+                high_watermark: data.high_watermark,
+                last_stable_offset: data.last_stable_offset,
+                log_start_offset: data.log_start_offset,
+                // Only READ_COMMITTED consumers are sent the list; the others
+                // get null, as from Kafka.
+                aborted_transactions: match isolation_level {
+                    IsolationLevel::ReadCommitted => Some(
+                        data.aborted_transactions
+                            .iter()
+                            .map(|txn| AbortedTransaction {
+                                producer_id: txn.producer_id,
+                                first_offset: txn.first_offset,
+                                ..Default::default()
+                            })
+                            .collect(),
+                    ),
+                    IsolationLevel::ReadUncommitted => None,
+                },
+                preferred_read_replica: -1,
+                records: records(data.records, version),
+                ..Default::default()
+            }
+        })
+        .collect();
+
+    FetchableTopicResponse {
+        topic: topic.topic.clone(),
+        topic_id,
+        partitions,
+        ..Default::default()
+    }
+}
+
+/// A partition answered with `error_code` and no records.
+fn error_partition(partition_index: i32, error_code: ErrorCode, version: i16) -> PartitionData {
+    PartitionData {
+        partition_index,
+        error_code: error_code as i16,
+        high_watermark: 0,
+        last_stable_offset: 0,
+        log_start_offset: 0,
+        aborted_transactions: None,
+        preferred_read_replica: -1,
+        records: records(Bytes::new(), version),
+        ..Default::default()
+    }
+}
+
+/// Flexible versions keep sending an empty record set as null.
+fn records(records: Bytes, version: i16) -> Option<Bytes> {
+    match records.is_empty() && ApiKey::Fetch.is_flexible(version) {
+        true => None,
+        false => Some(records),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handler::tests::TestBroker;
+    use crate::protocol::{
+        cluster_metadata::{Batch, Record},
+        messages::{
+            fetch_request::{FetchPartition, FetchTopic},
+            FetchRequest,
+        },
+        request::RequestBody,
+    };
+
+    fn fetch(
+        broker: &TestBroker,
+        fetch_offset: i64,
+        isolation_level: i8,
+        max_bytes: i32,
+    ) -> Vec<PartitionData> {
+        let response = broker
+            .send(
+                12,
+                RequestBody::Fetch(FetchRequest {
+                    max_bytes,
+                    isolation_level,
+                    topics: vec![FetchTopic {
+                        topic: "foo".to_string(),
+                        partitions: (0..2)
+                            .map(|partition| FetchPartition {
+                                partition,
+                                fetch_offset,
+                                partition_max_bytes: i32::MAX,
+                                ..Default::default()
+                            })
+                            .collect(),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }),
+            )
+            .unwrap();
+        let ResponseBody::Fetch(body) = response.body() else {
+            panic!("unexpected response {:?}", response);
+        };
+
+        body.responses[0].partitions.clone()
+    }
+
+    fn records_len(partition: &PartitionData) -> i32 {
+        partition
+            .records
+            .as_ref()
+            .map_or(0, |records| records.len() as i32)
+    }
+
+    fn broker_with_records() -> TestBroker {
+        let broker = TestBroker::new();
+        broker.create_topic("foo", 2);
+        for partition in 0..2 {
+            for _ in 0..2 {
+                let batch = Batch::new(
+                    0,
+                    -1,
+                    -1,
+                    -1,
+                    0,
+                    vec![Record::new(0, None, Bytes::from_static(b"record"))],
+                );
+                broker
+                    .state
+                    .logs
+                    .with_log("foo", partition, |log| log.append(batch))
+                    .unwrap();
+            }
+        }
+
+        broker
+    }
+
+    #[test]
+    fn aborted_transactions_are_null_for_read_uncommitted() {
+        let broker = broker_with_records();
+
+        let uncommitted = fetch(&broker, 0, 0, i32::MAX);
+        assert!(uncommitted[0].aborted_transactions.is_none());
+        let committed = fetch(&broker, 0, 1, i32::MAX);
+        assert_eq!(committed[0].aborted_transactions, Some(Vec::new()));
+    }
+
+    #[test]
+    fn a_fetch_offset_past_the_log_end_is_out_of_range() {
+        let broker = broker_with_records();
+
+        let partitions = fetch(&broker, 2, 0, i32::MAX);
+        assert_eq!(partitions[0].error_code, ErrorCode::None as i16);
+        let partitions = fetch(&broker, 3, 0, i32::MAX);
+        assert_eq!(partitions[0].error_code, ErrorCode::OffsetOutOfRange as i16);
+    }
+
+    #[test]
+    fn max_bytes_still_returns_the_first_batch_only() {
+        let broker = broker_with_records();
+        let whole = fetch(&broker, 0, 0, i32::MAX);
+        let batch_size = records_len(&whole[0]) / 2;
+
+        let partitions = fetch(&broker, 0, 0, 1);
+        assert_eq!(records_len(&partitions[0]), batch_size);
+        assert_eq!(records_len(&partitions[1]), 0);
+
+        let partitions = fetch(&broker, 0, 0, batch_size * 3);
+        assert_eq!(records_len(&partitions[0]), batch_size * 2);
+        assert_eq!(records_len(&partitions[1]), batch_size);
+    }
+}
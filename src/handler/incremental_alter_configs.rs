@@ -0,0 +1,69 @@
+use crate::metadata::controller::{AlterConfigOp, ControllerError};
+use crate::protocol::{
+    messages::{
+        incremental_alter_configs_response::AlterConfigsResourceResponse,
+        IncrementalAlterConfigsResponse,
+    },
+    primitives::ApiKey,
+    request::RequestV0,
+    response::ResponseBody,
+};
+
+use super::{alter_configs::error_and_message, BrokerState, RequestContext, RequestHandler};
+
+pub(crate) struct IncrementalAlterConfigsHandler;
+
+impl RequestHandler<BrokerState> for IncrementalAlterConfigsHandler {
+    fn api_key(&self) -> ApiKey {
+        ApiKey::IncrementalAlterConfigs
+    }
+
+    fn versions(&self) -> (i16, i16) {
+        (0, 1)
+    }
+
+    fn handle(&self, ctx: &RequestContext<'_, BrokerState>, request: &RequestV0) -> ResponseBody {
+        let Some(body) = request.body().as_incremental_alter_configs_request() else {
+            return ResponseBody::IncrementalAlterConfigs(
+                IncrementalAlterConfigsResponse::default(),
+            );
+        };
+
+        let responses = body
+            .resources
+            .iter()
+            .map(|resource| {
+                let result = resource
+                    .configs
+                    .iter()
+                    .map(|config| {
+                        AlterConfigOp::try_from(config.config_operation)
+                            .map(|op| (config.name.clone(), op, config.value.clone()))
+                    })
+                    .collect::<std::result::Result<Vec<_>, ControllerError>>()
+                    .and_then(|operations| {
+                        ctx.state.controller.incremental_alter_configs(
+                            resource.resource_type,
+                            &resource.resource_name,
+                            &operations,
+                            body.validate_only,
+                        )
+                    });
+
+                let (error_code, error_message) = error_and_message(result);
+                AlterConfigsResourceResponse {
+                    error_code: error_code as i16,
+                    error_message,
+                    resource_type: resource.resource_type,
+                    resource_name: resource.resource_name.clone(),
+                    ..Default::default()
+                }
+            })
+            .collect();
+
+        ResponseBody::IncrementalAlterConfigs(IncrementalAlterConfigsResponse {
+            responses,
+            ..Default::default()
+        })
+    }
+}
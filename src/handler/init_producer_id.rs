@@ -0,0 +1,150 @@
+use crate::protocol::{
+    messages::InitProducerIdResponse,
+    primitives::ApiKey,
+    request::RequestV0,
+    response::{ErrorCode, ResponseBody},
+};
+
+use super::{BrokerState, RequestContext, RequestHandler};
+
+pub(crate) struct InitProducerIdHandler;
+
+impl RequestHandler<BrokerState> for InitProducerIdHandler {
+    fn api_key(&self) -> ApiKey {
+        ApiKey::InitProducerId
+    }
+
+    fn versions(&self) -> (i16, i16) {
+        (0, 4)
+    }
+
+    fn handle(&self, ctx: &RequestContext<'_, BrokerState>, request: &RequestV0) -> ResponseBody {
+        let Some(body) = request.body().as_init_producer_id_request() else {
+            return ResponseBody::InitProducerId(InitProducerIdResponse::default());
+        };
+
+        let response = match ctx.state.transaction_coordinator.init_producer_id(
+            body.transactional_id.as_deref(),
+            body.transaction_timeout_ms,
+            body.producer_id,
+            body.producer_epoch,
+        ) {
+            Ok((producer_id, producer_epoch)) => InitProducerIdResponse {
+                producer_id,
+                producer_epoch,
+                ..Default::default()
+            },
+            Err(error_code) => InitProducerIdResponse {
+                // PRODUCER_FENCED only arrived in version 4.
+                error_code: match error_code {
+                    ErrorCode::ProducerFenced if request.header().request_api_version() < 4 => {
+                        ErrorCode::InvalidProducerEpoch
+                    }
+                    error_code => error_code,
+                } as i16,
+                producer_id: -1,
+                producer_epoch: -1,
+                ..Default::default()
+            },
+        };
+
+        ResponseBody::InitProducerId(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handler::tests::TestBroker;
+    use crate::protocol::{
+        messages::{
+            add_partitions_to_txn_request::AddPartitionsToTxnTopic, AddPartitionsToTxnRequest,
+            InitProducerIdRequest,
+        },
+        request::RequestBody,
+    };
+
+    fn init_producer_id(
+        broker: &TestBroker,
+        version: i16,
+        producer_id: i64,
+        producer_epoch: i16,
+    ) -> InitProducerIdResponse {
+        let response = broker
+            .send(
+                version,
+                RequestBody::InitProducerId(InitProducerIdRequest {
+                    transactional_id: Some("txn".to_string()),
+                    transaction_timeout_ms: 60_000,
+                    producer_id,
+                    producer_epoch,
+                    ..Default::default()
+                }),
+            )
+            .unwrap();
+        let ResponseBody::InitProducerId(body) = response.body() else {
+            panic!("unexpected response {:?}", response);
+        };
+
+        body.clone()
+    }
+
+    #[test]
+    fn each_init_bumps_the_epoch_and_fences_the_previous_producer() {
+        let broker = TestBroker::new();
+
+        let first = init_producer_id(&broker, 4, -1, -1);
+        assert_eq!(first.error_code, ErrorCode::None as i16);
+        assert_eq!(first.producer_epoch, 0);
+
+        let second = init_producer_id(&broker, 4, first.producer_id, 0);
+        assert_eq!(second.error_code, ErrorCode::None as i16);
+        assert_eq!(
+            (second.producer_id, second.producer_epoch),
+            (first.producer_id, 1)
+        );
+
+        let fenced = init_producer_id(&broker, 4, first.producer_id, 0);
+        assert_eq!(fenced.error_code, ErrorCode::ProducerFenced as i16);
+        let fenced = init_producer_id(&broker, 3, first.producer_id, 0);
+        assert_eq!(fenced.error_code, ErrorCode::InvalidProducerEpoch as i16);
+
+        let stale = broker.state.transaction_coordinator.add_partitions(
+            "txn",
+            first.producer_id,
+            0,
+            &[("foo".to_string(), 0)],
+        );
+        assert_eq!(stale, ErrorCode::ProducerFenced);
+    }
+
+    #[test]
+    fn a_producer_id_the_coordinator_did_not_hand_out_is_refused() {
+        let broker = TestBroker::new();
+        broker.create_topic("foo", 1);
+
+        let response = broker
+            .send(
+                3,
+                RequestBody::AddPartitionsToTxn(AddPartitionsToTxnRequest {
+                    v3_and_below_transactional_id: "txn".to_string(),
+                    v3_and_below_producer_id: 42,
+                    v3_and_below_producer_epoch: 0,
+                    v3_and_below_topics: vec![AddPartitionsToTxnTopic {
+                        name: "foo".to_string(),
+                        partitions: vec![0],
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }),
+            )
+            .unwrap();
+        let ResponseBody::AddPartitionsToTxn(body) = response.body() else {
+            panic!("unexpected response {:?}", response);
+        };
+        assert_eq!(
+            body.results_by_topic_v3_and_below[0].results_by_partition[0].partition_error_code,
+            ErrorCode::InvalidProducerIdMapping as i16
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use uuid::Uuid;
+
+use crate::metadata::image::TopicImage;
+use crate::protocol::{
+    messages::{
+        metadata_response::{MetadataResponsePartition, MetadataResponseTopic},
+        MetadataResponse,
+    },
+    primitives::ApiKey,
+    request::RequestV0,
+    response::{ErrorCode, ResponseBody},
+};
+
+use super::{BrokerState, RequestContext, RequestHandler};
+
+pub(crate) struct MetadataHandler;
+
+impl RequestHandler<BrokerState> for MetadataHandler {
+    fn api_key(&self) -> ApiKey {
+        ApiKey::Metadata
+    }
+
+    fn versions(&self) -> (i16, i16) {
+        (0, 12)
+    }
+
+    fn handle(&self, ctx: &RequestContext<'_, BrokerState>, request: &RequestV0) -> ResponseBody {
+        let image = ctx.state.controller.image();
+        let metadata_topic = |topic: &TopicImage| MetadataResponseTopic {
+            error_code: ErrorCode::None as i16,
+            name: Some(topic.name().to_string()),
+            topic_id: topic.id(),
+            is_internal: topic.is_internal(),
+            partitions: topic
+                .partitions()
+                .map(|partition| MetadataResponsePartition {
+                    error_code: ErrorCode::None as i16,
+                    partition_index: partition.partition_id(),
+                    leader_id: partition.leader(),
+                    leader_epoch: partition.leader_epoch(),
+                    replica_nodes: partition.replicas().to_vec(),
+                    isr_nodes: partition.isr().to_vec(),
+                    offline_replicas: ctx.offline_replicas(&image, partition),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+
+        // A null topic list asks for every topic, an empty one for none.
+        // Version 0 can't send null, so its empty list asks for every topic.
+        let topics = match request
+            .body()
+            .as_metadata_request()
+            .and_then(|body| body.topics.as_ref())
+            .filter(|topics| request.header().request_api_version() > 0 || !topics.is_empty())
+        {
+            Some(requested) => requested
+                .iter()
+                .map(|requested| match requested.name.as_deref() {
+                    Some(name) => match image.topic(name) {
+                        Some(topic) => metadata_topic(topic),
+                        None => {
+                            topic_error(ErrorCode::UnknownTopicOrPartition, Some(name), Uuid::nil())
+                        }
+                    },
+                    None => match image.topic_by_id(requested.topic_id) {
+                        Some(topic) => metadata_topic(topic),
+                        None => topic_error(ErrorCode::UnknownTopic, None, requested.topic_id),
+                    },
+                })
+                .collect(),
+            None => image.topics().map(metadata_topic).collect(),
+        };
+
+        ResponseBody::Metadata(MetadataResponse {
+            brokers: vec![ctx.broker()],
+            cluster_id: Some(ctx.state.controller.cluster_id().to_string()),
+            controller_id: ctx.state.controller.node_id(),
+            topics,
+            ..Default::default()
+        })
+    }
+}
+
+fn topic_error(error_code: ErrorCode, name: Option<&str>, topic_id: Uuid) -> MetadataResponseTopic {
+    MetadataResponseTopic {
+        error_code: error_code as i16,
+        name: name.map(str::to_string),
+        topic_id,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handler::tests::TestBroker;
+    use crate::protocol::{messages::MetadataRequest, request::RequestBody};
+
+    #[test]
+    fn empty_topic_list_asks_for_every_topic_only_in_version_0() {
+        let broker = TestBroker::new();
+        broker.create_topic("foo", 1);
+
+        for (version, expected) in [(0, 1), (1, 0), (12, 0)] {
+            let response = broker
+                .send(
+                    version,
+                    RequestBody::Metadata(MetadataRequest {
+                        topics: Some(Vec::new()),
+                        ..Default::default()
+                    }),
+                )
+                .unwrap();
+            let ResponseBody::Metadata(body) = response.body() else {
+                panic!("unexpected response {:?}", response);
+            };
+            assert_eq!(body.topics.len(), expected, "version {}", version);
+        }
+    }
+}
@@ -0,0 +1,529 @@
+//! One [`RequestHandler`] per API, collected in a [`HandlerRegistry`] that
+//! decodes requests, dispatches them and lists the APIs in ApiVersions.
+
+pub(crate) mod add_offsets_to_txn;
+pub(crate) mod add_partitions_to_txn;
+pub(crate) mod alter_configs;
+pub(crate) mod api_versions;
+pub(crate) mod create_partitions;
+pub(crate) mod create_topics;
+pub(crate) mod delete_topics;
+pub(crate) mod describe_cluster;
+pub(crate) mod describe_configs;
+pub(crate) mod describe_log_dirs;
+pub(crate) mod describe_topic_partitions;
+pub(crate) mod end_txn;
+pub(crate) mod fetch;
+pub(crate) mod incremental_alter_configs;
+pub(crate) mod init_producer_id;
+pub(crate) mod metadata;
+pub(crate) mod txn_offset_commit;
+pub(crate) mod update_features;
+
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+use bytes::{Buf, BytesMut};
+use uuid::Uuid;
+
+use crate::metadata::{
+    image::{MetadataImage, PartitionImage},
+    Controller,
+};
+use crate::protocol::{
+    bytes::FromBytes,
+    messages::{api_versions_response::ApiVersion, metadata_response::MetadataResponseBroker},
+    primitives::ApiKey,
+    request::{RequestBody, RequestHeader, RequestV0},
+    response::{ErrorCode, ResponseBody, ResponseHeader, ResponseV0},
+};
+use crate::storage::LogManager;
+use crate::transaction::TransactionCoordinator;
+
+use crate::Result;
+
+/// The largest request accepted, Kafka's default `socket.request.max.bytes`.
+const MAX_REQUEST_SIZE: i32 = 100 * 1024 * 1024;
+
+/// A buffer for a request of `message_size` bytes, holding the size prefix
+/// [`HandlerRegistry::decode`] expects and room for the rest.
+pub(crate) fn request_buffer(message_size: i32) -> Result<BytesMut> {
+    if !(0..=MAX_REQUEST_SIZE).contains(&message_size) {
+        return Err(anyhow::anyhow!("invalid request size {}", message_size).into());
+    }
+
+    let mut buf = BytesMut::zeroed(4 + message_size as usize);
+    buf[..4].copy_from_slice(&message_size.to_be_bytes());
+
+    Ok(buf)
+}
+
+/// Serves one API against the state `S` of a server.
+pub(crate) trait RequestHandler<S>: Send + Sync {
+    fn api_key(&self) -> ApiKey;
+
+    /// The oldest and newest version of the API this handler can decode and
+    /// answer.
+    fn versions(&self) -> (i16, i16);
+
+    /// Decodes the body of a request in `version`, which is one of
+    /// [`versions`](Self::versions) unless the API is ApiVersions.
+    fn decode(&self, mut buf: &mut dyn Buf, version: i16) -> Result<RequestBody> {
+        RequestBody::decode(&mut buf, self.api_key(), version)
+    }
+
+    fn handle(&self, ctx: &RequestContext<'_, S>, request: &RequestV0) -> ResponseBody;
+
+    /// Frames `body` behind the response header that goes with the version of
+    /// `request`, and encodes it in that version.
+    fn encode(&self, request: &RequestV0, body: ResponseBody) -> ResponseV0 {
+        let header = request.header();
+        let version = header.request_api_version();
+
+        ResponseV0::new(
+            ResponseHeader::new(self.api_key(), version, header.correlation_id()),
+            version,
+            body,
+        )
+    }
+}
+
+/// What a handler gets to answer a request with.
+pub(crate) struct RequestContext<'a, S> {
+    pub(crate) state: &'a S,
+    pub(crate) registry: &'a HandlerRegistry<S>,
+    /// The address the client connected to.
+    pub(crate) local_addr: SocketAddr,
+}
+
+/// The state shared by every connection of the broker.
+#[derive(Debug)]
+pub(crate) struct BrokerState {
+    pub(crate) controller: Arc<Controller>,
+    pub(crate) logs: Arc<LogManager>,
+    pub(crate) transaction_coordinator: Arc<TransactionCoordinator>,
+}
+
+impl RequestContext<'_, BrokerState> {
+    /// This broker, as reachable through the address the client connected to.
+    pub(crate) fn broker(&self) -> MetadataResponseBroker {
+        MetadataResponseBroker {
+            node_id: self.state.controller.node_id(),
+            host: self.local_addr.ip().to_string(),
+            port: self.local_addr.port() as i32,
+            ..Default::default()
+        }
+    }
+
+    /// The replicas of `partition` that can't serve it: those on brokers that
+    /// are fenced or unknown to the cluster, those placed in a directory their
+    /// broker didn't register, and this broker's own when the log directory
+    /// holding the partition is offline.
+    pub(crate) fn offline_replicas(
+        &self,
+        image: &MetadataImage,
+        partition: &PartitionImage,
+    ) -> Vec<i32> {
+        let node_id = self.state.controller.node_id();
+
+        partition
+            .replicas()
+            .iter()
+            .enumerate()
+            .filter(|(idx, replica)| {
+                let directory = partition
+                    .directories()
+                    .get(*idx)
+                    .copied()
+                    .unwrap_or_else(Uuid::nil);
+                if **replica == node_id {
+                    return self.state.logs.is_offline(directory);
+                }
+
+                match image.broker(**replica) {
+                    Some(broker) => {
+                        broker.fenced()
+                            || (!directory.is_nil()
+                                && !broker.log_dirs().is_empty()
+                                && !broker.log_dirs().contains(&directory))
+                    }
+                    None => true,
+                }
+            })
+            .map(|(_, replica)| *replica)
+            .collect()
+    }
+
+    pub(crate) fn partition_exists(&self, topic_name: &str, partition: i32) -> bool {
+        self.state
+            .controller
+            .image()
+            .topic(topic_name)
+            .and_then(|topic| topic.partition(partition))
+            .is_some()
+    }
+}
+
+/// `error_code` as version `version` of AddPartitionsToTxn, AddOffsetsToTxn
+/// or EndTxn reports it: clients older than version 2 don't know
+/// PRODUCER_FENCED and expect INVALID_PRODUCER_EPOCH instead.
+pub(crate) fn transaction_error(error_code: ErrorCode, version: i16) -> ErrorCode {
+    match error_code {
+        ErrorCode::ProducerFenced if version < 2 => ErrorCode::InvalidProducerEpoch,
+        error_code => error_code,
+    }
+}
+
+/// Counters kept for each API, named after Kafka's request metrics.
+#[derive(Debug, Default)]
+pub(crate) struct RequestMetrics {
+    requests: AtomicU64,
+    /// Requests in an unsupported version, or that couldn't be decoded and
+    /// closed their connection.
+    errors: AtomicU64,
+    total_time_micros: AtomicU64,
+}
+
+struct Registered<S> {
+    handler: Box<dyn RequestHandler<S>>,
+    metrics: RequestMetrics,
+}
+
+impl<S> Registered<S> {
+    /// Whether `version` of `api_key` can be decoded and answered.
+    fn supports(&self, api_key: ApiKey, version: i16) -> bool {
+        let (min_version, max_version) = self.handler.versions();
+        api_key == ApiKey::ApiVersions || (min_version..=max_version).contains(&version)
+    }
+}
+
+/// The handlers of a server keyed by the API they serve.
+pub(crate) struct HandlerRegistry<S> {
+    handlers: HashMap<ApiKey, Registered<S>>,
+}
+
+impl<S> std::fmt::Debug for HandlerRegistry<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HandlerRegistry")
+            .field("api_keys", &self.handlers.keys().collect::<Vec<&ApiKey>>())
+            .finish()
+    }
+}
+
+impl<S> HandlerRegistry<S> {
+    pub(crate) fn new() -> Self {
+        Self {
+            handlers: HashMap::new(),
+        }
+    }
+
+    /// Adds `handler`, replacing any handler registered for the same API.
+    pub(crate) fn register(mut self, handler: impl RequestHandler<S> + 'static) -> Self {
+        self.handlers.insert(
+            handler.api_key(),
+            Registered {
+                handler: Box::new(handler),
+                metrics: RequestMetrics::default(),
+            },
+        );
+        self
+    }
+
+    /// Decodes a size-prefixed request with the handler of its API. The body
+    /// of a version the handler doesn't support is left undecoded, and the
+    /// request is answered with UNSUPPORTED_VERSION. ApiVersions decodes every
+    /// version so the client can negotiate down.
+    pub(crate) fn decode<B: Buf>(&self, mut buf: &mut B) -> Result<RequestV0> {
+        buf.try_get_i32()
+            .map_err(|e| anyhow::anyhow!("failed to parse i32 for message_size: {}", e))?;
+
+        let header = RequestHeader::from_be_bytes(&mut buf)
+            .map_err(|e| anyhow::anyhow!("failed to parse RequestHeader: {}", e))?;
+        let api_key = *header.request_api_key();
+        let version = header.request_api_version();
+        let registered = self
+            .handlers
+            .get(&api_key)
+            .ok_or_else(|| anyhow::anyhow!("no handler for {:?}", api_key))?;
+
+        if !registered.supports(api_key, version) {
+            registered.metrics.errors.fetch_add(1, Ordering::Relaxed);
+            return Ok(RequestV0::new(header, RequestBody::empty(api_key)));
+        }
+
+        let body = registered.handler.decode(buf, version).inspect_err(|_| {
+            registered.metrics.errors.fetch_add(1, Ordering::Relaxed);
+        })?;
+
+        Ok(RequestV0::new(header, body))
+    }
+
+    /// Answers `request`, which was decoded by [`decode`](Self::decode).
+    pub(crate) fn handle(
+        &self,
+        state: &S,
+        local_addr: SocketAddr,
+        request: &RequestV0,
+    ) -> ResponseV0 {
+        let api_key = *request.header().request_api_key();
+        let registered = &self.handlers[&api_key];
+        let ctx = RequestContext {
+            state,
+            registry: self,
+            local_addr,
+        };
+
+        let started = Instant::now();
+        let body = match registered.supports(api_key, request.header().request_api_version()) {
+            true => registered.handler.handle(&ctx, request),
+            false => ResponseBody::error(api_key, ErrorCode::UnsupportedVersion),
+        };
+        let response = registered.handler.encode(request, body);
+
+        registered.metrics.requests.fetch_add(1, Ordering::Relaxed);
+        registered
+            .metrics
+            .total_time_micros
+            .fetch_add(started.elapsed().as_micros() as u64, Ordering::Relaxed);
+
+        response
+    }
+
+    /// The versions of every registered API, as ApiVersions lists them.
+    pub(crate) fn api_versions(&self) -> Vec<ApiVersion> {
+        let mut api_keys = self.handlers.keys().copied().collect::<Vec<ApiKey>>();
+        api_keys.sort_by_key(|api_key| *api_key as i16);
+
+        api_keys
+            .into_iter()
+            .map(|api_key| {
+                let (min_version, max_version) = self.handlers[&api_key].handler.versions();
+                ApiVersion {
+                    api_key: api_key as i16,
+                    min_version,
+                    max_version,
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
+
+    /// Prints the metrics of every API that has seen a request.
+    pub(crate) fn log_metrics(&self) {
+        let mut api_keys = self.handlers.keys().copied().collect::<Vec<ApiKey>>();
+        api_keys.sort_by_key(|api_key| *api_key as i16);
+
+        for api_key in api_keys {
+            let metrics = &self.handlers[&api_key].metrics;
+            let requests = metrics.requests.load(Ordering::Relaxed);
+            let errors = metrics.errors.load(Ordering::Relaxed);
+            if requests == 0 && errors == 0 {
+                continue;
+            }
+
+            let total_time_micros = metrics.total_time_micros.load(Ordering::Relaxed);
+            println!(
+                "RequestMetrics request={:?}: Requests: {}, Errors: {}, TotalTimeMs: {:.3}",
+                api_key,
+                requests,
+                errors,
+                total_time_micros as f64 / 1000.0
+            );
+        }
+    }
+}
+
+/// The registry of every API the broker implements.
+pub(crate) fn broker_handlers() -> HandlerRegistry<BrokerState> {
+    HandlerRegistry::new()
+        .register(api_versions::ApiVersionsHandler)
+        .register(describe_topic_partitions::DescribeTopicPartitionsHandler)
+        .register(fetch::FetchHandler)
+        .register(init_producer_id::InitProducerIdHandler)
+        .register(add_partitions_to_txn::AddPartitionsToTxnHandler)
+        .register(add_offsets_to_txn::AddOffsetsToTxnHandler)
+        .register(end_txn::EndTxnHandler)
+        .register(txn_offset_commit::TxnOffsetCommitHandler)
+        .register(create_topics::CreateTopicsHandler)
+        .register(delete_topics::DeleteTopicsHandler)
+        .register(create_partitions::CreatePartitionsHandler)
+        .register(describe_configs::DescribeConfigsHandler)
+        .register(alter_configs::AlterConfigsHandler)
+        .register(incremental_alter_configs::IncrementalAlterConfigsHandler)
+        .register(metadata::MetadataHandler)
+        .register(describe_cluster::DescribeClusterHandler)
+        .register(describe_log_dirs::DescribeLogDirsHandler)
+        .register(update_features::UpdateFeaturesHandler)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{fs, path::PathBuf};
+
+    use super::*;
+    use crate::config::ServerProperties;
+    use crate::metadata::controller::NewTopic;
+    use crate::protocol::bytes::{ToBytes, ToVersionedBytes};
+    use crate::protocol::messages::{
+        AddOffsetsToTxnRequest, AddPartitionsToTxnRequest, AlterConfigsRequest,
+        CreatePartitionsRequest, CreateTopicsRequest, DeleteTopicsRequest, DescribeClusterRequest,
+        DescribeConfigsRequest, DescribeLogDirsRequest, DescribeTopicPartitionsRequest,
+        EndTxnRequest, FetchRequest, IncrementalAlterConfigsRequest, InitProducerIdRequest,
+        MetadataRequest, TxnOffsetCommitRequest, UpdateFeaturesRequest,
+    };
+    use crate::storage::LogDir;
+
+    /// A broker over a fresh log directory that answers requests the way a
+    /// connection does: each request is encoded and decoded by the registry,
+    /// and its response encoded and decoded again.
+    pub(crate) struct TestBroker {
+        pub(crate) state: BrokerState,
+        handlers: HandlerRegistry<BrokerState>,
+        dir: PathBuf,
+    }
+
+    impl TestBroker {
+        pub(crate) fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("broker-{}", Uuid::new_v4()));
+            fs::create_dir_all(&dir).unwrap();
+            let logs = Arc::new(LogManager::new(vec![LogDir {
+                path: dir.clone(),
+                id: Uuid::new_v4(),
+            }]));
+            let controller = Controller::load(
+                Arc::clone(&logs),
+                ServerProperties::default(),
+                "cluster".to_string(),
+            )
+            .unwrap();
+            let controller = Arc::new(controller);
+            let transaction_coordinator =
+                TransactionCoordinator::load(Arc::clone(&logs), Arc::clone(&controller)).unwrap();
+
+            Self {
+                state: BrokerState {
+                    controller,
+                    logs,
+                    transaction_coordinator: Arc::new(transaction_coordinator),
+                },
+                handlers: broker_handlers(),
+                dir,
+            }
+        }
+
+        /// Creates `name` with `num_partitions` partitions on this broker.
+        pub(crate) fn create_topic(&self, name: &str, num_partitions: i32) {
+            self.state
+                .controller
+                .create_topic(
+                    &NewTopic {
+                        name: name.to_string(),
+                        num_partitions,
+                        replication_factor: -1,
+                        assignments: Vec::new(),
+                        configs: Vec::new(),
+                    },
+                    false,
+                )
+                .unwrap();
+        }
+
+        /// Sends `body` in `version`, failing like the connection would when
+        /// the registry refuses to decode it.
+        pub(crate) fn send(&self, version: i16, body: RequestBody) -> Result<ResponseV0> {
+            let api_key = body.api_key();
+            let request =
+                RequestV0::new(RequestHeader::new(api_key, version, 7, Some("test")), body);
+            let request = self.handlers.decode(&mut request.to_be_bytes())?;
+            let response = self.handlers.handle(
+                &self.state,
+                SocketAddr::from(([127, 0, 0, 1], 9092)),
+                &request,
+            );
+
+            let mut bytes = response.to_be_bytes();
+            let response = ResponseV0::decode(&mut bytes, api_key, version)?;
+            assert!(bytes.is_empty(), "response has {} bytes left", bytes.len());
+            assert_eq!(response.header().correlation_id(), 7);
+
+            Ok(response)
+        }
+    }
+
+    impl Drop for TestBroker {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    #[test]
+    fn every_api_answers_its_edge_versions_and_refuses_the_rest() {
+        let broker = TestBroker::new();
+        let requests = [
+            RequestBody::DescribeTopicPartitions(DescribeTopicPartitionsRequest::default()),
+            RequestBody::Fetch(FetchRequest::default()),
+            RequestBody::InitProducerId(InitProducerIdRequest::default()),
+            RequestBody::AddPartitionsToTxn(AddPartitionsToTxnRequest::default()),
+            RequestBody::AddOffsetsToTxn(AddOffsetsToTxnRequest::default()),
+            RequestBody::EndTxn(EndTxnRequest::default()),
+            RequestBody::TxnOffsetCommit(TxnOffsetCommitRequest::default()),
+            RequestBody::CreateTopics(CreateTopicsRequest::default()),
+            RequestBody::DeleteTopics(DeleteTopicsRequest::default()),
+            RequestBody::CreatePartitions(CreatePartitionsRequest::default()),
+            RequestBody::DescribeConfigs(DescribeConfigsRequest::default()),
+            RequestBody::AlterConfigs(AlterConfigsRequest::default()),
+            RequestBody::IncrementalAlterConfigs(IncrementalAlterConfigsRequest::default()),
+            RequestBody::Metadata(MetadataRequest::default()),
+            RequestBody::DescribeCluster(DescribeClusterRequest::default()),
+            RequestBody::DescribeLogDirs(DescribeLogDirsRequest::default()),
+            RequestBody::UpdateFeatures(UpdateFeaturesRequest::default()),
+        ];
+
+        for body in requests {
+            let api_key = body.api_key();
+            let (min_version, max_version) = broker.handlers.handlers[&api_key].handler.versions();
+            for version in [min_version, max_version] {
+                let response = broker.send(version, body.clone());
+                assert!(
+                    response.is_ok(),
+                    "{:?} v{}: {:?}",
+                    api_key,
+                    version,
+                    response
+                );
+            }
+            for version in [min_version - 1, max_version + 1] {
+                let response = broker.send(version, body.clone()).unwrap();
+                assert_eq!(
+                    response.body().to_versioned_be_bytes(version),
+                    ResponseBody::error(api_key, ErrorCode::UnsupportedVersion)
+                        .to_versioned_be_bytes(version),
+                    "{:?} v{} was not refused",
+                    api_key,
+                    version
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn an_unsupported_version_is_answered_with_unsupported_version() {
+        let broker = TestBroker::new();
+
+        let response = broker
+            .send(5, RequestBody::EndTxn(EndTxnRequest::default()))
+            .unwrap();
+        let ResponseBody::EndTxn(body) = response.body() else {
+            panic!("unexpected response {:?}", response);
+        };
+        assert_eq!(body.error_code, ErrorCode::UnsupportedVersion as i16);
+        assert_eq!(response.header().correlation_id(), 7);
+    }
+}
@@ -0,0 +1,77 @@
+use crate::protocol::{
+    messages::{
+        txn_offset_commit_response::{
+            TxnOffsetCommitResponsePartition, TxnOffsetCommitResponseTopic,
+        },
+        TxnOffsetCommitResponse,
+    },
+    primitives::ApiKey,
+    request::RequestV0,
+    response::ResponseBody,
+};
+use crate::transaction::coordinator::OffsetCommit;
+
+use super::{BrokerState, RequestContext, RequestHandler};
+
+pub(crate) struct TxnOffsetCommitHandler;
+
+impl RequestHandler<BrokerState> for TxnOffsetCommitHandler {
+    fn api_key(&self) -> ApiKey {
+        ApiKey::TxnOffsetCommit
+    }
+
+    fn versions(&self) -> (i16, i16) {
+        (0, 3)
+    }
+
+    fn handle(&self, ctx: &RequestContext<'_, BrokerState>, request: &RequestV0) -> ResponseBody {
+        let Some(body) = request.body().as_txn_offset_commit_request() else {
+            return ResponseBody::TxnOffsetCommit(TxnOffsetCommitResponse::default());
+        };
+
+        let offsets = body
+            .topics
+            .iter()
+            .flat_map(|topic| {
+                topic.partitions.iter().map(|partition| OffsetCommit {
+                    topic: topic.name.clone(),
+                    partition: partition.partition_index,
+                    offset: partition.committed_offset,
+                    leader_epoch: partition.committed_leader_epoch,
+                    metadata: partition.committed_metadata.clone().unwrap_or_default(),
+                })
+            })
+            .collect::<Vec<OffsetCommit>>();
+
+        let error_code = ctx.state.transaction_coordinator.commit_offsets(
+            &body.transactional_id,
+            &body.group_id,
+            body.producer_id,
+            body.producer_epoch,
+            &offsets,
+        );
+
+        let topics = body
+            .topics
+            .iter()
+            .map(|topic| TxnOffsetCommitResponseTopic {
+                name: topic.name.clone(),
+                partitions: topic
+                    .partitions
+                    .iter()
+                    .map(|partition| TxnOffsetCommitResponsePartition {
+                        partition_index: partition.partition_index,
+                        error_code: error_code as i16,
+                        ..Default::default()
+                    })
+                    .collect(),
+                ..Default::default()
+            })
+            .collect();
+
+        ResponseBody::TxnOffsetCommit(TxnOffsetCommitResponse {
+            topics,
+            ..Default::default()
+        })
+    }
+}
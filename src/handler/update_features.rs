@@ -0,0 +1,81 @@
+use crate::metadata::controller::{ControllerError, FeatureUpgradeType};
+use crate::protocol::{
+    messages::{update_features_response::UpdatableFeatureResult, UpdateFeaturesResponse},
+    primitives::ApiKey,
+    request::RequestV0,
+    response::{ErrorCode, ResponseBody},
+};
+
+use super::{BrokerState, RequestContext, RequestHandler};
+
+pub(crate) struct UpdateFeaturesHandler;
+
+impl RequestHandler<BrokerState> for UpdateFeaturesHandler {
+    fn api_key(&self) -> ApiKey {
+        ApiKey::UpdateFeatures
+    }
+
+    fn versions(&self) -> (i16, i16) {
+        (0, 1)
+    }
+
+    /// Applies every feature update or none of them, reporting the outcome
+    /// both for the request and for each feature.
+    fn handle(&self, ctx: &RequestContext<'_, BrokerState>, request: &RequestV0) -> ResponseBody {
+        let Some(body) = request.body().as_update_features_request() else {
+            return ResponseBody::UpdateFeatures(UpdateFeaturesResponse {
+                error_code: ErrorCode::InvalidRequest as i16,
+                error_message: None,
+                ..Default::default()
+            });
+        };
+
+        let version = request.header().request_api_version();
+        let result = body
+            .feature_updates
+            .iter()
+            .map(|update| {
+                // Version 0 has a flag where later versions have an upgrade type.
+                let upgrade_type = match (version, update.allow_downgrade) {
+                    (0, true) => Ok(FeatureUpgradeType::SafeDowngrade),
+                    (0, false) => Ok(FeatureUpgradeType::Upgrade),
+                    _ => FeatureUpgradeType::try_from(update.upgrade_type),
+                };
+                upgrade_type.map(|upgrade_type| {
+                    (
+                        update.feature.clone(),
+                        update.max_version_level,
+                        upgrade_type,
+                    )
+                })
+            })
+            .collect::<std::result::Result<Vec<_>, ControllerError>>()
+            .and_then(|updates| {
+                ctx.state
+                    .controller
+                    .update_features(&updates, body.validate_only)
+            });
+
+        let (error_code, error_message) = match result {
+            Ok(()) => (ErrorCode::None, None),
+            Err(e) => (e.error_code, Some(e.message)),
+        };
+        let results = body
+            .feature_updates
+            .iter()
+            .map(|update| UpdatableFeatureResult {
+                feature: update.feature.clone(),
+                error_code: error_code as i16,
+                error_message: error_message.clone(),
+                ..Default::default()
+            })
+            .collect();
+
+        ResponseBody::UpdateFeatures(UpdateFeaturesResponse {
+            error_code: error_code as i16,
+            error_message,
+            results,
+            ..Default::default()
+        })
+    }
+}
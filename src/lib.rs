@@ -1,9 +1,19 @@
+mod config;
+mod dump_log;
+mod format;
+mod handler;
+mod metadata;
 mod protocol;
 mod server_async;
 mod server_sync;
+mod storage;
+mod transaction;
 
 pub(crate) type Error = Box<dyn std::error::Error>;
 pub(crate) type Result<T> = std::result::Result<T, Error>;
 
+pub use config::ServerProperties;
+pub use dump_log::dump_log;
+pub use format::{format_storage, random_cluster_id};
 pub use server_async::ServerAsync;
 pub use server_sync::ServerSync;
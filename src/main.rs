@@ -1,8 +1,45 @@
-use codecrafters_kafka::ServerAsync;
+use std::path::Path;
+
+use codecrafters_kafka::{
+    dump_log, format_storage, random_cluster_id, ServerAsync, ServerProperties,
+};
 
 #[tokio::main]
 async fn main() {
-    let server = ServerAsync::new("127.0.0.1:9092").unwrap_or_else(|e| {
+    let args: Vec<String> = std::env::args().collect();
+
+    if let Some("dump-log") = args.get(1).map(String::as_str) {
+        let Some(path) = args.get(2) else {
+            eprintln!("usage: {} dump-log <segment>", args[0]);
+            std::process::exit(2);
+        };
+
+        dump_log(Path::new(path)).unwrap_or_else(|e| {
+            eprintln!("failed to dump {}: {}", path, e);
+            std::process::exit(1);
+        });
+        return;
+    }
+
+    if let Some("random-uuid") = args.get(1).map(String::as_str) {
+        println!("{}", random_cluster_id());
+        return;
+    }
+
+    if let Some("format") = args.get(1).map(String::as_str) {
+        format(&args);
+        return;
+    }
+
+    let properties = match args.get(1) {
+        Some(path) => ServerProperties::load(Path::new(path)).unwrap_or_else(|e| {
+            eprintln!("failed to read {}: {}", path, e);
+            std::process::exit(1);
+        }),
+        None => ServerProperties::default(),
+    };
+
+    let server = ServerAsync::new("127.0.0.1:9092", properties).unwrap_or_else(|e| {
         eprintln!("Failed to create server: {}", e);
         std::process::exit(1);
     });
@@ -12,3 +49,55 @@ async fn main() {
         std::process::exit(1);
     });
 }
+
+/// `format [-t <cluster id>] [-c <server.properties>] [--topic <name>[:<partitions>]]...`
+fn format(args: &[String]) {
+    let usage = || -> ! {
+        eprintln!(
+            "usage: {} format [-t <cluster id>] [-c <server.properties>] \
+             [--topic <name>[:<partitions>]]...",
+            args[0]
+        );
+        std::process::exit(2);
+    };
+
+    let mut cluster_id = None;
+    let mut properties = ServerProperties::default();
+    let mut topics = Vec::new();
+
+    let mut options = args[2..].iter();
+    while let Some(option) = options.next() {
+        let Some(value) = options.next() else {
+            usage();
+        };
+
+        match option.as_str() {
+            "-t" | "--cluster-id" => cluster_id = Some(value.as_str()),
+            "-c" | "--config" => {
+                properties = ServerProperties::load(Path::new(value)).unwrap_or_else(|e| {
+                    eprintln!("failed to read {}: {}", value, e);
+                    std::process::exit(1);
+                });
+            }
+            "--topic" => {
+                let (name, partitions) = match value.split_once(':') {
+                    Some((name, partitions)) => match partitions.parse() {
+                        Ok(partitions) => (name, partitions),
+                        Err(_) => usage(),
+                    },
+                    None => (value.as_str(), 1),
+                };
+                topics.push((name.to_string(), partitions));
+            }
+            _ => usage(),
+        }
+    }
+
+    match format_storage(&properties, cluster_id, &topics) {
+        Ok(cluster_id) => println!("Formatted storage with cluster id {}", cluster_id),
+        Err(e) => {
+            eprintln!("failed to format storage: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
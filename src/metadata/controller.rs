@@ -0,0 +1,1465 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    ops::Range,
+    sync::{Arc, Mutex, MutexGuard, RwLock},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use uuid::Uuid;
+
+use crate::{
+    config::{
+        self, broker_configs, cluster_default_configs, list_elements, topic_configs,
+        validate_broker_config, validate_topic_config, ConfigEntry, ConfigType, DynamicConfigs,
+        ServerProperties,
+    },
+    protocol::{
+        cluster_metadata::{
+            Batch, ConfigRecordValue, FeatureRecordValue, PartitionRecordValue,
+            ProducerIdsRecordValue, Record, RecordValue, RecordValueByType, RemoveTopicRecordValue,
+            TopicRecordValue, BROKER_RESOURCE_TYPE, TOPIC_RESOURCE_TYPE,
+        },
+        response::ErrorCode,
+    },
+    storage::LogManager,
+    transaction::coordinator::is_internal_topic,
+};
+
+use super::{
+    snapshot::{delete_snapshots_before, Snapshot, SnapshotId},
+    tailer::MetadataLogTailer,
+    MetadataImage,
+};
+
+pub(crate) const CLUSTER_METADATA_TOPIC: &str = "__cluster_metadata";
+pub(crate) const CLUSTER_METADATA_PARTITION: i32 = 0;
+
+/// Broker defaults for `num.partitions` and `default.replication.factor`.
+const DEFAULT_NUM_PARTITIONS: i32 = 1;
+const DEFAULT_REPLICATION_FACTOR: i16 = 1;
+
+const MAX_TOPIC_NAME_LENGTH: usize = 249;
+
+/// The most partitions a topic may have. Every partition is placed on this
+/// broker, so a larger count only exhausts its memory and file handles.
+const MAX_PARTITIONS: i32 = 10_000;
+
+pub(crate) const METADATA_VERSION_FEATURE: &str = "metadata.version";
+
+/// Features this broker supports, with the lowest and highest level of
+/// each. `metadata.version` 21 is 3.9-IV0.
+pub(crate) const SUPPORTED_FEATURES: &[(&str, i16, i16)] = &[
+    (METADATA_VERSION_FEATURE, 1, 21),
+    ("group.version", 0, 1),
+    ("transaction.version", 0, 2),
+];
+
+/// The image is snapshotted once this many records follow the latest
+/// snapshot, or once this long has passed since it with any record after it.
+/// Overridden by `metadata.log.max.records.between.snapshots` and
+/// `metadata.log.max.snapshot.interval.ms`.
+const DEFAULT_MAX_RECORDS_BETWEEN_SNAPSHOTS: i64 = 20_000;
+const DEFAULT_SNAPSHOT_INTERVAL_MS: u64 = 60 * 60 * 1000;
+
+/// How many producer ids are handed out at once, as in Kafka.
+const PRODUCER_ID_BLOCK_SIZE: i64 = 1000;
+
+const NO_PRODUCER_ID: i64 = -1;
+const NO_PRODUCER_EPOCH: i16 = -1;
+const NO_SEQUENCE: i32 = -1;
+
+/// A topic as requested through CreateTopics. `num_partitions` and
+/// `replication_factor` are -1 when `assignments` is used or the broker
+/// default applies.
+#[derive(Debug)]
+pub(crate) struct NewTopic {
+    pub(crate) name: String,
+    pub(crate) num_partitions: i32,
+    pub(crate) replication_factor: i16,
+    pub(crate) assignments: Vec<(i32, Vec<i32>)>,
+    pub(crate) configs: Vec<(String, Option<String>)>,
+}
+
+/// A topic created through CreateTopics, with every config it resolves to.
+#[derive(Debug)]
+pub(crate) struct CreatedTopic {
+    pub(crate) topic_id: Uuid,
+    pub(crate) num_partitions: i32,
+    pub(crate) replication_factor: i16,
+    pub(crate) configs: Vec<ConfigEntry>,
+}
+
+/// An `IncrementalAlterConfigs` operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum AlterConfigOp {
+    Set,
+    Delete,
+    Append,
+    Subtract,
+}
+
+impl TryFrom<i8> for AlterConfigOp {
+    type Error = ControllerError;
+
+    fn try_from(value: i8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Set),
+            1 => Ok(Self::Delete),
+            2 => Ok(Self::Append),
+            3 => Ok(Self::Subtract),
+            _ => Err(ControllerError::new(
+                ErrorCode::InvalidRequest,
+                format!("Unknown alter config operation {}", value),
+            )),
+        }
+    }
+}
+
+/// How an `UpdateFeatures` request may change a feature level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FeatureUpgradeType {
+    Upgrade,
+    SafeDowngrade,
+    UnsafeDowngrade,
+}
+
+impl TryFrom<i8> for FeatureUpgradeType {
+    type Error = ControllerError;
+
+    fn try_from(value: i8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(Self::Upgrade),
+            2 => Ok(Self::SafeDowngrade),
+            3 => Ok(Self::UnsafeDowngrade),
+            _ => Err(ControllerError::new(
+                ErrorCode::InvalidRequest,
+                format!("Unknown upgrade type {}", value),
+            )),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct ControllerError {
+    pub(crate) error_code: ErrorCode,
+    pub(crate) message: String,
+}
+
+impl ControllerError {
+    fn new(error_code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            error_code,
+            message: message.into(),
+        }
+    }
+}
+
+/// Owns the [`MetadataImage`] and is the only writer of the
+/// `__cluster_metadata` log, so every change is persisted before it becomes
+/// visible.
+///
+/// The image is never modified in place: each change is applied to a copy
+/// that then replaces it, so readers keep a consistent image for as long as
+/// they hold it without blocking writers.
+#[derive(Debug)]
+pub(crate) struct Controller {
+    logs: Arc<LogManager>,
+    properties: ServerProperties,
+    cluster_id: String,
+    image: RwLock<Arc<MetadataImage>>,
+    /// Serializes changes to the image, whether written by the controller or
+    /// read from the log by the tailer.
+    tailer: Mutex<MetadataLogTailer>,
+    /// End offset of the latest snapshot, and when it was written or loaded.
+    last_snapshot: Mutex<(i64, Instant)>,
+    max_records_between_snapshots: i64,
+    snapshot_interval: Duration,
+}
+
+/// A config resource as named in the config APIs.
+#[derive(Debug, Clone, Copy)]
+enum ConfigResource<'a> {
+    Topic(&'a str),
+    /// This broker, named by its node id.
+    Broker(i32),
+    /// The cluster-wide broker defaults, named by an empty broker id.
+    ClusterDefault,
+}
+
+impl ConfigResource<'_> {
+    fn resource_type(&self) -> i8 {
+        match self {
+            Self::Topic(_) => TOPIC_RESOURCE_TYPE,
+            Self::Broker(_) | Self::ClusterDefault => BROKER_RESOURCE_TYPE,
+        }
+    }
+
+    fn resource_name(&self) -> String {
+        match self {
+            Self::Topic(name) => name.to_string(),
+            Self::Broker(node_id) => node_id.to_string(),
+            Self::ClusterDefault => String::new(),
+        }
+    }
+}
+
+impl Controller {
+    /// Loads the image from the metadata log in the log directories of
+    /// `logs`, which have already been checked to belong to `cluster_id`.
+    pub(crate) fn load(
+        logs: Arc<LogManager>,
+        properties: ServerProperties,
+        cluster_id: String,
+    ) -> crate::Result<Self> {
+        let dir = logs.metadata_log_dir();
+
+        // Start from the latest snapshot, if any, and replay the log after it.
+        let mut image = match Snapshot::read_latest(&dir)? {
+            Some(snapshot) => MetadataImage::from_snapshot(&snapshot),
+            None => MetadataImage::default(),
+        };
+        let snapshot_end_offset = image.next_offset();
+        let mut tailer = MetadataLogTailer::new(dir);
+        for batch in tailer.poll()? {
+            if batch.last_offset() >= image.next_offset() {
+                image.apply_batch(&batch);
+            }
+        }
+        let node_id = properties.node_id();
+        place_new_partitions(&logs, node_id, &MetadataImage::default(), &image)?;
+
+        // The metadata log keeps the directories this node last registered
+        // with; if none of them is here, these directories belong to another
+        // copy of the node.
+        if let Some(broker) = image.broker(node_id) {
+            let registered = broker.log_dirs();
+            if !registered.is_empty()
+                && !logs
+                    .log_dirs()
+                    .iter()
+                    .any(|dir| registered.contains(&dir.id))
+            {
+                return Err(format!(
+                    "none of the log directories match the registration of node {} in the \
+                     metadata log",
+                    node_id
+                )
+                .into());
+            }
+        }
+
+        let max_records_between_snapshots = properties
+            .get("metadata.log.max.records.between.snapshots")
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_MAX_RECORDS_BETWEEN_SNAPSHOTS);
+        let snapshot_interval = Duration::from_millis(
+            properties
+                .get("metadata.log.max.snapshot.interval.ms")
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(DEFAULT_SNAPSHOT_INTERVAL_MS),
+        );
+
+        Ok(Self {
+            logs,
+            properties,
+            cluster_id,
+            image: RwLock::new(Arc::new(image)),
+            tailer: Mutex::new(tailer),
+            last_snapshot: Mutex::new((snapshot_end_offset, Instant::now())),
+            max_records_between_snapshots,
+            snapshot_interval,
+        })
+    }
+
+    pub(crate) fn cluster_id(&self) -> &str {
+        &self.cluster_id
+    }
+
+    pub(crate) fn node_id(&self) -> i32 {
+        self.properties.node_id()
+    }
+
+    /// The current image, which stays unchanged while it is held.
+    pub(crate) fn image(&self) -> Arc<MetadataImage> {
+        Arc::clone(&self.image.read().expect("metadata image lock poisoned"))
+    }
+
+    /// Applies the batches appended to the metadata log since the last poll,
+    /// skipping those the controller has already applied, and publishes the
+    /// resulting image.
+    pub(crate) fn poll_metadata_log(&self) -> std::io::Result<()> {
+        let mut tailer = self.tailer();
+        let image = self.image();
+
+        let batches = tailer
+            .poll()
+            .map_err(|e| self.logs.fail_metadata_log(e))?
+            .into_iter()
+            .filter(|batch| batch.last_offset() >= image.next_offset())
+            .collect::<Vec<Batch>>();
+        if batches.is_empty() {
+            return Ok(());
+        }
+
+        // The batches come from another writer, whose appends the log manager
+        // has to move past before this controller appends again.
+        self.logs
+            .with_log(CLUSTER_METADATA_TOPIC, CLUSTER_METADATA_PARTITION, |log| {
+                log.catch_up()
+            })?;
+
+        let mut next = MetadataImage::clone(&image);
+        for batch in &batches {
+            next.apply_batch(batch);
+        }
+        place_new_partitions(&self.logs, self.node_id(), &image, &next)?;
+        self.publish(next);
+
+        Ok(())
+    }
+
+    /// Writes a snapshot of the image once enough records or time have
+    /// accumulated since the latest one, then deletes the log segments and
+    /// older snapshots it covers.
+    pub(crate) fn maybe_snapshot(&self) -> std::io::Result<()> {
+        let _tailer = self.tailer();
+        let image = self.image();
+        let mut last_snapshot = self
+            .last_snapshot
+            .lock()
+            .expect("last snapshot lock poisoned");
+
+        let (end_offset, written_at) = *last_snapshot;
+        let new_records = image.next_offset() - end_offset;
+        if new_records <= 0 || image.in_transaction() {
+            return Ok(());
+        }
+        if new_records < self.max_records_between_snapshots
+            && written_at.elapsed() < self.snapshot_interval
+        {
+            return Ok(());
+        }
+
+        let id = SnapshotId {
+            end_offset: image.next_offset(),
+            epoch: image.last_epoch(),
+        };
+        let dir = self.logs.metadata_log_dir();
+        Snapshot::write(&dir, id, image.records(), now_ms())
+            .map_err(|e| self.logs.fail_metadata_log(e))?;
+
+        self.logs
+            .with_log(CLUSTER_METADATA_TOPIC, CLUSTER_METADATA_PARTITION, |log| {
+                log.roll()?;
+                log.delete_segments_before(id.end_offset)
+            })?;
+        delete_snapshots_before(&dir, id).map_err(|e| self.logs.fail_metadata_log(e))?;
+
+        *last_snapshot = (id.end_offset, Instant::now());
+
+        Ok(())
+    }
+
+    pub(crate) fn create_topic(
+        &self,
+        topic: &NewTopic,
+        validate_only: bool,
+    ) -> Result<CreatedTopic, ControllerError> {
+        let _tailer = self.tailer();
+        let image = self.image();
+
+        validate_topic_name(&topic.name)?;
+        if is_internal_topic(&topic.name) {
+            return Err(ControllerError::new(
+                ErrorCode::InvalidTopicException,
+                format!(
+                    "Topic '{}' is an internal topic and can't be created.",
+                    topic.name
+                ),
+            ));
+        }
+
+        if image.topic(&topic.name).is_some() {
+            return Err(ControllerError::new(
+                ErrorCode::TopicAlreadyExists,
+                format!("Topic '{}' already exists.", topic.name),
+            ));
+        }
+
+        let assignments = replica_assignments(topic, self.node_id())?;
+        let configs = validate_configs(&topic.configs)?;
+        for (name, value) in &configs {
+            validate_config(ConfigResource::Topic(&topic.name), name, value)?;
+        }
+
+        let dynamic = DynamicConfigs {
+            topic: configs.clone(),
+            ..self.dynamic_configs(&image, ConfigResource::Topic(&topic.name))
+        };
+        let created = CreatedTopic {
+            topic_id: Uuid::nil(),
+            num_partitions: assignments.len() as i32,
+            replication_factor: assignments.first().map(Vec::len).unwrap_or_default() as i16,
+            configs: topic_configs(&dynamic, &self.properties),
+        };
+
+        if validate_only {
+            return Ok(created);
+        }
+
+        let topic_id = Uuid::new_v4();
+        let directories = self.assign_directories(assignments.len())?;
+        let mut records = vec![RecordValueByType::Topic(TopicRecordValue::new(
+            &topic.name,
+            topic_id,
+        ))];
+        records.extend(assignments.iter().zip(&directories).enumerate().map(
+            |(partition, (replicas, directory_id))| {
+                RecordValueByType::Partition(PartitionRecordValue::new(
+                    partition as i32,
+                    topic_id,
+                    replicas.clone(),
+                    replica_directories(replicas, self.node_id(), *directory_id),
+                ))
+            },
+        ));
+        records.extend(configs.iter().map(|(name, value)| {
+            RecordValueByType::Config(ConfigRecordValue::new(
+                TOPIC_RESOURCE_TYPE,
+                &topic.name,
+                name,
+                Some(value),
+            ))
+        }));
+
+        self.append(&image, records)?;
+        self.create_logs(&topic.name, 0..created.num_partitions, &directories)?;
+
+        Ok(CreatedTopic {
+            topic_id,
+            ..created
+        })
+    }
+
+    /// Grows `topic` to `count` partitions. `assignments` holds the replicas
+    /// of each new partition, or is empty to place them on this broker.
+    pub(crate) fn create_partitions(
+        &self,
+        topic: &str,
+        count: i32,
+        assignments: &[Vec<i32>],
+        validate_only: bool,
+    ) -> Result<(), ControllerError> {
+        let _tailer = self.tailer();
+        let image = self.image();
+
+        let Some((topic_id, current)) = image
+            .topic(topic)
+            .map(|topic| (topic.id(), topic.partition_count() as i32))
+        else {
+            return Err(ControllerError::new(
+                ErrorCode::UnknownTopicOrPartition,
+                format!("The topic '{}' does not exist.", topic),
+            ));
+        };
+
+        if count < current {
+            return Err(ControllerError::new(
+                ErrorCode::InvalidPartitions,
+                format!(
+                    "Topic currently has {} partitions, which is higher than the requested {}.",
+                    current, count
+                ),
+            ));
+        }
+
+        if count == current {
+            return Err(ControllerError::new(
+                ErrorCode::InvalidPartitions,
+                format!("Topic already has {} partitions.", current),
+            ));
+        }
+
+        if count > MAX_PARTITIONS {
+            return Err(ControllerError::new(
+                ErrorCode::InvalidPartitions,
+                format!(
+                    "Number of partitions {} exceeds the maximum of {}.",
+                    count, MAX_PARTITIONS
+                ),
+            ));
+        }
+
+        let added = count - current;
+        let replicas = if assignments.is_empty() {
+            vec![vec![self.node_id()]; added as usize]
+        } else {
+            if assignments.len() as i32 != added {
+                return Err(ControllerError::new(
+                    ErrorCode::InvalidReplicaAssignment,
+                    format!(
+                        "Attempted to add {} additional partition(s), but only {} assignment(s) were specified.",
+                        added,
+                        assignments.len()
+                    ),
+                ));
+            }
+
+            for (partition, replicas) in (current..).zip(assignments) {
+                validate_replicas(partition, replicas, self.node_id())?;
+            }
+
+            assignments.to_vec()
+        };
+
+        if validate_only {
+            return Ok(());
+        }
+
+        let directories = self.assign_directories(replicas.len())?;
+        let records = (current..)
+            .zip(replicas)
+            .zip(&directories)
+            .map(|((partition, replicas), directory_id)| {
+                let directories = replica_directories(&replicas, self.node_id(), *directory_id);
+                RecordValueByType::Partition(PartitionRecordValue::new(
+                    partition,
+                    topic_id,
+                    replicas,
+                    directories,
+                ))
+            })
+            .collect();
+
+        self.append(&image, records)?;
+        self.create_logs(topic, current..count, &directories)
+    }
+
+    /// Deletes the topic named `name`, or the one with `topic_id` when no
+    /// name is given, returning its name and id.
+    pub(crate) fn delete_topic(
+        &self,
+        name: Option<&str>,
+        topic_id: Uuid,
+    ) -> Result<(String, Uuid), ControllerError> {
+        let _tailer = self.tailer();
+        let image = self.image();
+
+        let topic = match name {
+            Some(name) => image.topic(name),
+            None => image.topic_by_id(topic_id),
+        };
+
+        // Deleting them would take the state of the coordinators with them.
+        if let Some(name) = topic.map(|topic| topic.name()).or(name) {
+            if is_internal_topic(name) {
+                return Err(ControllerError::new(
+                    ErrorCode::InvalidRequest,
+                    format!(
+                        "Topic '{}' is an internal topic and can't be deleted.",
+                        name
+                    ),
+                ));
+            }
+        }
+
+        let Some((name, topic_id, partitions)) = topic.map(|topic| {
+            (
+                topic.name().to_string(),
+                topic.id(),
+                topic
+                    .partitions()
+                    .map(|partition| partition.partition_id())
+                    .collect::<Vec<i32>>(),
+            )
+        }) else {
+            return Err(match name {
+                Some(_) => ControllerError::new(
+                    ErrorCode::UnknownTopicOrPartition,
+                    "This server does not host this topic-partition.",
+                ),
+                None => ControllerError::new(
+                    ErrorCode::UnknownTopic,
+                    "This server does not host this topic ID.",
+                ),
+            });
+        };
+
+        self.append(
+            &image,
+            vec![RecordValueByType::RemoveTopic(RemoveTopicRecordValue::new(
+                topic_id,
+            ))],
+        )?;
+
+        for partition in partitions {
+            if let Err(e) = self.logs.remove_log(&name, partition) {
+                eprintln!("Failed to delete log for {}-{}: {}", name, partition, e);
+            }
+        }
+
+        Ok((name, topic_id))
+    }
+
+    /// Describes the configs of a resource, limited to `keys` when given.
+    pub(crate) fn describe_configs(
+        &self,
+        resource_type: i8,
+        resource_name: &str,
+        keys: Option<&[String]>,
+    ) -> Result<Vec<ConfigEntry>, ControllerError> {
+        let image = self.image();
+        let resource = config_resource(&image, self.node_id(), resource_type, resource_name)?;
+        let dynamic = self.dynamic_configs(&image, resource);
+
+        let entries = match resource {
+            ConfigResource::Topic(_) => topic_configs(&dynamic, &self.properties),
+            ConfigResource::Broker(_) => broker_configs(&dynamic, &self.properties),
+            ConfigResource::ClusterDefault => cluster_default_configs(&dynamic),
+        };
+
+        Ok(match keys {
+            Some(keys) => entries
+                .into_iter()
+                .filter(|entry| keys.contains(&entry.name))
+                .collect(),
+            None => entries,
+        })
+    }
+
+    /// Replaces every dynamic config of a resource with `configs`.
+    pub(crate) fn alter_configs(
+        &self,
+        resource_type: i8,
+        resource_name: &str,
+        configs: &[(String, Option<String>)],
+        validate_only: bool,
+    ) -> Result<(), ControllerError> {
+        let _tailer = self.tailer();
+        let image = self.image();
+        let resource = config_resource(&image, self.node_id(), resource_type, resource_name)?;
+
+        let configs = validate_configs(configs)?;
+        for (name, value) in &configs {
+            validate_config(resource, name, value)?;
+        }
+
+        let current = image.configs(resource.resource_type(), &resource.resource_name());
+        let mut changes = current
+            .keys()
+            .filter(|name| !configs.contains_key(*name))
+            .map(|name| (name.clone(), None))
+            .collect::<Vec<(String, Option<String>)>>();
+        changes.extend(
+            configs
+                .into_iter()
+                .filter(|(name, value)| current.get(name) != Some(value))
+                .map(|(name, value)| (name, Some(value))),
+        );
+
+        if validate_only {
+            return Ok(());
+        }
+
+        self.append_configs(&image, resource, changes)
+    }
+
+    /// Applies `SET`, `DELETE`, `APPEND` and `SUBTRACT` operations to the
+    /// dynamic configs of a resource, leaving other configs unchanged.
+    pub(crate) fn incremental_alter_configs(
+        &self,
+        resource_type: i8,
+        resource_name: &str,
+        operations: &[(String, AlterConfigOp, Option<String>)],
+        validate_only: bool,
+    ) -> Result<(), ControllerError> {
+        let _tailer = self.tailer();
+        let image = self.image();
+        let resource = config_resource(&image, self.node_id(), resource_type, resource_name)?;
+
+        let mut seen = HashSet::new();
+        if let Some((name, _, _)) = operations
+            .iter()
+            .find(|(name, _, _)| !seen.insert(name.as_str()))
+        {
+            return Err(ControllerError::new(
+                ErrorCode::InvalidRequest,
+                format!("Error due to duplicate config keys: {}", name),
+            ));
+        }
+
+        let dynamic = self.dynamic_configs(&image, resource);
+        let current = match resource {
+            ConfigResource::Topic(_) => &dynamic.topic,
+            ConfigResource::Broker(_) => &dynamic.broker,
+            ConfigResource::ClusterDefault => &dynamic.cluster_default,
+        };
+        let effective = match resource {
+            ConfigResource::Topic(_) => topic_configs(&dynamic, &self.properties),
+            _ => broker_configs(&dynamic, &self.properties),
+        };
+
+        let mut changes = Vec::new();
+        for (name, op, value) in operations {
+            if *op == AlterConfigOp::Delete {
+                changes.push((name.clone(), None));
+                continue;
+            }
+
+            let Some(value) = value else {
+                return Err(ControllerError::new(
+                    ErrorCode::InvalidRequest,
+                    format!("Null value not supported for: {}", name),
+                ));
+            };
+
+            let value = match op {
+                AlterConfigOp::Append | AlterConfigOp::Subtract => {
+                    if config::config_type(name) != ConfigType::List {
+                        return Err(ControllerError::new(
+                            ErrorCode::InvalidConfig,
+                            format!(
+                                "Config value append is not allowed for config key: {}",
+                                name
+                            ),
+                        ));
+                    }
+
+                    let old = current.get(name).cloned().or_else(|| {
+                        effective
+                            .iter()
+                            .find(|entry| &entry.name == name)
+                            .and_then(|entry| entry.value.clone())
+                    });
+                    let mut elements = list_elements(old.as_deref().unwrap_or_default());
+                    let operands = list_elements(value);
+
+                    if *op == AlterConfigOp::Append {
+                        elements.extend(
+                            operands
+                                .into_iter()
+                                .filter(|element| !elements.contains(element))
+                                .collect::<Vec<String>>(),
+                        );
+                    } else {
+                        elements.retain(|element| !operands.contains(element));
+                    }
+
+                    elements.join(",")
+                }
+                _ => value.clone(),
+            };
+
+            validate_config(resource, name, &value)?;
+            changes.push((name.clone(), Some(value)));
+        }
+
+        if validate_only {
+            return Ok(());
+        }
+
+        self.append_configs(&image, resource, changes)
+    }
+
+    /// Sets the finalized level of each feature in `updates`, all or none of
+    /// them. A level of 0 removes a feature.
+    pub(crate) fn update_features(
+        &self,
+        updates: &[(String, i16, FeatureUpgradeType)],
+        validate_only: bool,
+    ) -> Result<(), ControllerError> {
+        let _tailer = self.tailer();
+        let image = self.image();
+
+        let mut seen = HashSet::new();
+        let mut records = Vec::new();
+        for (name, level, upgrade_type) in updates {
+            if !seen.insert(name.as_str()) {
+                return Err(ControllerError::new(
+                    ErrorCode::InvalidRequest,
+                    format!("Feature {} was specified more than once.", name),
+                ));
+            }
+
+            let invalid = |reason: String| {
+                ControllerError::new(
+                    ErrorCode::InvalidUpdateVersion,
+                    format!(
+                        "Invalid update version {} for feature {}. {}",
+                        level, name, reason
+                    ),
+                )
+            };
+
+            let Some(&(_, min_level, max_level)) = SUPPORTED_FEATURES
+                .iter()
+                .find(|(feature, _, _)| feature == name)
+            else {
+                return Err(invalid(
+                    "The controller does not support the given feature.".to_string(),
+                ));
+            };
+
+            let current = image.feature_level(name).unwrap_or_default();
+            if *level == current {
+                continue;
+            }
+
+            if *level < current {
+                if *upgrade_type == FeatureUpgradeType::Upgrade {
+                    return Err(invalid(
+                        "Can't downgrade the version of this feature without setting the upgrade type to either safe or unsafe downgrade.".to_string(),
+                    ));
+                }
+                // Older metadata versions may not be able to express what
+                // the log already holds.
+                if name == METADATA_VERSION_FEATURE {
+                    return Err(invalid(match upgrade_type {
+                        FeatureUpgradeType::UnsafeDowngrade => {
+                            "Unsafe metadata downgrade is not supported in this version.".to_string()
+                        }
+                        _ => "Refusing to perform the requested downgrade because it might delete metadata information.".to_string(),
+                    }));
+                }
+            }
+
+            if (*level != 0 || name == METADATA_VERSION_FEATURE)
+                && !(min_level..=max_level).contains(level)
+            {
+                return Err(invalid(format!(
+                    "Local controller {} only supports versions {}-{}",
+                    self.node_id(),
+                    min_level,
+                    max_level
+                )));
+            }
+
+            records.push(RecordValueByType::Feature(FeatureRecordValue::new(
+                name, *level,
+            )));
+        }
+
+        if validate_only || records.is_empty() {
+            return Ok(());
+        }
+
+        self.append(&image, records)
+    }
+
+    /// Hands out the next block of producer ids to this broker, recording it
+    /// in the metadata log so no id is handed out twice.
+    pub(crate) fn allocate_producer_ids(&self) -> Result<Range<i64>, ControllerError> {
+        let _tailer = self.tailer();
+        let image = self.image();
+
+        let first = image.next_producer_id();
+        let broker_epoch = image
+            .broker(self.node_id())
+            .map(|broker| broker.epoch())
+            .unwrap_or(-1);
+        self.append(
+            &image,
+            vec![RecordValueByType::ProducerIds(ProducerIdsRecordValue::new(
+                self.node_id(),
+                broker_epoch,
+                first + PRODUCER_ID_BLOCK_SIZE,
+            ))],
+        )?;
+
+        Ok(first..first + PRODUCER_ID_BLOCK_SIZE)
+    }
+
+    fn dynamic_configs(&self, image: &MetadataImage, resource: ConfigResource) -> DynamicConfigs {
+        DynamicConfigs {
+            topic: match resource {
+                ConfigResource::Topic(name) => image.configs(TOPIC_RESOURCE_TYPE, name),
+                _ => BTreeMap::new(),
+            },
+            broker: image.configs(BROKER_RESOURCE_TYPE, &self.node_id().to_string()),
+            cluster_default: image.configs(BROKER_RESOURCE_TYPE, ""),
+        }
+    }
+
+    fn append_configs(
+        &self,
+        image: &MetadataImage,
+        resource: ConfigResource,
+        changes: Vec<(String, Option<String>)>,
+    ) -> Result<(), ControllerError> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+
+        let resource_name = resource.resource_name();
+        let records = changes
+            .iter()
+            .map(|(name, value)| {
+                RecordValueByType::Config(ConfigRecordValue::new(
+                    resource.resource_type(),
+                    &resource_name,
+                    name,
+                    value.as_deref(),
+                ))
+            })
+            .collect();
+
+        self.append(image, records)
+    }
+
+    /// Persists `records` as one batch of the metadata log, then publishes
+    /// `image` with the batch replayed.
+    fn append(
+        &self,
+        image: &MetadataImage,
+        records: Vec<RecordValueByType>,
+    ) -> Result<(), ControllerError> {
+        let records = records
+            .into_iter()
+            .enumerate()
+            .map(|(offset_delta, value)| {
+                Record::new(offset_delta as i32, None, RecordValue::new(value))
+            })
+            .collect();
+        let mut batch = Batch::new(
+            0,
+            NO_PRODUCER_ID,
+            NO_PRODUCER_EPOCH,
+            NO_SEQUENCE,
+            now_ms(),
+            records,
+        );
+
+        // Batches another writer appended since the last poll are missing
+        // from `image`, which the change was checked against. The change is
+        // refused until a poll has applied them.
+        let base_offset = self
+            .logs
+            .with_log(
+                CLUSTER_METADATA_TOPIC,
+                CLUSTER_METADATA_PARTITION,
+                |log| match log.catch_up()? {
+                    true => Ok(None),
+                    false => log.append(batch.clone()).map(Some),
+                },
+            )
+            .map_err(|e| {
+                ControllerError::new(
+                    ErrorCode::UnknownServerError,
+                    format!("Failed to write to the metadata log: {}", e),
+                )
+            })?
+            .ok_or_else(|| {
+                ControllerError::new(
+                    ErrorCode::NotController,
+                    "The metadata log has batches this controller has not applied yet.",
+                )
+            })?;
+
+        batch.set_base_offset(base_offset);
+        let mut next = image.clone();
+        next.apply_batch(&batch);
+        self.publish(next);
+
+        Ok(())
+    }
+
+    /// Picks the log directory of each of `count` new partitions.
+    fn assign_directories(&self, count: usize) -> Result<Vec<Uuid>, ControllerError> {
+        self.logs.assign_directories(count).map_err(|e| {
+            ControllerError::new(
+                ErrorCode::KafkaStorageError,
+                format!("Failed to read the log directories: {}", e),
+            )
+        })
+    }
+
+    /// Creates the logs of `partitions` of `topic`, each in the log directory
+    /// at the same position in `directories`.
+    fn create_logs(
+        &self,
+        topic: &str,
+        partitions: Range<i32>,
+        directories: &[Uuid],
+    ) -> Result<(), ControllerError> {
+        for (partition, directory_id) in partitions.zip(directories) {
+            self.logs
+                .create_log(topic, partition, *directory_id)
+                .map_err(|e| {
+                    ControllerError::new(
+                        ErrorCode::KafkaStorageError,
+                        format!("Failed to create log for {}-{}: {}", topic, partition, e),
+                    )
+                })?;
+        }
+
+        Ok(())
+    }
+
+    fn tailer(&self) -> MutexGuard<'_, MetadataLogTailer> {
+        self.tailer
+            .lock()
+            .expect("metadata log tailer lock poisoned")
+    }
+
+    fn publish(&self, image: MetadataImage) {
+        *self.image.write().expect("metadata image lock poisoned") = Arc::new(image);
+    }
+}
+
+/// The log directory of each of `replicas`: `directory_id` for this broker
+/// (`node_id`), and unassigned for the others, whose directories it doesn't
+/// know.
+fn replica_directories(replicas: &[i32], node_id: i32, directory_id: Uuid) -> Vec<Uuid> {
+    replicas
+        .iter()
+        .map(|replica| {
+            if *replica == node_id {
+                directory_id
+            } else {
+                Uuid::nil()
+            }
+        })
+        .collect()
+}
+
+/// Creates the logs of the partitions of this broker (`node_id`) that are in
+/// `next` but not in `previous`, each in the log directory the metadata
+/// assigns to it.
+fn place_new_partitions(
+    logs: &LogManager,
+    node_id: i32,
+    previous: &MetadataImage,
+    next: &MetadataImage,
+) -> std::io::Result<()> {
+    for topic in next.topics() {
+        for partition in topic.partitions() {
+            if previous
+                .partition(topic.id(), partition.partition_id())
+                .is_some()
+            {
+                continue;
+            }
+            let Some(replica) = partition
+                .replicas()
+                .iter()
+                .position(|replica| *replica == node_id)
+            else {
+                continue;
+            };
+
+            let directory_id = partition
+                .directories()
+                .get(replica)
+                .copied()
+                .unwrap_or_default();
+            logs.create_log(topic.name(), partition.partition_id(), directory_id)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_topic_name(name: &str) -> Result<(), ControllerError> {
+    let error = |message: String| {
+        Err(ControllerError::new(
+            ErrorCode::InvalidTopicException,
+            message,
+        ))
+    };
+
+    if name.is_empty() {
+        return error("Topic name is illegal, it can't be empty".to_string());
+    }
+
+    if name == "." || name == ".." {
+        return error("Topic name cannot be \".\" or \"..\"".to_string());
+    }
+
+    if name.len() > MAX_TOPIC_NAME_LENGTH {
+        return error(format!(
+            "Topic name is illegal, it can't be longer than {} characters, topic name: {}",
+            MAX_TOPIC_NAME_LENGTH, name
+        ));
+    }
+
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-')
+    {
+        return error(format!(
+            "Topic name \"{}\" is illegal, it contains a character other than ASCII alphanumerics, '.', '_' and '-'",
+            name
+        ));
+    }
+
+    Ok(())
+}
+
+/// Resolves the replicas of every partition, indexed by partition id. Without
+/// an explicit assignment every partition is placed on this broker
+/// (`node_id`).
+fn replica_assignments(topic: &NewTopic, node_id: i32) -> Result<Vec<Vec<i32>>, ControllerError> {
+    if topic.assignments.is_empty() {
+        let num_partitions = match topic.num_partitions {
+            -1 => DEFAULT_NUM_PARTITIONS,
+            n => n,
+        };
+        let replication_factor = match topic.replication_factor {
+            -1 => DEFAULT_REPLICATION_FACTOR,
+            n => n,
+        };
+
+        if num_partitions <= 0 {
+            return Err(ControllerError::new(
+                ErrorCode::InvalidPartitions,
+                "Number of partitions was set to an invalid non-positive value.",
+            ));
+        }
+
+        if num_partitions > MAX_PARTITIONS {
+            return Err(ControllerError::new(
+                ErrorCode::InvalidPartitions,
+                format!(
+                    "Number of partitions {} exceeds the maximum of {}.",
+                    num_partitions, MAX_PARTITIONS
+                ),
+            ));
+        }
+
+        if replication_factor <= 0 {
+            return Err(ControllerError::new(
+                ErrorCode::InvalidReplicationFactor,
+                "Replication factor must be larger than 0, or -1 to use the default value.",
+            ));
+        }
+
+        if replication_factor > 1 {
+            return Err(ControllerError::new(
+                ErrorCode::InvalidReplicationFactor,
+                format!(
+                    "Unable to replicate the partition {} time(s): The target replication factor of {} cannot be reached because only 1 broker(s) are registered.",
+                    replication_factor, replication_factor
+                ),
+            ));
+        }
+
+        return Ok(vec![vec![node_id]; num_partitions as usize]);
+    }
+
+    if topic.num_partitions != -1 || topic.replication_factor != -1 {
+        return Err(ControllerError::new(
+            ErrorCode::InvalidRequest,
+            "Both numPartitions or replicationFactor and replicasAssignments were set. Both cannot be used at the same time.",
+        ));
+    }
+
+    let mut assignments = topic.assignments.clone();
+    assignments.sort_by_key(|(partition, _)| *partition);
+
+    if assignments
+        .iter()
+        .enumerate()
+        .any(|(index, (partition, _))| index as i32 != *partition)
+    {
+        return Err(ControllerError::new(
+            ErrorCode::InvalidReplicaAssignment,
+            "Partitions should be a consecutive 0-based integer sequence",
+        ));
+    }
+
+    let replication_factor = assignments[0].1.len();
+    for (partition, replicas) in &assignments {
+        validate_replicas(*partition, replicas, node_id)?;
+
+        if replicas.len() != replication_factor {
+            return Err(ControllerError::new(
+                ErrorCode::InvalidReplicaAssignment,
+                "All partitions should have the same number of replicas",
+            ));
+        }
+    }
+
+    Ok(assignments
+        .into_iter()
+        .map(|(_, replicas)| replicas)
+        .collect())
+}
+
+/// Checks the replicas of `partition`, which can only be this broker
+/// (`node_id`).
+fn validate_replicas(
+    partition: i32,
+    replicas: &[i32],
+    node_id: i32,
+) -> Result<(), ControllerError> {
+    if replicas.is_empty() {
+        return Err(ControllerError::new(
+            ErrorCode::InvalidReplicaAssignment,
+            format!("Empty replica assignment for partition {}", partition),
+        ));
+    }
+
+    if replicas.iter().collect::<HashSet<_>>().len() != replicas.len() {
+        return Err(ControllerError::new(
+            ErrorCode::InvalidReplicaAssignment,
+            format!(
+                "Duplicate brokers in replica assignment for partition {}",
+                partition
+            ),
+        ));
+    }
+
+    if let Some(broker) = replicas.iter().find(|broker| **broker != node_id) {
+        return Err(ControllerError::new(
+            ErrorCode::InvalidReplicaAssignment,
+            format!(
+                "The manual partition assignment includes broker {}, but no such broker is registered.",
+                broker
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Rejects duplicate and null configs.
+fn validate_configs(
+    configs: &[(String, Option<String>)],
+) -> Result<BTreeMap<String, String>, ControllerError> {
+    let mut validated = BTreeMap::new();
+
+    for (name, value) in configs {
+        let Some(value) = value else {
+            return Err(ControllerError::new(
+                ErrorCode::InvalidConfig,
+                format!("Null value not supported for topic configs: {}", name),
+            ));
+        };
+
+        if validated.insert(name.clone(), value.clone()).is_some() {
+            return Err(ControllerError::new(
+                ErrorCode::InvalidConfig,
+                format!("Duplicate config {}", name),
+            ));
+        }
+    }
+
+    Ok(validated)
+}
+
+fn validate_config(
+    resource: ConfigResource,
+    name: &str,
+    value: &str,
+) -> Result<(), ControllerError> {
+    match resource {
+        ConfigResource::Topic(_) => validate_topic_config(name, value),
+        ConfigResource::Broker(_) | ConfigResource::ClusterDefault => {
+            validate_broker_config(name, value)
+        }
+    }
+    .map_err(|message| ControllerError::new(ErrorCode::InvalidConfig, message))
+}
+
+/// Resolves the resource named in a config request, which must be an
+/// existing topic, this broker (`node_id`) or the cluster defaults.
+fn config_resource<'a>(
+    image: &MetadataImage,
+    node_id: i32,
+    resource_type: i8,
+    resource_name: &'a str,
+) -> Result<ConfigResource<'a>, ControllerError> {
+    match resource_type {
+        TOPIC_RESOURCE_TYPE => {
+            if image.topic(resource_name).is_none() {
+                return Err(ControllerError::new(
+                    ErrorCode::UnknownTopicOrPartition,
+                    format!("Topic {} does not exist.", resource_name),
+                ));
+            }
+
+            Ok(ConfigResource::Topic(resource_name))
+        }
+        BROKER_RESOURCE_TYPE if resource_name.is_empty() => Ok(ConfigResource::ClusterDefault),
+        BROKER_RESOURCE_TYPE if resource_name == node_id.to_string() => {
+            Ok(ConfigResource::Broker(node_id))
+        }
+        BROKER_RESOURCE_TYPE => Err(ControllerError::new(
+            ErrorCode::InvalidRequest,
+            format!(
+                "Unexpected broker id, expected {} or empty string, but received {}",
+                node_id, resource_name
+            ),
+        )),
+        _ => Err(ControllerError::new(
+            ErrorCode::InvalidRequest,
+            format!("Unsupported resource type {}", resource_type),
+        )),
+    }
+}
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::partition_log::PartitionLog;
+
+    fn new_topic(num_partitions: i32) -> NewTopic {
+        NewTopic {
+            name: "foo".to_string(),
+            num_partitions,
+            replication_factor: -1,
+            assignments: Vec::new(),
+            configs: Vec::new(),
+        }
+    }
+
+    /// A controller over an empty, freshly created log directory, configured
+    /// with the lines of `properties`.
+    fn controller(properties: &str) -> (Controller, std::path::PathBuf) {
+        let path = std::env::temp_dir().join(format!("controller-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&path).unwrap();
+        let properties_path = path.join("server.properties");
+        std::fs::write(&properties_path, properties).unwrap();
+        let logs = LogManager::new(vec![crate::storage::LogDir {
+            path: path.clone(),
+            id: Uuid::new_v4(),
+        }]);
+        let controller = Controller::load(
+            Arc::new(logs),
+            ServerProperties::load(&properties_path).unwrap(),
+            "cluster".to_string(),
+        )
+        .unwrap();
+
+        (controller, path)
+    }
+
+    #[test]
+    fn appends_wait_for_batches_of_another_writer_to_be_applied() {
+        let (controller, path) = controller("");
+        let baz = NewTopic {
+            name: "baz".to_string(),
+            ..new_topic(1)
+        };
+        controller.create_topic(&baz, false).unwrap();
+        let mut other_writer =
+            PartitionLog::open(&path, CLUSTER_METADATA_TOPIC, CLUSTER_METADATA_PARTITION).unwrap();
+        let bar = Uuid::new_v4();
+        other_writer
+            .append(Batch::new(
+                0,
+                NO_PRODUCER_ID,
+                NO_PRODUCER_EPOCH,
+                NO_SEQUENCE,
+                0,
+                vec![Record::new(
+                    0,
+                    None,
+                    RecordValue::new(RecordValueByType::Topic(TopicRecordValue::new("bar", bar))),
+                )],
+            ))
+            .unwrap();
+
+        let e = controller.create_topic(&new_topic(1), false).unwrap_err();
+        assert_eq!(e.error_code, ErrorCode::NotController);
+        controller.poll_metadata_log().unwrap();
+        controller.create_topic(&new_topic(1), false).unwrap();
+        controller.poll_metadata_log().unwrap();
+
+        let image = controller.image();
+        assert_eq!(image.topic("bar").map(|topic| topic.id()), Some(bar));
+        assert_eq!(
+            image
+                .topic("foo")
+                .unwrap()
+                .partition(0)
+                .unwrap()
+                .partition_epoch(),
+            0
+        );
+        let offsets = MetadataLogTailer::new(controller.logs.metadata_log_dir())
+            .poll()
+            .unwrap()
+            .iter()
+            .map(|batch| batch.base_offset())
+            .collect::<Vec<i64>>();
+        assert_eq!(offsets.len(), 3);
+        assert!(offsets.windows(2).all(|pair| pair[0] < pair[1]));
+
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn replica_assignments_caps_num_partitions() {
+        assert_eq!(
+            replica_assignments(&new_topic(MAX_PARTITIONS), 1)
+                .unwrap()
+                .len(),
+            MAX_PARTITIONS as usize
+        );
+
+        for num_partitions in [MAX_PARTITIONS + 1, i32::MAX] {
+            let e = replica_assignments(&new_topic(num_partitions), 1).unwrap_err();
+            assert_eq!(e.error_code, ErrorCode::InvalidPartitions);
+        }
+    }
+
+    #[test]
+    fn create_partitions_caps_the_partition_count() {
+        let (controller, path) = controller("");
+        controller.create_topic(&new_topic(1), false).unwrap();
+
+        for count in [MAX_PARTITIONS + 1, i32::MAX] {
+            let e = controller
+                .create_partitions("foo", count, &[], false)
+                .unwrap_err();
+            assert_eq!(e.error_code, ErrorCode::InvalidPartitions);
+        }
+        controller.create_partitions("foo", 3, &[], true).unwrap();
+
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn partitions_and_configs_follow_the_configured_node_id() {
+        let (controller, path) = controller("node.id=5\n");
+        controller.create_topic(&new_topic(1), false).unwrap();
+        controller.create_partitions("foo", 2, &[], false).unwrap();
+        controller
+            .create_partitions("foo", 3, &[vec![5]], false)
+            .unwrap();
+
+        let image = controller.image();
+        let topic = image.topic("foo").unwrap();
+        for partition in topic.partitions() {
+            assert_eq!(partition.replicas(), [5]);
+            assert_eq!(partition.leader(), 5);
+            assert!(!partition.directories()[0].is_nil());
+            assert!(path
+                .join(format!("foo-{}", partition.partition_id()))
+                .is_dir());
+        }
+
+        let e = controller
+            .create_partitions("foo", 4, &[vec![1]], false)
+            .unwrap_err();
+        assert_eq!(e.error_code, ErrorCode::InvalidReplicaAssignment);
+
+        assert!(controller
+            .describe_configs(BROKER_RESOURCE_TYPE, "5", None)
+            .is_ok());
+        let e = controller
+            .describe_configs(BROKER_RESOURCE_TYPE, "1", None)
+            .unwrap_err();
+        assert_eq!(e.error_code, ErrorCode::InvalidRequest);
+
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+}
@@ -0,0 +1,733 @@
+use std::collections::{BTreeMap, HashMap};
+
+use uuid::Uuid;
+
+use super::snapshot::Snapshot;
+
+use crate::protocol::{
+    cluster_metadata::{
+        AccessControlEntryRecordValue, Batch, BrokerEndpoint, BrokerFeature,
+        ClientQuotaRecordValue, ConfigRecordValue, DelegationTokenRecordValue, FeatureRecordValue,
+        PartitionChangeRecordValue, PartitionRecordValue, ProducerIdsRecordValue,
+        RecordValueByType, RegisterBrokerRecordValue, TopicRecordValue,
+        UserScramCredentialRecordValue, TOPIC_RESOURCE_TYPE,
+    },
+    primitives::{CompactArray, INT32},
+};
+use crate::transaction::coordinator::is_internal_topic;
+
+/// The current state of the cluster, built by replaying the metadata log
+/// record by record.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct MetadataImage {
+    /// Offset of the first metadata log record not yet replayed.
+    next_offset: i64,
+    /// Leader epoch of the last batch replayed, which names a snapshot of
+    /// the image.
+    last_epoch: i32,
+    topics: HashMap<Uuid, TopicImage>,
+    topic_ids: BTreeMap<String, Uuid>,
+    brokers: BTreeMap<i32, BrokerImage>,
+    /// Dynamic configs keyed by resource type and name.
+    configs: HashMap<(i8, String), BTreeMap<String, String>>,
+    features: BTreeMap<String, i16>,
+    /// ACLs keyed by id.
+    acls: BTreeMap<Uuid, AccessControlEntryRecordValue>,
+    /// The latest block of producer ids handed out.
+    producer_ids: Option<ProducerIdsRecordValue>,
+    /// Client quotas keyed by entity and quota name.
+    client_quotas: BTreeMap<(QuotaEntity, String), ClientQuotaRecordValue>,
+    /// SCRAM credentials keyed by user name and mechanism.
+    scram_credentials: BTreeMap<(String, i8), UserScramCredentialRecordValue>,
+    /// Delegation tokens keyed by token id.
+    delegation_tokens: BTreeMap<String, DelegationTokenRecordValue>,
+    /// Records of unknown types, in log order since the image can't tell
+    /// which of them supersede others.
+    unknown: Vec<RecordValueByType>,
+    /// Records of an open metadata transaction, applied once it ends.
+    pending: Option<Vec<RecordValueByType>>,
+}
+
+/// A client quota entity as its `(type, name)` pairs, sorted.
+type QuotaEntity = Vec<(String, Option<String>)>;
+
+#[derive(Debug, Clone)]
+pub(crate) struct TopicImage {
+    name: String,
+    id: Uuid,
+    partitions: BTreeMap<i32, PartitionImage>,
+}
+
+impl TopicImage {
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub(crate) fn id(&self) -> Uuid {
+        self.id
+    }
+
+    pub(crate) fn is_internal(&self) -> bool {
+        is_internal_topic(&self.name)
+    }
+
+    pub(crate) fn partitions(&self) -> impl Iterator<Item = &PartitionImage> {
+        self.partitions.values()
+    }
+
+    pub(crate) fn partition(&self, partition_id: i32) -> Option<&PartitionImage> {
+        self.partitions.get(&partition_id)
+    }
+
+    pub(crate) fn partition_count(&self) -> usize {
+        self.partitions.len()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct PartitionImage {
+    partition_id: i32,
+    replicas: Vec<i32>,
+    isr: Vec<i32>,
+    removing_replicas: Vec<i32>,
+    adding_replicas: Vec<i32>,
+    leader: i32,
+    leader_recovery_state: i8,
+    leader_epoch: i32,
+    partition_epoch: i32,
+    directories: Vec<Uuid>,
+    eligible_leader_replicas: Vec<i32>,
+    last_known_elr: Vec<i32>,
+}
+
+impl PartitionImage {
+    pub(crate) fn partition_id(&self) -> i32 {
+        self.partition_id
+    }
+
+    pub(crate) fn replicas(&self) -> &[i32] {
+        &self.replicas
+    }
+
+    pub(crate) fn isr(&self) -> &[i32] {
+        &self.isr
+    }
+
+    pub(crate) fn removing_replicas(&self) -> &[i32] {
+        &self.removing_replicas
+    }
+
+    pub(crate) fn adding_replicas(&self) -> &[i32] {
+        &self.adding_replicas
+    }
+
+    pub(crate) fn leader(&self) -> i32 {
+        self.leader
+    }
+
+    pub(crate) fn leader_recovery_state(&self) -> i8 {
+        self.leader_recovery_state
+    }
+
+    pub(crate) fn leader_epoch(&self) -> i32 {
+        self.leader_epoch
+    }
+
+    pub(crate) fn partition_epoch(&self) -> i32 {
+        self.partition_epoch
+    }
+
+    pub(crate) fn directories(&self) -> &[Uuid] {
+        &self.directories
+    }
+
+    pub(crate) fn eligible_leader_replicas(&self) -> &[i32] {
+        &self.eligible_leader_replicas
+    }
+
+    pub(crate) fn last_known_elr(&self) -> &[i32] {
+        &self.last_known_elr
+    }
+
+    /// Applies a `PartitionChangeRecord`. Every change bumps the partition
+    /// epoch; a new leader also bumps the leader epoch.
+    fn apply(&mut self, change: &PartitionChangeRecordValue) {
+        if let Some(replicas) = change.replicas() {
+            self.replicas = replicas.to_vec();
+            if change.directories().is_none() {
+                self.directories = Vec::new();
+            }
+        }
+        if let Some(isr) = change.isr() {
+            self.isr = isr.to_vec();
+        }
+        if let Some(removing) = change.removing_replicas() {
+            self.removing_replicas = removing.to_vec();
+        }
+        if let Some(adding) = change.adding_replicas() {
+            self.adding_replicas = adding.to_vec();
+        }
+        if let Some(leader) = change.leader() {
+            self.leader = leader;
+            self.leader_epoch += 1;
+        }
+        if let Some(state) = change.leader_recovery_state() {
+            self.leader_recovery_state = state;
+        }
+        if let Some(directories) = change.directories() {
+            self.directories = directories.to_vec();
+        }
+        if let Some(elr) = change.eligible_leader_replicas() {
+            self.eligible_leader_replicas = elr.to_vec();
+        }
+        if let Some(last_known_elr) = change.last_known_elr() {
+            self.last_known_elr = last_known_elr.to_vec();
+        }
+
+        self.partition_epoch += 1;
+    }
+}
+
+impl From<&PartitionRecordValue> for PartitionImage {
+    fn from(record: &PartitionRecordValue) -> Self {
+        let int32s =
+            |array: &CompactArray<INT32>| array.iter().map(INT32::value).collect::<Vec<i32>>();
+
+        Self {
+            partition_id: record.partition_id(),
+            replicas: int32s(record.replica_array()),
+            isr: int32s(record.in_sync_replica_array()),
+            removing_replicas: int32s(record.removing_replicas_array()),
+            adding_replicas: int32s(record.adding_replicas_array()),
+            leader: record.leader(),
+            leader_recovery_state: record.leader_recovery_state(),
+            leader_epoch: record.leader_epoch(),
+            partition_epoch: record.partition_epoch(),
+            directories: record.directories_array().to_vec(),
+            eligible_leader_replicas: record
+                .eligible_leader_replicas()
+                .map(<[i32]>::to_vec)
+                .unwrap_or_default(),
+            last_known_elr: record
+                .last_known_elr()
+                .map(<[i32]>::to_vec)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct BrokerImage {
+    id: i32,
+    epoch: i64,
+    incarnation_id: Uuid,
+    end_points: Vec<BrokerEndpoint>,
+    features: Vec<BrokerFeature>,
+    rack: Option<String>,
+    fenced: bool,
+    in_controlled_shutdown: bool,
+    log_dirs: Vec<Uuid>,
+}
+
+impl BrokerImage {
+    pub(crate) fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub(crate) fn epoch(&self) -> i64 {
+        self.epoch
+    }
+
+    pub(crate) fn incarnation_id(&self) -> Uuid {
+        self.incarnation_id
+    }
+
+    pub(crate) fn end_points(&self) -> &[BrokerEndpoint] {
+        &self.end_points
+    }
+
+    pub(crate) fn features(&self) -> &[BrokerFeature] {
+        &self.features
+    }
+
+    pub(crate) fn rack(&self) -> Option<&str> {
+        self.rack.as_deref()
+    }
+
+    pub(crate) fn fenced(&self) -> bool {
+        self.fenced
+    }
+
+    pub(crate) fn in_controlled_shutdown(&self) -> bool {
+        self.in_controlled_shutdown
+    }
+
+    pub(crate) fn log_dirs(&self) -> &[Uuid] {
+        &self.log_dirs
+    }
+}
+
+impl From<&RegisterBrokerRecordValue> for BrokerImage {
+    fn from(record: &RegisterBrokerRecordValue) -> Self {
+        Self {
+            id: record.broker_id(),
+            epoch: record.broker_epoch(),
+            incarnation_id: record.incarnation_id(),
+            end_points: record.end_points().to_vec(),
+            features: record.features().to_vec(),
+            rack: record.rack().map(str::to_string),
+            fenced: record.fenced(),
+            in_controlled_shutdown: record.in_controlled_shutdown(),
+            log_dirs: record.log_dirs().to_vec(),
+        }
+    }
+}
+
+impl MetadataImage {
+    pub(crate) fn topic(&self, name: &str) -> Option<&TopicImage> {
+        self.topic_ids.get(name).and_then(|id| self.topics.get(id))
+    }
+
+    pub(crate) fn topic_by_id(&self, topic_id: Uuid) -> Option<&TopicImage> {
+        self.topics.get(&topic_id)
+    }
+
+    /// Every topic, ordered by name.
+    pub(crate) fn topics(&self) -> impl Iterator<Item = &TopicImage> {
+        self.topic_ids.values().filter_map(|id| self.topics.get(id))
+    }
+
+    pub(crate) fn partition(&self, topic_id: Uuid, partition_id: i32) -> Option<&PartitionImage> {
+        self.topic_by_id(topic_id)
+            .and_then(|topic| topic.partition(partition_id))
+    }
+
+    pub(crate) fn broker(&self, broker_id: i32) -> Option<&BrokerImage> {
+        self.brokers.get(&broker_id)
+    }
+
+    /// The first producer id not yet handed out in a block.
+    pub(crate) fn next_producer_id(&self) -> i64 {
+        self.producer_ids
+            .as_ref()
+            .map(ProducerIdsRecordValue::next_producer_id)
+            .unwrap_or_default()
+    }
+
+    /// The dynamic configs of a resource.
+    pub(crate) fn configs(
+        &self,
+        resource_type: i8,
+        resource_name: &str,
+    ) -> BTreeMap<String, String> {
+        self.configs
+            .get(&(resource_type, resource_name.to_string()))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn feature_level(&self, name: &str) -> Option<i16> {
+        self.features.get(name).copied()
+    }
+
+    pub(crate) fn features(&self) -> &BTreeMap<String, i16> {
+        &self.features
+    }
+
+    /// The image a snapshot captures, covering the log up to the snapshot's
+    /// end offset.
+    pub(crate) fn from_snapshot(snapshot: &Snapshot) -> Self {
+        let mut image = Self::default();
+        for batch in snapshot.batches() {
+            for record in batch.records() {
+                image.replay(record.record_value().value());
+            }
+        }
+        image.next_offset = snapshot.id().end_offset;
+        image.last_epoch = snapshot.id().epoch;
+
+        image
+    }
+
+    pub(crate) fn next_offset(&self) -> i64 {
+        self.next_offset
+    }
+
+    pub(crate) fn last_epoch(&self) -> i32 {
+        self.last_epoch
+    }
+
+    /// Whether a metadata transaction is open, in which case the image
+    /// doesn't yet reflect every record up to its next offset.
+    pub(crate) fn in_transaction(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// The records that rebuild this image when replayed, as written to a
+    /// snapshot.
+    pub(crate) fn records(&self) -> Vec<RecordValueByType> {
+        let mut records = Vec::new();
+
+        records.extend(self.features.iter().map(|(name, level)| {
+            RecordValueByType::Feature(FeatureRecordValue::new(name, *level))
+        }));
+        records.extend(self.brokers.values().map(|broker| {
+            RecordValueByType::RegisterBroker(RegisterBrokerRecordValue::from_image(broker))
+        }));
+        for topic in self.topics() {
+            records.push(RecordValueByType::Topic(TopicRecordValue::new(
+                &topic.name,
+                topic.id,
+            )));
+            records.extend(topic.partitions().map(|partition| {
+                RecordValueByType::Partition(PartitionRecordValue::from_image(topic.id, partition))
+            }));
+        }
+        let configs = self.configs.iter().collect::<BTreeMap<_, _>>();
+        for ((resource_type, resource_name), values) in configs {
+            records.extend(values.iter().map(|(name, value)| {
+                RecordValueByType::Config(ConfigRecordValue::new(
+                    *resource_type,
+                    resource_name,
+                    name,
+                    Some(value),
+                ))
+            }));
+        }
+        records.extend(
+            self.acls
+                .values()
+                .cloned()
+                .map(RecordValueByType::AccessControlEntry),
+        );
+        records.extend(
+            self.producer_ids
+                .iter()
+                .cloned()
+                .map(RecordValueByType::ProducerIds),
+        );
+        records.extend(
+            self.client_quotas
+                .values()
+                .cloned()
+                .map(RecordValueByType::ClientQuota),
+        );
+        records.extend(
+            self.scram_credentials
+                .values()
+                .cloned()
+                .map(RecordValueByType::UserScramCredential),
+        );
+        records.extend(
+            self.delegation_tokens
+                .values()
+                .cloned()
+                .map(RecordValueByType::DelegationToken),
+        );
+        records.extend(self.unknown.iter().cloned());
+
+        records
+    }
+
+    /// Replays every record of a batch read from, or just appended to, the
+    /// metadata log.
+    pub(crate) fn apply_batch(&mut self, batch: &Batch) {
+        for record in batch.records() {
+            self.replay(record.record_value().value());
+        }
+        self.next_offset = batch.last_offset() + 1;
+        self.last_epoch = batch.partition_leader_epoch();
+    }
+
+    /// Replays one record. Records between `BeginTransaction` and
+    /// `EndTransaction` are held back until the transaction ends, and dropped
+    /// if it is aborted.
+    pub(crate) fn replay(&mut self, value: &RecordValueByType) {
+        match value {
+            RecordValueByType::BeginTransaction(_) => {
+                self.pending = Some(Vec::new());
+            }
+            RecordValueByType::EndTransaction(_) => {
+                for value in self.pending.take().unwrap_or_default() {
+                    self.apply(&value);
+                }
+            }
+            RecordValueByType::AbortTransaction(_) => {
+                self.pending = None;
+            }
+            value => match &mut self.pending {
+                Some(pending) => pending.push(value.clone()),
+                None => self.apply(value),
+            },
+        }
+    }
+
+    fn apply(&mut self, value: &RecordValueByType) {
+        match value {
+            RecordValueByType::Topic(topic) => {
+                // A name bound to a new id drops the topic it named before.
+                if let Some(old_id) = self
+                    .topic_ids
+                    .insert(topic.name().to_string(), topic.topic_uuid())
+                    .filter(|old_id| *old_id != topic.topic_uuid())
+                {
+                    self.topics.remove(&old_id);
+                }
+                self.topics.insert(
+                    topic.topic_uuid(),
+                    TopicImage {
+                        name: topic.name().to_string(),
+                        id: topic.topic_uuid(),
+                        partitions: BTreeMap::new(),
+                    },
+                );
+            }
+            RecordValueByType::Partition(partition) => {
+                if let Some(topic) = self.topics.get_mut(&partition.topic_uuid()) {
+                    topic
+                        .partitions
+                        .insert(partition.partition_id(), PartitionImage::from(partition));
+                }
+            }
+            RecordValueByType::PartitionChange(change) => {
+                if let Some(partition) = self
+                    .topics
+                    .get_mut(&change.topic_uuid())
+                    .and_then(|topic| topic.partitions.get_mut(&change.partition_id()))
+                {
+                    partition.apply(change);
+                }
+            }
+            RecordValueByType::RemoveTopic(remove) => {
+                if let Some(topic) = self.topics.remove(&remove.topic_uuid()) {
+                    self.topic_ids.remove(&topic.name);
+                    self.configs.remove(&(TOPIC_RESOURCE_TYPE, topic.name));
+                }
+            }
+            RecordValueByType::Config(config) => {
+                let key = (config.resource_type(), config.resource_name().to_string());
+                match config.value() {
+                    Some(value) => {
+                        self.configs
+                            .entry(key)
+                            .or_default()
+                            .insert(config.name().to_string(), value.to_string());
+                    }
+                    None => {
+                        if let Some(configs) = self.configs.get_mut(&key) {
+                            configs.remove(config.name());
+                            if configs.is_empty() {
+                                self.configs.remove(&key);
+                            }
+                        }
+                    }
+                }
+            }
+            RecordValueByType::Feature(feature) => {
+                if feature.feature_level() == 0 {
+                    self.features.remove(feature.name());
+                } else {
+                    self.features
+                        .insert(feature.name().to_string(), feature.feature_level());
+                }
+            }
+            RecordValueByType::RegisterBroker(broker) => {
+                self.brokers
+                    .insert(broker.broker_id(), BrokerImage::from(broker));
+            }
+            RecordValueByType::UnregisterBroker(broker) => {
+                self.brokers.remove(&broker.broker_id());
+            }
+            RecordValueByType::FenceBroker(fence) => {
+                if let Some(broker) = self.brokers.get_mut(&fence.id()) {
+                    broker.fenced = true;
+                }
+            }
+            RecordValueByType::UnfenceBroker(unfence) => {
+                if let Some(broker) = self.brokers.get_mut(&unfence.id()) {
+                    broker.fenced = false;
+                }
+            }
+            RecordValueByType::BrokerRegistrationChange(change) => {
+                if let Some(broker) = self.brokers.get_mut(&change.broker_id()) {
+                    match change.fenced() {
+                        1 => broker.fenced = true,
+                        -1 => broker.fenced = false,
+                        _ => {}
+                    }
+                    if change.in_controlled_shutdown() == 1 {
+                        broker.in_controlled_shutdown = true;
+                    }
+                    if let Some(log_dirs) = change.log_dirs() {
+                        broker.log_dirs = log_dirs.to_vec();
+                    }
+                }
+            }
+            RecordValueByType::AccessControlEntry(acl) => {
+                self.acls.insert(acl.id(), acl.clone());
+            }
+            RecordValueByType::RemoveAccessControlEntry(remove) => {
+                self.acls.remove(&remove.id());
+            }
+            RecordValueByType::ProducerIds(producer_ids) => {
+                self.producer_ids = Some(producer_ids.clone());
+            }
+            RecordValueByType::ClientQuota(quota) => {
+                let mut entity = quota
+                    .entity()
+                    .iter()
+                    .map(|entity| {
+                        (
+                            entity.entity_type().to_string(),
+                            entity.entity_name().map(str::to_string),
+                        )
+                    })
+                    .collect::<QuotaEntity>();
+                entity.sort();
+                let key = (entity, quota.key().to_string());
+
+                if quota.remove() {
+                    self.client_quotas.remove(&key);
+                } else {
+                    self.client_quotas.insert(key, quota.clone());
+                }
+            }
+            RecordValueByType::UserScramCredential(credential) => {
+                self.scram_credentials.insert(
+                    (credential.name().to_string(), credential.mechanism()),
+                    credential.clone(),
+                );
+            }
+            RecordValueByType::DelegationToken(token) => {
+                self.delegation_tokens
+                    .insert(token.token_id().to_string(), token.clone());
+            }
+            RecordValueByType::Unknown { .. } => {
+                self.unknown.push(value.clone());
+            }
+            RecordValueByType::NoOp(_)
+            | RecordValueByType::BeginTransaction(_)
+            | RecordValueByType::EndTransaction(_)
+            | RecordValueByType::AbortTransaction(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::{BufMut, Bytes, BytesMut};
+
+    use super::*;
+    use crate::metadata::snapshot::SnapshotId;
+    use crate::protocol::{
+        bytes::{FromBytes, ToBytes},
+        cluster_metadata::{Record, RecordValue},
+    };
+
+    fn record(record_type: i8, body: &[u8]) -> RecordValueByType {
+        let mut bytes = BytesMut::new();
+        bytes.put_i8(1);
+        bytes.put_i8(record_type);
+        bytes.put_i8(0);
+        bytes.put_slice(body);
+
+        RecordValue::from_be_bytes(&mut bytes.freeze())
+            .unwrap()
+            .value()
+            .clone()
+    }
+
+    fn acl(id: Uuid) -> RecordValueByType {
+        let mut body = id.as_bytes().to_vec();
+        body.extend([2, 4, b'f', b'o', b'o', 3]);
+        body.extend([7, b'U', b's', b'e', b'r', b':', b'*', 2, b'*', 2, 3, 0]);
+        record(6, &body)
+    }
+
+    fn encoded(records: Vec<RecordValueByType>) -> Vec<Bytes> {
+        records
+            .into_iter()
+            .map(|value| RecordValue::new(value).to_be_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn snapshot_keeps_records_the_apis_dont_serve() {
+        let kept = Uuid::new_v4();
+        let removed = Uuid::new_v4();
+        let mut producer_ids = 1i32.to_be_bytes().to_vec();
+        producer_ids.extend(5i64.to_be_bytes());
+        producer_ids.extend(1000i64.to_be_bytes());
+        producer_ids.push(0);
+
+        let mut image = MetadataImage::default();
+        for value in [
+            acl(kept),
+            acl(removed),
+            record(16, &[removed.as_bytes().as_slice(), &[0]].concat()),
+            record(15, &producer_ids),
+            record(99, &[1, 2, 3]),
+        ] {
+            image.replay(&value);
+        }
+        let records = image.records();
+        assert_eq!(records.len(), 3);
+
+        let dir = std::env::temp_dir().join(format!("image-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let id = SnapshotId {
+            end_offset: 5,
+            epoch: 0,
+        };
+        Snapshot::write(&dir, id, records.clone(), 0).unwrap();
+        let snapshot = Snapshot::read_latest(&dir).unwrap().unwrap();
+
+        let loaded = MetadataImage::from_snapshot(&snapshot);
+        assert_eq!(loaded.acls.keys().collect::<Vec<&Uuid>>(), vec![&kept]);
+        assert_eq!(encoded(loaded.records()), encoded(records));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_name_bound_to_a_new_id_drops_the_old_topic() {
+        let old = Uuid::new_v4();
+        let new = Uuid::new_v4();
+
+        let mut image = MetadataImage::default();
+        for id in [old, new] {
+            image.replay(&RecordValueByType::Topic(TopicRecordValue::new("foo", id)));
+        }
+
+        assert!(image.topic_by_id(old).is_none());
+        assert_eq!(image.topic("foo").map(|topic| topic.id()), Some(new));
+        assert_eq!(image.topics().count(), 1);
+    }
+
+    #[test]
+    fn the_last_batch_sets_the_epoch_a_snapshot_is_named_after() {
+        let batch: Batch = Batch::new(
+            0,
+            -1,
+            -1,
+            -1,
+            0,
+            vec![Record::new(
+                0,
+                None,
+                RecordValue::new(RecordValueByType::Topic(TopicRecordValue::new(
+                    "foo",
+                    Uuid::new_v4(),
+                ))),
+            )],
+        );
+        let mut bytes = BytesMut::from(&batch.to_be_bytes()[..]);
+        // The partition leader epoch follows the base offset and batch length.
+        bytes[12..16].copy_from_slice(&7i32.to_be_bytes());
+        let batch = Batch::try_from(&mut bytes.freeze()).unwrap();
+
+        let mut image = MetadataImage::default();
+        image.apply_batch(&batch);
+        assert_eq!((image.next_offset(), image.last_epoch()), (1, 7));
+    }
+}
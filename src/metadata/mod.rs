@@ -0,0 +1,7 @@
+pub(crate) mod controller;
+pub(crate) mod image;
+pub(crate) mod snapshot;
+pub(crate) mod tailer;
+
+pub(crate) use controller::Controller;
+pub(crate) use image::MetadataImage;
@@ -0,0 +1,251 @@
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::Path,
+};
+
+use bytes::{Buf, Bytes, BytesMut};
+
+use crate::protocol::{
+    bytes::ToBytes,
+    cluster_metadata::{Batch, Record, RecordValue, RecordValueByType},
+    control_record::{
+        ControlRecord, ControlRecordValue, SnapshotFooterRecord, SnapshotHeaderRecord,
+    },
+};
+
+const SNAPSHOT_SUFFIX: &str = ".checkpoint";
+/// Suffix of a snapshot being written, which is renamed once complete.
+const PARTIAL_SUFFIX: &str = ".part";
+const MAX_RECORDS_PER_BATCH: usize = 1000;
+
+/// Identifies a snapshot by the offset it covers the log up to, exclusive,
+/// and the epoch of the last record it contains.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct SnapshotId {
+    pub(crate) end_offset: i64,
+    pub(crate) epoch: i32,
+}
+
+impl SnapshotId {
+    pub(crate) fn file_name(&self) -> String {
+        format!(
+            "{:020}-{:010}{}",
+            self.end_offset, self.epoch, SNAPSHOT_SUFFIX
+        )
+    }
+
+    fn parse(file_name: &str) -> Option<Self> {
+        let (end_offset, epoch) = file_name.strip_suffix(SNAPSHOT_SUFFIX)?.split_once('-')?;
+
+        Some(Self {
+            end_offset: end_offset.parse().ok()?,
+            epoch: epoch.parse().ok()?,
+        })
+    }
+}
+
+/// A `.checkpoint` file of the metadata log: batches of metadata records
+/// between a `SnapshotHeader` and a `SnapshotFooter` control record.
+#[derive(Debug)]
+pub(crate) struct Snapshot {
+    id: SnapshotId,
+    batches: Vec<Batch>,
+}
+
+impl Snapshot {
+    pub(crate) fn id(&self) -> SnapshotId {
+        self.id
+    }
+
+    /// The batches holding metadata records, without the header and footer.
+    pub(crate) fn batches(&self) -> impl Iterator<Item = &Batch> {
+        self.batches.iter().filter(|batch| !batch.is_control())
+    }
+
+    /// Reads the snapshot with the highest id in `dir`, if there is one. A
+    /// corrupt snapshot is passed over for the next older one, and is only an
+    /// error when none of them can be read.
+    pub(crate) fn read_latest(dir: &Path) -> std::io::Result<Option<Self>> {
+        let mut corrupt = None;
+        for id in snapshot_ids(dir)?.into_iter().rev() {
+            match Self::read(dir, id) {
+                Ok(snapshot) => return Ok(Some(snapshot)),
+                Err(e) if e.kind() == std::io::ErrorKind::InvalidData => {
+                    eprintln!("Skipping snapshot: {}", e);
+                    corrupt.get_or_insert(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        corrupt.map_or(Ok(None), Err)
+    }
+
+    /// Writes `records` as the snapshot `id` in `dir`. The snapshot only
+    /// appears under its final name once it has been fully written.
+    pub(crate) fn write(
+        dir: &Path,
+        id: SnapshotId,
+        records: Vec<RecordValueByType>,
+        timestamp: i64,
+    ) -> std::io::Result<()> {
+        let mut buf = BytesMut::new();
+        let mut offset = 0i64;
+
+        let mut put_batch = |mut batch: Batch| {
+            batch.set_base_offset(offset);
+            offset = batch.last_offset() + 1;
+            buf.extend(batch.to_be_bytes());
+        };
+
+        put_batch(Batch::control(
+            -1,
+            -1,
+            timestamp,
+            vec![ControlRecord::new(
+                0,
+                ControlRecordValue::SnapshotHeader(SnapshotHeaderRecord::new(timestamp)),
+            )],
+        ));
+        let mut records = records.into_iter().peekable();
+        while records.peek().is_some() {
+            let batch = records
+                .by_ref()
+                .take(MAX_RECORDS_PER_BATCH)
+                .enumerate()
+                .map(|(offset_delta, value)| {
+                    Record::new(offset_delta as i32, None, RecordValue::new(value))
+                })
+                .collect();
+            put_batch(Batch::new(0, -1, -1, -1, timestamp, batch));
+        }
+        put_batch(Batch::control(
+            -1,
+            -1,
+            timestamp,
+            vec![ControlRecord::new(
+                0,
+                ControlRecordValue::SnapshotFooter(SnapshotFooterRecord::new()),
+            )],
+        ));
+
+        let path = dir.join(id.file_name());
+        let partial = dir.join(format!("{}{}", id.file_name(), PARTIAL_SUFFIX));
+        let mut file = File::create(&partial)?;
+        file.write_all(&buf)?;
+        file.sync_all()?;
+        fs::rename(partial, path)
+    }
+
+    fn read(dir: &Path, id: SnapshotId) -> std::io::Result<Self> {
+        let path = dir.join(id.file_name());
+        let mut bytes = Bytes::from(fs::read(&path)?);
+
+        let mut batches = Vec::new();
+        while bytes.has_remaining() {
+            batches.push(
+                Batch::try_from(&mut bytes).map_err(|e| {
+                    invalid_snapshot(&path, format!("failed to parse batch: {}", e))
+                })?,
+            );
+        }
+
+        let first = batches
+            .first()
+            .and_then(|batch| batch.control_records().first());
+        if !matches!(
+            first.map(|record| record.value()),
+            Some(ControlRecordValue::SnapshotHeader(_))
+        ) {
+            return Err(invalid_snapshot(&path, "missing snapshot header"));
+        }
+
+        let last = batches
+            .last()
+            .and_then(|batch| batch.control_records().last());
+        if !matches!(
+            last.map(|record| record.value()),
+            Some(ControlRecordValue::SnapshotFooter(_))
+        ) {
+            return Err(invalid_snapshot(&path, "missing snapshot footer"));
+        }
+
+        Ok(Self { id, batches })
+    }
+}
+
+/// Ids of the snapshots in `dir`, in order.
+pub(crate) fn snapshot_ids(dir: &Path) -> std::io::Result<Vec<SnapshotId>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut ids = Vec::new();
+    for entry in entries {
+        if let Some(id) = entry?.file_name().to_str().and_then(SnapshotId::parse) {
+            ids.push(id);
+        }
+    }
+    ids.sort_unstable();
+
+    Ok(ids)
+}
+
+/// Deletes every snapshot in `dir` older than `id`.
+pub(crate) fn delete_snapshots_before(dir: &Path, id: SnapshotId) -> std::io::Result<()> {
+    for older in snapshot_ids(dir)?.into_iter().filter(|older| *older < id) {
+        match fs::remove_file(dir.join(older.file_name())) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn invalid_snapshot(path: &Path, message: impl std::fmt::Display) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("invalid snapshot {}: {}", path.display(), message),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_corrupt_latest_snapshot_falls_back_to_the_one_before() {
+        let dir = std::env::temp_dir().join(format!("snapshot-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let older = SnapshotId {
+            end_offset: 5,
+            epoch: 1,
+        };
+        let newer = SnapshotId {
+            end_offset: 9,
+            epoch: 2,
+        };
+        Snapshot::write(&dir, older, Vec::new(), 0).unwrap();
+        Snapshot::write(&dir, newer, Vec::new(), 0).unwrap();
+
+        let path = dir.join(newer.file_name());
+        let len = fs::metadata(&path).unwrap().len();
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(len - 1)
+            .unwrap();
+        assert_eq!(Snapshot::read_latest(&dir).unwrap().unwrap().id(), older);
+
+        fs::remove_file(dir.join(older.file_name())).unwrap();
+        let e = Snapshot::read_latest(&dir).unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::InvalidData);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
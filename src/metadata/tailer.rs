@@ -0,0 +1,94 @@
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::PathBuf,
+};
+
+use bytes::{Buf, Bytes};
+
+use crate::{
+    protocol::cluster_metadata::Batch,
+    storage::partition_log::{segment_base_offsets, segment_file_name},
+};
+
+/// Follows the segments of the `__cluster_metadata` log as they are written,
+/// returning each batch once it is complete.
+#[derive(Debug)]
+pub(crate) struct MetadataLogTailer {
+    dir: PathBuf,
+    /// Base offset of the segment being read.
+    segment: Option<i64>,
+    /// Byte position in that segment up to which batches have been returned.
+    position: u64,
+}
+
+impl MetadataLogTailer {
+    pub(crate) fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            segment: None,
+            position: 0,
+        }
+    }
+
+    /// Reads the batches written since the last poll, moving on to the next
+    /// segment once the current one has been read to its end.
+    pub(crate) fn poll(&mut self) -> std::io::Result<Vec<Batch>> {
+        let segments = segment_base_offsets(&self.dir)?;
+        let mut index = match self.segment {
+            Some(current) => segments
+                .iter()
+                .position(|base| *base >= current)
+                .unwrap_or(segments.len()),
+            None => 0,
+        };
+
+        let mut batches = Vec::new();
+        while let Some(&base) = segments.get(index) {
+            if self.segment != Some(base) {
+                self.segment = Some(base);
+                self.position = 0;
+            }
+
+            // A batch cut off at the end of the last segment may still be
+            // being written, so stop and pick it up on the next poll.
+            let read_to_end = self.read_segment(base, &mut batches)?;
+            if !read_to_end || index + 1 == segments.len() {
+                break;
+            }
+            index += 1;
+        }
+
+        Ok(batches)
+    }
+
+    /// Appends the complete batches after the current position of segment
+    /// `base` to `batches`, returning whether the whole segment was read.
+    fn read_segment(&mut self, base: i64, batches: &mut Vec<Batch>) -> std::io::Result<bool> {
+        let mut file = match File::open(self.dir.join(segment_file_name(base))) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(true),
+            Err(e) => return Err(e),
+        };
+        file.seek(SeekFrom::Start(self.position))?;
+
+        let mut buf = Vec::new();
+        file.read_to_end(&mut buf)?;
+        let mut bytes = Bytes::from(buf);
+
+        while bytes.has_remaining() {
+            let mut rest = bytes.clone();
+            match Batch::try_from(&mut rest) {
+                Ok(batch) => {
+                    self.position += (bytes.remaining() - rest.remaining()) as u64;
+                    batches.push(batch);
+                    bytes = rest;
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(false),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(true)
+    }
+}
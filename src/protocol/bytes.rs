@@ -5,3 +5,13 @@ pub trait ToBytes {
 pub trait FromBytes: Sized {
     fn from_be_bytes<B: bytes::Buf>(buf: &mut B) -> crate::Result<Self>;
 }
+
+/// Decoding of messages whose layout depends on the API version.
+pub trait FromVersionedBytes: Sized {
+    fn from_versioned_be_bytes<B: bytes::Buf>(buf: &mut B, version: i16) -> crate::Result<Self>;
+}
+
+/// Encoding of messages whose layout depends on the API version.
+pub trait ToVersionedBytes {
+    fn to_versioned_be_bytes(&self, version: i16) -> bytes::Bytes;
+}
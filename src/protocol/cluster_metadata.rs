@@ -2,174 +2,153 @@ use bytes::{Buf, BufMut, Bytes, BytesMut};
 
 use super::{
     bytes::{FromBytes, ToBytes},
-    primitives::{CompactArray, CompactString, VarInt, INT32},
+    control_record::ControlRecord,
+    primitives::{self, CompactArray, TaggedFields, VarInt, INT32},
 };
 
-use std::{
-    collections::BTreeMap,
-    fs::File,
-    io::{BufReader, Read},
+use crate::{
+    metadata::image::{BrokerImage, PartitionImage},
+    protocol::primitives::UnsignedVarInt,
+    Result,
 };
 
-use crate::{protocol::primitives::UnsignedVarInt, Result};
+/// Batch attribute bit set when the batch belongs to a transaction.
+pub(crate) const TRANSACTIONAL_ATTRIBUTE: u16 = 0x10;
+/// Batch attribute bit set when the batch holds control records.
+pub(crate) const CONTROL_ATTRIBUTE: u16 = 0x20;
 
-#[derive(Debug)]
-pub(crate) struct ClusterMetadata {
-    batches: BTreeMap<i64, Batch>,
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Batch<V = RecordValue> {
+    base_offset: i64,
+    partition_leader_epoch: i32,
+    magic_byte: u8,
+    crc: u32,
+    attributes: u16,
+    last_offset_delta: i32,
+    base_timestamp: i64,
+    max_timestamp: i64,
+    producer_id: i64,
+    producer_epoch: i16,
+    base_sequence: i32,
+    records: Vec<Record<V>>,
+    // Populated instead of `records` for control batches.
+    control_records: Vec<ControlRecord>,
 }
 
-impl ClusterMetadata {
-    fn find_batches_by_topic(&self, topic: &str) -> Vec<&Batch> {
-        self.batches
-            .iter()
-            .find(|(_, batch)| !batch.find_topic_records_by_topic(topic).is_empty())
-            .into_iter()
-            .map(|(_, batch)| batch)
-            .collect()
+impl<V> Batch<V> {
+    pub(crate) fn new(
+        attributes: u16,
+        producer_id: i64,
+        producer_epoch: i16,
+        base_sequence: i32,
+        timestamp: i64,
+        records: Vec<Record<V>>,
+    ) -> Self {
+        Self {
+            base_offset: 0,
+            partition_leader_epoch: 0,
+            magic_byte: 2,
+            crc: 0,
+            attributes,
+            last_offset_delta: records.len() as i32 - 1,
+            base_timestamp: timestamp,
+            max_timestamp: timestamp,
+            producer_id,
+            producer_epoch,
+            base_sequence,
+            records,
+            control_records: Vec::new(),
+        }
     }
-}
 
-impl ClusterMetadata {
-    pub fn values(&self) -> std::collections::btree_map::Values<'_, i64, Batch> {
-        self.batches.values()
+    /// A control batch. Batches written on behalf of a producer (transaction
+    /// markers) are flagged transactional as well.
+    pub(crate) fn control(
+        producer_id: i64,
+        producer_epoch: i16,
+        timestamp: i64,
+        control_records: Vec<ControlRecord>,
+    ) -> Self {
+        let mut attributes = CONTROL_ATTRIBUTE;
+        if producer_id >= 0 {
+            attributes |= TRANSACTIONAL_ATTRIBUTE;
+        }
+
+        Self {
+            base_offset: 0,
+            partition_leader_epoch: 0,
+            magic_byte: 2,
+            crc: 0,
+            attributes,
+            last_offset_delta: control_records.len() as i32 - 1,
+            base_timestamp: timestamp,
+            max_timestamp: timestamp,
+            producer_id,
+            producer_epoch,
+            base_sequence: -1,
+            records: Vec::new(),
+            control_records,
+        }
     }
 
-    pub fn find_topic_records_by_topic(&self, topic: &str) -> Vec<&Record> {
-        self.batches
-            .values()
-            .flat_map(|batch| batch.find_topic_records_by_topic(topic))
-            .collect()
+    pub(crate) fn base_offset(&self) -> i64 {
+        self.base_offset
     }
 
-    pub fn find_topic_records_by_id(&self, topic_id: &uuid::Uuid) -> Vec<&Record> {
-        self.batches
-            .values()
-            .flat_map(|batch| batch.find_topic_records_by_id(topic_id))
-            .collect()
+    pub(crate) fn set_base_offset(&mut self, base_offset: i64) {
+        self.base_offset = base_offset;
     }
 
-    pub fn find_partition_records_by_topic_uuid(
-        &self,
-        topic_uuid: uuid::Uuid,
-    ) -> Vec<&PartitionRecordValue> {
-        self.batches
-            .values()
-            .flat_map(|batch| batch.find_partition_records_by_topic_uuid(topic_uuid))
-            .collect()
+    pub(crate) fn last_offset(&self) -> i64 {
+        self.base_offset + self.last_offset_delta as i64
     }
 
-    pub fn find_partition_record_ids_by_topic_uuid(&self, topic_uuid: uuid::Uuid) -> Vec<i32> {
-        self.find_partition_records_by_topic_uuid(topic_uuid)
-            .iter()
-            .map(|pr| pr.partition_id())
-            .collect::<Vec<i32>>()
+    pub(crate) fn is_transactional(&self) -> bool {
+        self.attributes & TRANSACTIONAL_ATTRIBUTE != 0
     }
-}
 
-impl TryFrom<File> for ClusterMetadata {
-    type Error = std::io::Error;
+    pub(crate) fn is_control(&self) -> bool {
+        self.attributes & CONTROL_ATTRIBUTE != 0
+    }
 
-    fn try_from(file: File) -> std::result::Result<Self, Self::Error> {
-        let mut reader = BufReader::new(file);
-        let mut batches = BTreeMap::new();
+    pub(crate) fn producer_id(&self) -> i64 {
+        self.producer_id
+    }
 
-        let mut vec = Vec::new();
-        reader.read_to_end(&mut vec).map_err(|e| {
-            std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                format!("failed to read file: {}", e),
-            )
-        })?;
-        let mut bytes = Bytes::from(vec);
+    pub(crate) fn producer_epoch(&self) -> i16 {
+        self.producer_epoch
+    }
 
-        loop {
-            match Batch::try_from(&mut bytes) {
-                Ok(batch) => {
-                    batches.insert(batch.base_offset, batch);
-                }
-                Err(e) => {
-                    if e.kind() == std::io::ErrorKind::UnexpectedEof {
-                        // End of file reached, break the loop
-                        break;
-                    }
-                    return Err(std::io::Error::new(
-                        std::io::ErrorKind::InvalidData,
-                        format!("failed to parse batch: {}", e),
-                    ));
-                }
-            }
-        }
+    pub(crate) fn base_sequence(&self) -> i32 {
+        self.base_sequence
+    }
 
-        Ok(ClusterMetadata { batches })
+    pub(crate) fn partition_leader_epoch(&self) -> i32 {
+        self.partition_leader_epoch
     }
-}
 
-#[derive(Debug, Default, Clone)]
-pub(crate) struct Batch {
-    base_offset: i64,
-    batch_length: i32,
-    partition_leader_epoch: i32,
-    magic_byte: u8,
-    crc: u32,
-    attributes: u16,
-    last_offset_delta: i32,
-    base_timestamp: i64,
-    max_timestamp: i64,
-    producer_id: i64,
-    producer_epoch: i16,
-    base_sequence: i32,
-    records: Vec<Record>,
-}
-
-impl Batch {
-    fn find_topic_records_by_topic(&self, topic: &str) -> Vec<&Record> {
-        self.records
-            .iter()
-            .filter(|record| {
-                if let RecordValueByType::Topic(topic_value) = &record.record_value.value {
-                    topic_value.name == topic
-                } else {
-                    false
-                }
-            })
-            .collect()
+    pub(crate) fn crc(&self) -> u32 {
+        self.crc
     }
 
-    fn find_topic_records_by_id(&self, topic_id: &uuid::Uuid) -> Vec<&Record> {
-        self.records
-            .iter()
-            .filter(|record| {
-                if let RecordValueByType::Topic(topic_value) = &record.record_value.value {
-                    &topic_value.topic_uuid == topic_id
-                } else {
-                    false
-                }
-            })
-            .collect()
+    pub(crate) fn base_timestamp(&self) -> i64 {
+        self.base_timestamp
     }
 
-    fn find_partition_records_by_topic_uuid(
-        &self,
-        topic_uuid: uuid::Uuid,
-    ) -> Vec<&PartitionRecordValue> {
-        self.records
-            .iter()
-            .filter_map(|record| {
-                if let RecordValueByType::Partition(partition_value) = &record.record_value.value {
-                    if partition_value.topic_uuid == topic_uuid {
-                        Some(partition_value)
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                }
-            })
-            .collect()
+    pub(crate) fn records(&self) -> &[Record<V>] {
+        &self.records
+    }
+
+    pub(crate) fn control_records(&self) -> &[ControlRecord] {
+        &self.control_records
+    }
+
+    pub(crate) fn record_count(&self) -> usize {
+        self.records.len() + self.control_records.len()
     }
 }
 
-impl TryFrom<&mut bytes::Bytes> for Batch {
+impl<V: FromBytes> TryFrom<&mut bytes::Bytes> for Batch<V> {
     type Error = std::io::Error;
 
     fn try_from(bytes: &mut bytes::Bytes) -> std::result::Result<Self, Self::Error> {
@@ -180,6 +159,22 @@ impl TryFrom<&mut bytes::Bytes> for Batch {
             )
         })?;
         let batch_length = bytes.try_get_i32()?;
+        if batch_length < 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("negative batch length {}", batch_length),
+            ));
+        }
+        if bytes.remaining() < batch_length as usize {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                format!(
+                    "batch length {} exceeds remaining {} bytes",
+                    batch_length,
+                    bytes.remaining()
+                ),
+            ));
+        }
         let mut bytes = bytes.split_to(batch_length as usize);
 
         let partition_leader_epoch = bytes.try_get_i32()?;
@@ -199,22 +194,27 @@ impl TryFrom<&mut bytes::Bytes> for Batch {
         let base_sequence = bytes.try_get_i32()?;
 
         let records_length = bytes.try_get_i32()?;
-        let mut records = Vec::with_capacity(records_length as usize);
+        let mut records = Vec::new();
+        let mut control_records = Vec::new();
 
         for _ in 0..records_length {
-            let record = Record::try_from(&mut bytes).map_err(|e| {
+            let parse_error = |e: crate::Error| {
                 std::io::Error::new(
                     std::io::ErrorKind::InvalidData,
                     format!("failed to parse record from batch: {}", e),
                 )
-            })?;
+            };
 
-            records.push(record);
+            if attributes & CONTROL_ATTRIBUTE != 0 {
+                let record = Record::<Bytes>::try_from(&mut bytes).map_err(parse_error)?;
+                control_records.push(ControlRecord::try_from(&record).map_err(parse_error)?);
+            } else {
+                records.push(Record::try_from(&mut bytes).map_err(parse_error)?);
+            }
         }
 
         Ok(Batch {
             base_offset,
-            batch_length,
             partition_leader_epoch,
             magic_byte,
             crc,
@@ -226,6 +226,7 @@ impl TryFrom<&mut bytes::Bytes> for Batch {
             producer_epoch,
             base_sequence,
             records,
+            control_records,
         })
     }
 }
@@ -250,67 +251,120 @@ fn crc_checksum(bytes: &mut Bytes) -> Result<u32> {
     Ok(crc_checksum)
 }
 
-impl ToBytes for Batch {
+impl<V: ToBytes> ToBytes for Batch<V> {
     fn to_be_bytes(&self) -> Bytes {
+        // Everything after the CRC field is covered by the checksum, so it is
+        // encoded first and the length and CRC are derived from it.
+        let mut body = BytesMut::new();
+
+        body.put_u16(self.attributes);
+        body.put_i32(self.last_offset_delta);
+        body.put_i64(self.base_timestamp);
+        body.put_i64(self.max_timestamp);
+        body.put_i64(self.producer_id);
+        body.put_i16(self.producer_epoch);
+        body.put_i32(self.base_sequence);
+
+        body.put_i32(self.record_count() as i32);
+        for record in &self.records {
+            body.extend(record.to_be_bytes());
+        }
+        for record in &self.control_records {
+            body.extend(record.to_be_bytes());
+        }
+
+        let crc = crc32c::crc32c(&body);
+        // partition leader epoch + magic byte + crc precede the checksummed body
+        let batch_length = 4 + 1 + 4 + body.len() as i32;
+
         let mut bytes = BytesMut::new();
 
         bytes.put_i64(self.base_offset);
-        bytes.put_i32(self.batch_length);
+        bytes.put_i32(batch_length);
         bytes.put_i32(self.partition_leader_epoch);
         bytes.put_u8(self.magic_byte);
-        bytes.put_u32(self.crc);
-        bytes.put_u16(self.attributes);
-        bytes.put_i32(self.last_offset_delta);
-        bytes.put_i64(self.base_timestamp);
-        bytes.put_i64(self.max_timestamp);
-        bytes.put_i64(self.producer_id);
-        bytes.put_i16(self.producer_epoch);
-        bytes.put_i32(self.base_sequence);
-
-        bytes.put_i32(self.records.len() as i32);
-        for record in &self.records {
-            bytes.extend(record.to_be_bytes());
-        }
+        bytes.put_u32(crc);
+        bytes.extend(body);
 
         bytes.freeze()
     }
 }
 
 #[derive(Debug, Clone)]
-pub(crate) struct Record {
+pub(crate) struct Record<V = RecordValue> {
     record_length: VarInt,
     attributes: u8,
     timestamp_delta: VarInt,
     offset_delta: VarInt,
-    key: Vec<u8>,
-    record_value: RecordValue,
+    key: Option<Bytes>,
+    record_value: V,
     headers_array_count: u32,
 }
 
-impl Record {
-    pub(crate) fn record_value(&self) -> &RecordValue {
+impl<V> Record<V> {
+    pub(crate) fn record_value(&self) -> &V {
         &self.record_value
     }
+
+    pub(crate) fn key(&self) -> Option<&Bytes> {
+        self.key.as_ref()
+    }
+
+    pub(crate) fn offset_delta(&self) -> i32 {
+        self.offset_delta.value()
+    }
 }
 
-impl ToBytes for Record {
-    fn to_be_bytes(&self) -> Bytes {
+impl<V: ToBytes> Record<V> {
+    pub(crate) fn new(offset_delta: i32, key: Option<Bytes>, record_value: V) -> Self {
+        let mut record = Self {
+            record_length: VarInt::from(0),
+            attributes: 0,
+            timestamp_delta: VarInt::from(0),
+            offset_delta: VarInt::from(offset_delta),
+            key,
+            record_value,
+            headers_array_count: 0,
+        };
+        record.record_length = VarInt::from(record.encode_body().len() as i32);
+
+        record
+    }
+
+    fn encode_body(&self) -> Bytes {
         let mut bytes = BytesMut::new();
 
-        bytes.extend(self.record_length.to_be_bytes());
         bytes.put_u8(self.attributes);
         bytes.extend(self.timestamp_delta.to_be_bytes());
         bytes.extend(self.offset_delta.to_be_bytes());
-        bytes.extend(VarInt::from(self.key.len() as i32).to_be_bytes());
-        bytes.extend(&self.key);
-        bytes.extend(self.record_value.to_be_bytes());
+        match &self.key {
+            Some(key) => {
+                bytes.extend(VarInt::from(key.len() as i32).to_be_bytes());
+                bytes.extend(key);
+            }
+            None => bytes.extend(VarInt::from(-1).to_be_bytes()),
+        }
+        let value = self.record_value.to_be_bytes();
+        bytes.extend(VarInt::from(value.len() as i32).to_be_bytes());
+        bytes.extend(value);
         bytes.extend(UnsignedVarInt::from(self.headers_array_count).to_be_bytes());
 
         bytes.freeze()
     }
 }
 
-impl TryFrom<&mut bytes::Bytes> for Record {
+impl<V: ToBytes> ToBytes for Record<V> {
+    fn to_be_bytes(&self) -> Bytes {
+        let mut bytes = BytesMut::new();
+
+        bytes.extend(self.record_length.to_be_bytes());
+        bytes.extend(self.encode_body());
+
+        bytes.freeze()
+    }
+}
+
+impl<V: FromBytes> TryFrom<&mut bytes::Bytes> for Record<V> {
     type Error = crate::Error;
 
     fn try_from(mut bytes: &mut bytes::Bytes) -> std::result::Result<Self, Self::Error> {
@@ -320,9 +374,9 @@ impl TryFrom<&mut bytes::Bytes> for Record {
         let offset_delta = VarInt::from_be_bytes(&mut bytes)?;
         let key_length = VarInt::from_be_bytes(&mut bytes)?.value();
         let key = if key_length < 0 {
-            Vec::new()
+            None
         } else {
-            bytes.split_to(key_length as usize).to_vec()
+            Some(bytes.split_to(key_length as usize))
         };
 
         let value_length = VarInt::from_be_bytes(&mut bytes)?.value();
@@ -332,7 +386,7 @@ impl TryFrom<&mut bytes::Bytes> for Record {
             bytes.split_to(value_length as usize)
         };
 
-        let record_value = RecordValue::try_from(&mut record_contents).map_err(|e| {
+        let record_value = V::from_be_bytes(&mut record_contents).map_err(|e| {
             std::io::Error::new(
                 std::io::ErrorKind::InvalidData,
                 format!("failed to parse record value: {}", e),
@@ -362,20 +416,30 @@ pub(crate) struct RecordValue {
 }
 
 impl RecordValue {
+    pub(crate) fn new(value: RecordValueByType) -> Self {
+        let (record_type, version) = value.record_type_and_version();
+
+        Self {
+            frame_version: 1,
+            record_type,
+            version,
+            value,
+        }
+    }
+
     pub(crate) fn value(&self) -> &RecordValueByType {
         &self.value
     }
 }
 
-impl TryFrom<&mut bytes::Bytes> for RecordValue {
-    type Error = crate::Error;
-
-    fn try_from(bytes: &mut bytes::Bytes) -> std::result::Result<Self, Self::Error> {
+impl FromBytes for RecordValue {
+    fn from_be_bytes<B: Buf>(buf: &mut B) -> Result<Self> {
+        let bytes = &mut buf.copy_to_bytes(buf.remaining());
         let frame_version = bytes.try_get_i8()?;
         let record_type = bytes.try_get_i8()?;
         let version = bytes.try_get_i8()?;
 
-        let value = RecordValueByType::try_from(bytes, record_type).map_err(|e| {
+        let value = RecordValueByType::try_from(bytes, record_type, version).map_err(|e| {
             std::io::Error::new(
                 std::io::ErrorKind::InvalidData,
                 format!("failed to parse record value: {}", e),
@@ -404,45 +468,165 @@ impl ToBytes for RecordValue {
     }
 }
 
+const REGISTER_BROKER_RECORD_TYPE: i8 = 0;
+const UNREGISTER_BROKER_RECORD_TYPE: i8 = 1;
+const TOPIC_RECORD_TYPE: i8 = 2;
+const PARTITION_RECORD_TYPE: i8 = 3;
+const CONFIG_RECORD_TYPE: i8 = 4;
+const PARTITION_CHANGE_RECORD_TYPE: i8 = 5;
+const ACCESS_CONTROL_ENTRY_RECORD_TYPE: i8 = 6;
+const FENCE_BROKER_RECORD_TYPE: i8 = 7;
+const UNFENCE_BROKER_RECORD_TYPE: i8 = 8;
+const REMOVE_TOPIC_RECORD_TYPE: i8 = 9;
+const DELEGATION_TOKEN_RECORD_TYPE: i8 = 10;
+const USER_SCRAM_CREDENTIAL_RECORD_TYPE: i8 = 11;
+const FEATURE_RECORD_TYPE: i8 = 12;
+const CLIENT_QUOTA_RECORD_TYPE: i8 = 14;
+const PRODUCER_IDS_RECORD_TYPE: i8 = 15;
+const REMOVE_ACCESS_CONTROL_ENTRY_RECORD_TYPE: i8 = 16;
+const BROKER_REGISTRATION_CHANGE_RECORD_TYPE: i8 = 17;
+const NO_OP_RECORD_TYPE: i8 = 20;
+const BEGIN_TRANSACTION_RECORD_TYPE: i8 = 23;
+const END_TRANSACTION_RECORD_TYPE: i8 = 24;
+const ABORT_TRANSACTION_RECORD_TYPE: i8 = 25;
+
+/// `ConfigResource.Type` of topic configs.
+pub(crate) const TOPIC_RESOURCE_TYPE: i8 = 2;
+/// `ConfigResource.Type` of broker configs.
+pub(crate) const BROKER_RESOURCE_TYPE: i8 = 4;
+
+/// `PartitionChangeRecord.Leader` when the leader is unchanged.
+pub(crate) const NO_LEADER_CHANGE: i32 = -2;
+
 #[derive(Debug, Clone)]
 pub(crate) enum RecordValueByType {
+    RegisterBroker(RegisterBrokerRecordValue),
+    UnregisterBroker(UnregisterBrokerRecordValue),
     Feature(FeatureRecordValue),
     Topic(TopicRecordValue),
     Partition(PartitionRecordValue),
-    Unknown(bytes::Bytes),
+    Config(ConfigRecordValue),
+    PartitionChange(PartitionChangeRecordValue),
+    AccessControlEntry(AccessControlEntryRecordValue),
+    RemoveAccessControlEntry(RemoveAccessControlEntryRecordValue),
+    FenceBroker(FenceBrokerRecordValue),
+    UnfenceBroker(UnfenceBrokerRecordValue),
+    RemoveTopic(RemoveTopicRecordValue),
+    DelegationToken(DelegationTokenRecordValue),
+    UserScramCredential(UserScramCredentialRecordValue),
+    ClientQuota(ClientQuotaRecordValue),
+    ProducerIds(ProducerIdsRecordValue),
+    BrokerRegistrationChange(BrokerRegistrationChangeRecordValue),
+    NoOp(NoOpRecordValue),
+    BeginTransaction(BeginTransactionRecordValue),
+    EndTransaction(EndTransactionRecordValue),
+    AbortTransaction(AbortTransactionRecordValue),
+    /// A record type this broker doesn't know, kept undecoded so it can be
+    /// written back as it was.
+    Unknown {
+        record_type: i8,
+        version: i8,
+        value: Bytes,
+    },
 }
 
 impl RecordValueByType {
-    fn try_from(bytes: &mut bytes::Bytes, record_type: i8) -> Result<Self> {
+    fn try_from(bytes: &mut bytes::Bytes, record_type: i8, version: i8) -> Result<Self> {
         match record_type {
-            12 => Ok(Self::Feature(FeatureRecordValue::try_from(bytes)?)),
-            2 => Ok(Self::Topic(TopicRecordValue::try_from(bytes)?)),
-            3 => Ok(Self::Partition(PartitionRecordValue::try_from(bytes)?)),
-            _ => Ok(Self::Unknown(Bytes::copy_from_slice(bytes))),
-        }
-    }
-
-    pub(crate) fn as_feature(&self) -> Option<&FeatureRecordValue> {
-        if let Self::Feature(v) = self {
-            Some(v)
-        } else {
-            None
-        }
-    }
-
-    pub(crate) fn as_topic_record(&self) -> Option<&TopicRecordValue> {
-        if let Self::Topic(topic_value) = self {
-            Some(topic_value)
-        } else {
-            None
+            REGISTER_BROKER_RECORD_TYPE => Ok(Self::RegisterBroker(
+                RegisterBrokerRecordValue::decode(bytes, version)?,
+            )),
+            UNREGISTER_BROKER_RECORD_TYPE => Ok(Self::UnregisterBroker(
+                UnregisterBrokerRecordValue::try_from(bytes)?,
+            )),
+            FEATURE_RECORD_TYPE => Ok(Self::Feature(FeatureRecordValue::try_from(bytes)?)),
+            TOPIC_RECORD_TYPE => Ok(Self::Topic(TopicRecordValue::try_from(bytes)?)),
+            PARTITION_RECORD_TYPE => Ok(Self::Partition(PartitionRecordValue::decode(
+                bytes, version,
+            )?)),
+            CONFIG_RECORD_TYPE => Ok(Self::Config(ConfigRecordValue::try_from(bytes)?)),
+            PARTITION_CHANGE_RECORD_TYPE => Ok(Self::PartitionChange(
+                PartitionChangeRecordValue::decode(bytes, version)?,
+            )),
+            ACCESS_CONTROL_ENTRY_RECORD_TYPE => Ok(Self::AccessControlEntry(
+                AccessControlEntryRecordValue::try_from(bytes)?,
+            )),
+            REMOVE_ACCESS_CONTROL_ENTRY_RECORD_TYPE => Ok(Self::RemoveAccessControlEntry(
+                RemoveAccessControlEntryRecordValue::try_from(bytes)?,
+            )),
+            FENCE_BROKER_RECORD_TYPE => {
+                Ok(Self::FenceBroker(FenceBrokerRecordValue::try_from(bytes)?))
+            }
+            UNFENCE_BROKER_RECORD_TYPE => Ok(Self::UnfenceBroker(
+                UnfenceBrokerRecordValue::try_from(bytes)?,
+            )),
+            REMOVE_TOPIC_RECORD_TYPE => {
+                Ok(Self::RemoveTopic(RemoveTopicRecordValue::try_from(bytes)?))
+            }
+            DELEGATION_TOKEN_RECORD_TYPE => Ok(Self::DelegationToken(
+                DelegationTokenRecordValue::try_from(bytes)?,
+            )),
+            USER_SCRAM_CREDENTIAL_RECORD_TYPE => Ok(Self::UserScramCredential(
+                UserScramCredentialRecordValue::try_from(bytes)?,
+            )),
+            CLIENT_QUOTA_RECORD_TYPE => {
+                Ok(Self::ClientQuota(ClientQuotaRecordValue::try_from(bytes)?))
+            }
+            PRODUCER_IDS_RECORD_TYPE => {
+                Ok(Self::ProducerIds(ProducerIdsRecordValue::try_from(bytes)?))
+            }
+            BROKER_REGISTRATION_CHANGE_RECORD_TYPE => Ok(Self::BrokerRegistrationChange(
+                BrokerRegistrationChangeRecordValue::decode(bytes, version)?,
+            )),
+            NO_OP_RECORD_TYPE => Ok(Self::NoOp(NoOpRecordValue::try_from(bytes)?)),
+            BEGIN_TRANSACTION_RECORD_TYPE => Ok(Self::BeginTransaction(
+                BeginTransactionRecordValue::try_from(bytes)?,
+            )),
+            END_TRANSACTION_RECORD_TYPE => Ok(Self::EndTransaction(
+                EndTransactionRecordValue::try_from(bytes)?,
+            )),
+            ABORT_TRANSACTION_RECORD_TYPE => Ok(Self::AbortTransaction(
+                AbortTransactionRecordValue::try_from(bytes)?,
+            )),
+            _ => Ok(Self::Unknown {
+                record_type,
+                version,
+                value: Bytes::copy_from_slice(bytes),
+            }),
         }
     }
 
-    pub(crate) fn as_partition(&self) -> Option<&PartitionRecordValue> {
-        if let Self::Partition(v) = self {
-            Some(v)
-        } else {
-            None
+    /// The record type and version this value is written with.
+    fn record_type_and_version(&self) -> (i8, i8) {
+        match self {
+            Self::RegisterBroker(v) => (REGISTER_BROKER_RECORD_TYPE, v.version),
+            Self::UnregisterBroker(_) => (UNREGISTER_BROKER_RECORD_TYPE, 0),
+            Self::Feature(_) => (FEATURE_RECORD_TYPE, 0),
+            Self::Topic(_) => (TOPIC_RECORD_TYPE, 0),
+            Self::Partition(v) => (PARTITION_RECORD_TYPE, v.version),
+            Self::Config(_) => (CONFIG_RECORD_TYPE, 0),
+            Self::PartitionChange(v) => (PARTITION_CHANGE_RECORD_TYPE, v.version),
+            Self::AccessControlEntry(_) => (ACCESS_CONTROL_ENTRY_RECORD_TYPE, 0),
+            Self::RemoveAccessControlEntry(_) => (REMOVE_ACCESS_CONTROL_ENTRY_RECORD_TYPE, 0),
+            Self::FenceBroker(_) => (FENCE_BROKER_RECORD_TYPE, 0),
+            Self::UnfenceBroker(_) => (UNFENCE_BROKER_RECORD_TYPE, 0),
+            Self::RemoveTopic(_) => (REMOVE_TOPIC_RECORD_TYPE, 0),
+            Self::DelegationToken(_) => (DELEGATION_TOKEN_RECORD_TYPE, 0),
+            Self::UserScramCredential(_) => (USER_SCRAM_CREDENTIAL_RECORD_TYPE, 0),
+            Self::ClientQuota(_) => (CLIENT_QUOTA_RECORD_TYPE, 0),
+            Self::ProducerIds(_) => (PRODUCER_IDS_RECORD_TYPE, 0),
+            Self::BrokerRegistrationChange(v) => {
+                (BROKER_REGISTRATION_CHANGE_RECORD_TYPE, v.version)
+            }
+            Self::NoOp(_) => (NO_OP_RECORD_TYPE, 0),
+            Self::BeginTransaction(_) => (BEGIN_TRANSACTION_RECORD_TYPE, 0),
+            Self::EndTransaction(_) => (END_TRANSACTION_RECORD_TYPE, 0),
+            Self::AbortTransaction(_) => (ABORT_TRANSACTION_RECORD_TYPE, 0),
+            Self::Unknown {
+                record_type,
+                version,
+                ..
+            } => (*record_type, *version),
         }
     }
 }
@@ -450,231 +634,655 @@ impl RecordValueByType {
 impl ToBytes for RecordValueByType {
     fn to_be_bytes(&self) -> Bytes {
         match self {
+            Self::RegisterBroker(v) => v.to_be_bytes(),
+            Self::UnregisterBroker(v) => v.to_be_bytes(),
             Self::Feature(feature_value) => feature_value.to_be_bytes(),
             Self::Topic(topic_value) => topic_value.to_be_bytes(),
             Self::Partition(partition_value) => partition_value.to_be_bytes(),
-            Self::Unknown(bytes) => bytes.clone(),
+            Self::Config(config_value) => config_value.to_be_bytes(),
+            Self::PartitionChange(v) => v.to_be_bytes(),
+            Self::AccessControlEntry(v) => v.to_be_bytes(),
+            Self::RemoveAccessControlEntry(v) => v.to_be_bytes(),
+            Self::FenceBroker(v) => v.to_be_bytes(),
+            Self::UnfenceBroker(v) => v.to_be_bytes(),
+            Self::RemoveTopic(remove_topic_value) => remove_topic_value.to_be_bytes(),
+            Self::DelegationToken(v) => v.to_be_bytes(),
+            Self::UserScramCredential(v) => v.to_be_bytes(),
+            Self::ClientQuota(v) => v.to_be_bytes(),
+            Self::ProducerIds(v) => v.to_be_bytes(),
+            Self::BrokerRegistrationChange(v) => v.to_be_bytes(),
+            Self::NoOp(v) => v.to_be_bytes(),
+            Self::BeginTransaction(v) => v.to_be_bytes(),
+            Self::EndTransaction(v) => v.to_be_bytes(),
+            Self::AbortTransaction(v) => v.to_be_bytes(),
+            Self::Unknown { value, .. } => value.clone(),
         }
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct FeatureRecordValue {
-    name: String,
-    feature_level: i16,
-    tagged_fields_count: u32,
+fn invalid_data(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
 }
 
-impl TryFrom<&mut bytes::Bytes> for FeatureRecordValue {
-    type Error = crate::Error;
+fn read_uuid(bytes: &mut Bytes, field: &str) -> Result<uuid::Uuid> {
+    if bytes.remaining() < 16 {
+        return Err(invalid_data(format!("truncated UUID for {}", field)).into());
+    }
 
-    fn try_from(mut bytes: &mut bytes::Bytes) -> std::result::Result<Self, Self::Error> {
-        let name = CompactString::from_be_bytes(&mut bytes)
-            .map_err(|e| {
-                std::io::Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    format!("invalid feature name: {}", e),
-                )
-            })?
-            .to_string();
+    Ok(uuid::Uuid::from_slice(&bytes.split_to(16))
+        .map_err(|e| invalid_data(format!("invalid UUID for {}: {}", field, e)))?)
+}
 
-        let feature_level = bytes.try_get_i16()?;
-        let tagged_fields_count = UnsignedVarInt::from_be_bytes(&mut bytes)?.value();
+fn read_string(bytes: &mut Bytes, field: &str) -> Result<String> {
+    Ok(primitives::get_string(bytes, true)
+        .map_err(|e| invalid_data(format!("invalid {}: {}", field, e)))?)
+}
 
-        Ok(Self {
-            name,
-            feature_level,
-            tagged_fields_count,
-        })
-    }
+fn read_nullable_string(bytes: &mut Bytes, field: &str) -> Result<Option<String>> {
+    Ok(primitives::get_nullable_string(bytes, true)
+        .map_err(|e| invalid_data(format!("invalid {}: {}", field, e)))?)
 }
 
-impl ToBytes for FeatureRecordValue {
-    fn to_be_bytes(&self) -> Bytes {
-        let mut bytes = BytesMut::new();
+/// Reads the length of a compact array, `None` when the array is null.
+fn read_array_len(bytes: &mut Bytes) -> Result<Option<usize>> {
+    primitives::get_array_len(bytes, true)
+}
 
-        bytes.extend(CompactString::from(self.name.clone()).to_be_bytes());
-        bytes.put_i16(self.feature_level);
-        bytes.extend(UnsignedVarInt::from(self.tagged_fields_count).to_be_bytes());
+fn read_array<T>(
+    bytes: &mut Bytes,
+    field: &str,
+    mut read: impl FnMut(&mut Bytes) -> Result<T>,
+) -> Result<Option<Vec<T>>> {
+    let Some(len) = read_array_len(bytes)? else {
+        return Ok(None);
+    };
+
+    (0..len)
+        .map(|_| read(bytes).map_err(|e| invalid_data(format!("invalid {}: {}", field, e)).into()))
+        .collect::<Result<Vec<T>>>()
+        .map(Some)
+}
 
-        bytes.freeze()
+fn read_int32s(bytes: &mut Bytes, field: &str) -> Result<Option<Vec<i32>>> {
+    read_array(bytes, field, |bytes| Ok(bytes.try_get_i32()?))
+}
+
+fn read_uuids(bytes: &mut Bytes, field: &str) -> Result<Option<Vec<uuid::Uuid>>> {
+    read_array(bytes, field, |bytes| read_uuid(bytes, field))
+}
+
+fn read_compact_bytes(bytes: &mut Bytes, field: &str) -> Result<Bytes> {
+    let len =
+        read_array_len(bytes)?.ok_or_else(|| invalid_data(format!("null bytes for {}", field)))?;
+
+    if bytes.remaining() < len {
+        return Err(invalid_data(format!("truncated bytes for {}", field)).into());
     }
+
+    Ok(bytes.split_to(len))
 }
 
-#[derive(Debug, Clone)]
-pub struct TopicRecordValue {
-    name: String,
-    topic_uuid: uuid::Uuid,
-    tagged_fields_count: u32,
+fn put_string(bytes: &mut BytesMut, value: &str) {
+    primitives::put_string(bytes, value, true);
 }
 
-impl TopicRecordValue {
-    pub fn topic_uuid(&self) -> uuid::Uuid {
-        self.topic_uuid
+fn put_nullable_string(bytes: &mut BytesMut, value: Option<&str>) {
+    primitives::put_nullable_string(bytes, value, true);
+}
+
+fn put_array_len(bytes: &mut BytesMut, len: Option<usize>) {
+    primitives::put_array_len(bytes, len, true);
+}
+
+fn put_int32s(bytes: &mut BytesMut, values: Option<&[i32]>) {
+    put_array_len(bytes, values.map(<[i32]>::len));
+
+    for value in values.unwrap_or_default() {
+        bytes.put_i32(*value);
     }
+}
 
-    pub fn name(&self) -> &str {
-        &self.name
+fn put_uuids(bytes: &mut BytesMut, values: Option<&[uuid::Uuid]>) {
+    put_array_len(bytes, values.map(<[uuid::Uuid]>::len));
+
+    for value in values.unwrap_or_default() {
+        bytes.extend(value.as_bytes());
     }
 }
 
-impl TryFrom<&mut bytes::Bytes> for TopicRecordValue {
-    type Error = crate::Error;
+fn put_compact_bytes(bytes: &mut BytesMut, value: &[u8]) {
+    put_array_len(bytes, Some(value.len()));
+    bytes.extend_from_slice(value);
+}
 
-    fn try_from(mut bytes: &mut bytes::Bytes) -> std::result::Result<Self, Self::Error> {
-        let name = CompactString::from_be_bytes(&mut bytes)
-            .map_err(|e| {
-                std::io::Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    format!("invalid topic name: {}", e),
-                )
-            })?
-            .to_string();
+/// Writes `known` fields together with `unknown` ones, ordered by tag as the
+/// protocol requires.
+fn put_tagged_fields(bytes: &mut BytesMut, known: Vec<(u32, Bytes)>, unknown: &TaggedFields) {
+    let mut fields = unknown.clone();
+    for (tag, data) in known {
+        fields.insert(tag, data);
+    }
 
-        let topic_uuid = uuid::Uuid::from_slice(&bytes.split_to(16)).map_err(|_| {
-            std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                "invalid UUID in topic record",
-            )
-        })?;
-        let tagged_fields_count = UnsignedVarInt::from_be_bytes(&mut bytes)?.value();
+    bytes.extend(fields.to_be_bytes());
+}
+
+/// Encodes a tagged field's value with `put`.
+fn tagged(put: impl FnOnce(&mut BytesMut)) -> Bytes {
+    let mut bytes = BytesMut::new();
+    put(&mut bytes);
+    bytes.freeze()
+}
+
+#[derive(Debug, Clone)]
+pub struct BrokerEndpoint {
+    name: String,
+    host: String,
+    port: u16,
+    security_protocol: i16,
+    tagged_fields: TaggedFields,
+}
 
+impl BrokerEndpoint {
+    fn decode(bytes: &mut Bytes) -> Result<Self> {
         Ok(Self {
-            name,
-            topic_uuid,
-            tagged_fields_count,
+            name: read_string(bytes, "endpoint name")?,
+            host: read_string(bytes, "endpoint host")?,
+            port: bytes.try_get_u16()?,
+            security_protocol: bytes.try_get_i16()?,
+            tagged_fields: TaggedFields::from_be_bytes(bytes)?,
         })
     }
+
+    fn encode(&self, bytes: &mut BytesMut) {
+        put_string(bytes, &self.name);
+        put_string(bytes, &self.host);
+        bytes.put_u16(self.port);
+        bytes.put_i16(self.security_protocol);
+        put_tagged_fields(bytes, Vec::new(), &self.tagged_fields);
+    }
 }
 
-impl ToBytes for TopicRecordValue {
-    fn to_be_bytes(&self) -> Bytes {
-        let mut bytes = BytesMut::new();
+#[derive(Debug, Clone)]
+pub struct BrokerFeature {
+    name: String,
+    min_supported_version: i16,
+    max_supported_version: i16,
+    tagged_fields: TaggedFields,
+}
 
-        bytes.extend(CompactString::from(self.name.clone()).to_be_bytes());
-        bytes.extend(self.topic_uuid.as_bytes());
-        bytes.extend(UnsignedVarInt::from(self.tagged_fields_count).to_be_bytes());
+impl BrokerFeature {
+    fn decode(bytes: &mut Bytes) -> Result<Self> {
+        Ok(Self {
+            name: read_string(bytes, "feature name")?,
+            min_supported_version: bytes.try_get_i16()?,
+            max_supported_version: bytes.try_get_i16()?,
+            tagged_fields: TaggedFields::from_be_bytes(bytes)?,
+        })
+    }
 
-        bytes.freeze()
+    fn encode(&self, bytes: &mut BytesMut) {
+        put_string(bytes, &self.name);
+        bytes.put_i16(self.min_supported_version);
+        bytes.put_i16(self.max_supported_version);
+        put_tagged_fields(bytes, Vec::new(), &self.tagged_fields);
     }
 }
 
+/// `RegisterBrokerRecord`, versions 0-3.
 #[derive(Debug, Clone)]
-pub struct PartitionRecordValue {
-    partition_id: i32,
-    topic_uuid: uuid::Uuid,
-    replica_array: CompactArray<INT32>,
-    in_sync_replica_array: CompactArray<INT32>,
-    removing_replicas_array: CompactArray<INT32>,
-    adding_replicas_array: CompactArray<INT32>,
-    leader: i32,
-    leader_epoch: i32,
-    partition_epoch: i32,
-    directories_array: Vec<uuid::Uuid>,
-    tagged_fields_count: u32,
+pub struct RegisterBrokerRecordValue {
+    version: i8,
+    broker_id: i32,
+    /// Version 2+.
+    is_migrating_zk_broker: bool,
+    incarnation_id: uuid::Uuid,
+    broker_epoch: i64,
+    end_points: Vec<BrokerEndpoint>,
+    features: Vec<BrokerFeature>,
+    rack: Option<String>,
+    fenced: bool,
+    /// Version 1+.
+    in_controlled_shutdown: bool,
+    /// Version 3+.
+    log_dirs: Vec<uuid::Uuid>,
+    tagged_fields: TaggedFields,
 }
 
-impl PartitionRecordValue {
-    pub fn partition_id(&self) -> i32 {
-        self.partition_id
+impl RegisterBrokerRecordValue {
+    /// The registration of a broker in its current state, as written to a
+    /// snapshot.
+    pub(crate) fn from_image(broker: &BrokerImage) -> Self {
+        Self {
+            version: 3,
+            broker_id: broker.id(),
+            is_migrating_zk_broker: false,
+            incarnation_id: broker.incarnation_id(),
+            broker_epoch: broker.epoch(),
+            end_points: broker.end_points().to_vec(),
+            features: broker.features().to_vec(),
+            rack: broker.rack().map(str::to_string),
+            fenced: broker.fenced(),
+            in_controlled_shutdown: broker.in_controlled_shutdown(),
+            log_dirs: broker.log_dirs().to_vec(),
+            tagged_fields: TaggedFields::new(),
+        }
     }
 
-    pub fn topic_uuid(&self) -> uuid::Uuid {
-        self.topic_uuid
+    pub fn broker_id(&self) -> i32 {
+        self.broker_id
     }
 
-    pub fn replica_array(&self) -> &CompactArray<INT32> {
-        &self.replica_array
+    pub fn incarnation_id(&self) -> uuid::Uuid {
+        self.incarnation_id
     }
 
-    pub fn in_sync_replica_array(&self) -> &CompactArray<INT32> {
-        &self.in_sync_replica_array
+    pub fn broker_epoch(&self) -> i64 {
+        self.broker_epoch
     }
 
-    pub fn removing_replicas_array(&self) -> &CompactArray<INT32> {
-        &self.removing_replicas_array
+    pub fn end_points(&self) -> &[BrokerEndpoint] {
+        &self.end_points
     }
 
-    pub fn adding_replicas_array(&self) -> &CompactArray<INT32> {
-        &self.adding_replicas_array
+    pub fn features(&self) -> &[BrokerFeature] {
+        &self.features
     }
 
-    pub fn leader(&self) -> i32 {
-        self.leader
+    pub fn rack(&self) -> Option<&str> {
+        self.rack.as_deref()
     }
 
-    pub fn leader_epoch(&self) -> i32 {
-        self.leader_epoch
+    pub fn fenced(&self) -> bool {
+        self.fenced
     }
 
-    pub fn partition_epoch(&self) -> i32 {
-        self.partition_epoch
+    pub fn in_controlled_shutdown(&self) -> bool {
+        self.in_controlled_shutdown
     }
 
-    pub fn directories_array(&self) -> &[uuid::Uuid] {
-        &self.directories_array
+    pub fn log_dirs(&self) -> &[uuid::Uuid] {
+        &self.log_dirs
     }
 
-    pub fn tagged_fields_count(&self) -> u32 {
-        self.tagged_fields_count
-    }
-}
+    fn decode(bytes: &mut Bytes, version: i8) -> Result<Self> {
+        let broker_id = bytes.try_get_i32()?;
+        let is_migrating_zk_broker = version >= 2 && bytes.try_get_u8()? != 0;
+        let incarnation_id = read_uuid(bytes, "incarnation id")?;
+        let broker_epoch = bytes.try_get_i64()?;
+        let end_points =
+            read_array(bytes, "end points", BrokerEndpoint::decode)?.unwrap_or_default();
+        let features = read_array(bytes, "features", BrokerFeature::decode)?.unwrap_or_default();
+        let rack = read_nullable_string(bytes, "rack")?;
+        let fenced = bytes.try_get_u8()? != 0;
+        let in_controlled_shutdown = version >= 1 && bytes.try_get_u8()? != 0;
+        let log_dirs = if version >= 3 {
+            read_uuids(bytes, "log dirs")?.unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        let tagged_fields = TaggedFields::from_be_bytes(bytes)?;
 
-impl TryFrom<&mut bytes::Bytes> for PartitionRecordValue {
-    type Error = crate::Error;
+        Ok(Self {
+            version,
+            broker_id,
+            is_migrating_zk_broker,
+            incarnation_id,
+            broker_epoch,
+            end_points,
+            features,
+            rack,
+            fenced,
+            in_controlled_shutdown,
+            log_dirs,
+            tagged_fields,
+        })
+    }
+}
 
-    fn try_from(mut bytes: &mut bytes::Bytes) -> std::result::Result<Self, Self::Error> {
-        let partition_id = bytes.try_get_i32()?;
-        let topic_uuid = uuid::Uuid::from_slice(&bytes.split_to(16)).map_err(|_| {
-            std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                "Invalid UUID in partition record",
-            )
-        })?;
+impl ToBytes for RegisterBrokerRecordValue {
+    fn to_be_bytes(&self) -> Bytes {
+        let mut bytes = BytesMut::new();
 
-        let replica_array = CompactArray::from_be_bytes(&mut bytes).map_err(|_| {
-            std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                "failed to parse replica array",
-            )
-        })?;
-        let in_sync_replica_array = CompactArray::from_be_bytes(&mut bytes).map_err(|_| {
-            std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                "failed to parse in-sync replica array",
-            )
-        })?;
-        let removing_replicas_array = CompactArray::from_be_bytes(&mut bytes).map_err(|_| {
-            std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                "failed to parse removing replicas array",
-            )
-        })?;
-        let adding_replicas_array = CompactArray::from_be_bytes(&mut bytes).map_err(|_| {
-            std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                "failed to parse adding replicas array",
-            )
-        })?;
+        bytes.put_i32(self.broker_id);
+        if self.version >= 2 {
+            bytes.put_u8(self.is_migrating_zk_broker as u8);
+        }
+        bytes.extend(self.incarnation_id.as_bytes());
+        bytes.put_i64(self.broker_epoch);
+        put_array_len(&mut bytes, Some(self.end_points.len()));
+        for end_point in &self.end_points {
+            end_point.encode(&mut bytes);
+        }
+        put_array_len(&mut bytes, Some(self.features.len()));
+        for feature in &self.features {
+            feature.encode(&mut bytes);
+        }
+        put_nullable_string(&mut bytes, self.rack.as_deref());
+        bytes.put_u8(self.fenced as u8);
+        if self.version >= 1 {
+            bytes.put_u8(self.in_controlled_shutdown as u8);
+        }
+        if self.version >= 3 {
+            put_uuids(&mut bytes, Some(&self.log_dirs));
+        }
+        put_tagged_fields(&mut bytes, Vec::new(), &self.tagged_fields);
+
+        bytes.freeze()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UnregisterBrokerRecordValue {
+    broker_id: i32,
+    broker_epoch: i64,
+    tagged_fields: TaggedFields,
+}
+
+impl UnregisterBrokerRecordValue {
+    pub fn broker_id(&self) -> i32 {
+        self.broker_id
+    }
+}
+
+impl TryFrom<&mut bytes::Bytes> for UnregisterBrokerRecordValue {
+    type Error = crate::Error;
+
+    fn try_from(bytes: &mut bytes::Bytes) -> std::result::Result<Self, Self::Error> {
+        Ok(Self {
+            broker_id: bytes.try_get_i32()?,
+            broker_epoch: bytes.try_get_i64()?,
+            tagged_fields: TaggedFields::from_be_bytes(bytes)?,
+        })
+    }
+}
+
+impl ToBytes for UnregisterBrokerRecordValue {
+    fn to_be_bytes(&self) -> Bytes {
+        let mut bytes = BytesMut::new();
+
+        bytes.put_i32(self.broker_id);
+        bytes.put_i64(self.broker_epoch);
+        put_tagged_fields(&mut bytes, Vec::new(), &self.tagged_fields);
+
+        bytes.freeze()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FeatureRecordValue {
+    name: String,
+    feature_level: i16,
+    tagged_fields: TaggedFields,
+}
+
+impl FeatureRecordValue {
+    pub fn new(name: &str, feature_level: i16) -> Self {
+        Self {
+            name: name.to_string(),
+            feature_level,
+            tagged_fields: TaggedFields::new(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn feature_level(&self) -> i16 {
+        self.feature_level
+    }
+}
+
+impl TryFrom<&mut bytes::Bytes> for FeatureRecordValue {
+    type Error = crate::Error;
+
+    fn try_from(bytes: &mut bytes::Bytes) -> std::result::Result<Self, Self::Error> {
+        Ok(Self {
+            name: read_string(bytes, "feature name")?,
+            feature_level: bytes.try_get_i16()?,
+            tagged_fields: TaggedFields::from_be_bytes(bytes)?,
+        })
+    }
+}
+
+impl ToBytes for FeatureRecordValue {
+    fn to_be_bytes(&self) -> Bytes {
+        let mut bytes = BytesMut::new();
+
+        put_string(&mut bytes, &self.name);
+        bytes.put_i16(self.feature_level);
+        put_tagged_fields(&mut bytes, Vec::new(), &self.tagged_fields);
+
+        bytes.freeze()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TopicRecordValue {
+    name: String,
+    topic_uuid: uuid::Uuid,
+    tagged_fields: TaggedFields,
+}
+
+impl TopicRecordValue {
+    pub fn new(name: &str, topic_uuid: uuid::Uuid) -> Self {
+        Self {
+            name: name.to_string(),
+            topic_uuid,
+            tagged_fields: TaggedFields::new(),
+        }
+    }
+
+    pub fn topic_uuid(&self) -> uuid::Uuid {
+        self.topic_uuid
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl TryFrom<&mut bytes::Bytes> for TopicRecordValue {
+    type Error = crate::Error;
+
+    fn try_from(bytes: &mut bytes::Bytes) -> std::result::Result<Self, Self::Error> {
+        Ok(Self {
+            name: read_string(bytes, "topic name")?,
+            topic_uuid: read_uuid(bytes, "topic id")?,
+            tagged_fields: TaggedFields::from_be_bytes(bytes)?,
+        })
+    }
+}
+
+impl ToBytes for TopicRecordValue {
+    fn to_be_bytes(&self) -> Bytes {
+        let mut bytes = BytesMut::new();
+
+        put_string(&mut bytes, &self.name);
+        bytes.extend(self.topic_uuid.as_bytes());
+        put_tagged_fields(&mut bytes, Vec::new(), &self.tagged_fields);
+
+        bytes.freeze()
+    }
+}
+
+const LEADER_RECOVERY_STATE_TAG: u32 = 0;
+const ELIGIBLE_LEADER_REPLICAS_TAG: u32 = 1;
+const LAST_KNOWN_ELR_TAG: u32 = 2;
+
+/// `PartitionRecord`, versions 0-2.
+#[derive(Debug, Clone)]
+pub struct PartitionRecordValue {
+    version: i8,
+    partition_id: i32,
+    topic_uuid: uuid::Uuid,
+    replica_array: CompactArray<INT32>,
+    in_sync_replica_array: CompactArray<INT32>,
+    removing_replicas_array: CompactArray<INT32>,
+    adding_replicas_array: CompactArray<INT32>,
+    leader: i32,
+    /// Tagged field 0.
+    leader_recovery_state: i8,
+    leader_epoch: i32,
+    partition_epoch: i32,
+    /// Version 1+.
+    directories_array: Vec<uuid::Uuid>,
+    /// Tagged field 1, version 2+.
+    eligible_leader_replicas: Option<Vec<i32>>,
+    /// Tagged field 2, version 2+.
+    last_known_elr: Option<Vec<i32>>,
+    tagged_fields: TaggedFields,
+}
+
+impl PartitionRecordValue {
+    /// A new partition led by the first of `replicas`, with every replica in
+    /// sync. `directories` holds the log directory of each replica.
+    pub fn new(
+        partition_id: i32,
+        topic_uuid: uuid::Uuid,
+        replicas: Vec<i32>,
+        directories: Vec<uuid::Uuid>,
+    ) -> Self {
+        let leader = replicas.first().copied().unwrap_or(-1);
+        let replica_array = replicas
+            .into_iter()
+            .map(INT32::from)
+            .collect::<CompactArray<INT32>>();
+
+        Self {
+            version: 1,
+            partition_id,
+            topic_uuid,
+            in_sync_replica_array: replica_array.clone(),
+            replica_array,
+            removing_replicas_array: CompactArray::new(),
+            adding_replicas_array: CompactArray::new(),
+            leader,
+            leader_recovery_state: 0,
+            leader_epoch: 0,
+            partition_epoch: 0,
+            directories_array: directories,
+            eligible_leader_replicas: None,
+            last_known_elr: None,
+            tagged_fields: TaggedFields::new(),
+        }
+    }
+
+    /// A partition in its current state, as written to a snapshot.
+    pub(crate) fn from_image(topic_uuid: uuid::Uuid, partition: &PartitionImage) -> Self {
+        let int32s = |nodes: &[i32]| nodes.iter().copied().map(INT32::from).collect();
+        let elr = |nodes: &[i32]| (!nodes.is_empty()).then(|| nodes.to_vec());
+        let eligible_leader_replicas = elr(partition.eligible_leader_replicas());
+        let last_known_elr = elr(partition.last_known_elr());
+
+        Self {
+            version: if eligible_leader_replicas.is_some() || last_known_elr.is_some() {
+                2
+            } else {
+                1
+            },
+            partition_id: partition.partition_id(),
+            topic_uuid,
+            replica_array: int32s(partition.replicas()),
+            in_sync_replica_array: int32s(partition.isr()),
+            removing_replicas_array: int32s(partition.removing_replicas()),
+            adding_replicas_array: int32s(partition.adding_replicas()),
+            leader: partition.leader(),
+            leader_recovery_state: partition.leader_recovery_state(),
+            leader_epoch: partition.leader_epoch(),
+            partition_epoch: partition.partition_epoch(),
+            directories_array: partition.directories().to_vec(),
+            eligible_leader_replicas,
+            last_known_elr,
+            tagged_fields: TaggedFields::new(),
+        }
+    }
+
+    pub fn partition_id(&self) -> i32 {
+        self.partition_id
+    }
+
+    pub fn topic_uuid(&self) -> uuid::Uuid {
+        self.topic_uuid
+    }
+
+    pub fn replica_array(&self) -> &CompactArray<INT32> {
+        &self.replica_array
+    }
+
+    pub fn in_sync_replica_array(&self) -> &CompactArray<INT32> {
+        &self.in_sync_replica_array
+    }
+
+    pub fn removing_replicas_array(&self) -> &CompactArray<INT32> {
+        &self.removing_replicas_array
+    }
+
+    pub fn adding_replicas_array(&self) -> &CompactArray<INT32> {
+        &self.adding_replicas_array
+    }
+
+    pub fn leader(&self) -> i32 {
+        self.leader
+    }
+
+    pub fn leader_recovery_state(&self) -> i8 {
+        self.leader_recovery_state
+    }
+
+    pub fn leader_epoch(&self) -> i32 {
+        self.leader_epoch
+    }
+
+    pub fn partition_epoch(&self) -> i32 {
+        self.partition_epoch
+    }
+
+    pub fn directories_array(&self) -> &[uuid::Uuid] {
+        &self.directories_array
+    }
 
+    pub fn eligible_leader_replicas(&self) -> Option<&[i32]> {
+        self.eligible_leader_replicas.as_deref()
+    }
+
+    pub fn last_known_elr(&self) -> Option<&[i32]> {
+        self.last_known_elr.as_deref()
+    }
+
+    fn decode(bytes: &mut Bytes, version: i8) -> Result<Self> {
+        let partition_id = bytes.try_get_i32()?;
+        let topic_uuid = read_uuid(bytes, "topic id")?;
+        let replica_array = compact_int32s(read_int32s(bytes, "replicas")?);
+        let in_sync_replica_array = compact_int32s(read_int32s(bytes, "isr")?);
+        let removing_replicas_array = compact_int32s(read_int32s(bytes, "removing replicas")?);
+        let adding_replicas_array = compact_int32s(read_int32s(bytes, "adding replicas")?);
         let leader = bytes.try_get_i32()?;
         let leader_epoch = bytes.try_get_i32()?;
         let partition_epoch = bytes.try_get_i32()?;
+        let directories_array = if version >= 1 {
+            read_uuids(bytes, "directories")?.unwrap_or_default()
+        } else {
+            Vec::new()
+        };
 
-        let directories_array_len = UnsignedVarInt::from_be_bytes(&mut bytes)?.value() - 1;
-        let mut directories_array = Vec::with_capacity(directories_array_len as usize);
-        for _ in 0..directories_array_len {
-            directories_array.push(uuid::Uuid::from_slice(&bytes.split_to(16)).map_err(|_| {
-                std::io::Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    "invalid UUID in partition record",
-                )
-            })?);
-        }
-
-        let tagged_fields_count = UnsignedVarInt::from_be_bytes(&mut bytes)?.value();
+        let mut tagged_fields = TaggedFields::from_be_bytes(bytes)?;
+        let leader_recovery_state = tagged_fields
+            .take(LEADER_RECOVERY_STATE_TAG)
+            .map(|mut data| data.try_get_i8())
+            .transpose()?
+            .unwrap_or_default();
+        let (eligible_leader_replicas, last_known_elr) = if version >= 2 {
+            (
+                take_tagged_int32s(&mut tagged_fields, ELIGIBLE_LEADER_REPLICAS_TAG)?,
+                take_tagged_int32s(&mut tagged_fields, LAST_KNOWN_ELR_TAG)?,
+            )
+        } else {
+            (None, None)
+        };
 
         Ok(Self {
+            version,
             partition_id,
             topic_uuid,
             replica_array,
@@ -682,10 +1290,13 @@ impl TryFrom<&mut bytes::Bytes> for PartitionRecordValue {
             removing_replicas_array,
             adding_replicas_array,
             leader,
+            leader_recovery_state,
             leader_epoch,
             partition_epoch,
             directories_array,
-            tagged_fields_count,
+            eligible_leader_replicas,
+            last_known_elr,
+            tagged_fields,
         })
     }
 }
@@ -703,14 +1314,1008 @@ impl ToBytes for PartitionRecordValue {
         bytes.put_i32(self.leader);
         bytes.put_i32(self.leader_epoch);
         bytes.put_i32(self.partition_epoch);
+        if self.version >= 1 {
+            put_uuids(&mut bytes, Some(&self.directories_array));
+        }
+
+        let mut known = Vec::new();
+        if self.leader_recovery_state != 0 {
+            known.push((
+                LEADER_RECOVERY_STATE_TAG,
+                tagged(|b| b.put_i8(self.leader_recovery_state)),
+            ));
+        }
+        if self.version >= 2 {
+            if let Some(elr) = &self.eligible_leader_replicas {
+                known.push((
+                    ELIGIBLE_LEADER_REPLICAS_TAG,
+                    tagged(|b| put_int32s(b, Some(elr))),
+                ));
+            }
+            if let Some(last_known_elr) = &self.last_known_elr {
+                known.push((
+                    LAST_KNOWN_ELR_TAG,
+                    tagged(|b| put_int32s(b, Some(last_known_elr))),
+                ));
+            }
+        }
+        put_tagged_fields(&mut bytes, known, &self.tagged_fields);
+
+        bytes.freeze()
+    }
+}
+
+fn compact_int32s(values: Option<Vec<i32>>) -> CompactArray<INT32> {
+    values
+        .unwrap_or_default()
+        .into_iter()
+        .map(INT32::from)
+        .collect()
+}
+
+fn take_tagged_int32s(fields: &mut TaggedFields, tag: u32) -> Result<Option<Vec<i32>>> {
+    match fields.take(tag) {
+        Some(mut data) => read_int32s(&mut data, "tagged field"),
+        None => Ok(None),
+    }
+}
 
-        bytes.extend(UnsignedVarInt::from(self.directories_array.len() as u32 + 1).to_be_bytes());
+#[derive(Debug, Clone)]
+pub struct ConfigRecordValue {
+    resource_type: i8,
+    resource_name: String,
+    name: String,
+    value: Option<String>,
+    tagged_fields: TaggedFields,
+}
 
-        for directory in &self.directories_array {
-            bytes.extend(directory.as_bytes());
+impl ConfigRecordValue {
+    pub fn new(resource_type: i8, resource_name: &str, name: &str, value: Option<&str>) -> Self {
+        Self {
+            resource_type,
+            resource_name: resource_name.to_string(),
+            name: name.to_string(),
+            value: value.map(str::to_string),
+            tagged_fields: TaggedFields::new(),
         }
+    }
+
+    pub fn resource_type(&self) -> i8 {
+        self.resource_type
+    }
+
+    pub fn resource_name(&self) -> &str {
+        &self.resource_name
+    }
 
-        bytes.extend(UnsignedVarInt::from(self.tagged_fields_count).to_be_bytes());
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// `None` deletes a previously set value.
+    pub fn value(&self) -> Option<&str> {
+        self.value.as_deref()
+    }
+}
+
+impl TryFrom<&mut bytes::Bytes> for ConfigRecordValue {
+    type Error = crate::Error;
+
+    fn try_from(bytes: &mut bytes::Bytes) -> std::result::Result<Self, Self::Error> {
+        Ok(Self {
+            resource_type: bytes.try_get_i8()?,
+            resource_name: read_string(bytes, "config resource name")?,
+            name: read_string(bytes, "config name")?,
+            value: read_nullable_string(bytes, "config value")?,
+            tagged_fields: TaggedFields::from_be_bytes(bytes)?,
+        })
+    }
+}
+
+impl ToBytes for ConfigRecordValue {
+    fn to_be_bytes(&self) -> Bytes {
+        let mut bytes = BytesMut::new();
+
+        bytes.put_i8(self.resource_type);
+        put_string(&mut bytes, &self.resource_name);
+        put_string(&mut bytes, &self.name);
+        put_nullable_string(&mut bytes, self.value.as_deref());
+        put_tagged_fields(&mut bytes, Vec::new(), &self.tagged_fields);
+
+        bytes.freeze()
+    }
+}
+
+const CHANGE_ISR_TAG: u32 = 0;
+const CHANGE_LEADER_TAG: u32 = 1;
+const CHANGE_REPLICAS_TAG: u32 = 2;
+const CHANGE_REMOVING_REPLICAS_TAG: u32 = 3;
+const CHANGE_ADDING_REPLICAS_TAG: u32 = 4;
+const CHANGE_LEADER_RECOVERY_STATE_TAG: u32 = 5;
+const CHANGE_DIRECTORIES_TAG: u32 = 6;
+const CHANGE_ELIGIBLE_LEADER_REPLICAS_TAG: u32 = 7;
+const CHANGE_LAST_KNOWN_ELR_TAG: u32 = 8;
+
+/// `PartitionChangeRecord`, versions 0-2. Every field after the topic id is
+/// tagged, and absent fields leave the partition unchanged.
+#[derive(Debug, Clone)]
+pub struct PartitionChangeRecordValue {
+    version: i8,
+    partition_id: i32,
+    topic_uuid: uuid::Uuid,
+    isr: Option<Vec<i32>>,
+    /// [`NO_LEADER_CHANGE`] when the leader is unchanged.
+    leader: i32,
+    replicas: Option<Vec<i32>>,
+    removing_replicas: Option<Vec<i32>>,
+    adding_replicas: Option<Vec<i32>>,
+    /// -1 when unchanged.
+    leader_recovery_state: i8,
+    /// Version 1+.
+    directories: Option<Vec<uuid::Uuid>>,
+    /// Version 2+.
+    eligible_leader_replicas: Option<Vec<i32>>,
+    /// Version 2+.
+    last_known_elr: Option<Vec<i32>>,
+    tagged_fields: TaggedFields,
+}
+
+impl PartitionChangeRecordValue {
+    pub fn partition_id(&self) -> i32 {
+        self.partition_id
+    }
+
+    pub fn topic_uuid(&self) -> uuid::Uuid {
+        self.topic_uuid
+    }
+
+    pub fn isr(&self) -> Option<&[i32]> {
+        self.isr.as_deref()
+    }
+
+    /// The new leader, or `None` when it is unchanged.
+    pub fn leader(&self) -> Option<i32> {
+        (self.leader != NO_LEADER_CHANGE).then_some(self.leader)
+    }
+
+    pub fn replicas(&self) -> Option<&[i32]> {
+        self.replicas.as_deref()
+    }
+
+    pub fn removing_replicas(&self) -> Option<&[i32]> {
+        self.removing_replicas.as_deref()
+    }
+
+    pub fn adding_replicas(&self) -> Option<&[i32]> {
+        self.adding_replicas.as_deref()
+    }
+
+    pub fn leader_recovery_state(&self) -> Option<i8> {
+        (self.leader_recovery_state != -1).then_some(self.leader_recovery_state)
+    }
+
+    pub fn directories(&self) -> Option<&[uuid::Uuid]> {
+        self.directories.as_deref()
+    }
+
+    pub fn eligible_leader_replicas(&self) -> Option<&[i32]> {
+        self.eligible_leader_replicas.as_deref()
+    }
+
+    pub fn last_known_elr(&self) -> Option<&[i32]> {
+        self.last_known_elr.as_deref()
+    }
+
+    fn decode(bytes: &mut Bytes, version: i8) -> Result<Self> {
+        let partition_id = bytes.try_get_i32()?;
+        let topic_uuid = read_uuid(bytes, "topic id")?;
+
+        let mut tagged_fields = TaggedFields::from_be_bytes(bytes)?;
+        let isr = take_tagged_int32s(&mut tagged_fields, CHANGE_ISR_TAG)?;
+        let leader = tagged_fields
+            .take(CHANGE_LEADER_TAG)
+            .map(|mut data| data.try_get_i32())
+            .transpose()?
+            .unwrap_or(NO_LEADER_CHANGE);
+        let replicas = take_tagged_int32s(&mut tagged_fields, CHANGE_REPLICAS_TAG)?;
+        let removing_replicas =
+            take_tagged_int32s(&mut tagged_fields, CHANGE_REMOVING_REPLICAS_TAG)?;
+        let adding_replicas = take_tagged_int32s(&mut tagged_fields, CHANGE_ADDING_REPLICAS_TAG)?;
+        let leader_recovery_state = tagged_fields
+            .take(CHANGE_LEADER_RECOVERY_STATE_TAG)
+            .map(|mut data| data.try_get_i8())
+            .transpose()?
+            .unwrap_or(-1);
+        let directories = if version >= 1 {
+            match tagged_fields.take(CHANGE_DIRECTORIES_TAG) {
+                Some(mut data) => read_uuids(&mut data, "directories")?,
+                None => None,
+            }
+        } else {
+            None
+        };
+        let (eligible_leader_replicas, last_known_elr) = if version >= 2 {
+            (
+                take_tagged_int32s(&mut tagged_fields, CHANGE_ELIGIBLE_LEADER_REPLICAS_TAG)?,
+                take_tagged_int32s(&mut tagged_fields, CHANGE_LAST_KNOWN_ELR_TAG)?,
+            )
+        } else {
+            (None, None)
+        };
+
+        Ok(Self {
+            version,
+            partition_id,
+            topic_uuid,
+            isr,
+            leader,
+            replicas,
+            removing_replicas,
+            adding_replicas,
+            leader_recovery_state,
+            directories,
+            eligible_leader_replicas,
+            last_known_elr,
+            tagged_fields,
+        })
+    }
+}
+
+impl ToBytes for PartitionChangeRecordValue {
+    fn to_be_bytes(&self) -> Bytes {
+        let mut bytes = BytesMut::new();
+
+        bytes.put_i32(self.partition_id);
+        bytes.extend(self.topic_uuid.as_bytes());
+
+        let mut known = Vec::new();
+        let int32_fields = [
+            (CHANGE_ISR_TAG, &self.isr),
+            (CHANGE_REPLICAS_TAG, &self.replicas),
+            (CHANGE_REMOVING_REPLICAS_TAG, &self.removing_replicas),
+            (CHANGE_ADDING_REPLICAS_TAG, &self.adding_replicas),
+        ];
+        for (tag, values) in int32_fields {
+            if let Some(values) = values {
+                known.push((tag, tagged(|b| put_int32s(b, Some(values)))));
+            }
+        }
+        if self.leader != NO_LEADER_CHANGE {
+            known.push((CHANGE_LEADER_TAG, tagged(|b| b.put_i32(self.leader))));
+        }
+        if self.leader_recovery_state != -1 {
+            known.push((
+                CHANGE_LEADER_RECOVERY_STATE_TAG,
+                tagged(|b| b.put_i8(self.leader_recovery_state)),
+            ));
+        }
+        if self.version >= 1 {
+            if let Some(directories) = &self.directories {
+                known.push((
+                    CHANGE_DIRECTORIES_TAG,
+                    tagged(|b| put_uuids(b, Some(directories))),
+                ));
+            }
+        }
+        if self.version >= 2 {
+            let elr_fields = [
+                (
+                    CHANGE_ELIGIBLE_LEADER_REPLICAS_TAG,
+                    &self.eligible_leader_replicas,
+                ),
+                (CHANGE_LAST_KNOWN_ELR_TAG, &self.last_known_elr),
+            ];
+            for (tag, values) in elr_fields {
+                if let Some(values) = values {
+                    known.push((tag, tagged(|b| put_int32s(b, Some(values)))));
+                }
+            }
+        }
+        put_tagged_fields(&mut bytes, known, &self.tagged_fields);
+
+        bytes.freeze()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AccessControlEntryRecordValue {
+    id: uuid::Uuid,
+    resource_type: i8,
+    resource_name: String,
+    pattern_type: i8,
+    principal: String,
+    host: String,
+    operation: i8,
+    permission_type: i8,
+    tagged_fields: TaggedFields,
+}
+
+impl AccessControlEntryRecordValue {
+    pub fn id(&self) -> uuid::Uuid {
+        self.id
+    }
+}
+
+impl TryFrom<&mut bytes::Bytes> for AccessControlEntryRecordValue {
+    type Error = crate::Error;
+
+    fn try_from(bytes: &mut bytes::Bytes) -> std::result::Result<Self, Self::Error> {
+        Ok(Self {
+            id: read_uuid(bytes, "acl id")?,
+            resource_type: bytes.try_get_i8()?,
+            resource_name: read_string(bytes, "acl resource name")?,
+            pattern_type: bytes.try_get_i8()?,
+            principal: read_string(bytes, "acl principal")?,
+            host: read_string(bytes, "acl host")?,
+            operation: bytes.try_get_i8()?,
+            permission_type: bytes.try_get_i8()?,
+            tagged_fields: TaggedFields::from_be_bytes(bytes)?,
+        })
+    }
+}
+
+impl ToBytes for AccessControlEntryRecordValue {
+    fn to_be_bytes(&self) -> Bytes {
+        let mut bytes = BytesMut::new();
+
+        bytes.extend(self.id.as_bytes());
+        bytes.put_i8(self.resource_type);
+        put_string(&mut bytes, &self.resource_name);
+        bytes.put_i8(self.pattern_type);
+        put_string(&mut bytes, &self.principal);
+        put_string(&mut bytes, &self.host);
+        bytes.put_i8(self.operation);
+        bytes.put_i8(self.permission_type);
+        put_tagged_fields(&mut bytes, Vec::new(), &self.tagged_fields);
+
+        bytes.freeze()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RemoveAccessControlEntryRecordValue {
+    id: uuid::Uuid,
+    tagged_fields: TaggedFields,
+}
+
+impl RemoveAccessControlEntryRecordValue {
+    pub fn id(&self) -> uuid::Uuid {
+        self.id
+    }
+}
+
+impl TryFrom<&mut bytes::Bytes> for RemoveAccessControlEntryRecordValue {
+    type Error = crate::Error;
+
+    fn try_from(bytes: &mut bytes::Bytes) -> std::result::Result<Self, Self::Error> {
+        Ok(Self {
+            id: read_uuid(bytes, "acl id")?,
+            tagged_fields: TaggedFields::from_be_bytes(bytes)?,
+        })
+    }
+}
+
+impl ToBytes for RemoveAccessControlEntryRecordValue {
+    fn to_be_bytes(&self) -> Bytes {
+        let mut bytes = BytesMut::new();
+
+        bytes.extend(self.id.as_bytes());
+        put_tagged_fields(&mut bytes, Vec::new(), &self.tagged_fields);
+
+        bytes.freeze()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FenceBrokerRecordValue {
+    id: i32,
+    epoch: i64,
+    tagged_fields: TaggedFields,
+}
+
+impl FenceBrokerRecordValue {
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+}
+
+impl TryFrom<&mut bytes::Bytes> for FenceBrokerRecordValue {
+    type Error = crate::Error;
+
+    fn try_from(bytes: &mut bytes::Bytes) -> std::result::Result<Self, Self::Error> {
+        Ok(Self {
+            id: bytes.try_get_i32()?,
+            epoch: bytes.try_get_i64()?,
+            tagged_fields: TaggedFields::from_be_bytes(bytes)?,
+        })
+    }
+}
+
+impl ToBytes for FenceBrokerRecordValue {
+    fn to_be_bytes(&self) -> Bytes {
+        let mut bytes = BytesMut::new();
+
+        bytes.put_i32(self.id);
+        bytes.put_i64(self.epoch);
+        put_tagged_fields(&mut bytes, Vec::new(), &self.tagged_fields);
+
+        bytes.freeze()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UnfenceBrokerRecordValue {
+    id: i32,
+    epoch: i64,
+    tagged_fields: TaggedFields,
+}
+
+impl UnfenceBrokerRecordValue {
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+}
+
+impl TryFrom<&mut bytes::Bytes> for UnfenceBrokerRecordValue {
+    type Error = crate::Error;
+
+    fn try_from(bytes: &mut bytes::Bytes) -> std::result::Result<Self, Self::Error> {
+        Ok(Self {
+            id: bytes.try_get_i32()?,
+            epoch: bytes.try_get_i64()?,
+            tagged_fields: TaggedFields::from_be_bytes(bytes)?,
+        })
+    }
+}
+
+impl ToBytes for UnfenceBrokerRecordValue {
+    fn to_be_bytes(&self) -> Bytes {
+        let mut bytes = BytesMut::new();
+
+        bytes.put_i32(self.id);
+        bytes.put_i64(self.epoch);
+        put_tagged_fields(&mut bytes, Vec::new(), &self.tagged_fields);
+
+        bytes.freeze()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RemoveTopicRecordValue {
+    topic_uuid: uuid::Uuid,
+    tagged_fields: TaggedFields,
+}
+
+impl RemoveTopicRecordValue {
+    pub fn new(topic_uuid: uuid::Uuid) -> Self {
+        Self {
+            topic_uuid,
+            tagged_fields: TaggedFields::new(),
+        }
+    }
+
+    pub fn topic_uuid(&self) -> uuid::Uuid {
+        self.topic_uuid
+    }
+}
+
+impl TryFrom<&mut bytes::Bytes> for RemoveTopicRecordValue {
+    type Error = crate::Error;
+
+    fn try_from(bytes: &mut bytes::Bytes) -> std::result::Result<Self, Self::Error> {
+        Ok(Self {
+            topic_uuid: read_uuid(bytes, "topic id")?,
+            tagged_fields: TaggedFields::from_be_bytes(bytes)?,
+        })
+    }
+}
+
+impl ToBytes for RemoveTopicRecordValue {
+    fn to_be_bytes(&self) -> Bytes {
+        let mut bytes = BytesMut::new();
+
+        bytes.extend(self.topic_uuid.as_bytes());
+        put_tagged_fields(&mut bytes, Vec::new(), &self.tagged_fields);
+
+        bytes.freeze()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DelegationTokenRecordValue {
+    owner: String,
+    requester: String,
+    renewers: Vec<String>,
+    issue_timestamp: i64,
+    max_timestamp: i64,
+    expiration_timestamp: i64,
+    token_id: String,
+    tagged_fields: TaggedFields,
+}
+
+impl DelegationTokenRecordValue {
+    pub fn token_id(&self) -> &str {
+        &self.token_id
+    }
+}
+
+impl TryFrom<&mut bytes::Bytes> for DelegationTokenRecordValue {
+    type Error = crate::Error;
+
+    fn try_from(bytes: &mut bytes::Bytes) -> std::result::Result<Self, Self::Error> {
+        Ok(Self {
+            owner: read_string(bytes, "token owner")?,
+            requester: read_string(bytes, "token requester")?,
+            renewers: read_array(bytes, "token renewers", |bytes| {
+                read_string(bytes, "token renewer")
+            })?
+            .unwrap_or_default(),
+            issue_timestamp: bytes.try_get_i64()?,
+            max_timestamp: bytes.try_get_i64()?,
+            expiration_timestamp: bytes.try_get_i64()?,
+            token_id: read_string(bytes, "token id")?,
+            tagged_fields: TaggedFields::from_be_bytes(bytes)?,
+        })
+    }
+}
+
+impl ToBytes for DelegationTokenRecordValue {
+    fn to_be_bytes(&self) -> Bytes {
+        let mut bytes = BytesMut::new();
+
+        put_string(&mut bytes, &self.owner);
+        put_string(&mut bytes, &self.requester);
+        put_array_len(&mut bytes, Some(self.renewers.len()));
+        for renewer in &self.renewers {
+            put_string(&mut bytes, renewer);
+        }
+        bytes.put_i64(self.issue_timestamp);
+        bytes.put_i64(self.max_timestamp);
+        bytes.put_i64(self.expiration_timestamp);
+        put_string(&mut bytes, &self.token_id);
+        put_tagged_fields(&mut bytes, Vec::new(), &self.tagged_fields);
+
+        bytes.freeze()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UserScramCredentialRecordValue {
+    name: String,
+    mechanism: i8,
+    salt: Bytes,
+    stored_key: Bytes,
+    server_key: Bytes,
+    iterations: i32,
+    tagged_fields: TaggedFields,
+}
+
+impl UserScramCredentialRecordValue {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn mechanism(&self) -> i8 {
+        self.mechanism
+    }
+}
+
+impl TryFrom<&mut bytes::Bytes> for UserScramCredentialRecordValue {
+    type Error = crate::Error;
+
+    fn try_from(bytes: &mut bytes::Bytes) -> std::result::Result<Self, Self::Error> {
+        Ok(Self {
+            name: read_string(bytes, "user name")?,
+            mechanism: bytes.try_get_i8()?,
+            salt: read_compact_bytes(bytes, "salt")?,
+            stored_key: read_compact_bytes(bytes, "stored key")?,
+            server_key: read_compact_bytes(bytes, "server key")?,
+            iterations: bytes.try_get_i32()?,
+            tagged_fields: TaggedFields::from_be_bytes(bytes)?,
+        })
+    }
+}
+
+impl ToBytes for UserScramCredentialRecordValue {
+    fn to_be_bytes(&self) -> Bytes {
+        let mut bytes = BytesMut::new();
+
+        put_string(&mut bytes, &self.name);
+        bytes.put_i8(self.mechanism);
+        put_compact_bytes(&mut bytes, &self.salt);
+        put_compact_bytes(&mut bytes, &self.stored_key);
+        put_compact_bytes(&mut bytes, &self.server_key);
+        bytes.put_i32(self.iterations);
+        put_tagged_fields(&mut bytes, Vec::new(), &self.tagged_fields);
+
+        bytes.freeze()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ClientQuotaEntity {
+    entity_type: String,
+    /// `None` for the default entity of the type.
+    entity_name: Option<String>,
+    tagged_fields: TaggedFields,
+}
+
+impl ClientQuotaEntity {
+    pub fn entity_type(&self) -> &str {
+        &self.entity_type
+    }
+
+    pub fn entity_name(&self) -> Option<&str> {
+        self.entity_name.as_deref()
+    }
+
+    fn decode(bytes: &mut Bytes) -> Result<Self> {
+        Ok(Self {
+            entity_type: read_string(bytes, "entity type")?,
+            entity_name: read_nullable_string(bytes, "entity name")?,
+            tagged_fields: TaggedFields::from_be_bytes(bytes)?,
+        })
+    }
+
+    fn encode(&self, bytes: &mut BytesMut) {
+        put_string(bytes, &self.entity_type);
+        put_nullable_string(bytes, self.entity_name.as_deref());
+        put_tagged_fields(bytes, Vec::new(), &self.tagged_fields);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ClientQuotaRecordValue {
+    entity: Vec<ClientQuotaEntity>,
+    key: String,
+    value: f64,
+    remove: bool,
+    tagged_fields: TaggedFields,
+}
+
+impl ClientQuotaRecordValue {
+    pub fn entity(&self) -> &[ClientQuotaEntity] {
+        &self.entity
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn remove(&self) -> bool {
+        self.remove
+    }
+}
+
+impl TryFrom<&mut bytes::Bytes> for ClientQuotaRecordValue {
+    type Error = crate::Error;
+
+    fn try_from(bytes: &mut bytes::Bytes) -> std::result::Result<Self, Self::Error> {
+        Ok(Self {
+            entity: read_array(bytes, "quota entity", ClientQuotaEntity::decode)?
+                .unwrap_or_default(),
+            key: read_string(bytes, "quota key")?,
+            value: bytes.try_get_f64()?,
+            remove: bytes.try_get_u8()? != 0,
+            tagged_fields: TaggedFields::from_be_bytes(bytes)?,
+        })
+    }
+}
+
+impl ToBytes for ClientQuotaRecordValue {
+    fn to_be_bytes(&self) -> Bytes {
+        let mut bytes = BytesMut::new();
+
+        put_array_len(&mut bytes, Some(self.entity.len()));
+        for entity in &self.entity {
+            entity.encode(&mut bytes);
+        }
+        put_string(&mut bytes, &self.key);
+        bytes.put_f64(self.value);
+        bytes.put_u8(self.remove as u8);
+        put_tagged_fields(&mut bytes, Vec::new(), &self.tagged_fields);
+
+        bytes.freeze()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ProducerIdsRecordValue {
+    broker_id: i32,
+    broker_epoch: i64,
+    next_producer_id: i64,
+    tagged_fields: TaggedFields,
+}
+
+impl ProducerIdsRecordValue {
+    pub fn new(broker_id: i32, broker_epoch: i64, next_producer_id: i64) -> Self {
+        Self {
+            broker_id,
+            broker_epoch,
+            next_producer_id,
+            tagged_fields: TaggedFields::new(),
+        }
+    }
+
+    pub fn next_producer_id(&self) -> i64 {
+        self.next_producer_id
+    }
+}
+
+impl TryFrom<&mut bytes::Bytes> for ProducerIdsRecordValue {
+    type Error = crate::Error;
+
+    fn try_from(bytes: &mut bytes::Bytes) -> std::result::Result<Self, Self::Error> {
+        Ok(Self {
+            broker_id: bytes.try_get_i32()?,
+            broker_epoch: bytes.try_get_i64()?,
+            next_producer_id: bytes.try_get_i64()?,
+            tagged_fields: TaggedFields::from_be_bytes(bytes)?,
+        })
+    }
+}
+
+impl ToBytes for ProducerIdsRecordValue {
+    fn to_be_bytes(&self) -> Bytes {
+        let mut bytes = BytesMut::new();
+
+        bytes.put_i32(self.broker_id);
+        bytes.put_i64(self.broker_epoch);
+        bytes.put_i64(self.next_producer_id);
+        put_tagged_fields(&mut bytes, Vec::new(), &self.tagged_fields);
+
+        bytes.freeze()
+    }
+}
+
+const CHANGE_FENCED_TAG: u32 = 0;
+const CHANGE_IN_CONTROLLED_SHUTDOWN_TAG: u32 = 1;
+const CHANGE_LOG_DIRS_TAG: u32 = 2;
+
+/// `BrokerRegistrationChangeRecord`, versions 0-2.
+#[derive(Debug, Clone)]
+pub struct BrokerRegistrationChangeRecordValue {
+    version: i8,
+    broker_id: i32,
+    broker_epoch: i64,
+    /// Tagged field 0: -1 unfenced, 1 fenced, 0 unchanged.
+    fenced: i8,
+    /// Tagged field 1, version 1+: 1 when shutting down, 0 unchanged.
+    in_controlled_shutdown: i8,
+    /// Tagged field 2, version 2+.
+    log_dirs: Option<Vec<uuid::Uuid>>,
+    tagged_fields: TaggedFields,
+}
+
+impl BrokerRegistrationChangeRecordValue {
+    pub fn broker_id(&self) -> i32 {
+        self.broker_id
+    }
+
+    pub fn fenced(&self) -> i8 {
+        self.fenced
+    }
+
+    pub fn in_controlled_shutdown(&self) -> i8 {
+        self.in_controlled_shutdown
+    }
+
+    pub fn log_dirs(&self) -> Option<&[uuid::Uuid]> {
+        self.log_dirs.as_deref()
+    }
+
+    fn decode(bytes: &mut Bytes, version: i8) -> Result<Self> {
+        let broker_id = bytes.try_get_i32()?;
+        let broker_epoch = bytes.try_get_i64()?;
+
+        let mut tagged_fields = TaggedFields::from_be_bytes(bytes)?;
+        let fenced = tagged_fields
+            .take(CHANGE_FENCED_TAG)
+            .map(|mut data| data.try_get_i8())
+            .transpose()?
+            .unwrap_or_default();
+        let in_controlled_shutdown = if version >= 1 {
+            tagged_fields
+                .take(CHANGE_IN_CONTROLLED_SHUTDOWN_TAG)
+                .map(|mut data| data.try_get_i8())
+                .transpose()?
+                .unwrap_or_default()
+        } else {
+            0
+        };
+        let log_dirs = match tagged_fields.take(CHANGE_LOG_DIRS_TAG) {
+            Some(mut data) if version >= 2 => read_uuids(&mut data, "log dirs")?,
+            Some(data) => {
+                tagged_fields.insert(CHANGE_LOG_DIRS_TAG, data);
+                None
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            version,
+            broker_id,
+            broker_epoch,
+            fenced,
+            in_controlled_shutdown,
+            log_dirs,
+            tagged_fields,
+        })
+    }
+}
+
+impl ToBytes for BrokerRegistrationChangeRecordValue {
+    fn to_be_bytes(&self) -> Bytes {
+        let mut bytes = BytesMut::new();
+
+        bytes.put_i32(self.broker_id);
+        bytes.put_i64(self.broker_epoch);
+
+        let mut known = Vec::new();
+        if self.fenced != 0 {
+            known.push((CHANGE_FENCED_TAG, tagged(|b| b.put_i8(self.fenced))));
+        }
+        if self.version >= 1 && self.in_controlled_shutdown != 0 {
+            known.push((
+                CHANGE_IN_CONTROLLED_SHUTDOWN_TAG,
+                tagged(|b| b.put_i8(self.in_controlled_shutdown)),
+            ));
+        }
+        if let Some(log_dirs) = self.log_dirs.as_deref().filter(|_| self.version >= 2) {
+            known.push((
+                CHANGE_LOG_DIRS_TAG,
+                tagged(|b| put_uuids(b, Some(log_dirs))),
+            ));
+        }
+        put_tagged_fields(&mut bytes, known, &self.tagged_fields);
+
+        bytes.freeze()
+    }
+}
+
+/// Written by the controller to advance the high watermark when there are
+/// no other changes.
+#[derive(Debug, Clone)]
+pub struct NoOpRecordValue {
+    tagged_fields: TaggedFields,
+}
+
+impl TryFrom<&mut bytes::Bytes> for NoOpRecordValue {
+    type Error = crate::Error;
+
+    fn try_from(bytes: &mut bytes::Bytes) -> std::result::Result<Self, Self::Error> {
+        Ok(Self {
+            tagged_fields: TaggedFields::from_be_bytes(bytes)?,
+        })
+    }
+}
+
+impl ToBytes for NoOpRecordValue {
+    fn to_be_bytes(&self) -> Bytes {
+        let mut bytes = BytesMut::new();
+
+        put_tagged_fields(&mut bytes, Vec::new(), &self.tagged_fields);
+
+        bytes.freeze()
+    }
+}
+
+const TRANSACTION_NAME_TAG: u32 = 0;
+
+/// Starts a group of records that only take effect once an
+/// `EndTransactionRecord` follows.
+#[derive(Debug, Clone)]
+pub struct BeginTransactionRecordValue {
+    /// Tagged field 0.
+    name: Option<String>,
+    tagged_fields: TaggedFields,
+}
+
+impl TryFrom<&mut bytes::Bytes> for BeginTransactionRecordValue {
+    type Error = crate::Error;
+
+    fn try_from(bytes: &mut bytes::Bytes) -> std::result::Result<Self, Self::Error> {
+        let mut tagged_fields = TaggedFields::from_be_bytes(bytes)?;
+        let name = match tagged_fields.take(TRANSACTION_NAME_TAG) {
+            Some(mut data) => read_nullable_string(&mut data, "transaction name")?,
+            None => None,
+        };
+
+        Ok(Self {
+            name,
+            tagged_fields,
+        })
+    }
+}
+
+impl ToBytes for BeginTransactionRecordValue {
+    fn to_be_bytes(&self) -> Bytes {
+        let mut bytes = BytesMut::new();
+
+        let known = self
+            .name
+            .as_deref()
+            .map(|name| {
+                (
+                    TRANSACTION_NAME_TAG,
+                    tagged(|b| put_nullable_string(b, Some(name))),
+                )
+            })
+            .into_iter()
+            .collect();
+        put_tagged_fields(&mut bytes, known, &self.tagged_fields);
+
+        bytes.freeze()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EndTransactionRecordValue {
+    tagged_fields: TaggedFields,
+}
+
+impl TryFrom<&mut bytes::Bytes> for EndTransactionRecordValue {
+    type Error = crate::Error;
+
+    fn try_from(bytes: &mut bytes::Bytes) -> std::result::Result<Self, Self::Error> {
+        Ok(Self {
+            tagged_fields: TaggedFields::from_be_bytes(bytes)?,
+        })
+    }
+}
+
+impl ToBytes for EndTransactionRecordValue {
+    fn to_be_bytes(&self) -> Bytes {
+        let mut bytes = BytesMut::new();
+
+        put_tagged_fields(&mut bytes, Vec::new(), &self.tagged_fields);
+
+        bytes.freeze()
+    }
+}
+
+const ABORT_REASON_TAG: u32 = 0;
+
+#[derive(Debug, Clone)]
+pub struct AbortTransactionRecordValue {
+    /// Tagged field 0.
+    reason: Option<String>,
+    tagged_fields: TaggedFields,
+}
+
+impl TryFrom<&mut bytes::Bytes> for AbortTransactionRecordValue {
+    type Error = crate::Error;
+
+    fn try_from(bytes: &mut bytes::Bytes) -> std::result::Result<Self, Self::Error> {
+        let mut tagged_fields = TaggedFields::from_be_bytes(bytes)?;
+        let reason = match tagged_fields.take(ABORT_REASON_TAG) {
+            Some(mut data) => read_nullable_string(&mut data, "abort reason")?,
+            None => None,
+        };
+
+        Ok(Self {
+            reason,
+            tagged_fields,
+        })
+    }
+}
+
+impl ToBytes for AbortTransactionRecordValue {
+    fn to_be_bytes(&self) -> Bytes {
+        let mut bytes = BytesMut::new();
+
+        let known = self
+            .reason
+            .as_deref()
+            .map(|reason| {
+                (
+                    ABORT_REASON_TAG,
+                    tagged(|b| put_nullable_string(b, Some(reason))),
+                )
+            })
+            .into_iter()
+            .collect();
+        put_tagged_fields(&mut bytes, known, &self.tagged_fields);
 
         bytes.freeze()
     }
@@ -0,0 +1,480 @@
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use super::{
+    bytes::{FromBytes, ToBytes},
+    cluster_metadata::Record,
+    primitives::{CompactArray, TaggedFields, INT32},
+};
+
+use crate::Result;
+
+const CONTROL_RECORD_KEY_VERSION: i16 = 0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ControlRecordType {
+    Abort,
+    Commit,
+    LeaderChange,
+    SnapshotHeader,
+    SnapshotFooter,
+    KRaftVersion,
+    KRaftVoters,
+    Unknown(i16),
+}
+
+impl From<i16> for ControlRecordType {
+    fn from(value: i16) -> Self {
+        match value {
+            0 => Self::Abort,
+            1 => Self::Commit,
+            2 => Self::LeaderChange,
+            3 => Self::SnapshotHeader,
+            4 => Self::SnapshotFooter,
+            5 => Self::KRaftVersion,
+            6 => Self::KRaftVoters,
+            other => Self::Unknown(other),
+        }
+    }
+}
+
+impl From<ControlRecordType> for i16 {
+    fn from(value: ControlRecordType) -> Self {
+        match value {
+            ControlRecordType::Abort => 0,
+            ControlRecordType::Commit => 1,
+            ControlRecordType::LeaderChange => 2,
+            ControlRecordType::SnapshotHeader => 3,
+            ControlRecordType::SnapshotFooter => 4,
+            ControlRecordType::KRaftVersion => 5,
+            ControlRecordType::KRaftVoters => 6,
+            ControlRecordType::Unknown(other) => other,
+        }
+    }
+}
+
+/// A record of a control batch. The key carries the control type and the
+/// value is decoded according to it.
+#[derive(Debug, Clone)]
+pub(crate) struct ControlRecord {
+    offset_delta: i32,
+    value: ControlRecordValue,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum ControlRecordValue {
+    Abort(EndTxnMarker),
+    Commit(EndTxnMarker),
+    LeaderChange(LeaderChangeMessage),
+    SnapshotHeader(SnapshotHeaderRecord),
+    SnapshotFooter(SnapshotFooterRecord),
+    KRaftVersion(KRaftVersionRecord),
+    /// Control types this broker doesn't interpret, kept verbatim.
+    Unknown {
+        record_type: ControlRecordType,
+        value: Bytes,
+    },
+}
+
+impl ControlRecord {
+    pub(crate) fn new(offset_delta: i32, value: ControlRecordValue) -> Self {
+        Self {
+            offset_delta,
+            value,
+        }
+    }
+
+    pub(crate) fn end_txn_marker(commit: bool, coordinator_epoch: i32) -> Self {
+        let marker = EndTxnMarker::new(coordinator_epoch);
+        let value = if commit {
+            ControlRecordValue::Commit(marker)
+        } else {
+            ControlRecordValue::Abort(marker)
+        };
+
+        Self::new(0, value)
+    }
+
+    pub(crate) fn offset_delta(&self) -> i32 {
+        self.offset_delta
+    }
+
+    pub(crate) fn value(&self) -> &ControlRecordValue {
+        &self.value
+    }
+
+    pub(crate) fn record_type(&self) -> ControlRecordType {
+        match &self.value {
+            ControlRecordValue::Abort(_) => ControlRecordType::Abort,
+            ControlRecordValue::Commit(_) => ControlRecordType::Commit,
+            ControlRecordValue::LeaderChange(_) => ControlRecordType::LeaderChange,
+            ControlRecordValue::SnapshotHeader(_) => ControlRecordType::SnapshotHeader,
+            ControlRecordValue::SnapshotFooter(_) => ControlRecordType::SnapshotFooter,
+            ControlRecordValue::KRaftVersion(_) => ControlRecordType::KRaftVersion,
+            ControlRecordValue::Unknown { record_type, .. } => *record_type,
+        }
+    }
+
+    fn encode_key(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(4);
+
+        buf.put_i16(CONTROL_RECORD_KEY_VERSION);
+        buf.put_i16(self.record_type().into());
+
+        buf.freeze()
+    }
+
+    fn encode_value(&self) -> Bytes {
+        match &self.value {
+            ControlRecordValue::Abort(marker) | ControlRecordValue::Commit(marker) => {
+                marker.to_be_bytes()
+            }
+            ControlRecordValue::LeaderChange(message) => message.to_be_bytes(),
+            ControlRecordValue::SnapshotHeader(header) => header.to_be_bytes(),
+            ControlRecordValue::SnapshotFooter(footer) => footer.to_be_bytes(),
+            ControlRecordValue::KRaftVersion(version) => version.to_be_bytes(),
+            ControlRecordValue::Unknown { value, .. } => value.clone(),
+        }
+    }
+}
+
+impl TryFrom<&Record<Bytes>> for ControlRecord {
+    type Error = crate::Error;
+
+    fn try_from(record: &Record<Bytes>) -> std::result::Result<Self, Self::Error> {
+        let mut key = record.key().cloned().ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "control record without key",
+            )
+        })?;
+
+        let _version = key.try_get_i16()?;
+        let record_type = ControlRecordType::from(key.try_get_i16()?);
+
+        let mut value = record.record_value().clone();
+        let value = match record_type {
+            ControlRecordType::Abort => {
+                ControlRecordValue::Abort(EndTxnMarker::from_be_bytes(&mut value)?)
+            }
+            ControlRecordType::Commit => {
+                ControlRecordValue::Commit(EndTxnMarker::from_be_bytes(&mut value)?)
+            }
+            ControlRecordType::LeaderChange => {
+                ControlRecordValue::LeaderChange(LeaderChangeMessage::from_be_bytes(&mut value)?)
+            }
+            ControlRecordType::SnapshotHeader => {
+                ControlRecordValue::SnapshotHeader(SnapshotHeaderRecord::from_be_bytes(&mut value)?)
+            }
+            ControlRecordType::SnapshotFooter => {
+                ControlRecordValue::SnapshotFooter(SnapshotFooterRecord::from_be_bytes(&mut value)?)
+            }
+            ControlRecordType::KRaftVersion => {
+                ControlRecordValue::KRaftVersion(KRaftVersionRecord::from_be_bytes(&mut value)?)
+            }
+            ControlRecordType::KRaftVoters | ControlRecordType::Unknown(_) => {
+                ControlRecordValue::Unknown { record_type, value }
+            }
+        };
+
+        Ok(Self {
+            offset_delta: record.offset_delta(),
+            value,
+        })
+    }
+}
+
+impl From<&ControlRecord> for Record<Bytes> {
+    fn from(record: &ControlRecord) -> Self {
+        Record::new(
+            record.offset_delta,
+            Some(record.encode_key()),
+            record.encode_value(),
+        )
+    }
+}
+
+impl ToBytes for ControlRecord {
+    fn to_be_bytes(&self) -> Bytes {
+        Record::from(self).to_be_bytes()
+    }
+}
+
+/// Value of a COMMIT or ABORT marker.
+#[derive(Debug, Clone)]
+pub(crate) struct EndTxnMarker {
+    version: i16,
+    coordinator_epoch: i32,
+}
+
+impl EndTxnMarker {
+    pub(crate) fn new(coordinator_epoch: i32) -> Self {
+        Self {
+            version: 0,
+            coordinator_epoch,
+        }
+    }
+
+    pub(crate) fn coordinator_epoch(&self) -> i32 {
+        self.coordinator_epoch
+    }
+}
+
+impl FromBytes for EndTxnMarker {
+    fn from_be_bytes<B: Buf>(buf: &mut B) -> Result<Self> {
+        let version = buf.try_get_i16()?;
+        let coordinator_epoch = buf.try_get_i32()?;
+
+        Ok(Self {
+            version,
+            coordinator_epoch,
+        })
+    }
+}
+
+impl ToBytes for EndTxnMarker {
+    fn to_be_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(6);
+
+        buf.put_i16(self.version);
+        buf.put_i32(self.coordinator_epoch);
+
+        buf.freeze()
+    }
+}
+
+/// Written by a KRaft leader when it is elected.
+#[derive(Debug, Clone)]
+pub(crate) struct LeaderChangeMessage {
+    version: i16,
+    leader_id: i32,
+    voters: CompactArray<Voter>,
+    granted_voters: CompactArray<Voter>,
+    tag: TaggedFields,
+}
+
+impl LeaderChangeMessage {
+    pub(crate) fn leader_id(&self) -> i32 {
+        self.leader_id
+    }
+}
+
+impl FromBytes for LeaderChangeMessage {
+    fn from_be_bytes<B: Buf>(buf: &mut B) -> Result<Self> {
+        let version = buf.try_get_i16()?;
+        let leader_id = buf.try_get_i32()?;
+        let voters = CompactArray::from_be_bytes(buf)?;
+        let granted_voters = CompactArray::from_be_bytes(buf)?;
+        let tag = TaggedFields::from_be_bytes(buf)?;
+
+        Ok(Self {
+            version,
+            leader_id,
+            voters,
+            granted_voters,
+            tag,
+        })
+    }
+}
+
+impl ToBytes for LeaderChangeMessage {
+    fn to_be_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::new();
+
+        buf.put_i16(self.version);
+        buf.put_i32(self.leader_id);
+        buf.extend(self.voters.to_be_bytes());
+        buf.extend(self.granted_voters.to_be_bytes());
+        buf.extend(self.tag.to_be_bytes());
+
+        buf.freeze()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Voter {
+    voter_id: INT32,
+    tag: TaggedFields,
+}
+
+impl FromBytes for Voter {
+    fn from_be_bytes<B: Buf>(buf: &mut B) -> Result<Self> {
+        let voter_id = INT32::from_be_bytes(buf)?;
+        let tag = TaggedFields::from_be_bytes(buf)?;
+
+        Ok(Self { voter_id, tag })
+    }
+}
+
+impl ToBytes for Voter {
+    fn to_be_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::new();
+
+        buf.extend(self.voter_id.to_be_bytes());
+        buf.extend(self.tag.to_be_bytes());
+
+        buf.freeze()
+    }
+}
+
+/// First record of a KRaft snapshot.
+#[derive(Debug, Clone)]
+pub(crate) struct SnapshotHeaderRecord {
+    version: i16,
+    last_contained_log_timestamp: i64,
+    tag: TaggedFields,
+}
+
+impl SnapshotHeaderRecord {
+    pub(crate) fn new(last_contained_log_timestamp: i64) -> Self {
+        Self {
+            version: 0,
+            last_contained_log_timestamp,
+            tag: TaggedFields::new(),
+        }
+    }
+
+    pub(crate) fn last_contained_log_timestamp(&self) -> i64 {
+        self.last_contained_log_timestamp
+    }
+}
+
+impl FromBytes for SnapshotHeaderRecord {
+    fn from_be_bytes<B: Buf>(buf: &mut B) -> Result<Self> {
+        let version = buf.try_get_i16()?;
+        let last_contained_log_timestamp = buf.try_get_i64()?;
+        let tag = TaggedFields::from_be_bytes(buf)?;
+
+        Ok(Self {
+            version,
+            last_contained_log_timestamp,
+            tag,
+        })
+    }
+}
+
+impl ToBytes for SnapshotHeaderRecord {
+    fn to_be_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::new();
+
+        buf.put_i16(self.version);
+        buf.put_i64(self.last_contained_log_timestamp);
+        buf.extend(self.tag.to_be_bytes());
+
+        buf.freeze()
+    }
+}
+
+/// Last record of a KRaft snapshot.
+#[derive(Debug, Clone)]
+pub(crate) struct SnapshotFooterRecord {
+    version: i16,
+    tag: TaggedFields,
+}
+
+impl SnapshotFooterRecord {
+    pub(crate) fn new() -> Self {
+        Self {
+            version: 0,
+            tag: TaggedFields::new(),
+        }
+    }
+}
+
+impl FromBytes for SnapshotFooterRecord {
+    fn from_be_bytes<B: Buf>(buf: &mut B) -> Result<Self> {
+        let version = buf.try_get_i16()?;
+        let tag = TaggedFields::from_be_bytes(buf)?;
+
+        Ok(Self { version, tag })
+    }
+}
+
+impl ToBytes for SnapshotFooterRecord {
+    fn to_be_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::new();
+
+        buf.put_i16(self.version);
+        buf.extend(self.tag.to_be_bytes());
+
+        buf.freeze()
+    }
+}
+
+/// The `kraft.version` in effect for the metadata log.
+#[derive(Debug, Clone)]
+pub(crate) struct KRaftVersionRecord {
+    version: i16,
+    kraft_version: i16,
+    tag: TaggedFields,
+}
+
+impl KRaftVersionRecord {
+    pub(crate) fn kraft_version(&self) -> i16 {
+        self.kraft_version
+    }
+}
+
+impl FromBytes for KRaftVersionRecord {
+    fn from_be_bytes<B: Buf>(buf: &mut B) -> Result<Self> {
+        let version = buf.try_get_i16()?;
+        let kraft_version = buf.try_get_i16()?;
+        let tag = TaggedFields::from_be_bytes(buf)?;
+
+        Ok(Self {
+            version,
+            kraft_version,
+            tag,
+        })
+    }
+}
+
+impl ToBytes for KRaftVersionRecord {
+    fn to_be_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::new();
+
+        buf.put_i16(self.version);
+        buf.put_i16(self.kraft_version);
+        buf.extend(self.tag.to_be_bytes());
+
+        buf.freeze()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tagged_fields_are_skipped_and_kept() {
+        let mut tag = TaggedFields::new();
+        tag.insert(0, Bytes::from_static(b"unknown"));
+        let mut voter = BytesMut::new();
+        voter.put_i32(2);
+        voter.extend(tag.to_be_bytes());
+
+        let mut buf = BytesMut::new();
+        buf.put_i16(0);
+        buf.put_i32(1);
+        for _ in 0..2 {
+            buf.put_u8(2);
+            buf.extend_from_slice(&voter);
+        }
+        buf.extend(tag.to_be_bytes());
+        let bytes = buf.freeze();
+
+        let message = LeaderChangeMessage::from_be_bytes(&mut bytes.clone()).unwrap();
+        assert_eq!(message.leader_id(), 1);
+        assert_eq!(message.granted_voters.iter().count(), 1);
+        assert_eq!(
+            message
+                .granted_voters
+                .iter()
+                .next()
+                .unwrap()
+                .voter_id
+                .value(),
+            2
+        );
+        assert_eq!(message.to_be_bytes(), bytes);
+    }
+}
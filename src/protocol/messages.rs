@@ -0,0 +1,84 @@
+//! The request and response messages generated by `build.rs` from the Kafka
+//! schemas in `schema/`, one module per message.
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use uuid::Uuid;
+
+use super::bytes::{FromBytes, FromVersionedBytes, ToBytes, ToVersionedBytes};
+use super::primitives::{
+    get_array_len, get_nullable_bytes, get_nullable_string, get_string, put_array_len,
+    put_nullable_bytes, put_nullable_string, put_string, TaggedFields,
+};
+
+use crate::Result;
+
+include!(concat!(env!("OUT_DIR"), "/messages.rs"));
+
+/// A value of a message with every field of `version` set to something other
+/// than its default.
+#[cfg(test)]
+pub(crate) trait Sample {
+    fn sample(version: i16) -> Self;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! assert_round_trips {
+        ($($message:ident),* $(,)?) => {
+            $(
+                for version in $message::MIN_VERSION..=$message::MAX_VERSION {
+                    let message = $message::sample(version);
+                    let mut bytes = message.to_versioned_be_bytes(version);
+                    let decoded = $message::from_versioned_be_bytes(&mut bytes, version).unwrap();
+
+                    assert_eq!(decoded, message, "{} v{}", stringify!($message), version);
+                    assert!(bytes.is_empty(), "{} v{} left bytes", stringify!($message), version);
+                }
+            )*
+        };
+    }
+
+    #[test]
+    fn every_message_round_trips_in_every_version() {
+        assert_round_trips!(
+            AddOffsetsToTxnRequest,
+            AddOffsetsToTxnResponse,
+            AddPartitionsToTxnRequest,
+            AddPartitionsToTxnResponse,
+            AlterConfigsRequest,
+            AlterConfigsResponse,
+            ApiVersionsRequest,
+            ApiVersionsResponse,
+            CreatePartitionsRequest,
+            CreatePartitionsResponse,
+            CreateTopicsRequest,
+            CreateTopicsResponse,
+            DeleteTopicsRequest,
+            DeleteTopicsResponse,
+            DescribeClusterRequest,
+            DescribeClusterResponse,
+            DescribeConfigsRequest,
+            DescribeConfigsResponse,
+            DescribeLogDirsRequest,
+            DescribeLogDirsResponse,
+            DescribeTopicPartitionsRequest,
+            DescribeTopicPartitionsResponse,
+            EndTxnRequest,
+            EndTxnResponse,
+            FetchRequest,
+            FetchResponse,
+            IncrementalAlterConfigsRequest,
+            IncrementalAlterConfigsResponse,
+            InitProducerIdRequest,
+            InitProducerIdResponse,
+            MetadataRequest,
+            MetadataResponse,
+            TxnOffsetCommitRequest,
+            TxnOffsetCommitResponse,
+            UpdateFeaturesRequest,
+            UpdateFeaturesResponse,
+        );
+    }
+}
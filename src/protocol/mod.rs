@@ -2,6 +2,8 @@ pub mod bytes;
 
 pub mod error;
 
+pub mod messages;
+
 pub mod primitives;
 
 pub mod request;
@@ -9,3 +11,5 @@ pub mod request;
 pub mod response;
 
 pub(crate) mod cluster_metadata;
+
+pub(crate) mod control_record;
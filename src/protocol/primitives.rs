@@ -7,15 +7,29 @@ use crate::Result;
 
 use super::{
     bytes::{FromBytes, ToBytes},
-    cluster_metadata::Batch,
     error::{self, IoError},
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub(crate) enum ApiKey {
     ApiVersions = 18,
     DescribeTopicPartitions = 75,
     Fetch = 1,
+    InitProducerId = 22,
+    AddPartitionsToTxn = 24,
+    AddOffsetsToTxn = 25,
+    EndTxn = 26,
+    TxnOffsetCommit = 28,
+    CreateTopics = 19,
+    DeleteTopics = 20,
+    CreatePartitions = 37,
+    DescribeConfigs = 32,
+    AlterConfigs = 33,
+    IncrementalAlterConfigs = 44,
+    Metadata = 3,
+    DescribeCluster = 60,
+    DescribeLogDirs = 35,
+    UpdateFeatures = 57,
 }
 
 impl ToBytes for ApiKey {
@@ -25,6 +39,21 @@ impl ToBytes for ApiKey {
             ApiKey::ApiVersions => 18_i16,
             ApiKey::DescribeTopicPartitions => 75_i16,
             ApiKey::Fetch => 1_i16,
+            ApiKey::InitProducerId => 22_i16,
+            ApiKey::AddPartitionsToTxn => 24_i16,
+            ApiKey::AddOffsetsToTxn => 25_i16,
+            ApiKey::EndTxn => 26_i16,
+            ApiKey::TxnOffsetCommit => 28_i16,
+            ApiKey::CreateTopics => 19_i16,
+            ApiKey::DeleteTopics => 20_i16,
+            ApiKey::CreatePartitions => 37_i16,
+            ApiKey::DescribeConfigs => 32_i16,
+            ApiKey::AlterConfigs => 33_i16,
+            ApiKey::IncrementalAlterConfigs => 44_i16,
+            ApiKey::Metadata => 3_i16,
+            ApiKey::DescribeCluster => 60_i16,
+            ApiKey::DescribeLogDirs => 35_i16,
+            ApiKey::UpdateFeatures => 57_i16,
         };
 
         buf.put_i16(val);
@@ -32,123 +61,59 @@ impl ToBytes for ApiKey {
     }
 }
 
-impl FromBytes for ApiKey {
-    fn from_be_bytes<B: Buf>(buf: &mut B) -> Result<Self> {
-        let key = buf.try_get_i16()?;
-
-        match key {
-            18 => Ok(ApiKey::ApiVersions),
-            75 => Ok(ApiKey::DescribeTopicPartitions),
-            1 => Ok(ApiKey::Fetch),
-            _ => Err(error::UnsupportedApiKeyError::new(key).into()),
-        }
+impl ApiKey {
+    /// Whether `version` of this API is flexible. The first flexible version
+    /// of each API is generated from its schema by `build.rs`.
+    pub(crate) fn is_flexible(&self, version: i16) -> bool {
+        version >= self.first_flexible_version()
     }
-}
-
-#[derive(Debug, Clone)]
-pub struct NullableString {
-    value: Option<String>,
-}
 
-impl ToBytes for NullableString {
-    fn to_be_bytes(&self) -> Bytes {
-        let mut buf = BytesMut::new();
-        if self.value.is_none() {
-            buf.put_i16(-1);
+    /// Request headers gain tagged fields (v2) in flexible versions.
+    pub(crate) fn request_header_version(&self, version: i16) -> i16 {
+        if self.is_flexible(version) {
+            2
         } else {
-            let value = self.value.as_ref().unwrap();
-            buf.put_i16(value.len() as i16);
-            buf.put_slice(value.as_bytes());
+            1
         }
-
-        buf.freeze()
     }
-}
-
-impl FromBytes for NullableString {
-    fn from_be_bytes<B: Buf>(buf: &mut B) -> Result<Self> {
-        let len = buf.try_get_i16()?;
 
-        if len == -1 {
-            Ok(NullableString { value: None })
+    /// Response headers gain tagged fields (v1) in flexible versions, except
+    /// for ApiVersions whose response a client must read before it knows
+    /// which versions the broker supports.
+    pub(crate) fn response_header_version(&self, version: i16) -> i16 {
+        if self.is_flexible(version) && *self != ApiKey::ApiVersions {
+            1
         } else {
-            let mut str_buf = vec![0u8; len as usize];
-            buf.copy_to_slice(&mut str_buf);
-            let value = String::from_utf8(str_buf)
-                .map_err(|e| IoError::new(format!("failed to parse NullableString: {}", e)))?;
-
-            Ok(NullableString { value: Some(value) })
-        }
-    }
-}
-
-#[derive(Debug, Default, Clone)]
-pub struct CompactString {
-    value: String,
-}
-
-impl CompactString {
-    pub fn from_string(value: String) -> Self {
-        Self { value }
-    }
-
-    pub fn from_str(value: &str) -> Self {
-        Self {
-            value: value.to_string(),
-        }
-    }
-
-    pub fn as_str(&self) -> &str {
-        &self.value
-    }
-}
-
-impl std::fmt::Display for CompactString {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.value)
-    }
-}
-
-impl From<String> for CompactString {
-    fn from(value: String) -> Self {
-        CompactString { value }
-    }
-}
-
-impl ToBytes for CompactString {
-    fn to_be_bytes(&self) -> Bytes {
-        let mut buf = BytesMut::new();
-
-        // Adjust the length to match the protocol
-        let len = UnsignedVarInt::new((self.value.len() + 1) as u32);
-
-        buf.put_slice(len.to_be_bytes().as_ref());
-        if len.value > 0 {
-            buf.put_slice(self.value.as_bytes());
+            0
         }
-
-        buf.freeze()
     }
 }
 
-impl FromBytes for CompactString {
+impl FromBytes for ApiKey {
     fn from_be_bytes<B: Buf>(buf: &mut B) -> Result<Self> {
-        // Adjust the length to match the protocol
-        let len = UnsignedVarInt::from_be_bytes(buf)?.value - 1;
+        let key = buf.try_get_i16()?;
 
-        if len == 0 {
-            return Ok(CompactString {
-                value: String::new(),
-            });
+        match key {
+            18 => Ok(ApiKey::ApiVersions),
+            75 => Ok(ApiKey::DescribeTopicPartitions),
+            1 => Ok(ApiKey::Fetch),
+            22 => Ok(ApiKey::InitProducerId),
+            24 => Ok(ApiKey::AddPartitionsToTxn),
+            25 => Ok(ApiKey::AddOffsetsToTxn),
+            26 => Ok(ApiKey::EndTxn),
+            28 => Ok(ApiKey::TxnOffsetCommit),
+            19 => Ok(ApiKey::CreateTopics),
+            20 => Ok(ApiKey::DeleteTopics),
+            37 => Ok(ApiKey::CreatePartitions),
+            32 => Ok(ApiKey::DescribeConfigs),
+            33 => Ok(ApiKey::AlterConfigs),
+            44 => Ok(ApiKey::IncrementalAlterConfigs),
+            3 => Ok(ApiKey::Metadata),
+            60 => Ok(ApiKey::DescribeCluster),
+            35 => Ok(ApiKey::DescribeLogDirs),
+            57 => Ok(ApiKey::UpdateFeatures),
+            _ => Err(error::UnsupportedApiKeyError::new(key).into()),
         }
-
-        let mut str_buf = vec![0u8; len as usize];
-        buf.copy_to_slice(&mut str_buf);
-
-        let value = String::from_utf8(str_buf)
-            .map_err(|e| IoError::new(format! {"failed to parse CompactString: {}", e}))?;
-
-        Ok(CompactString { value })
     }
 }
 
@@ -158,19 +123,26 @@ pub struct CompactArray<T> {
 }
 
 impl<T> CompactArray<T> {
-    pub fn from_vec(array: Vec<T>) -> Self {
-        Self { array }
-    }
-
     pub fn new() -> Self {
         Self { array: Vec::new() }
     }
 
-    pub fn to_vec(&self) -> Vec<T>
-    where
-        T: Clone,
-    {
-        self.array.clone()
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.array.iter()
+    }
+}
+
+impl<T> Default for CompactArray<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> FromIterator<T> for CompactArray<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self {
+            array: iter.into_iter().collect(),
+        }
     }
 }
 
@@ -181,11 +153,6 @@ where
     fn to_be_bytes(&self) -> Bytes {
         let mut buf = BytesMut::new();
 
-        if self.array.is_empty() {
-            buf.put_u8(0);
-            return buf.freeze();
-        }
-
         // Adjust length to match the protocol
         let len = UnsignedVarInt::new((self.array.len() + 1) as u32);
         buf.put_slice(len.to_be_bytes().as_ref());
@@ -203,95 +170,209 @@ where
     T: FromBytes,
 {
     fn from_be_bytes<B: Buf>(buf: &mut B) -> Result<Self> {
-        let len = UnsignedVarInt::from_be_bytes(buf)?.value;
+        let len = get_array_len(buf, true)?.unwrap_or_default();
+        let array = (0..len)
+            .map(|_| T::from_be_bytes(buf))
+            .collect::<Result<Vec<T>>>()?;
 
-        match len {
-            0 => Ok(CompactArray { array: Vec::new() }),
-            _ => {
-                let len = len - 1; // Adjust length to match the protocol
-                let mut array = Vec::with_capacity(len as usize);
+        Ok(CompactArray { array })
+    }
+}
 
-                for _ in 0..len {
-                    array.push(T::from_be_bytes(buf)?);
-                }
+impl FromBytes for Bytes {
+    fn from_be_bytes<B: Buf>(buf: &mut B) -> Result<Self> {
+        Ok(buf.copy_to_bytes(buf.remaining()))
+    }
+}
 
-                Ok(CompactArray { array })
-            }
-        }
+impl ToBytes for Bytes {
+    fn to_be_bytes(&self) -> Bytes {
+        self.clone()
     }
 }
 
-#[derive(Debug, Default, Clone)]
-pub(crate) struct CompactNullableBytes {
-    bytes: Bytes,
+/// The tagged fields closing a structure in flexible versions: an unsigned
+/// varint count followed by the tag, size and bytes of each field.
+///
+/// Fields a message knows are read out with [`TaggedFields::take`]; the rest
+/// are kept so the structure re-encodes unchanged.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TaggedFields {
+    fields: Vec<(u32, Bytes)>,
 }
 
-impl FromBytes for CompactNullableBytes {
-    fn from_be_bytes<B: Buf>(buf: &mut B) -> Result<Self> {
-        let len = UnsignedVarInt::from_be_bytes(buf)?.value;
+impl TaggedFields {
+    pub fn new() -> Self {
+        Self { fields: Vec::new() }
+    }
 
-        if len == 0 {
-            return Ok(CompactNullableBytes {
-                bytes: Bytes::new(),
-            });
+    /// Removes the field with `tag`, returning its bytes.
+    pub fn take(&mut self, tag: u32) -> Option<Bytes> {
+        let index = self.fields.iter().position(|(field, _)| *field == tag)?;
+
+        Some(self.fields.remove(index).1)
+    }
+
+    /// Sets the field with `tag`, replacing any earlier value.
+    pub fn insert(&mut self, tag: u32, data: Bytes) {
+        match self.fields.iter_mut().find(|(field, _)| *field == tag) {
+            Some((_, value)) => *value = data,
+            None => self.fields.push((tag, data)),
         }
+    }
+}
 
-        let mut bytes = vec![0u8; (len - 1) as usize];
-        buf.copy_to_slice(&mut bytes);
+impl FromBytes for TaggedFields {
+    fn from_be_bytes<B: Buf>(buf: &mut B) -> Result<Self> {
+        let count = UnsignedVarInt::from_be_bytes(buf)?.value;
+
+        let mut fields: Vec<(u32, Bytes)> = Vec::new();
+        for _ in 0..count {
+            let tag = UnsignedVarInt::from_be_bytes(buf)?.value;
+            let size = UnsignedVarInt::from_be_bytes(buf)?.value as usize;
+            if fields.last().is_some_and(|(last, _)| *last >= tag) {
+                return Err(IoError::new(format!("tagged field {} out of order", tag)).into());
+            }
+            if buf.remaining() < size {
+                return Err(IoError::new(format!("truncated tagged field {}", tag)).into());
+            }
 
-        Ok(CompactNullableBytes {
-            bytes: Bytes::from(bytes),
-        })
+            fields.push((tag, buf.copy_to_bytes(size)));
+        }
+
+        Ok(TaggedFields { fields })
     }
 }
 
-impl ToBytes for CompactNullableBytes {
+impl ToBytes for TaggedFields {
     fn to_be_bytes(&self) -> Bytes {
-        let mut buf = BytesMut::new();
+        let mut fields: Vec<&(u32, Bytes)> = self.fields.iter().collect();
+        fields.sort_by_key(|(tag, _)| *tag);
 
-        if self.bytes.is_empty() {
-            buf.put_u8(0);
-            return buf.freeze();
+        let mut buf = BytesMut::new();
+        buf.put_slice(&UnsignedVarInt::new(fields.len() as u32).to_be_bytes());
+        for (tag, data) in fields {
+            buf.put_slice(&UnsignedVarInt::new(*tag).to_be_bytes());
+            buf.put_slice(&UnsignedVarInt::new(data.len() as u32).to_be_bytes());
+            buf.put_slice(data);
         }
 
-        // Adjust length to match the protocol
-        let len = UnsignedVarInt::new((self.bytes.len() + 1) as u32);
-        buf.put_slice(len.to_be_bytes().as_ref());
-        buf.put_slice(&self.bytes);
-
         buf.freeze()
     }
 }
 
-#[derive(Debug, Default, Clone)]
-pub(crate) struct CompactRecords {
-    records: CompactNullableBytes,
+/// Writes the length of an array, or `None` for a null one. Flexible versions
+/// use a compact (length + 1) unsigned varint, older ones an INT32.
+pub(crate) fn put_array_len(buf: &mut BytesMut, len: Option<usize>, flexible: bool) {
+    match (len, flexible) {
+        (Some(len), true) => buf.put_slice(&UnsignedVarInt::new(len as u32 + 1).to_be_bytes()),
+        (None, true) => buf.put_u8(0),
+        (Some(len), false) => buf.put_i32(len as i32),
+        (None, false) => buf.put_i32(-1),
+    }
 }
 
-impl From<Batch> for CompactRecords {
-    fn from(batch: Batch) -> Self {
-        CompactRecords {
-            records: CompactNullableBytes {
-                bytes: batch.to_be_bytes(),
-            },
-        }
+/// Reads an array length written by [`put_array_len`], `None` for a null
+/// array.
+pub(crate) fn get_array_len<B: Buf>(buf: &mut B, flexible: bool) -> Result<Option<usize>> {
+    let len = if flexible {
+        UnsignedVarInt::from_be_bytes(buf)?.value as i64 - 1
+    } else {
+        buf.try_get_i32()? as i64
+    };
+    if len < 0 {
+        return Ok(None);
+    }
+    // Every element takes at least a byte, which bounds what a corrupt
+    // length can make us allocate.
+    if len as usize > buf.remaining() {
+        return Err(IoError::new(format!("array length {} exceeds the request", len)).into());
     }
+
+    Ok(Some(len as usize))
 }
 
-impl From<Bytes> for CompactRecords {
-    fn from(bytes: Bytes) -> Self {
-        CompactRecords {
-            records: CompactNullableBytes { bytes },
-        }
+/// Writes a string, compact in flexible versions.
+pub(crate) fn put_string(buf: &mut BytesMut, value: &str, flexible: bool) {
+    if flexible {
+        buf.put_slice(&UnsignedVarInt::new(value.len() as u32 + 1).to_be_bytes());
+    } else {
+        buf.put_i16(value.len() as i16);
     }
+    buf.put_slice(value.as_bytes());
 }
 
-impl ToBytes for CompactRecords {
-    fn to_be_bytes(&self) -> Bytes {
-        self.records.to_be_bytes()
+/// Writes a string that may be null.
+pub(crate) fn put_nullable_string(buf: &mut BytesMut, value: Option<&str>, flexible: bool) {
+    match (value, flexible) {
+        (Some(value), _) => put_string(buf, value, flexible),
+        (None, true) => buf.put_u8(0),
+        (None, false) => buf.put_i16(-1),
     }
 }
 
+/// Reads a string written by [`put_string`].
+pub(crate) fn get_string<B: Buf>(buf: &mut B, flexible: bool) -> Result<String> {
+    get_nullable_string(buf, flexible)?
+        .ok_or_else(|| IoError::new("unexpected null string".to_string()).into())
+}
+
+/// Reads a string that may be null.
+pub(crate) fn get_nullable_string<B: Buf>(buf: &mut B, flexible: bool) -> Result<Option<String>> {
+    let len = if flexible {
+        UnsignedVarInt::from_be_bytes(buf)?.value as i64 - 1
+    } else {
+        buf.try_get_i16()? as i64
+    };
+    if len < 0 {
+        return Ok(None);
+    }
+    if len as usize > buf.remaining() {
+        return Err(IoError::new(format!("string length {} exceeds the request", len)).into());
+    }
+
+    let bytes = buf.copy_to_bytes(len as usize);
+    String::from_utf8(bytes.to_vec())
+        .map(Some)
+        .map_err(|e| IoError::new(format!("failed to parse string: {}", e)).into())
+}
+
+/// Writes a byte sequence such as a record set, compact in flexible versions.
+pub(crate) fn put_bytes(buf: &mut BytesMut, bytes: &[u8], flexible: bool) {
+    if flexible {
+        buf.put_slice(&UnsignedVarInt::new(bytes.len() as u32 + 1).to_be_bytes());
+    } else {
+        buf.put_i32(bytes.len() as i32);
+    }
+    buf.put_slice(bytes);
+}
+
+/// Writes a byte sequence that may be null.
+pub(crate) fn put_nullable_bytes(buf: &mut BytesMut, bytes: Option<&[u8]>, flexible: bool) {
+    match (bytes, flexible) {
+        (Some(bytes), _) => put_bytes(buf, bytes, flexible),
+        (None, true) => buf.put_u8(0),
+        (None, false) => buf.put_i32(-1),
+    }
+}
+
+/// Reads a byte sequence that may be null.
+pub(crate) fn get_nullable_bytes<B: Buf>(buf: &mut B, flexible: bool) -> Result<Option<Bytes>> {
+    let len = if flexible {
+        UnsignedVarInt::from_be_bytes(buf)?.value as i64 - 1
+    } else {
+        buf.try_get_i32()? as i64
+    };
+    if len < 0 {
+        return Ok(None);
+    }
+    if len as usize > buf.remaining() {
+        return Err(IoError::new(format!("bytes length {} exceeds the request", len)).into());
+    }
+
+    Ok(Some(buf.copy_to_bytes(len as usize)))
+}
+
 // VarInt encoding/decoding follows the variable-length zig-zag encoding scheme
 // from Google Protocol Buffers.
 #[derive(Debug, Default, Clone)]
@@ -426,32 +507,6 @@ impl ToBytes for UnsignedVarInt {
     }
 }
 
-#[derive(Debug, Clone)]
-pub(crate) struct INT16 {
-    value: i16,
-}
-
-impl From<i16> for INT16 {
-    fn from(value: i16) -> Self {
-        INT16 { value }
-    }
-}
-
-impl FromBytes for INT16 {
-    fn from_be_bytes<B: Buf>(buf: &mut B) -> Result<Self> {
-        let value = buf.try_get_i16()?;
-        Ok(INT16 { value })
-    }
-}
-
-impl ToBytes for INT16 {
-    fn to_be_bytes(&self) -> Bytes {
-        let mut buf = BytesMut::with_capacity(2);
-        buf.put_i16(self.value);
-        buf.freeze()
-    }
-}
-
 #[derive(Debug, Clone)]
 pub(crate) struct INT32 {
     value: i32,
@@ -511,53 +566,3 @@ impl ToBytes for INT32 {
         buf.freeze()
     }
 }
-
-#[derive(Debug, Clone)]
-pub(crate) struct INT64 {
-    value: i64,
-}
-
-impl INT64 {
-    pub(crate) fn value(&self) -> i64 {
-        self.value
-    }
-}
-
-impl From<i64> for INT64 {
-    fn from(value: i64) -> Self {
-        INT64 { value }
-    }
-}
-
-impl std::fmt::Display for INT64 {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "INT64({})", self.value)
-    }
-}
-
-impl<R: Read> TryFrom<&mut BufReader<R>> for INT64 {
-    type Error = std::io::Error;
-
-    fn try_from(reader: &mut BufReader<R>) -> std::result::Result<Self, Self::Error> {
-        let mut buf = [0u8; 8];
-        reader.read_exact(&mut buf)?;
-        let value = i64::from_be_bytes(buf);
-        Ok(INT64 { value })
-    }
-}
-
-impl FromBytes for INT64 {
-    fn from_be_bytes<B: Buf>(buf: &mut B) -> Result<Self> {
-        let value = buf.try_get_i64()?;
-        Ok(INT64 { value })
-    }
-}
-
-impl ToBytes for INT64 {
-    fn to_be_bytes(&self) -> Bytes {
-        let mut buf = BytesMut::with_capacity(4);
-        buf.put_i64(self.value);
-
-        buf.freeze()
-    }
-}
@@ -3,20 +3,51 @@ use bytes::{Buf, BufMut, Bytes, BytesMut};
 use crate::Result;
 
 use super::{
-    bytes::{FromBytes, ToBytes},
-    primitives::{ApiKey, CompactArray, CompactString, NullableString},
+    bytes::{FromBytes, FromVersionedBytes, ToBytes, ToVersionedBytes},
+    messages::{
+        AddOffsetsToTxnRequest, AddPartitionsToTxnRequest, AlterConfigsRequest, ApiVersionsRequest,
+        CreatePartitionsRequest, CreateTopicsRequest, DeleteTopicsRequest, DescribeClusterRequest,
+        DescribeConfigsRequest, DescribeLogDirsRequest, DescribeTopicPartitionsRequest,
+        EndTxnRequest, FetchRequest, IncrementalAlterConfigsRequest, InitProducerIdRequest,
+        MetadataRequest, TxnOffsetCommitRequest, UpdateFeaturesRequest,
+    },
+    primitives::{get_nullable_string, put_nullable_string, ApiKey, TaggedFields},
 };
 
 #[derive(Debug)]
-pub struct RequestHeaderV2 {
+pub struct RequestHeader {
     request_api_key: ApiKey,
     request_api_version: i16,
     correlation_id: i32,
-    client_id: NullableString,
-    tag: CompactArray<NullableString>,
+    client_id: Option<String>,
+    /// Only v2 headers, sent with flexible versions, have tagged fields.
+    tag: Option<TaggedFields>,
 }
 
-impl RequestHeaderV2 {
+impl RequestHeader {
+    /// The header of a request for `api_key` in `api_version`, with tagged
+    /// fields when the version is flexible.
+    #[cfg(test)]
+    pub fn new(
+        request_api_key: ApiKey,
+        request_api_version: i16,
+        correlation_id: i32,
+        client_id: Option<&str>,
+    ) -> Self {
+        let tag = match request_api_key.request_header_version(request_api_version) {
+            2 => Some(TaggedFields::new()),
+            _ => None,
+        };
+
+        Self {
+            request_api_key,
+            request_api_version,
+            correlation_id,
+            client_id: client_id.map(str::to_string),
+            tag,
+        }
+    }
+
     pub fn request_api_version(&self) -> i16 {
         self.request_api_version
     }
@@ -30,22 +61,23 @@ impl RequestHeaderV2 {
     }
 }
 
-impl ToBytes for RequestHeaderV2 {
+impl ToBytes for RequestHeader {
     fn to_be_bytes(&self) -> Bytes {
-        use bytes::BufMut;
-
         let mut buf = BytesMut::new();
 
         buf.extend_from_slice(&self.request_api_key.to_be_bytes());
         buf.put_i16(self.request_api_version);
         buf.put_i32(self.correlation_id);
-        buf.extend_from_slice(&self.client_id.to_be_bytes());
-        buf.extend_from_slice(&self.tag.to_be_bytes());
+        put_nullable_string(&mut buf, self.client_id.as_deref(), false);
+        if let Some(tag) = &self.tag {
+            buf.extend_from_slice(&tag.to_be_bytes());
+        }
 
         buf.freeze()
     }
 }
-impl FromBytes for RequestHeaderV2 {
+
+impl FromBytes for RequestHeader {
     fn from_be_bytes<B: Buf>(mut buf: &mut B) -> Result<Self> {
         let request_api_key = ApiKey::from_be_bytes(&mut buf)
             .map_err(|e| anyhow::anyhow!("failed to parse request_api_key: {}", e))?;
@@ -58,16 +90,14 @@ impl FromBytes for RequestHeaderV2 {
             .try_get_i32()
             .map_err(|e| anyhow::anyhow!("failed to parse i32 for correlation_id: {}", e))?;
 
-        let client_id = NullableString::from_be_bytes(&mut buf)
+        let client_id = get_nullable_string(buf, false)
             .map_err(|e| anyhow::anyhow!("failed to parse NullableString for client_id: {}", e))?;
-        let tag = CompactArray::<NullableString>::from_be_bytes(&mut buf).map_err(|e| {
-            anyhow::anyhow!(
-                "failed to parse CompactArray<NullableString> for tag: {}",
-                e
-            )
-        })?;
-
-        Ok(RequestHeaderV2 {
+        let tag = match request_api_key.request_header_version(request_api_version) {
+            2 => Some(parse_tag(buf)?),
+            _ => None,
+        };
+
+        Ok(RequestHeader {
             request_api_key,
             request_api_version,
             correlation_id,
@@ -77,422 +107,414 @@ impl FromBytes for RequestHeaderV2 {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum RequestBody {
-    ApiVersionsRequestV4(ApiVersionsRequestV4),
-    DescribeTopicPartitionsRequestV0(DescribeTopicPartitionsRequestV0),
-    FetchRequestV16(FetchRequestV16),
+    ApiVersions(ApiVersionsRequest),
+    DescribeTopicPartitions(DescribeTopicPartitionsRequest),
+    Fetch(FetchRequest),
+    AddPartitionsToTxn(AddPartitionsToTxnRequest),
+    AddOffsetsToTxn(AddOffsetsToTxnRequest),
+    EndTxn(EndTxnRequest),
+    InitProducerId(InitProducerIdRequest),
+    TxnOffsetCommit(TxnOffsetCommitRequest),
+    CreateTopics(CreateTopicsRequest),
+    DeleteTopics(DeleteTopicsRequest),
+    CreatePartitions(CreatePartitionsRequest),
+    DescribeConfigs(DescribeConfigsRequest),
+    AlterConfigs(AlterConfigsRequest),
+    IncrementalAlterConfigs(IncrementalAlterConfigsRequest),
+    Metadata(MetadataRequest),
+    DescribeCluster(DescribeClusterRequest),
+    DescribeLogDirs(DescribeLogDirsRequest),
+    UpdateFeatures(UpdateFeaturesRequest),
 }
 
 impl RequestBody {
-    pub fn as_describe_topic_partitions_request_v0(
-        &self,
-    ) -> Option<&DescribeTopicPartitionsRequestV0> {
-        if let Self::DescribeTopicPartitionsRequestV0(v) = self {
+    #[cfg(test)]
+    pub fn api_key(&self) -> ApiKey {
+        match self {
+            RequestBody::ApiVersions(_) => ApiKey::ApiVersions,
+            RequestBody::DescribeTopicPartitions(_) => ApiKey::DescribeTopicPartitions,
+            RequestBody::Fetch(_) => ApiKey::Fetch,
+            RequestBody::AddPartitionsToTxn(_) => ApiKey::AddPartitionsToTxn,
+            RequestBody::AddOffsetsToTxn(_) => ApiKey::AddOffsetsToTxn,
+            RequestBody::EndTxn(_) => ApiKey::EndTxn,
+            RequestBody::InitProducerId(_) => ApiKey::InitProducerId,
+            RequestBody::TxnOffsetCommit(_) => ApiKey::TxnOffsetCommit,
+            RequestBody::CreateTopics(_) => ApiKey::CreateTopics,
+            RequestBody::DeleteTopics(_) => ApiKey::DeleteTopics,
+            RequestBody::CreatePartitions(_) => ApiKey::CreatePartitions,
+            RequestBody::DescribeConfigs(_) => ApiKey::DescribeConfigs,
+            RequestBody::AlterConfigs(_) => ApiKey::AlterConfigs,
+            RequestBody::IncrementalAlterConfigs(_) => ApiKey::IncrementalAlterConfigs,
+            RequestBody::Metadata(_) => ApiKey::Metadata,
+            RequestBody::DescribeCluster(_) => ApiKey::DescribeCluster,
+            RequestBody::DescribeLogDirs(_) => ApiKey::DescribeLogDirs,
+            RequestBody::UpdateFeatures(_) => ApiKey::UpdateFeatures,
+        }
+    }
+
+    /// A default body for `api_key`, standing in for a request in a version
+    /// that can't be decoded.
+    pub fn empty(api_key: ApiKey) -> Self {
+        match api_key {
+            ApiKey::ApiVersions => RequestBody::ApiVersions(ApiVersionsRequest::default()),
+            ApiKey::DescribeTopicPartitions => {
+                RequestBody::DescribeTopicPartitions(DescribeTopicPartitionsRequest::default())
+            }
+            ApiKey::Fetch => RequestBody::Fetch(FetchRequest::default()),
+            ApiKey::AddPartitionsToTxn => {
+                RequestBody::AddPartitionsToTxn(AddPartitionsToTxnRequest::default())
+            }
+            ApiKey::AddOffsetsToTxn => {
+                RequestBody::AddOffsetsToTxn(AddOffsetsToTxnRequest::default())
+            }
+            ApiKey::EndTxn => RequestBody::EndTxn(EndTxnRequest::default()),
+            ApiKey::InitProducerId => RequestBody::InitProducerId(InitProducerIdRequest::default()),
+            ApiKey::TxnOffsetCommit => {
+                RequestBody::TxnOffsetCommit(TxnOffsetCommitRequest::default())
+            }
+            ApiKey::CreateTopics => RequestBody::CreateTopics(CreateTopicsRequest::default()),
+            ApiKey::DeleteTopics => RequestBody::DeleteTopics(DeleteTopicsRequest::default()),
+            ApiKey::CreatePartitions => {
+                RequestBody::CreatePartitions(CreatePartitionsRequest::default())
+            }
+            ApiKey::DescribeConfigs => {
+                RequestBody::DescribeConfigs(DescribeConfigsRequest::default())
+            }
+            ApiKey::AlterConfigs => RequestBody::AlterConfigs(AlterConfigsRequest::default()),
+            ApiKey::IncrementalAlterConfigs => {
+                RequestBody::IncrementalAlterConfigs(IncrementalAlterConfigsRequest::default())
+            }
+            ApiKey::Metadata => RequestBody::Metadata(MetadataRequest::default()),
+            ApiKey::DescribeCluster => {
+                RequestBody::DescribeCluster(DescribeClusterRequest::default())
+            }
+            ApiKey::DescribeLogDirs => {
+                RequestBody::DescribeLogDirs(DescribeLogDirsRequest::default())
+            }
+            ApiKey::UpdateFeatures => RequestBody::UpdateFeatures(UpdateFeaturesRequest::default()),
+        }
+    }
+
+    /// Decodes the body of a request for `api_key` in `version`.
+    pub fn decode<B: Buf>(buf: &mut B, api_key: ApiKey, version: i16) -> Result<Self> {
+        match api_key {
+            ApiKey::ApiVersions => Ok(RequestBody::ApiVersions(
+                ApiVersionsRequest::from_versioned_be_bytes(buf, version)
+                    .map_err(|e| anyhow::anyhow!("failed to parse ApiVersionsRequest: {}", e))?,
+            )),
+            ApiKey::DescribeTopicPartitions => Ok(RequestBody::DescribeTopicPartitions(
+                DescribeTopicPartitionsRequest::from_versioned_be_bytes(buf, version).map_err(
+                    |e| anyhow::anyhow!("failed to parse DescribeTopicPartitionsRequest: {}", e),
+                )?,
+            )),
+            ApiKey::Fetch => Ok(RequestBody::Fetch(
+                FetchRequest::from_versioned_be_bytes(buf, version)
+                    .map_err(|e| anyhow::anyhow!("failed to parse FetchRequest: {}", e))?,
+            )),
+            ApiKey::AddPartitionsToTxn => Ok(RequestBody::AddPartitionsToTxn(
+                AddPartitionsToTxnRequest::from_versioned_be_bytes(buf, version).map_err(|e| {
+                    anyhow::anyhow!("failed to parse AddPartitionsToTxnRequest: {}", e)
+                })?,
+            )),
+            ApiKey::AddOffsetsToTxn => Ok(RequestBody::AddOffsetsToTxn(
+                AddOffsetsToTxnRequest::from_versioned_be_bytes(buf, version).map_err(|e| {
+                    anyhow::anyhow!("failed to parse AddOffsetsToTxnRequest: {}", e)
+                })?,
+            )),
+            ApiKey::EndTxn => Ok(RequestBody::EndTxn(
+                EndTxnRequest::from_versioned_be_bytes(buf, version)
+                    .map_err(|e| anyhow::anyhow!("failed to parse EndTxnRequest: {}", e))?,
+            )),
+            ApiKey::InitProducerId => Ok(RequestBody::InitProducerId(
+                InitProducerIdRequest::from_versioned_be_bytes(buf, version)
+                    .map_err(|e| anyhow::anyhow!("failed to parse InitProducerIdRequest: {}", e))?,
+            )),
+            ApiKey::TxnOffsetCommit => Ok(RequestBody::TxnOffsetCommit(
+                TxnOffsetCommitRequest::from_versioned_be_bytes(buf, version).map_err(|e| {
+                    anyhow::anyhow!("failed to parse TxnOffsetCommitRequest: {}", e)
+                })?,
+            )),
+            ApiKey::CreateTopics => Ok(RequestBody::CreateTopics(
+                CreateTopicsRequest::from_versioned_be_bytes(buf, version)
+                    .map_err(|e| anyhow::anyhow!("failed to parse CreateTopicsRequest: {}", e))?,
+            )),
+            ApiKey::DeleteTopics => Ok(RequestBody::DeleteTopics(
+                DeleteTopicsRequest::from_versioned_be_bytes(buf, version)
+                    .map_err(|e| anyhow::anyhow!("failed to parse DeleteTopicsRequest: {}", e))?,
+            )),
+            ApiKey::CreatePartitions => Ok(RequestBody::CreatePartitions(
+                CreatePartitionsRequest::from_versioned_be_bytes(buf, version).map_err(|e| {
+                    anyhow::anyhow!("failed to parse CreatePartitionsRequest: {}", e)
+                })?,
+            )),
+            ApiKey::DescribeConfigs => Ok(RequestBody::DescribeConfigs(
+                DescribeConfigsRequest::from_versioned_be_bytes(buf, version).map_err(|e| {
+                    anyhow::anyhow!("failed to parse DescribeConfigsRequest: {}", e)
+                })?,
+            )),
+            ApiKey::AlterConfigs => Ok(RequestBody::AlterConfigs(
+                AlterConfigsRequest::from_versioned_be_bytes(buf, version)
+                    .map_err(|e| anyhow::anyhow!("failed to parse AlterConfigsRequest: {}", e))?,
+            )),
+            ApiKey::IncrementalAlterConfigs => Ok(RequestBody::IncrementalAlterConfigs(
+                IncrementalAlterConfigsRequest::from_versioned_be_bytes(buf, version).map_err(
+                    |e| anyhow::anyhow!("failed to parse IncrementalAlterConfigsRequest: {}", e),
+                )?,
+            )),
+            ApiKey::Metadata => Ok(RequestBody::Metadata(
+                MetadataRequest::from_versioned_be_bytes(buf, version)
+                    .map_err(|e| anyhow::anyhow!("failed to parse MetadataRequest: {}", e))?,
+            )),
+            ApiKey::DescribeCluster => Ok(RequestBody::DescribeCluster(
+                DescribeClusterRequest::from_versioned_be_bytes(buf, version).map_err(|e| {
+                    anyhow::anyhow!("failed to parse DescribeClusterRequest: {}", e)
+                })?,
+            )),
+            ApiKey::DescribeLogDirs => Ok(RequestBody::DescribeLogDirs(
+                DescribeLogDirsRequest::from_versioned_be_bytes(buf, version).map_err(|e| {
+                    anyhow::anyhow!("failed to parse DescribeLogDirsRequest: {}", e)
+                })?,
+            )),
+            ApiKey::UpdateFeatures => Ok(RequestBody::UpdateFeatures(
+                UpdateFeaturesRequest::from_versioned_be_bytes(buf, version)
+                    .map_err(|e| anyhow::anyhow!("failed to parse UpdateFeaturesRequest: {}", e))?,
+            )),
+        }
+    }
+
+    pub fn as_describe_topic_partitions_request(&self) -> Option<&DescribeTopicPartitionsRequest> {
+        if let Self::DescribeTopicPartitions(v) = self {
             Some(v)
         } else {
             None
         }
     }
 
-    pub fn as_fetch_request_v16(&self) -> Option<&FetchRequestV16> {
-        if let Self::FetchRequestV16(v) = self {
+    pub fn as_fetch_request(&self) -> Option<&FetchRequest> {
+        if let Self::Fetch(v) = self {
             Some(v)
         } else {
             None
         }
     }
-}
-
-#[derive(Debug)]
-pub struct RequestV0 {
-    message_size: i32,
-    header: RequestHeaderV2,
-    body: RequestBody,
-}
 
-impl RequestV0 {
-    pub fn header(&self) -> &RequestHeaderV2 {
-        &self.header
+    pub fn as_add_partitions_to_txn_request(&self) -> Option<&AddPartitionsToTxnRequest> {
+        if let Self::AddPartitionsToTxn(v) = self {
+            Some(v)
+        } else {
+            None
+        }
     }
 
-    pub fn body(&self) -> &RequestBody {
-        &self.body
+    pub fn as_add_offsets_to_txn_request(&self) -> Option<&AddOffsetsToTxnRequest> {
+        if let Self::AddOffsetsToTxn(v) = self {
+            Some(v)
+        } else {
+            None
+        }
     }
-}
-
-impl ToBytes for RequestV0 {
-    fn to_be_bytes(&self) -> Bytes {
-        let mut buf = BytesMut::new();
-
-        buf.put_i32(self.message_size);
-        buf.extend_from_slice(&self.header.to_be_bytes());
 
-        buf.freeze()
+    pub fn as_end_txn_request(&self) -> Option<&EndTxnRequest> {
+        if let Self::EndTxn(v) = self {
+            Some(v)
+        } else {
+            None
+        }
     }
-}
-
-impl FromBytes for RequestV0 {
-    fn from_be_bytes<B: bytes::Buf>(mut buf: &mut B) -> Result<Self> {
-        let message_size = buf
-            .try_get_i32()
-            .map_err(|e| anyhow::anyhow!("failed to parse i32 for message_size: {}", e))?;
 
-        let header = RequestHeaderV2::from_be_bytes(&mut buf)
-            .map_err(|e| anyhow::anyhow!("failed to parse RequestHeaderV2: {}", e))?;
-
-        let body = match header.request_api_key {
-            ApiKey::ApiVersions => RequestBody::ApiVersionsRequestV4(
-                ApiVersionsRequestV4::from_be_bytes(&mut buf)
-                    .map_err(|e| anyhow::anyhow!("failed to parse ApiVersionsRequestV4: {}", e))?,
-            ),
-            ApiKey::DescribeTopicPartitions => RequestBody::DescribeTopicPartitionsRequestV0(
-                DescribeTopicPartitionsRequestV0::from_be_bytes(&mut buf).map_err(|e| {
-                    anyhow::anyhow!("failed to parse DescribeTopicPartitionsRequestV0: {}", e)
-                })?,
-            ),
-            ApiKey::Fetch => RequestBody::FetchRequestV16(
-                FetchRequestV16::from_be_bytes(&mut buf)
-                    .map_err(|e| anyhow::anyhow!("failed to parse FetchRequestV4: {}", e))?,
-            ),
-        };
-
-        Ok(RequestV0 {
-            message_size,
-            header,
-            body,
-        })
+    pub fn as_init_producer_id_request(&self) -> Option<&InitProducerIdRequest> {
+        if let Self::InitProducerId(v) = self {
+            Some(v)
+        } else {
+            None
+        }
     }
-}
-
-#[derive(Debug)]
-pub struct ApiVersionsRequestV4 {
-    client_software_name: CompactString,
-    client_software_version: CompactString,
-    tag: CompactArray<NullableString>,
-}
 
-impl FromBytes for ApiVersionsRequestV4 {
-    fn from_be_bytes<B: bytes::Buf>(buf: &mut B) -> Result<Self> {
-        let client_software_name = CompactString::from_be_bytes(buf).map_err(|e| {
-            anyhow::anyhow!(
-                "failed to parse CompactString for client_software_name: {}",
-                e
-            )
-        })?;
-        let client_software_version = CompactString::from_be_bytes(buf).map_err(|e| {
-            anyhow::anyhow!(
-                "failed to parse CompactString for client_software_version: {}",
-                e
-            )
-        })?;
-        let tag = CompactArray::<NullableString>::from_be_bytes(buf).map_err(|e| {
-            anyhow::anyhow!(
-                "failed to parse CompactArray<NullableString> for tag: {}",
-                e
-            )
-        })?;
-
-        Ok(ApiVersionsRequestV4 {
-            client_software_name,
-            client_software_version,
-            tag,
-        })
+    pub fn as_txn_offset_commit_request(&self) -> Option<&TxnOffsetCommitRequest> {
+        if let Self::TxnOffsetCommit(v) = self {
+            Some(v)
+        } else {
+            None
+        }
     }
-}
-
-#[derive(Debug)]
-pub struct DescribeTopicPartitionsRequestV0 {
-    topics: CompactArray<Topic>,
-    response_partiotion_limit: i32,
-    cursor: u8,
-    tag: CompactArray<NullableString>,
-}
 
-impl DescribeTopicPartitionsRequestV0 {
-    pub fn topics(&self) -> &CompactArray<Topic> {
-        &self.topics
+    pub fn as_create_topics_request(&self) -> Option<&CreateTopicsRequest> {
+        if let Self::CreateTopics(v) = self {
+            Some(v)
+        } else {
+            None
+        }
     }
 
-    pub fn topic_names(&self) -> Vec<String> {
-        self.topics
-            .to_vec()
-            .iter()
-            .map(|topic| topic.topic().to_string())
-            .collect()
+    pub fn as_delete_topics_request(&self) -> Option<&DeleteTopicsRequest> {
+        if let Self::DeleteTopics(v) = self {
+            Some(v)
+        } else {
+            None
+        }
     }
-}
 
-impl FromBytes for DescribeTopicPartitionsRequestV0 {
-    fn from_be_bytes<B: bytes::Buf>(buf: &mut B) -> Result<Self> {
-        let topics = CompactArray::<Topic>::from_be_bytes(buf).map_err(|e| {
-            anyhow::anyhow!("failed to parse CompactArray<Topic> for topics: {}", e)
-        })?;
-
-        let response_partition_limit = buf.try_get_i32().map_err(|e| {
-            anyhow::anyhow!("failed to parse i32 for response_partition_limit: {}", e)
-        })?;
-
-        let cursor = buf
-            .try_get_u8()
-            .map_err(|e| anyhow::anyhow!("failed to parse u8 for cursor: {}", e))?;
-
-        let tag = CompactArray::<NullableString>::from_be_bytes(buf).map_err(|e| {
-            anyhow::anyhow!(
-                "failed to parse CompactArray<NullableString> for tag: {}",
-                e
-            )
-        })?;
-
-        Ok(DescribeTopicPartitionsRequestV0 {
-            topics,
-            response_partiotion_limit: response_partition_limit,
-            cursor,
-            tag,
-        })
+    pub fn as_create_partitions_request(&self) -> Option<&CreatePartitionsRequest> {
+        if let Self::CreatePartitions(v) = self {
+            Some(v)
+        } else {
+            None
+        }
     }
-}
 
-impl Default for DescribeTopicPartitionsRequestV0 {
-    fn default() -> Self {
-        DescribeTopicPartitionsRequestV0 {
-            topics: CompactArray::new(),
-            response_partiotion_limit: 0,
-            cursor: u8::MAX,
-            tag: CompactArray::new(),
+    pub fn as_describe_configs_request(&self) -> Option<&DescribeConfigsRequest> {
+        if let Self::DescribeConfigs(v) = self {
+            Some(v)
+        } else {
+            None
         }
     }
-}
-
-#[derive(Debug, Clone)]
-pub struct Topic {
-    topic: CompactString,
-    tag: CompactArray<NullableString>,
-}
 
-impl Topic {
-    pub fn topic(&self) -> &str {
-        &self.topic.as_str()
+    pub fn as_alter_configs_request(&self) -> Option<&AlterConfigsRequest> {
+        if let Self::AlterConfigs(v) = self {
+            Some(v)
+        } else {
+            None
+        }
     }
-}
 
-impl FromBytes for Topic {
-    fn from_be_bytes<B: bytes::Buf>(buf: &mut B) -> Result<Self> {
-        let topic = CompactString::from_be_bytes(buf)
-            .map_err(|e| anyhow::anyhow!("failed to parse CompactString for topic: {}", e))?;
-        let tag = CompactArray::<NullableString>::from_be_bytes(buf).map_err(|e| {
-            anyhow::anyhow!(
-                "failed to parse CompactArray<NullableString> for tag: {}",
-                e
-            )
-        })?;
-
-        Ok(Topic { topic, tag })
+    pub fn as_incremental_alter_configs_request(&self) -> Option<&IncrementalAlterConfigsRequest> {
+        if let Self::IncrementalAlterConfigs(v) = self {
+            Some(v)
+        } else {
+            None
+        }
     }
-}
-
-#[derive(Debug, Clone)]
-pub struct FetchRequestV16 {
-    max_wait_ms: i32,
-    min_bytes: i32,
-    max_bytes: i32,
-    isolation_level: i8,
-    session_id: i32,
-    session_epoch: i32,
-    topics: CompactArray<TopicsPartitions>,
-    forgotten_topics: CompactArray<ForgottenTopic>,
-    rack_id: CompactString,
-}
 
-impl FetchRequestV16 {
-    pub fn topics(&self) -> &CompactArray<TopicsPartitions> {
-        &self.topics
+    pub fn as_metadata_request(&self) -> Option<&MetadataRequest> {
+        if let Self::Metadata(v) = self {
+            Some(v)
+        } else {
+            None
+        }
     }
-}
 
-impl Default for FetchRequestV16 {
-    fn default() -> Self {
-        FetchRequestV16 {
-            max_wait_ms: 500,
-            min_bytes: 1,
-            max_bytes: 1048576,
-            isolation_level: 0,
-            session_id: -1,
-            session_epoch: -1,
-            topics: CompactArray::new(),
-            forgotten_topics: CompactArray::new(),
-            rack_id: CompactString::default(),
+    pub fn as_describe_cluster_request(&self) -> Option<&DescribeClusterRequest> {
+        if let Self::DescribeCluster(v) = self {
+            Some(v)
+        } else {
+            None
         }
     }
-}
-
-impl FromBytes for FetchRequestV16 {
-    fn from_be_bytes<B: bytes::Buf>(buf: &mut B) -> Result<Self> {
-        let max_wait_ms = buf
-            .try_get_i32()
-            .map_err(|e| anyhow::anyhow!("failed to parse i32 for max_wait_ms: {}", e))?;
-
-        let min_bytes = buf
-            .try_get_i32()
-            .map_err(|e| anyhow::anyhow!("failed to parse i32 for min_bytes: {}", e))?;
-
-        let max_bytes = buf
-            .try_get_i32()
-            .map_err(|e| anyhow::anyhow!("failed to parse i32 for max_bytes: {}", e))?;
-
-        let isolation_level = buf
-            .try_get_i8()
-            .map_err(|e| anyhow::anyhow!("failed to parse i8 for isolation_level: {}", e))?;
-
-        let session_id = buf
-            .try_get_i32()
-            .map_err(|e| anyhow::anyhow!("failed to parse i32 for session_id: {}", e))?;
 
-        let session_epoch = buf
-            .try_get_i32()
-            .map_err(|e| anyhow::anyhow!("failed to parse i32 for session_epoch: {}", e))?;
-
-        let topics = CompactArray::<TopicsPartitions>::from_be_bytes(buf).map_err(|e| {
-            anyhow::anyhow!("failed to parse CompactArray<TopicsPartitions>: {}", e)
-        })?;
-
-        let forgotten_topics = CompactArray::<ForgottenTopic>::from_be_bytes(buf)
-            .map_err(|e| anyhow::anyhow!("failed to parse CompactArray<ForgottenTopic>: {}", e))?;
-
-        let rack_id = CompactString::from_be_bytes(buf)
-            .map_err(|e| anyhow::anyhow!("failed to parse CompactString for rack_id: {}", e))?;
-
-        Ok(FetchRequestV16 {
-            max_wait_ms,
-            min_bytes,
-            max_bytes,
-            isolation_level,
-            session_id,
-            session_epoch,
-            topics,
-            forgotten_topics,
-            rack_id,
-        })
+    pub fn as_describe_log_dirs_request(&self) -> Option<&DescribeLogDirsRequest> {
+        if let Self::DescribeLogDirs(v) = self {
+            Some(v)
+        } else {
+            None
+        }
     }
-}
-
-#[derive(Debug, Clone)]
-pub struct TopicsPartitions {
-    topic_id: uuid::Uuid,
-    partitions: CompactArray<Partition>,
-    tag: CompactArray<NullableString>,
-}
 
-impl TopicsPartitions {
-    pub fn topic_id(&self) -> uuid::Uuid {
-        self.topic_id
+    pub fn as_update_features_request(&self) -> Option<&UpdateFeaturesRequest> {
+        if let Self::UpdateFeatures(v) = self {
+            Some(v)
+        } else {
+            None
+        }
     }
 }
 
-impl Default for TopicsPartitions {
-    fn default() -> Self {
-        TopicsPartitions {
-            topic_id: uuid::Uuid::nil(),
-            partitions: CompactArray::new(),
-            tag: CompactArray::new(),
+impl ToVersionedBytes for RequestBody {
+    fn to_versioned_be_bytes(&self, version: i16) -> Bytes {
+        match self {
+            RequestBody::ApiVersions(body) => body.to_versioned_be_bytes(version),
+            RequestBody::DescribeTopicPartitions(body) => body.to_versioned_be_bytes(version),
+            RequestBody::Fetch(body) => body.to_versioned_be_bytes(version),
+            RequestBody::AddPartitionsToTxn(body) => body.to_versioned_be_bytes(version),
+            RequestBody::AddOffsetsToTxn(body) => body.to_versioned_be_bytes(version),
+            RequestBody::EndTxn(body) => body.to_versioned_be_bytes(version),
+            RequestBody::InitProducerId(body) => body.to_versioned_be_bytes(version),
+            RequestBody::TxnOffsetCommit(body) => body.to_versioned_be_bytes(version),
+            RequestBody::CreateTopics(body) => body.to_versioned_be_bytes(version),
+            RequestBody::DeleteTopics(body) => body.to_versioned_be_bytes(version),
+            RequestBody::CreatePartitions(body) => body.to_versioned_be_bytes(version),
+            RequestBody::DescribeConfigs(body) => body.to_versioned_be_bytes(version),
+            RequestBody::AlterConfigs(body) => body.to_versioned_be_bytes(version),
+            RequestBody::IncrementalAlterConfigs(body) => body.to_versioned_be_bytes(version),
+            RequestBody::Metadata(body) => body.to_versioned_be_bytes(version),
+            RequestBody::DescribeCluster(body) => body.to_versioned_be_bytes(version),
+            RequestBody::DescribeLogDirs(body) => body.to_versioned_be_bytes(version),
+            RequestBody::UpdateFeatures(body) => body.to_versioned_be_bytes(version),
         }
     }
 }
 
-impl FromBytes for TopicsPartitions {
-    fn from_be_bytes<B: bytes::Buf>(buf: &mut B) -> Result<Self> {
-        let mut buf16 = [0u8; 16];
-        buf.copy_to_slice(&mut buf16);
-
-        let topic_id = uuid::Uuid::from_slice(&buf16)
-            .map_err(|e| anyhow::anyhow!("failed to parse Uuid for topic_id: {}", e))?;
-
-        let partitions = CompactArray::<Partition>::from_be_bytes(buf)
-            .map_err(|e| anyhow::anyhow!("failed to parse CompactArray<Partition>: {}", e))?;
+#[derive(Debug)]
+pub struct RequestV0 {
+    header: RequestHeader,
+    body: RequestBody,
+}
 
-        let tag = CompactArray::<NullableString>::from_be_bytes(buf).map_err(|e| {
-            anyhow::anyhow!(
-                "failed to parse CompactArray<NullableString> for tag: {}",
-                e
-            )
-        })?;
+impl RequestV0 {
+    pub fn new(header: RequestHeader, body: RequestBody) -> Self {
+        Self { header, body }
+    }
 
-        Ok(TopicsPartitions {
-            topic_id,
-            partitions,
-            tag,
-        })
+    pub fn header(&self) -> &RequestHeader {
+        &self.header
     }
-}
 
-#[derive(Debug, Clone)]
-pub struct Partition {
-    partition: i32,
-    current_leader_epoch: i32,
-    fetch_offset: i64,
-    last_fetched_epoch: i32,
-    log_start_offset: i64,
-    partition_max_bytes: i32,
+    pub fn body(&self) -> &RequestBody {
+        &self.body
+    }
 }
 
-impl FromBytes for Partition {
-    fn from_be_bytes<B: bytes::Buf>(buf: &mut B) -> Result<Self> {
-        let partition = buf
-            .try_get_i32()
-            .map_err(|e| anyhow::anyhow!("failed to parse i32 for partition: {}", e))?;
+/// The body is encoded in the version named by the header.
+impl ToBytes for RequestV0 {
+    fn to_be_bytes(&self) -> Bytes {
+        let header = self.header.to_be_bytes();
+        let body = self
+            .body
+            .to_versioned_be_bytes(self.header.request_api_version);
+        let mut buf = BytesMut::with_capacity(4 + header.len() + body.len());
 
-        let current_leader_epoch = buf
-            .try_get_i32()
-            .map_err(|e| anyhow::anyhow!("failed to parse i32 for current_leader_epoch: {}", e))?;
+        buf.put_i32((header.len() + body.len()) as i32);
+        buf.extend_from_slice(&header);
+        buf.extend_from_slice(&body);
 
-        let fetch_offset = buf
-            .try_get_i64()
-            .map_err(|e| anyhow::anyhow!("failed to parse i64 for fetch_offset: {}", e))?;
+        buf.freeze()
+    }
+}
 
-        let last_fetched_epoch = buf
-            .try_get_i32()
-            .map_err(|e| anyhow::anyhow!("failed to parse i32 for last_fetched_epoch: {}", e))?;
+/// Decodes any version the schemas know, whether or not a broker serves it.
+impl FromBytes for RequestV0 {
+    fn from_be_bytes<B: Buf>(buf: &mut B) -> Result<Self> {
+        buf.try_get_i32()
+            .map_err(|e| anyhow::anyhow!("failed to parse i32 for message_size: {}", e))?;
 
-        let log_start_offset = buf
-            .try_get_i64()
-            .map_err(|e| anyhow::anyhow!("failed to parse i64 for log_start_offset: {}", e))?;
+        let header = RequestHeader::from_be_bytes(buf)
+            .map_err(|e| anyhow::anyhow!("failed to parse RequestHeader: {}", e))?;
+        let body = RequestBody::decode(buf, header.request_api_key, header.request_api_version)?;
 
-        let partition_max_bytes = buf
-            .try_get_i32()
-            .map_err(|e| anyhow::anyhow!("failed to parse i32 for partition_max_bytes: {}", e))?;
-
-        Ok(Partition {
-            partition,
-            current_leader_epoch,
-            fetch_offset,
-            last_fetched_epoch,
-            log_start_offset,
-            partition_max_bytes,
-        })
+        Ok(RequestV0::new(header, body))
     }
 }
 
-#[derive(Debug, Clone)]
-pub(crate) struct ForgottenTopic {
-    topic_id: uuid::Uuid,
-    partitions: i32,
+fn parse_tag<B: bytes::Buf>(buf: &mut B) -> Result<TaggedFields> {
+    TaggedFields::from_be_bytes(buf)
+        .map_err(|e| anyhow::anyhow!("failed to parse TaggedFields for tag: {}", e).into())
 }
 
-impl FromBytes for ForgottenTopic {
-    fn from_be_bytes<B: bytes::Buf>(buf: &mut B) -> Result<Self> {
-        let mut buf16 = [0u8; 16];
-        buf.copy_to_slice(&mut buf16);
-
-        let topic_id = uuid::Uuid::from_slice(&buf16)
-            .map_err(|e| anyhow::anyhow!("failed to parse Uuid for topic_id: {}", e))?;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        let partitions = buf
-            .try_get_i32()
-            .map_err(|e| anyhow::anyhow!("failed to parse i32 for partitions: {}", e))?;
+    #[test]
+    fn header_with_a_client_id_past_the_request_is_an_error() {
+        let mut buf = BytesMut::new();
+        buf.put_i16(ApiKey::ApiVersions as i16);
+        buf.put_i16(4);
+        buf.put_i32(7);
+        buf.put_i16(100);
+        buf.put_slice(b"abc");
 
-        Ok(ForgottenTopic {
-            topic_id,
-            partitions,
-        })
+        assert!(RequestHeader::from_be_bytes(&mut buf.freeze()).is_err());
     }
 }
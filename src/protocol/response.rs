@@ -1,47 +1,118 @@
-use bytes::{BufMut, Bytes, BytesMut};
-use uuid::Uuid;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use crate::Result;
 
 use super::{
-    bytes::ToBytes,
-    cluster_metadata::PartitionRecordValue,
-    primitives::{
-        ApiKey, CompactArray, CompactRecords, CompactString, NullableString, VarInt, INT32,
+    bytes::{FromBytes, FromVersionedBytes, ToBytes, ToVersionedBytes},
+    messages::{
+        AddOffsetsToTxnResponse, AddPartitionsToTxnResponse, AlterConfigsResponse,
+        ApiVersionsResponse, CreatePartitionsResponse, CreateTopicsResponse, DeleteTopicsResponse,
+        DescribeClusterResponse, DescribeConfigsResponse, DescribeLogDirsResponse,
+        DescribeTopicPartitionsResponse, EndTxnResponse, FetchResponse,
+        IncrementalAlterConfigsResponse, InitProducerIdResponse, MetadataResponse,
+        TxnOffsetCommitResponse, UpdateFeaturesResponse,
     },
+    primitives::{ApiKey, TaggedFields},
 };
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ErrorCode {
     None = 0,
     UnknownServerError = -1,
+    OffsetOutOfRange = 1,
     UnsupportedVersion = 35,
     UnknownTopicOrPartition = 3,
+    NotLeaderOrFollower = 6,
     UnknownTopic = 100,
+    CoordinatorNotAvailable = 15,
+    InvalidTopicException = 17,
+    TopicAlreadyExists = 36,
+    InvalidPartitions = 37,
+    InvalidReplicationFactor = 38,
+    InvalidReplicaAssignment = 39,
+    InvalidConfig = 40,
+    NotController = 41,
+    InvalidRequest = 42,
+    InvalidProducerEpoch = 47,
+    InvalidTxnState = 48,
+    InvalidProducerIdMapping = 49,
+    InvalidTransactionTimeout = 50,
+    ConcurrentTransactions = 51,
+    OperationNotAttempted = 55,
+    KafkaStorageError = 56,
+    ProducerFenced = 90,
+    InvalidUpdateVersion = 95,
+    UnsupportedEndpointType = 119,
 }
 
 #[derive(Debug)]
 pub struct ResponseV0 {
-    message_size: i32,
     header: ResponseHeader,
+    /// The version `body` is encoded in, which only the request carries.
+    api_version: i16,
     body: ResponseBody,
 }
 
 impl ResponseV0 {
-    pub fn new(message_size: i32, header: ResponseHeader, body: ResponseBody) -> Self {
+    pub fn new(header: ResponseHeader, api_version: i16, body: ResponseBody) -> Self {
         Self {
-            message_size,
             header,
+            api_version,
             body,
         }
     }
+
+    #[cfg(test)]
+    pub fn header(&self) -> &ResponseHeader {
+        &self.header
+    }
+
+    #[cfg(test)]
+    pub fn api_version(&self) -> i16 {
+        self.api_version
+    }
+
+    #[cfg(test)]
+    pub fn body(&self) -> &ResponseBody {
+        &self.body
+    }
+
+    /// Decodes a size-prefixed response to a request for `api_key` in
+    /// `api_version`, which the response itself doesn't say.
+    ///
+    /// A broker that doesn't support the version of an ApiVersions request
+    /// answers UNSUPPORTED_VERSION in v0, so that response is decoded in v0.
+    #[cfg(test)]
+    pub fn decode<B: Buf>(buf: &mut B, api_key: ApiKey, api_version: i16) -> Result<Self> {
+        buf.try_get_i32()
+            .map_err(|e| anyhow::anyhow!("failed to parse i32 for message_size: {}", e))?;
+
+        let header = ResponseHeader::from_versioned_be_bytes(
+            buf,
+            api_key.response_header_version(api_version),
+        )
+        .map_err(|e| anyhow::anyhow!("failed to parse ResponseHeader: {}", e))?;
+        // The error code comes first in every version of the body.
+        let unsupported = (ErrorCode::UnsupportedVersion as i16).to_be_bytes();
+        let api_version = match api_key {
+            ApiKey::ApiVersions if buf.chunk().starts_with(&unsupported) => 0,
+            _ => api_version,
+        };
+        let body = ResponseBody::decode(buf, api_key, api_version)?;
+
+        Ok(ResponseV0::new(header, api_version, body))
+    }
 }
 
 impl ToBytes for ResponseV0 {
     fn to_be_bytes(&self) -> Bytes {
-        let mut buf = BytesMut::new();
+        let header = self.header.to_be_bytes();
+        let body = self.body.to_versioned_be_bytes(self.api_version);
+        let mut buf = BytesMut::with_capacity(4 + header.len() + body.len());
 
-        buf.put_i32(self.message_size);
-        buf.extend_from_slice(&self.header.to_be_bytes());
-        buf.extend_from_slice(&self.body.to_be_bytes());
+        buf.put_i32((header.len() + body.len()) as i32);
+        buf.extend_from_slice(&header);
+        buf.extend_from_slice(&body);
 
         buf.freeze()
     }
@@ -53,6 +124,24 @@ pub enum ResponseHeader {
     V1(ResponseHeaderV1),
 }
 
+impl ResponseHeader {
+    /// The header of a response to `api_key` in `api_version`.
+    pub fn new(api_key: ApiKey, api_version: i16, correlation_id: i32) -> Self {
+        match api_key.response_header_version(api_version) {
+            0 => ResponseHeader::V0(ResponseHeaderV0::new(correlation_id)),
+            _ => ResponseHeader::V1(ResponseHeaderV1::new(correlation_id)),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn correlation_id(&self) -> i32 {
+        match self {
+            ResponseHeader::V0(header) => header.correlation_id,
+            ResponseHeader::V1(header) => header.correlation_id,
+        }
+    }
+}
+
 impl ToBytes for ResponseHeader {
     fn to_be_bytes(&self) -> Bytes {
         match self {
@@ -62,6 +151,16 @@ impl ToBytes for ResponseHeader {
     }
 }
 
+/// `version` is the version of the header, not of the API.
+impl FromVersionedBytes for ResponseHeader {
+    fn from_versioned_be_bytes<B: Buf>(buf: &mut B, version: i16) -> Result<Self> {
+        match version {
+            0 => Ok(ResponseHeader::V0(ResponseHeaderV0::from_be_bytes(buf)?)),
+            _ => Ok(ResponseHeader::V1(ResponseHeaderV1::from_be_bytes(buf)?)),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ResponseHeaderV0 {
     correlation_id: i32,
@@ -83,17 +182,27 @@ impl ToBytes for ResponseHeaderV0 {
     }
 }
 
+impl FromBytes for ResponseHeaderV0 {
+    fn from_be_bytes<B: Buf>(buf: &mut B) -> Result<Self> {
+        let correlation_id = buf
+            .try_get_i32()
+            .map_err(|e| anyhow::anyhow!("failed to parse i32 for correlation_id: {}", e))?;
+
+        Ok(ResponseHeaderV0 { correlation_id })
+    }
+}
+
 #[derive(Debug)]
 pub struct ResponseHeaderV1 {
     correlation_id: i32,
-    tag: CompactArray<NullableString>,
+    tag: TaggedFields,
 }
 
 impl ResponseHeaderV1 {
     pub fn new(correlation_id: i32) -> Self {
         Self {
             correlation_id,
-            tag: CompactArray::new(),
+            tag: TaggedFields::new(),
         }
     }
 }
@@ -109,482 +218,222 @@ impl ToBytes for ResponseHeaderV1 {
     }
 }
 
-#[derive(Debug)]
-pub enum ResponseBody {
-    ApiVersionsResponseV4(ApiVersionsResponseBodyV4),
-    DescribeTopicPartiotionsResponseV0(DescribeTopicPartiotionsResponseBodyV0),
-    FetchResponseV16(FetchResponseBodyV16),
-}
-
-impl ToBytes for ResponseBody {
-    fn to_be_bytes(&self) -> Bytes {
-        match self {
-            ResponseBody::ApiVersionsResponseV4(body) => body.to_be_bytes(),
-            ResponseBody::DescribeTopicPartiotionsResponseV0(body) => body.to_be_bytes(),
-            ResponseBody::FetchResponseV16(body) => body.to_be_bytes(),
-        }
-    }
-}
-
-#[derive(Debug)]
-pub struct ApiVersionsResponseBodyV4 {
-    pub error_code: ErrorCode,
-    pub api_versions: CompactArray<ApiVersion>,
-    pub throttle_time_ms: i32,
-    pub tag: CompactArray<NullableString>,
-}
-
-impl ApiVersionsResponseBodyV4 {
-    pub fn new(
-        error_code: ErrorCode,
-        api_versions: CompactArray<ApiVersion>,
-        throttle_time_ms: i32,
-        tag: CompactArray<NullableString>,
-    ) -> Self {
-        Self {
-            error_code,
-            api_versions,
-            throttle_time_ms,
-            tag,
-        }
-    }
-}
-
-impl ToBytes for ApiVersionsResponseBodyV4 {
-    fn to_be_bytes(&self) -> Bytes {
-        let mut buf = BytesMut::new();
-
-        buf.put_i16(self.error_code as i16);
-        buf.extend_from_slice(&self.api_versions.to_be_bytes());
-        buf.put_i32(self.throttle_time_ms);
-        buf.extend_from_slice(&self.tag.to_be_bytes());
+impl FromBytes for ResponseHeaderV1 {
+    fn from_be_bytes<B: Buf>(buf: &mut B) -> Result<Self> {
+        let correlation_id = buf
+            .try_get_i32()
+            .map_err(|e| anyhow::anyhow!("failed to parse i32 for correlation_id: {}", e))?;
+        let tag = TaggedFields::from_be_bytes(buf)
+            .map_err(|e| anyhow::anyhow!("failed to parse TaggedFields for tag: {}", e))?;
 
-        buf.freeze()
-    }
-}
-
-#[derive(Debug)]
-pub struct ApiVersion {
-    api_key: ApiKey,
-    min_version: i16,
-    max_version: i16,
-    tag: CompactArray<NullableString>,
-}
-
-impl ApiVersion {
-    pub fn new(
-        api_key: ApiKey,
-        min_version: i16,
-        max_version: i16,
-        tag: CompactArray<NullableString>,
-    ) -> Self {
-        Self {
-            api_key,
-            min_version,
-            max_version,
-            tag,
-        }
-    }
-}
-
-impl ToBytes for ApiVersion {
-    fn to_be_bytes(&self) -> Bytes {
-        let mut buf = BytesMut::new();
-
-        buf.extend_from_slice(&self.api_key.to_be_bytes());
-        buf.put_i16(self.min_version);
-        buf.put_i16(self.max_version);
-        buf.extend_from_slice(&self.tag.to_be_bytes());
-
-        buf.freeze()
-    }
-}
-
-#[derive(Debug)]
-pub struct DescribeTopicPartiotionsResponseBodyV0 {
-    throttle_time_ms: i32,
-    topics: CompactArray<Topic>,
-    next_cursor: u8,
-    tag: CompactArray<NullableString>,
-}
-
-impl DescribeTopicPartiotionsResponseBodyV0 {
-    pub fn new(
-        throttle_time_ms: i32,
-        topics: CompactArray<Topic>,
-        next_cursor: u8,
-        tag: CompactArray<NullableString>,
-    ) -> Self {
-        Self {
-            throttle_time_ms,
-            topics,
-            next_cursor,
+        Ok(ResponseHeaderV1 {
+            correlation_id,
             tag,
-        }
-    }
-}
-
-impl ToBytes for DescribeTopicPartiotionsResponseBodyV0 {
-    fn to_be_bytes(&self) -> Bytes {
-        let mut buf = BytesMut::new();
-
-        buf.put_i32(self.throttle_time_ms);
-        buf.extend_from_slice(&self.topics.to_be_bytes());
-        buf.put_u8(self.next_cursor);
-        buf.extend_from_slice(&self.tag.to_be_bytes());
-
-        buf.freeze()
+        })
     }
 }
 
 #[derive(Debug)]
-pub struct Topic {
-    error_code: ErrorCode,
-    name: CompactString,
-    id: Uuid,
-    is_internal: bool,
-    partitions: CompactArray<Partition>,
-    authorized_operations: u32,
-    tag: CompactArray<NullableString>,
-}
-
-impl Topic {
-    pub fn new(
-        error_code: ErrorCode,
-        name: CompactString,
-        id: Uuid,
-        is_internal: bool,
-        partitions: CompactArray<Partition>,
-        authorized_operations: u32,
-        tag: CompactArray<NullableString>,
-    ) -> Self {
-        Self {
-            error_code,
-            name,
-            id,
-            is_internal,
-            partitions,
-            authorized_operations,
-            tag,
-        }
-    }
-
-    pub fn from_unknown_topic(topic_name: &str) -> Topic {
-        Self {
-            error_code: ErrorCode::UnknownTopicOrPartition,
-            name: CompactString::from_str(topic_name),
-            id: Uuid::nil(),
-            is_internal: false,
-            partitions: CompactArray::new(),
-            authorized_operations: 0,
-            tag: CompactArray::new(),
-        }
-    }
-}
-
-impl ToBytes for Topic {
-    fn to_be_bytes(&self) -> Bytes {
-        let mut buf = BytesMut::new();
-
-        buf.put_i16(self.error_code as i16);
-        buf.extend_from_slice(&self.name.to_be_bytes());
-        buf.extend_from_slice(self.id.as_bytes());
-        buf.put_u8(self.is_internal as u8);
-        buf.extend_from_slice(&self.partitions.to_be_bytes());
-        buf.put_u32(self.authorized_operations);
-        buf.extend_from_slice(&self.tag.to_be_bytes());
-
-        buf.freeze()
-    }
-}
-
-#[derive(Debug)]
-pub(crate) struct Partition {
-    error_code: ErrorCode,
-    partition_index: i32,
-    leader: i32,
-    leader_epoch: i32,
-    // replica_nodes_len: VarInt, // varint
-    replica_nodes: CompactArray<INT32>, // replica nodes
-    // isr_nodes_len: VarInt, // varint
-    isr_nodes: CompactArray<INT32>,   // in-sync replica nodes
-    eligible_leader_replicas: VarInt, //varint 0 for now
-    last_known_elr: u8,
-    offline_replicas: u8,
-    tag_buffer: u8,
-}
-
-impl Partition {
-    pub(crate) fn new(
-        error_code: ErrorCode,
-        partition_index: i32,
-        leader: i32,
-        leader_epoch: i32,
-        replica_nodes: CompactArray<INT32>,
-        isr_nodes: CompactArray<INT32>,
-        eligible_leader_replicas: VarInt,
-        last_known_elr: u8,
-        offline_replicas: u8,
-        tag_buffer: u8,
-    ) -> Self {
-        Self {
-            error_code,
-            partition_index,
-            leader,
-            leader_epoch,
-            replica_nodes,
-            isr_nodes,
-            eligible_leader_replicas,
-            last_known_elr,
-            offline_replicas,
-            tag_buffer,
-        }
-    }
-}
-
-impl ToBytes for Partition {
-    fn to_be_bytes(&self) -> Bytes {
-        let mut buf = BytesMut::new();
-
-        buf.put_i16(self.error_code as i16);
-        buf.put_i32(self.partition_index);
-        buf.put_i32(self.leader);
-        buf.put_i32(self.leader_epoch);
-        buf.extend_from_slice(&self.replica_nodes.to_be_bytes());
-        buf.extend_from_slice(&self.isr_nodes.to_be_bytes());
-        buf.extend_from_slice(&self.eligible_leader_replicas.to_be_bytes());
-        buf.put_u8(self.last_known_elr);
-        buf.put_u8(self.offline_replicas);
-        buf.put_u8(self.tag_buffer);
-
-        buf.freeze()
-    }
-}
-
-impl From<&PartitionRecordValue> for Partition {
-    fn from(partition_record: &PartitionRecordValue) -> Self {
-        Partition {
-            error_code: ErrorCode::None,
-            partition_index: partition_record.partition_id(),
-            leader: partition_record.leader(),
-            leader_epoch: partition_record.leader_epoch(),
-            replica_nodes: partition_record.replica_array().clone(),
-            isr_nodes: partition_record.in_sync_replica_array().clone(),
-            eligible_leader_replicas: VarInt::from(0),
-            last_known_elr: 0,
-            offline_replicas: 0,
-            tag_buffer: 0,
-        }
-    }
-}
-
-#[derive(Debug)]
-pub(crate) struct FetchResponseBodyV16 {
-    throttle_time_ms: i32,
-    error_code: ErrorCode,
-    session_id: i32,
-    responses: CompactArray<FetchResponseTopic>,
-    tag: CompactArray<NullableString>,
-}
-
-impl FetchResponseBodyV16 {
-    pub(crate) fn with_record_for_topic(
-        topic_id: uuid::Uuid,
-        record_batch: CompactRecords,
-    ) -> Self {
-        Self {
-            throttle_time_ms: 0,
-            error_code: ErrorCode::None,
-            session_id: 0,
-            responses: CompactArray::from_vec(vec![FetchResponseTopic::new(
-                topic_id,
-                CompactArray::from_vec(vec![FetchResponsePartition::new(
-                    0,
-                    ErrorCode::None,
-                    0,
-                    0,
-                    0,
-                    CompactArray::new(),
-                    0,
-                    record_batch,
-                )]),
-            )]),
-            tag: CompactArray::new(),
-        }
-    }
-
-    pub(crate) fn unknown_topic(topic_id: uuid::Uuid) -> Self {
-        Self {
-            throttle_time_ms: 0,
-            error_code: ErrorCode::None,
-            session_id: 0,
-            responses: CompactArray::from_vec(vec![FetchResponseTopic::new(
-                topic_id,
-                CompactArray::from_vec(vec![FetchResponsePartition::new(
-                    0,
-                    ErrorCode::UnknownTopic,
-                    0,
-                    0,
-                    0,
-                    CompactArray::new(),
-                    0,
-                    Default::default(),
-                )]),
-            )]),
-            tag: CompactArray::new(),
-        }
-    }
-
-    pub(crate) fn empty_topic(topic_id: uuid::Uuid) -> Self {
-        Self {
-            throttle_time_ms: 0,
-            error_code: ErrorCode::None,
-            session_id: 0,
-            responses: CompactArray::from_vec(vec![FetchResponseTopic::new(
-                topic_id,
-                CompactArray::from_vec(vec![FetchResponsePartition::new(
-                    0,
-                    ErrorCode::None,
-                    0,
-                    0,
-                    0,
-                    CompactArray::new(),
-                    0,
-                    Default::default(),
-                )]),
-            )]),
-            tag: CompactArray::new(),
-        }
-    }
-}
-
-impl Default for FetchResponseBodyV16 {
-    fn default() -> Self {
-        Self {
-            throttle_time_ms: 0,
-            error_code: ErrorCode::None,
-            session_id: 0,
-            responses: CompactArray::new(),
-            tag: CompactArray::new(),
+pub enum ResponseBody {
+    ApiVersions(ApiVersionsResponse),
+    DescribeTopicPartitions(DescribeTopicPartitionsResponse),
+    Fetch(FetchResponse),
+    AddPartitionsToTxn(AddPartitionsToTxnResponse),
+    AddOffsetsToTxn(AddOffsetsToTxnResponse),
+    EndTxn(EndTxnResponse),
+    InitProducerId(InitProducerIdResponse),
+    TxnOffsetCommit(TxnOffsetCommitResponse),
+    CreateTopics(CreateTopicsResponse),
+    DeleteTopics(DeleteTopicsResponse),
+    CreatePartitions(CreatePartitionsResponse),
+    DescribeConfigs(DescribeConfigsResponse),
+    AlterConfigs(AlterConfigsResponse),
+    IncrementalAlterConfigs(IncrementalAlterConfigsResponse),
+    Metadata(MetadataResponse),
+    DescribeCluster(DescribeClusterResponse),
+    DescribeLogDirs(DescribeLogDirsResponse),
+    UpdateFeatures(UpdateFeaturesResponse),
+}
+
+impl ResponseBody {
+    /// A response to `api_key` carrying `error_code` in its top-level error
+    /// field. Responses without one only report errors per resource and come
+    /// back empty.
+    pub fn error(api_key: ApiKey, error_code: ErrorCode) -> Self {
+        let error_code = error_code as i16;
+        match api_key {
+            ApiKey::ApiVersions => ResponseBody::ApiVersions(ApiVersionsResponse {
+                error_code,
+                ..Default::default()
+            }),
+            ApiKey::DescribeTopicPartitions => {
+                ResponseBody::DescribeTopicPartitions(DescribeTopicPartitionsResponse::default())
+            }
+            ApiKey::Fetch => ResponseBody::Fetch(FetchResponse {
+                error_code,
+                ..Default::default()
+            }),
+            ApiKey::AddPartitionsToTxn => {
+                ResponseBody::AddPartitionsToTxn(AddPartitionsToTxnResponse {
+                    error_code,
+                    ..Default::default()
+                })
+            }
+            ApiKey::AddOffsetsToTxn => ResponseBody::AddOffsetsToTxn(AddOffsetsToTxnResponse {
+                error_code,
+                ..Default::default()
+            }),
+            ApiKey::EndTxn => ResponseBody::EndTxn(EndTxnResponse {
+                error_code,
+                ..Default::default()
+            }),
+            ApiKey::InitProducerId => ResponseBody::InitProducerId(InitProducerIdResponse {
+                error_code,
+                ..Default::default()
+            }),
+            ApiKey::TxnOffsetCommit => {
+                ResponseBody::TxnOffsetCommit(TxnOffsetCommitResponse::default())
+            }
+            ApiKey::CreateTopics => ResponseBody::CreateTopics(CreateTopicsResponse::default()),
+            ApiKey::DeleteTopics => ResponseBody::DeleteTopics(DeleteTopicsResponse::default()),
+            ApiKey::CreatePartitions => {
+                ResponseBody::CreatePartitions(CreatePartitionsResponse::default())
+            }
+            ApiKey::DescribeConfigs => {
+                ResponseBody::DescribeConfigs(DescribeConfigsResponse::default())
+            }
+            ApiKey::AlterConfigs => ResponseBody::AlterConfigs(AlterConfigsResponse::default()),
+            ApiKey::IncrementalAlterConfigs => {
+                ResponseBody::IncrementalAlterConfigs(IncrementalAlterConfigsResponse::default())
+            }
+            ApiKey::Metadata => ResponseBody::Metadata(MetadataResponse::default()),
+            ApiKey::DescribeCluster => ResponseBody::DescribeCluster(DescribeClusterResponse {
+                error_code,
+                ..Default::default()
+            }),
+            ApiKey::DescribeLogDirs => ResponseBody::DescribeLogDirs(DescribeLogDirsResponse {
+                error_code,
+                ..Default::default()
+            }),
+            ApiKey::UpdateFeatures => ResponseBody::UpdateFeatures(UpdateFeaturesResponse {
+                error_code,
+                ..Default::default()
+            }),
         }
     }
-}
-
-impl ToBytes for FetchResponseBodyV16 {
-    fn to_be_bytes(&self) -> Bytes {
-        let mut buf = BytesMut::new();
-
-        buf.put_i32(self.throttle_time_ms);
-        buf.put_i16(self.error_code as i16);
-        buf.put_i32(self.session_id);
-        buf.extend_from_slice(&self.responses.to_be_bytes());
-        buf.extend_from_slice(&self.tag.to_be_bytes());
-
-        buf.freeze()
-    }
-}
-
-#[derive(Debug)]
-pub(crate) struct FetchResponseTopic {
-    topic_id: uuid::Uuid,
-    partitions: CompactArray<FetchResponsePartition>,
-    tag: CompactArray<NullableString>,
-}
 
-impl FetchResponseTopic {
-    pub(crate) fn new(
-        topic_id: uuid::Uuid,
-        partitions: CompactArray<FetchResponsePartition>,
-    ) -> Self {
-        Self {
-            topic_id,
-            partitions,
-            tag: CompactArray::new(),
+    /// Decodes the body of a response to `api_key` in `version`.
+    #[cfg(test)]
+    pub fn decode<B: Buf>(buf: &mut B, api_key: ApiKey, version: i16) -> Result<Self> {
+        match api_key {
+            ApiKey::ApiVersions => Ok(ResponseBody::ApiVersions(
+                ApiVersionsResponse::from_versioned_be_bytes(buf, version)
+                    .map_err(|e| anyhow::anyhow!("failed to parse ApiVersionsResponse: {}", e))?,
+            )),
+            ApiKey::DescribeTopicPartitions => Ok(ResponseBody::DescribeTopicPartitions(
+                DescribeTopicPartitionsResponse::from_versioned_be_bytes(buf, version).map_err(
+                    |e| anyhow::anyhow!("failed to parse DescribeTopicPartitionsResponse: {}", e),
+                )?,
+            )),
+            ApiKey::Fetch => Ok(ResponseBody::Fetch(
+                FetchResponse::from_versioned_be_bytes(buf, version)
+                    .map_err(|e| anyhow::anyhow!("failed to parse FetchResponse: {}", e))?,
+            )),
+            ApiKey::AddPartitionsToTxn => Ok(ResponseBody::AddPartitionsToTxn(
+                AddPartitionsToTxnResponse::from_versioned_be_bytes(buf, version).map_err(|e| {
+                    anyhow::anyhow!("failed to parse AddPartitionsToTxnResponse: {}", e)
+                })?,
+            )),
+            ApiKey::AddOffsetsToTxn => Ok(ResponseBody::AddOffsetsToTxn(
+                AddOffsetsToTxnResponse::from_versioned_be_bytes(buf, version).map_err(|e| {
+                    anyhow::anyhow!("failed to parse AddOffsetsToTxnResponse: {}", e)
+                })?,
+            )),
+            ApiKey::EndTxn => Ok(ResponseBody::EndTxn(
+                EndTxnResponse::from_versioned_be_bytes(buf, version)
+                    .map_err(|e| anyhow::anyhow!("failed to parse EndTxnResponse: {}", e))?,
+            )),
+            ApiKey::InitProducerId => Ok(ResponseBody::InitProducerId(
+                InitProducerIdResponse::from_versioned_be_bytes(buf, version).map_err(|e| {
+                    anyhow::anyhow!("failed to parse InitProducerIdResponse: {}", e)
+                })?,
+            )),
+            ApiKey::TxnOffsetCommit => Ok(ResponseBody::TxnOffsetCommit(
+                TxnOffsetCommitResponse::from_versioned_be_bytes(buf, version).map_err(|e| {
+                    anyhow::anyhow!("failed to parse TxnOffsetCommitResponse: {}", e)
+                })?,
+            )),
+            ApiKey::CreateTopics => Ok(ResponseBody::CreateTopics(
+                CreateTopicsResponse::from_versioned_be_bytes(buf, version)
+                    .map_err(|e| anyhow::anyhow!("failed to parse CreateTopicsResponse: {}", e))?,
+            )),
+            ApiKey::DeleteTopics => Ok(ResponseBody::DeleteTopics(
+                DeleteTopicsResponse::from_versioned_be_bytes(buf, version)
+                    .map_err(|e| anyhow::anyhow!("failed to parse DeleteTopicsResponse: {}", e))?,
+            )),
+            ApiKey::CreatePartitions => Ok(ResponseBody::CreatePartitions(
+                CreatePartitionsResponse::from_versioned_be_bytes(buf, version).map_err(|e| {
+                    anyhow::anyhow!("failed to parse CreatePartitionsResponse: {}", e)
+                })?,
+            )),
+            ApiKey::DescribeConfigs => Ok(ResponseBody::DescribeConfigs(
+                DescribeConfigsResponse::from_versioned_be_bytes(buf, version).map_err(|e| {
+                    anyhow::anyhow!("failed to parse DescribeConfigsResponse: {}", e)
+                })?,
+            )),
+            ApiKey::AlterConfigs => Ok(ResponseBody::AlterConfigs(
+                AlterConfigsResponse::from_versioned_be_bytes(buf, version)
+                    .map_err(|e| anyhow::anyhow!("failed to parse AlterConfigsResponse: {}", e))?,
+            )),
+            ApiKey::IncrementalAlterConfigs => Ok(ResponseBody::IncrementalAlterConfigs(
+                IncrementalAlterConfigsResponse::from_versioned_be_bytes(buf, version).map_err(
+                    |e| anyhow::anyhow!("failed to parse IncrementalAlterConfigsResponse: {}", e),
+                )?,
+            )),
+            ApiKey::Metadata => Ok(ResponseBody::Metadata(
+                MetadataResponse::from_versioned_be_bytes(buf, version)
+                    .map_err(|e| anyhow::anyhow!("failed to parse MetadataResponse: {}", e))?,
+            )),
+            ApiKey::DescribeCluster => Ok(ResponseBody::DescribeCluster(
+                DescribeClusterResponse::from_versioned_be_bytes(buf, version).map_err(|e| {
+                    anyhow::anyhow!("failed to parse DescribeClusterResponse: {}", e)
+                })?,
+            )),
+            ApiKey::DescribeLogDirs => Ok(ResponseBody::DescribeLogDirs(
+                DescribeLogDirsResponse::from_versioned_be_bytes(buf, version).map_err(|e| {
+                    anyhow::anyhow!("failed to parse DescribeLogDirsResponse: {}", e)
+                })?,
+            )),
+            ApiKey::UpdateFeatures => Ok(ResponseBody::UpdateFeatures(
+                UpdateFeaturesResponse::from_versioned_be_bytes(buf, version).map_err(|e| {
+                    anyhow::anyhow!("failed to parse UpdateFeaturesResponse: {}", e)
+                })?,
+            )),
         }
     }
 }
 
-impl ToBytes for FetchResponseTopic {
-    fn to_be_bytes(&self) -> Bytes {
-        let mut buf = BytesMut::new();
-
-        buf.extend_from_slice(self.topic_id.as_bytes());
-        buf.extend_from_slice(&self.partitions.to_be_bytes());
-        buf.extend_from_slice(&self.tag.to_be_bytes());
-
-        buf.freeze()
-    }
-}
-
-#[derive(Debug)]
-pub(crate) struct FetchResponsePartition {
-    partition_index: i32,
-    error_code: ErrorCode,
-    high_watermark: i64,
-    last_stable_offset: i64,
-    log_start_offset: i64,
-    aborted_transactions: CompactArray<AbortedTransaction>,
-    prefrred_read_replica: i32,
-    records: CompactRecords,
-    tag: CompactArray<NullableString>,
-}
-
-impl FetchResponsePartition {
-    pub(crate) fn new(
-        partition_index: i32,
-        error_code: ErrorCode,
-        high_watermark: i64,
-        last_stable_offset: i64,
-        log_start_offset: i64,
-        aborted_transactions: CompactArray<AbortedTransaction>,
-        prefrred_read_replica: i32,
-        records: CompactRecords,
-    ) -> Self {
-        Self {
-            partition_index,
-            error_code,
-            high_watermark,
-            last_stable_offset,
-            log_start_offset,
-            aborted_transactions,
-            prefrred_read_replica,
-            records,
-            tag: CompactArray::new(),
+impl ToVersionedBytes for ResponseBody {
+    fn to_versioned_be_bytes(&self, version: i16) -> Bytes {
+        match self {
+            ResponseBody::ApiVersions(body) => body.to_versioned_be_bytes(version),
+            ResponseBody::DescribeTopicPartitions(body) => body.to_versioned_be_bytes(version),
+            ResponseBody::Fetch(body) => body.to_versioned_be_bytes(version),
+            ResponseBody::AddPartitionsToTxn(body) => body.to_versioned_be_bytes(version),
+            ResponseBody::AddOffsetsToTxn(body) => body.to_versioned_be_bytes(version),
+            ResponseBody::EndTxn(body) => body.to_versioned_be_bytes(version),
+            ResponseBody::InitProducerId(body) => body.to_versioned_be_bytes(version),
+            ResponseBody::TxnOffsetCommit(body) => body.to_versioned_be_bytes(version),
+            ResponseBody::CreateTopics(body) => body.to_versioned_be_bytes(version),
+            ResponseBody::DeleteTopics(body) => body.to_versioned_be_bytes(version),
+            ResponseBody::CreatePartitions(body) => body.to_versioned_be_bytes(version),
+            ResponseBody::DescribeConfigs(body) => body.to_versioned_be_bytes(version),
+            ResponseBody::AlterConfigs(body) => body.to_versioned_be_bytes(version),
+            ResponseBody::IncrementalAlterConfigs(body) => body.to_versioned_be_bytes(version),
+            ResponseBody::Metadata(body) => body.to_versioned_be_bytes(version),
+            ResponseBody::DescribeCluster(body) => body.to_versioned_be_bytes(version),
+            ResponseBody::DescribeLogDirs(body) => body.to_versioned_be_bytes(version),
+            ResponseBody::UpdateFeatures(body) => body.to_versioned_be_bytes(version),
         }
     }
 }
-
-impl ToBytes for FetchResponsePartition {
-    fn to_be_bytes(&self) -> Bytes {
-        let mut buf = BytesMut::new();
-
-        buf.put_i32(self.partition_index);
-        buf.put_i16(self.error_code as i16);
-        buf.put_i64(self.high_watermark);
-        buf.put_i64(self.last_stable_offset);
-        buf.put_i64(self.log_start_offset);
-        buf.extend_from_slice(&self.aborted_transactions.to_be_bytes());
-        buf.put_i32(self.prefrred_read_replica);
-        buf.extend_from_slice(&self.records.to_be_bytes());
-        buf.extend_from_slice(&self.tag.to_be_bytes());
-
-        buf.freeze()
-    }
-}
-
-#[derive(Debug)]
-pub(crate) struct AbortedTransaction {
-    producer_id: i64,
-    first_offset: i64,
-}
-
-impl ToBytes for AbortedTransaction {
-    fn to_be_bytes(&self) -> Bytes {
-        let mut buf = BytesMut::new();
-
-        buf.put_i64(self.producer_id);
-        buf.put_i64(self.first_offset);
-
-        buf.freeze()
-    }
-}
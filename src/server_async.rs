@@ -1,53 +1,61 @@
-use std::{
-    fs::File,
-    io::{BufReader, Read},
-    net::SocketAddr,
-    sync::Arc,
-};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
 
-use bytes::{Bytes, BytesMut};
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
     net::{TcpListener, TcpStream},
 };
 
-use crate::protocol::{
-    bytes::{FromBytes, ToBytes},
-    cluster_metadata::ClusterMetadata,
-    primitives::{ApiKey, CompactArray, CompactString},
-    request::{DescribeTopicPartitionsRequestV0, FetchRequestV16, RequestV0, TopicsPartitions},
-    response::{
-        ApiVersion, ApiVersionsResponseBodyV4, DescribeTopicPartiotionsResponseBodyV0, ErrorCode,
-        FetchResponseBodyV16, Partition, ResponseBody, ResponseHeader, ResponseHeaderV0,
-        ResponseHeaderV1, ResponseV0, Topic,
-    },
-};
+use crate::config::ServerProperties;
+use crate::handler::{self, BrokerState, HandlerRegistry};
+use crate::metadata::Controller;
+use crate::protocol::{bytes::ToBytes, request::RequestV0, response::ResponseV0};
+use crate::storage::{meta_properties, LogManager};
+use crate::transaction::TransactionCoordinator;
 
 use crate::Result;
 
+const TRANSACTION_TIMEOUT_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+const METADATA_LOG_POLL_INTERVAL: Duration = Duration::from_millis(500);
+const REQUEST_METRICS_LOG_INTERVAL: Duration = Duration::from_secs(60);
+
 #[derive(Debug)]
 pub struct ServerAsync {
     address: String,
-    metadata: Arc<ClusterMetadata>,
+    state: Arc<BrokerState>,
+    handlers: Arc<HandlerRegistry<BrokerState>>,
+    /// Whether the request metrics are logged, which `metrics.recording.level`
+    /// asks for at DEBUG and TRACE.
+    log_request_metrics: bool,
 }
 
 impl ServerAsync {
-    pub fn new(address: &str) -> Result<Self> {
-        let metadata =
-            File::open("/tmp/kraft-combined-logs/__cluster_metadata-0/00000000000000000000.log")
-                .map_err(|e| anyhow::anyhow!("failed to read cluster metadata {}", e))
-                .and_then(|file| {
-                    ClusterMetadata::try_from(file)
-                        .map_err(|e| anyhow::anyhow!("failed to parse cluster metadata: {}", e))
-                });
-
-        match metadata {
-            Ok(metadata) => Ok(ServerAsync {
-                address: address.to_string(),
-                metadata: Arc::new(metadata),
+    pub fn new(address: &str, properties: ServerProperties) -> Result<Self> {
+        let (cluster_id, log_dirs) =
+            meta_properties::load_log_dirs(&properties.log_dirs(), properties.node_id())
+                .map_err(|e| anyhow::anyhow!("failed to load log directories: {}", e))?;
+        let log_request_metrics = matches!(
+            properties.get("metrics.recording.level"),
+            Some("DEBUG" | "TRACE")
+        );
+        let logs = Arc::new(LogManager::new(log_dirs));
+        let controller = Arc::new(
+            Controller::load(Arc::clone(&logs), properties, cluster_id)
+                .map_err(|e| anyhow::anyhow!("failed to load cluster metadata: {}", e))?,
+        );
+        let transaction_coordinator =
+            TransactionCoordinator::load(Arc::clone(&logs), Arc::clone(&controller))
+                .map_err(|e| anyhow::anyhow!("failed to load transaction state: {}", e))?;
+
+        Ok(ServerAsync {
+            address: address.to_string(),
+            state: Arc::new(BrokerState {
+                controller,
+                logs,
+                transaction_coordinator: Arc::new(transaction_coordinator),
             }),
-            Err(e) => Err(anyhow::anyhow!("failed to initialize server: {}", e).into()),
-        }
+            handlers: Arc::new(handler::broker_handlers()),
+            log_request_metrics,
+        })
     }
 
     pub async fn run(&self) -> Result<()> {
@@ -55,10 +63,65 @@ impl ServerAsync {
             .await
             .map_err(|e| format!("failed to bind to address {}: {}", self.address, e))?;
 
+        let transaction_coordinator = Arc::clone(&self.state.transaction_coordinator);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(TRANSACTION_TIMEOUT_CHECK_INTERVAL);
+            loop {
+                interval.tick().await;
+                let coordinator = Arc::clone(&transaction_coordinator);
+                // Aborting writes markers to disk, so keep it off the runtime threads.
+                let _ =
+                    tokio::task::spawn_blocking(move || coordinator.abort_timed_out_transactions())
+                        .await;
+            }
+        });
+
+        // Ends once the metadata log has failed, which stops the broker.
+        let controller = Arc::clone(&self.state.controller);
+        let logs = Arc::clone(&self.state.logs);
+        let mut metadata_log = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(METADATA_LOG_POLL_INTERVAL);
+            while !logs.metadata_log_failed() {
+                interval.tick().await;
+                let controller = Arc::clone(&controller);
+                let polled = tokio::task::spawn_blocking(move || {
+                    controller.poll_metadata_log()?;
+                    controller.maybe_snapshot()
+                })
+                .await;
+                if let Ok(Err(e)) = polled {
+                    eprintln!("failed to poll or snapshot the metadata log: {}", e);
+                }
+            }
+        });
+
+        if self.log_request_metrics {
+            let handlers = Arc::clone(&self.handlers);
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(REQUEST_METRICS_LOG_INTERVAL);
+                // The first tick completes right away, before any request.
+                interval.tick().await;
+                loop {
+                    interval.tick().await;
+                    handlers.log_metrics();
+                }
+            });
+        }
+
         loop {
-            match listener.accept().await {
+            let accepted = tokio::select! {
+                accepted = listener.accept() => accepted,
+                _ = &mut metadata_log => return Err("the metadata log has failed".into()),
+            };
+
+            match accepted {
                 Ok((stream, _)) => {
-                    let conn = Connection::new(stream, Arc::clone(&self.metadata)).await?;
+                    let conn = Connection::new(
+                        stream,
+                        Arc::clone(&self.state),
+                        Arc::clone(&self.handlers),
+                    )
+                    .await?;
 
                     tokio::spawn(async move {
                         conn.handle().await;
@@ -75,17 +138,26 @@ impl ServerAsync {
 struct Connection {
     stream: TcpStream,
     peer_addr: SocketAddr,
-    metadata: Arc<ClusterMetadata>,
+    local_addr: SocketAddr,
+    state: Arc<BrokerState>,
+    handlers: Arc<HandlerRegistry<BrokerState>>,
 }
 
 impl Connection {
-    async fn new(stream: TcpStream, metadata: Arc<ClusterMetadata>) -> Result<Self> {
+    async fn new(
+        stream: TcpStream,
+        state: Arc<BrokerState>,
+        handlers: Arc<HandlerRegistry<BrokerState>>,
+    ) -> Result<Self> {
         let peer_addr = stream.peer_addr()?;
+        let local_addr = stream.local_addr()?;
 
         Ok(Connection {
             stream,
             peer_addr,
-            metadata,
+            local_addr,
+            state,
+            handlers,
         })
     }
 
@@ -93,8 +165,6 @@ impl Connection {
         self.stream.write_all(&response.to_be_bytes()).await?;
         self.stream.flush().await?;
 
-        println!("client {}: sent response: {:?}", self.peer_addr, response);
-
         Ok(())
     }
 
@@ -108,9 +178,22 @@ impl Connection {
                 }
             };
 
-            println!("client {}: parsed request: {:?}", self.peer_addr, request);
-
-            let response = self.build_response(&request);
+            // Handlers append to logs and sync them while holding locks, so
+            // keep them off the runtime threads.
+            let state = Arc::clone(&self.state);
+            let handlers = Arc::clone(&self.handlers);
+            let local_addr = self.local_addr;
+            let response = match tokio::task::spawn_blocking(move || {
+                handlers.handle(&state, local_addr, &request)
+            })
+            .await
+            {
+                Ok(response) => response,
+                Err(e) => {
+                    eprintln!("client {}: error handling request: {}", self.peer_addr, e);
+                    return;
+                }
+            };
 
             if let Err(e) = self.write_response(response).await {
                 eprintln!("error writing response to client {}: {}", self.peer_addr, e);
@@ -119,200 +202,19 @@ impl Connection {
         }
     }
 
+    /// Reads the next request whole, as framed by its size prefix.
     async fn read_request(&mut self) -> Result<RequestV0> {
-        let mut buf = BytesMut::with_capacity(1024);
-        let n = self.stream.read_buf(&mut buf).await?;
-        if n == 0 {
-            return Err(("connection closed").into());
-        }
-
-        println!("client {}: received {} bytes", self.peer_addr, n);
-
-        RequestV0::from_be_bytes(&mut buf)
-    }
-
-    fn build_response(&self, request: &RequestV0) -> ResponseV0 {
-        let response_header = Self::build_response_header(request);
-        let response_body = self.build_response_body(request);
-        let message_size =
-            response_body.to_be_bytes().len() as i32 + response_header.to_be_bytes().len() as i32;
-
-        ResponseV0::new(message_size, response_header, response_body)
-    }
-
-    fn build_response_header(request: &RequestV0) -> ResponseHeader {
-        match request.header().request_api_key() {
-            ApiKey::ApiVersions => {
-                ResponseHeader::V0(ResponseHeaderV0::new(request.header().correlation_id()))
-            }
-            ApiKey::DescribeTopicPartitions => {
-                ResponseHeader::V1(ResponseHeaderV1::new(request.header().correlation_id()))
+        let message_size = match self.stream.read_i32().await {
+            Ok(message_size) => message_size,
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                return Err(("connection closed").into());
             }
-            ApiKey::Fetch => {
-                ResponseHeader::V1(ResponseHeaderV1::new(request.header().correlation_id()))
-            }
-        }
-    }
-
-    fn build_response_body(&self, request: &RequestV0) -> ResponseBody {
-        match request.header().request_api_key() {
-            ApiKey::ApiVersions => Self::build_api_versions_response(request),
-            ApiKey::DescribeTopicPartitions => {
-                self.build_describe_topic_partitions_response(request)
-            }
-            ApiKey::Fetch => self.build_fetch_response(request),
-        }
-    }
-
-    fn build_api_versions_response(request: &RequestV0) -> ResponseBody {
-        let version = request.header().request_api_version();
-        if (0..=4).contains(&version) {
-            ResponseBody::ApiVersionsResponseV4(ApiVersionsResponseBodyV4::new(
-                ErrorCode::None,
-                CompactArray::from_vec(vec![
-                    ApiVersion::new(ApiKey::ApiVersions, 0, 4, CompactArray::new()),
-                    ApiVersion::new(ApiKey::DescribeTopicPartitions, 0, 0, CompactArray::new()),
-                    ApiVersion::new(ApiKey::Fetch, 4, 16, CompactArray::new()),
-                ]),
-                0,
-                CompactArray::new(),
-            ))
-        } else {
-            ResponseBody::ApiVersionsResponseV4(ApiVersionsResponseBodyV4::new(
-                ErrorCode::UnsupportedVersion,
-                CompactArray::new(),
-                0,
-                CompactArray::new(),
-            ))
-        }
-    }
-
-    fn build_describe_topic_partitions_response(&self, request: &RequestV0) -> ResponseBody {
-        let topic_names = request
-            .body()
-            .as_describe_topic_partitions_request_v0()
-            .unwrap_or(&DescribeTopicPartitionsRequestV0::default())
-            .topic_names();
+            Err(e) => return Err(e.into()),
+        };
 
-        let topics = topic_names
-            .into_iter()
-            .map(|topic_name| {
-                let topic_records = self.metadata.find_topic_records_by_topic(&topic_name);
+        let mut buf = handler::request_buffer(message_size)?;
+        self.stream.read_exact(&mut buf[4..]).await?;
 
-                if let Some(record) = topic_records.first() {
-                    let topic_uuid = record
-                        .record_value()
-                        .value()
-                        .as_topic_record()
-                        .unwrap()
-                        .topic_uuid();
-
-                    let partition_records = self
-                        .metadata
-                        .find_partition_records_by_topic_uuid(topic_uuid);
-
-                    let partitions = CompactArray::from_vec(
-                        partition_records
-                            .into_iter()
-                            .map(Partition::from)
-                            .collect::<Vec<Partition>>(),
-                    );
-
-                    Topic::new(
-                        ErrorCode::None,
-                        CompactString::from_str(&topic_name),
-                        topic_uuid,
-                        false,
-                        partitions,
-                        0,
-                        CompactArray::new(),
-                    )
-                } else {
-                    Topic::from_unknown_topic(&topic_name)
-                }
-            })
-            .collect::<Vec<Topic>>();
-
-        ResponseBody::DescribeTopicPartiotionsResponseV0(
-            DescribeTopicPartiotionsResponseBodyV0::new(
-                0,
-                CompactArray::from_vec(topics),
-                u8::MAX,
-                CompactArray::new(),
-            ),
-        )
-    }
-
-    fn build_fetch_response(&self, request: &RequestV0) -> ResponseBody {
-        let topic_id = request
-            .body()
-            .as_fetch_request_v16()
-            .unwrap_or(&FetchRequestV16::default())
-            .topics()
-            .to_vec()
-            .first()
-            .unwrap_or(&TopicsPartitions::default())
-            .topic_id();
-
-        if topic_id.is_nil() {
-            return ResponseBody::FetchResponseV16(FetchResponseBodyV16::default());
-        }
-
-        let topic_records = self.metadata.find_topic_records_by_id(&topic_id);
-
-        if topic_records.is_empty() {
-            println!("No topic records found for topic ID: {}", topic_id);
-            return ResponseBody::FetchResponseV16(FetchResponseBodyV16::unknown_topic(topic_id));
-        }
-
-        let topic_name = topic_records
-            .first()
-            .expect("topic records should not be empty")
-            .record_value()
-            .value()
-            .as_topic_record()
-            .expect("record value should be a topic record")
-            .name();
-
-        if topic_name.is_empty() {
-            println!("Topic name is empty for topic ID: {}", topic_id);
-
-            return ResponseBody::FetchResponseV16(FetchResponseBodyV16::empty_topic(topic_id));
-        }
-
-        let partition_ids = self
-            .metadata
-            .find_partition_record_ids_by_topic_uuid(topic_id);
-
-        match &partition_ids.first() {
-            Some(partition_id) => {
-                let filename = format!(
-                    "/tmp/kraft-combined-logs/{}-{}/00000000000000000000.log",
-                    topic_name, partition_id
-                );
-                let file = File::open(filename);
-                match file {
-                    Err(e) => {
-                        eprintln!(
-                            "Failed to open file for topic: {}, partition: {}, error: {}",
-                            topic_name, partition_id, e
-                        );
-                        ResponseBody::FetchResponseV16(FetchResponseBodyV16::empty_topic(topic_id))
-                    }
-                    Ok(file) => {
-                        let mut reader = BufReader::new(file);
-                        let mut buf = Vec::new();
-                        reader.read_to_end(&mut buf).unwrap();
-                        let bytes = Bytes::from(buf);
-
-                        ResponseBody::FetchResponseV16(FetchResponseBodyV16::with_record_for_topic(
-                            topic_id,
-                            bytes.into(),
-                        ))
-                    }
-                }
-            }
-            _ => ResponseBody::FetchResponseV16(FetchResponseBodyV16::empty_topic(topic_id)),
-        }
+        self.handlers.decode(&mut buf)
     }
 }
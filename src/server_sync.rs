@@ -3,15 +3,8 @@ use std::{
     net::{SocketAddr, TcpListener, TcpStream},
 };
 
-use crate::protocol::{
-    bytes::{FromBytes, ToBytes},
-    primitives::{ApiKey, CompactArray},
-    request::RequestV0,
-    response::{
-        ApiVersion, ApiVersionsResponseBodyV4, ErrorCode, ResponseBody, ResponseHeader,
-        ResponseHeaderV1, ResponseV0,
-    },
-};
+use crate::handler::{self, api_versions::ApiVersionsHandler, HandlerRegistry};
+use crate::protocol::{bytes::ToBytes, request::RequestV0, response::ResponseV0};
 
 use crate::Result;
 
@@ -48,12 +41,21 @@ impl ServerSync {
 struct Connection {
     stream: TcpStream,
     peer_addr: SocketAddr,
+    local_addr: SocketAddr,
+    /// ApiVersions is the only API this server implements.
+    handlers: HandlerRegistry<()>,
 }
 
 impl Connection {
     fn new(stream: TcpStream) -> Result<Self> {
         let peer_addr = stream.peer_addr()?;
-        Ok(Connection { stream, peer_addr })
+        let local_addr = stream.local_addr()?;
+        Ok(Connection {
+            stream,
+            peer_addr,
+            local_addr,
+            handlers: HandlerRegistry::new().register(ApiVersionsHandler),
+        })
     }
 
     fn write_response(&mut self, response: ResponseV0) -> std::io::Result<()> {
@@ -71,9 +73,7 @@ impl Connection {
                 }
             };
 
-            println!("client {}: parsed request: {:?}", self.peer_addr, request);
-
-            let response = self.build_response(&request);
+            let response = self.handlers.handle(&(), self.local_addr, &request);
 
             if let Err(e) = self.write_response(response) {
                 eprintln!("error writing response to client {}: {}", self.peer_addr, e);
@@ -82,44 +82,20 @@ impl Connection {
         }
     }
 
+    /// Reads the next request whole, as framed by its size prefix.
     fn read_request(&mut self) -> Result<RequestV0> {
-        let mut buf = [0; 1024];
-        let n = self.stream.read(&mut buf)?;
-        if n == 0 {
-            return Err(("connection closed").into());
+        let mut message_size = [0; 4];
+        match self.stream.read_exact(&mut message_size) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                return Err(("connection closed").into());
+            }
+            Err(e) => return Err(e.into()),
         }
 
-        println!("client {}: received {} bytes", self.peer_addr, n);
-
-        let rdr = &mut std::io::Cursor::new(&buf[..n]);
-
-        RequestV0::from_be_bytes(rdr)
-    }
-
-    fn build_response(&self, request: &RequestV0) -> ResponseV0 {
-        let response_body = match request.header().request_api_version() {
-            0..=4 => ApiVersionsResponseBodyV4::new(
-                ErrorCode::None,
-                CompactArray::from_vec(vec![
-                    ApiVersion::new(ApiKey::ApiVersions, 0, 4, CompactArray::new()),
-                    ApiVersion::new(ApiKey::DescribeTopicPartitions, 0, 0, CompactArray::new()),
-                ]),
-                0,
-                CompactArray::new(),
-            ),
-            _ => ApiVersionsResponseBodyV4::new(
-                ErrorCode::UnsupportedVersion,
-                CompactArray::new(),
-                0,
-                CompactArray::new(),
-            ),
-        };
+        let mut buf = handler::request_buffer(i32::from_be_bytes(message_size))?;
+        self.stream.read_exact(&mut buf[4..])?;
 
-        let response_header = ResponseHeaderV1::new(request.header().correlation_id());
-        ResponseV0::new(
-            response_body.to_be_bytes().len() as i32 + response_header.to_be_bytes().len() as i32,
-            ResponseHeader::V1(response_header),
-            ResponseBody::ApiVersionsResponseV4(response_body),
-        )
+        self.handlers.decode(&mut buf)
     }
 }
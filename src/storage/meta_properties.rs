@@ -0,0 +1,233 @@
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use uuid::Uuid;
+
+use super::LogDir;
+
+pub(crate) const META_PROPERTIES_FILE: &str = "meta.properties";
+
+/// `meta.properties` version written for KRaft log directories.
+const KRAFT_VERSION: i32 = 1;
+
+const BASE64_URL_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// The identity of a log directory, stored as `meta.properties` in it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct MetaProperties {
+    pub(crate) version: i32,
+    pub(crate) cluster_id: String,
+    pub(crate) node_id: i32,
+    pub(crate) directory_id: Option<Uuid>,
+}
+
+impl MetaProperties {
+    pub(crate) fn new(cluster_id: &str, node_id: i32, directory_id: Uuid) -> Self {
+        Self {
+            version: KRAFT_VERSION,
+            cluster_id: cluster_id.to_string(),
+            node_id,
+            directory_id: Some(directory_id),
+        }
+    }
+
+    /// Reads the `meta.properties` of `dir`, or `None` when the directory
+    /// hasn't been formatted.
+    pub(crate) fn read(dir: &Path) -> std::io::Result<Option<Self>> {
+        let path = dir.join(META_PROPERTIES_FILE);
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let values = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| line.split_once('='))
+            .map(|(key, value)| (key.trim(), value.trim()))
+            .collect::<Vec<(&str, &str)>>();
+        let get = |name: &str| {
+            values
+                .iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| *value)
+        };
+        let invalid = |message: String| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("invalid {}: {}", path.display(), message),
+            )
+        };
+
+        let version = get("version")
+            .ok_or_else(|| invalid("missing version".to_string()))?
+            .parse()
+            .map_err(|e| invalid(format!("invalid version: {}", e)))?;
+        let cluster_id = get("cluster.id")
+            .ok_or_else(|| invalid("missing cluster.id".to_string()))?
+            .to_string();
+        let node_id = get("node.id")
+            .ok_or_else(|| invalid("missing node.id".to_string()))?
+            .parse()
+            .map_err(|e| invalid(format!("invalid node.id: {}", e)))?;
+        let directory_id = get("directory.id")
+            .map(|value| {
+                decode_uuid(value).ok_or_else(|| invalid(format!("invalid directory.id {}", value)))
+            })
+            .transpose()?;
+
+        Ok(Some(Self {
+            version,
+            cluster_id,
+            node_id,
+            directory_id,
+        }))
+    }
+
+    pub(crate) fn write(&self, dir: &Path) -> std::io::Result<()> {
+        fs::create_dir_all(dir)?;
+
+        let mut contents = String::from("#\n");
+        contents.push_str(&format!("node.id={}\n", self.node_id));
+        if let Some(directory_id) = self.directory_id {
+            contents.push_str(&format!("directory.id={}\n", encode_uuid(directory_id)));
+        }
+        contents.push_str(&format!("version={}\n", self.version));
+        contents.push_str(&format!("cluster.id={}\n", self.cluster_id));
+
+        let mut file = fs::File::create(dir.join(META_PROPERTIES_FILE))?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()
+    }
+}
+
+/// Reads the `meta.properties` of every log directory and checks they were
+/// formatted for one cluster and for `node_id`, returning the cluster id and
+/// the directories in the order of `paths`. Directories formatted without a
+/// `directory.id` are given one.
+pub(crate) fn load_log_dirs(
+    paths: &[PathBuf],
+    node_id: i32,
+) -> std::io::Result<(String, Vec<LogDir>)> {
+    let invalid = |message: String| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+
+    let mut cluster_id: Option<String> = None;
+    let mut log_dirs: Vec<LogDir> = Vec::new();
+    for path in paths {
+        let mut properties = MetaProperties::read(path)?.ok_or_else(|| {
+            invalid(format!(
+                "No {} found in {} (have you run `format` to format the directory?)",
+                META_PROPERTIES_FILE,
+                path.display()
+            ))
+        })?;
+
+        if properties.version != KRAFT_VERSION {
+            return Err(invalid(format!(
+                "Unsupported {} version {} in {}",
+                META_PROPERTIES_FILE,
+                properties.version,
+                path.display()
+            )));
+        }
+        if properties.node_id != node_id {
+            return Err(invalid(format!(
+                "Stored node id {} doesn't match the configured node id {} in {}",
+                properties.node_id,
+                node_id,
+                path.display()
+            )));
+        }
+        match &cluster_id {
+            Some(expected) if *expected != properties.cluster_id => {
+                return Err(invalid(format!(
+                    "Invalid cluster.id in {}. Expected {}, but read {}",
+                    path.display(),
+                    expected,
+                    properties.cluster_id
+                )));
+            }
+            Some(_) => {}
+            None => cluster_id = Some(properties.cluster_id.clone()),
+        }
+
+        let id = match properties.directory_id {
+            Some(id) => id,
+            None => {
+                let id = random_uuid();
+                properties.directory_id = Some(id);
+                properties.write(path)?;
+                id
+            }
+        };
+        if log_dirs.iter().any(|log_dir| log_dir.id == id) {
+            return Err(invalid(format!(
+                "Duplicate directory.id {} in {}",
+                encode_uuid(id),
+                path.display()
+            )));
+        }
+
+        log_dirs.push(LogDir {
+            path: path.clone(),
+            id,
+        });
+    }
+
+    let cluster_id = cluster_id.ok_or_else(|| invalid("No log directories".to_string()))?;
+
+    Ok((cluster_id, log_dirs))
+}
+
+/// A random id in Kafka's string form, as used for cluster and directory ids.
+/// Ids starting with `-` are avoided so they can't be mistaken for options.
+pub(crate) fn random_uuid() -> Uuid {
+    loop {
+        let uuid = Uuid::new_v4();
+        if !encode_uuid(uuid).starts_with('-') {
+            return uuid;
+        }
+    }
+}
+
+/// Encodes `uuid` as unpadded URL-safe base64, the form Kafka uses for ids.
+pub(crate) fn encode_uuid(uuid: Uuid) -> String {
+    let mut encoded = String::with_capacity(22);
+    for chunk in uuid.as_bytes().chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, byte)| {
+            bits | (*byte as u32) << (16 - 8 * i)
+        });
+        for i in 0..=chunk.len() {
+            encoded.push(BASE64_URL_ALPHABET[(bits >> (18 - 6 * i) & 0x3f) as usize] as char);
+        }
+    }
+
+    encoded
+}
+
+/// Decodes an id written by [`encode_uuid`].
+pub(crate) fn decode_uuid(encoded: &str) -> Option<Uuid> {
+    if encoded.len() != 22 {
+        return None;
+    }
+
+    let mut bytes = Vec::with_capacity(16);
+    for chunk in encoded.as_bytes().chunks(4) {
+        let mut bits = 0u32;
+        for (i, c) in chunk.iter().enumerate() {
+            let value = BASE64_URL_ALPHABET.iter().position(|a| a == c)? as u32;
+            bits |= value << (18 - 6 * i);
+        }
+        for i in 0..chunk.len() - 1 {
+            bytes.push((bits >> (16 - 8 * i)) as u8);
+        }
+    }
+
+    Uuid::from_slice(&bytes).ok()
+}
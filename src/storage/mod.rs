@@ -0,0 +1,536 @@
+pub(crate) mod meta_properties;
+pub(crate) mod partition_log;
+
+pub(crate) mod txn_index;
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+};
+
+use partition_log::{segment_base_offsets, segment_file_name, PartitionLog};
+use uuid::Uuid;
+
+use crate::metadata::controller::{CLUSTER_METADATA_PARTITION, CLUSTER_METADATA_TOPIC};
+
+/// Directory holding every partition log as well as `__cluster_metadata`,
+/// unless `log.dirs` says otherwise.
+pub(crate) const LOG_DIR: &str = "/tmp/kraft-combined-logs";
+
+/// A configured log directory and the `directory.id` it was formatted with.
+#[derive(Debug, Clone)]
+pub(crate) struct LogDir {
+    pub(crate) path: PathBuf,
+    pub(crate) id: Uuid,
+}
+
+/// The log of a partition and the directory it is in, once opened.
+type LogSlot = Arc<Mutex<Option<(Uuid, PartitionLog)>>>;
+
+/// A partition log found in a log directory, with the size of its segments.
+#[derive(Debug, Clone)]
+pub(crate) struct PartitionSize {
+    pub(crate) topic: String,
+    pub(crate) partition: i32,
+    pub(crate) size: u64,
+}
+
+/// Keeps one open [`PartitionLog`] per topic partition so appends from
+/// different connections are serialized through a single writer. Each
+/// partition has a lock of its own, so partitions are read and written in
+/// parallel.
+///
+/// Partitions are spread over several log directories. A partition stays in
+/// the directory it was created in, and the metadata log is always kept in
+/// the first one.
+///
+/// An I/O error in a directory takes the whole directory offline: its
+/// partitions fail with an error from then on while the other directories
+/// keep serving. Like in Kafka, a corrupt or truncated segment counts as an
+/// I/O error, since the disk can no longer be trusted. The metadata log fails on its own, apart from the partitions next to it,
+/// and the broker stops once it has.
+#[derive(Debug)]
+pub(crate) struct LogManager {
+    log_dirs: Vec<LogDir>,
+    logs: Mutex<HashMap<(String, i32), LogSlot>>,
+    /// How many partitions each directory holds, for placing new ones.
+    partition_counts: Mutex<HashMap<Uuid, usize>>,
+    offline_dirs: Mutex<HashSet<Uuid>>,
+    metadata_log_failed: AtomicBool,
+}
+
+impl LogManager {
+    pub(crate) fn new(log_dirs: Vec<LogDir>) -> Self {
+        assert!(!log_dirs.is_empty(), "at least one log directory is needed");
+
+        let logs = Self {
+            log_dirs,
+            logs: Mutex::new(HashMap::new()),
+            partition_counts: Mutex::new(HashMap::new()),
+            offline_dirs: Mutex::new(HashSet::new()),
+            metadata_log_failed: AtomicBool::new(false),
+        };
+
+        for log_dir in &logs.log_dirs {
+            match count_partitions(log_dir) {
+                Ok(count) => {
+                    logs.lock_partition_counts().insert(log_dir.id, count);
+                }
+                Err(e) => {
+                    logs.take_offline(log_dir, e);
+                }
+            }
+        }
+
+        logs
+    }
+
+    pub(crate) fn log_dirs(&self) -> &[LogDir] {
+        &self.log_dirs
+    }
+
+    pub(crate) fn is_online(&self, log_dir: &LogDir) -> bool {
+        !self.lock_offline_dirs().contains(&log_dir.id)
+    }
+
+    /// Whether `directory_id` is one of these log directories and offline.
+    pub(crate) fn is_offline(&self, directory_id: Uuid) -> bool {
+        self.lock_offline_dirs().contains(&directory_id)
+    }
+
+    /// Whether the metadata log has failed, after which the broker can't
+    /// follow the cluster and has to stop.
+    pub(crate) fn metadata_log_failed(&self) -> bool {
+        self.metadata_log_failed.load(Ordering::Relaxed)
+    }
+
+    /// Runs `f` against the log of `topic`-`partition`, opening (and creating)
+    /// it on first use. An I/O error from `f` takes the directory of the
+    /// partition offline, or fails the metadata log when that is the log.
+    pub(crate) fn with_log<T>(
+        &self,
+        topic: &str,
+        partition: i32,
+        f: impl FnOnce(&mut PartitionLog) -> std::io::Result<T>,
+    ) -> std::io::Result<T> {
+        let is_metadata_log = topic == CLUSTER_METADATA_TOPIC;
+        if is_metadata_log && self.metadata_log_failed() {
+            return Err(std::io::Error::other("the metadata log has failed"));
+        }
+
+        let slot = Arc::clone(
+            self.lock_logs()
+                .entry((topic.to_string(), partition))
+                .or_default(),
+        );
+        let mut slot = slot.lock().expect("partition log lock poisoned");
+
+        // Logs of a directory that went offline are closed on next use.
+        if let Some((directory_id, _)) = &*slot {
+            if !is_metadata_log && self.lock_offline_dirs().contains(directory_id) {
+                *slot = None;
+            }
+        }
+
+        let fail = |log_dir: &LogDir, e| match is_metadata_log {
+            true => self.fail_metadata_log(e),
+            false => self.take_offline(log_dir, e),
+        };
+
+        if slot.is_none() {
+            let log_dir = self.locate(topic, partition)?;
+            let log = PartitionLog::open(&log_dir.path, topic, partition)
+                .map_err(|e| fail(log_dir, e))?;
+            *slot = Some((log_dir.id, log));
+        }
+
+        let (directory_id, log) = slot.as_mut().expect("log was just opened");
+        let directory_id = *directory_id;
+        f(log).map_err(
+            |e| match self.log_dirs.iter().find(|dir| dir.id == directory_id) {
+                Some(log_dir) => fail(log_dir, e),
+                None => e,
+            },
+        )
+    }
+
+    /// Creates the directory of `topic`-`partition` in the log directory
+    /// `directory_id`, unless the partition already exists in some directory.
+    /// An unknown `directory_id` places it like any other new partition.
+    pub(crate) fn create_log(
+        &self,
+        topic: &str,
+        partition: i32,
+        directory_id: Uuid,
+    ) -> std::io::Result<()> {
+        let name = partition_dir_name(topic, partition);
+        if self.find(&name).is_some() {
+            return Ok(());
+        }
+
+        let log_dir = match self.log_dirs.iter().find(|dir| dir.id == directory_id) {
+            // The partition stays offline along with its directory.
+            Some(log_dir) if !self.is_online(log_dir) => return Ok(()),
+            Some(log_dir) => log_dir,
+            None => self.least_loaded(&[])?,
+        };
+        fs::create_dir_all(log_dir.path.join(name)).map_err(|e| self.take_offline(log_dir, e))?;
+        self.count_partition(log_dir, true);
+
+        Ok(())
+    }
+
+    /// Picks a log directory for each of `count` new partitions, spreading
+    /// them so every directory ends up holding about as many partitions.
+    pub(crate) fn assign_directories(&self, count: usize) -> std::io::Result<Vec<Uuid>> {
+        let mut assigned = Vec::with_capacity(count);
+        for _ in 0..count {
+            assigned.push(self.least_loaded(&assigned)?.id);
+        }
+
+        Ok(assigned)
+    }
+
+    /// The directory holding the segments and snapshots of the metadata log.
+    pub(crate) fn metadata_log_dir(&self) -> PathBuf {
+        self.log_dirs[0].path.join(partition_dir_name(
+            CLUSTER_METADATA_TOPIC,
+            CLUSTER_METADATA_PARTITION,
+        ))
+    }
+
+    /// The partition logs in `log_dir` other than the metadata log.
+    pub(crate) fn partition_sizes(&self, log_dir: &LogDir) -> std::io::Result<Vec<PartitionSize>> {
+        if !self.is_online(log_dir) {
+            return Err(offline_error(log_dir));
+        }
+
+        self.scan_partitions(log_dir)
+            .map_err(|e| self.take_offline(log_dir, e))
+    }
+
+    fn scan_partitions(&self, log_dir: &LogDir) -> std::io::Result<Vec<PartitionSize>> {
+        let mut partitions = Vec::new();
+        for entry in fs::read_dir(&log_dir.path)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let Some((topic, partition)) = name.to_str().and_then(parse_partition_dir_name) else {
+                continue;
+            };
+            if !entry.file_type()?.is_dir() || topic == CLUSTER_METADATA_TOPIC {
+                continue;
+            }
+
+            let dir = entry.path();
+            let mut size = 0;
+            for base_offset in segment_base_offsets(&dir)? {
+                size += fs::metadata(dir.join(segment_file_name(base_offset)))?.len();
+            }
+
+            partitions.push(PartitionSize {
+                topic,
+                partition,
+                size,
+            });
+        }
+        partitions.sort_by(|a, b| (&a.topic, a.partition).cmp(&(&b.topic, b.partition)));
+
+        Ok(partitions)
+    }
+
+    /// Closes the log of `topic`-`partition` and deletes its directory.
+    pub(crate) fn remove_log(&self, topic: &str, partition: i32) -> std::io::Result<()> {
+        let slot = self.lock_logs().remove(&(topic.to_string(), partition));
+        // Waits for whoever is using the log before deleting it under them.
+        let _closed = slot.as_ref().map(|slot| {
+            let mut slot = slot.lock().expect("partition log lock poisoned");
+            *slot = None;
+            slot
+        });
+
+        let Some(log_dir) = self.find(&partition_dir_name(topic, partition)) else {
+            return Ok(());
+        };
+        match fs::remove_dir_all(log_dir.path.join(partition_dir_name(topic, partition))) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => {
+                self.count_partition(log_dir, false);
+                Ok(())
+            }
+        }
+    }
+
+    /// The log directory `topic`-`partition` is in, or the one it should be
+    /// created in.
+    fn locate(&self, topic: &str, partition: i32) -> std::io::Result<&LogDir> {
+        // The metadata log outlives the partitions of its directory going
+        // offline, until it fails itself.
+        if topic == CLUSTER_METADATA_TOPIC {
+            return Ok(&self.log_dirs[0]);
+        }
+
+        if let Some(log_dir) = self.find(&partition_dir_name(topic, partition)) {
+            return Ok(log_dir);
+        }
+        // A partition missing from the online directories may live in an
+        // offline one, and creating it again elsewhere would lose its data.
+        if let Some(log_dir) = self.log_dirs.iter().find(|dir| !self.is_online(dir)) {
+            return Err(offline_error(log_dir));
+        }
+
+        let log_dir = self.least_loaded(&[])?;
+        self.count_partition(log_dir, true);
+
+        Ok(log_dir)
+    }
+
+    /// The online directory holding `partition_dir_name`.
+    fn find(&self, partition_dir_name: &str) -> Option<&LogDir> {
+        self.log_dirs
+            .iter()
+            .filter(|dir| self.is_online(dir))
+            .find(|dir| dir.path.join(partition_dir_name).is_dir())
+    }
+
+    /// The online directory holding the fewest partitions, counting those in
+    /// `pending` as already placed. Ties go to the earliest directory.
+    fn least_loaded(&self, pending: &[Uuid]) -> std::io::Result<&LogDir> {
+        let partition_counts = self.lock_partition_counts();
+        let mut least: Option<(usize, &LogDir)> = None;
+        for log_dir in self.log_dirs.iter().filter(|dir| self.is_online(dir)) {
+            let count = partition_counts.get(&log_dir.id).copied().unwrap_or(0)
+                + pending.iter().filter(|id| **id == log_dir.id).count();
+
+            if least.map(|(fewest, _)| count < fewest).unwrap_or(true) {
+                least = Some((count, log_dir));
+            }
+        }
+
+        least
+            .map(|(_, log_dir)| log_dir)
+            .ok_or_else(|| std::io::Error::other("all log directories are offline"))
+    }
+
+    /// Marks the metadata log failed after `e` failed reading or writing it,
+    /// handing `e` back to the caller.
+    pub(crate) fn fail_metadata_log(&self, e: std::io::Error) -> std::io::Error {
+        if self.metadata_log_failed.swap(true, Ordering::Relaxed) {
+            return e;
+        }
+
+        eprintln!(
+            "Shutting down because the metadata log in {} has failed: {}",
+            self.log_dirs[0].path.display(),
+            e
+        );
+
+        e
+    }
+
+    /// Takes `log_dir` offline after `e` failed an operation in it, handing
+    /// `e` back to the caller.
+    fn take_offline(&self, log_dir: &LogDir, e: std::io::Error) -> std::io::Error {
+        let mut offline_dirs = self.lock_offline_dirs();
+        if !offline_dirs.insert(log_dir.id) {
+            return e;
+        }
+
+        eprintln!(
+            "Log directory {} is offline due to an I/O error: {}",
+            log_dir.path.display(),
+            e
+        );
+        eprintln!(
+            "OfflineLogDirectoryCount: {} of {} log directories",
+            offline_dirs.len(),
+            self.log_dirs.len()
+        );
+
+        e
+    }
+
+    /// Counts a partition directory created in, or removed from, `log_dir`.
+    fn count_partition(&self, log_dir: &LogDir, created: bool) {
+        let mut partition_counts = self.lock_partition_counts();
+        let count = partition_counts.entry(log_dir.id).or_default();
+        *count = match created {
+            true => *count + 1,
+            false => count.saturating_sub(1),
+        };
+    }
+
+    fn lock_logs(&self) -> MutexGuard<'_, HashMap<(String, i32), LogSlot>> {
+        self.logs.lock().expect("log manager lock poisoned")
+    }
+
+    fn lock_partition_counts(&self) -> MutexGuard<'_, HashMap<Uuid, usize>> {
+        self.partition_counts
+            .lock()
+            .expect("partition counts lock poisoned")
+    }
+
+    fn lock_offline_dirs(&self) -> MutexGuard<'_, HashSet<Uuid>> {
+        self.offline_dirs
+            .lock()
+            .expect("offline log dirs lock poisoned")
+    }
+}
+
+/// The partition directories in `log_dir`, other than the metadata log.
+fn count_partitions(log_dir: &LogDir) -> std::io::Result<usize> {
+    let entries = match fs::read_dir(&log_dir.path) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+
+    let mut count = 0;
+    for entry in entries {
+        let is_partition = entry?
+            .file_name()
+            .to_str()
+            .and_then(parse_partition_dir_name)
+            .is_some_and(|(topic, _)| topic != CLUSTER_METADATA_TOPIC);
+        count += usize::from(is_partition);
+    }
+
+    Ok(count)
+}
+
+fn offline_error(log_dir: &LogDir) -> std::io::Error {
+    std::io::Error::other(format!(
+        "log directory {} is offline",
+        log_dir.path.display()
+    ))
+}
+
+fn partition_dir_name(topic: &str, partition: i32) -> String {
+    format!("{}-{}", topic, partition)
+}
+
+fn parse_partition_dir_name(name: &str) -> Option<(String, i32)> {
+    let (topic, partition) = name.rsplit_once('-')?;
+    Some((topic.to_string(), partition.parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A log manager over a single, freshly created log directory.
+    fn log_manager() -> LogManager {
+        let path = std::env::temp_dir().join(format!("log-manager-{}", Uuid::new_v4()));
+        fs::create_dir_all(&path).unwrap();
+
+        LogManager::new(vec![LogDir {
+            path,
+            id: Uuid::new_v4(),
+        }])
+    }
+
+    /// Puts a directory where the first segment of `topic`-`partition`
+    /// belongs, so reading it fails with an I/O error.
+    fn break_log(logs: &LogManager, topic: &str, partition: i32) {
+        fs::create_dir_all(
+            logs.log_dirs()[0]
+                .path
+                .join(partition_dir_name(topic, partition))
+                .join(segment_file_name(0)),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn partitions_are_locked_apart() {
+        let logs = log_manager();
+
+        let nested = logs.with_log("foo", 0, |_| logs.with_log("foo", 1, |log| log.read_all()));
+        assert!(nested.is_ok());
+
+        fs::remove_dir_all(&logs.log_dirs()[0].path).unwrap();
+    }
+
+    #[test]
+    fn new_partitions_go_to_the_directory_holding_fewest() {
+        let dirs: Vec<LogDir> = (0..2)
+            .map(|_| LogDir {
+                path: std::env::temp_dir().join(format!("log-manager-{}", Uuid::new_v4())),
+                id: Uuid::new_v4(),
+            })
+            .collect();
+        fs::create_dir_all(dirs[0].path.join(partition_dir_name("foo", 0))).unwrap();
+        let logs = LogManager::new(dirs.clone());
+
+        assert_eq!(
+            logs.assign_directories(3).unwrap(),
+            vec![dirs[1].id, dirs[0].id, dirs[1].id]
+        );
+        logs.create_log("bar", 0, dirs[1].id).unwrap();
+        logs.create_log("bar", 1, dirs[1].id).unwrap();
+        assert_eq!(logs.assign_directories(1).unwrap(), vec![dirs[0].id]);
+        logs.remove_log("bar", 0).unwrap();
+        logs.remove_log("bar", 1).unwrap();
+        assert_eq!(logs.assign_directories(1).unwrap(), vec![dirs[1].id]);
+
+        for dir in dirs {
+            let _ = fs::remove_dir_all(dir.path);
+        }
+    }
+
+    #[test]
+    fn corrupt_data_takes_the_directory_offline() {
+        let logs = log_manager();
+        let partition_dir = logs.log_dirs()[0].path.join(partition_dir_name("foo", 0));
+        fs::create_dir_all(&partition_dir).unwrap();
+        let mut batch = 0i64.to_be_bytes().to_vec();
+        batch.extend((-1i32).to_be_bytes());
+        fs::write(partition_dir.join(segment_file_name(0)), batch).unwrap();
+
+        let e = logs.with_log("foo", 0, |log| log.read_all()).unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::InvalidData);
+        assert!(!logs.is_online(&logs.log_dirs()[0]));
+        assert!(logs.with_log("bar", 0, |log| log.read_all()).is_err());
+
+        fs::remove_dir_all(&logs.log_dirs()[0].path).unwrap();
+    }
+
+    #[test]
+    fn partition_failure_keeps_the_metadata_log_next_to_it() {
+        let logs = log_manager();
+        break_log(&logs, "foo", 0);
+
+        assert!(logs.with_log("foo", 0, |log| log.read_all()).is_err());
+        assert!(!logs.is_online(&logs.log_dirs()[0]));
+        assert!(logs.with_log("bar", 0, |log| log.read_all()).is_err());
+        assert!(logs
+            .with_log(CLUSTER_METADATA_TOPIC, CLUSTER_METADATA_PARTITION, |log| {
+                log.read_all()
+            })
+            .is_ok());
+        assert!(!logs.metadata_log_failed());
+
+        fs::remove_dir_all(&logs.log_dirs()[0].path).unwrap();
+    }
+
+    #[test]
+    fn metadata_log_failure_keeps_the_partitions_next_to_it() {
+        let logs = log_manager();
+        break_log(&logs, CLUSTER_METADATA_TOPIC, CLUSTER_METADATA_PARTITION);
+
+        assert!(logs
+            .with_log(CLUSTER_METADATA_TOPIC, CLUSTER_METADATA_PARTITION, |log| {
+                log.read_all()
+            })
+            .is_err());
+        assert!(logs.metadata_log_failed());
+        assert!(logs.is_online(&logs.log_dirs()[0]));
+        assert!(logs.with_log("foo", 0, |log| log.read_all()).is_ok());
+
+        fs::remove_dir_all(&logs.log_dirs()[0].path).unwrap();
+    }
+}
@@ -0,0 +1,615 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use bytes::{Buf, Bytes, BytesMut};
+
+use crate::protocol::{
+    bytes::ToBytes, cluster_metadata::Batch, control_record::ControlRecordValue,
+};
+
+use super::txn_index::{AbortedTxn, TxnIndex};
+
+/// The base offset and batch length that precede every batch.
+const BATCH_HEADER_SIZE: usize = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum IsolationLevel {
+    ReadUncommitted,
+    ReadCommitted,
+}
+
+impl From<i8> for IsolationLevel {
+    fn from(value: i8) -> Self {
+        match value {
+            1 => Self::ReadCommitted,
+            _ => Self::ReadUncommitted,
+        }
+    }
+}
+
+/// Result of reading a partition from a given offset.
+#[derive(Debug, Default)]
+pub(crate) struct FetchData {
+    pub(crate) records: Bytes,
+    pub(crate) high_watermark: i64,
+    pub(crate) last_stable_offset: i64,
+    pub(crate) log_start_offset: i64,
+    /// Only populated for `read_committed` fetches.
+    pub(crate) aborted_transactions: Vec<AbortedTxn>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct BatchPosition {
+    last_offset: i64,
+    position: usize,
+    size: usize,
+}
+
+/// A segment file, the batches in it and its `.txnindex`.
+#[derive(Debug)]
+struct Segment {
+    base_offset: i64,
+    path: PathBuf,
+    batches: Vec<BatchPosition>,
+    txn_index: TxnIndex,
+}
+
+impl Segment {
+    /// Reads `len` bytes of the segment starting at `position`.
+    fn read_range(&self, position: usize, len: usize) -> std::io::Result<Bytes> {
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(position as u64))?;
+        let mut buf = vec![0; len];
+        file.read_exact(&mut buf)?;
+
+        Ok(Bytes::from(buf))
+    }
+
+    fn read_all(&self) -> std::io::Result<Bytes> {
+        match File::open(&self.path) {
+            Ok(mut file) => {
+                let mut buf = Vec::new();
+                file.read_to_end(&mut buf)?;
+                Ok(Bytes::from(buf))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Bytes::new()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Append-only log of a single topic partition, kept as segment files in
+/// `<log dir>/<topic>-<partition>/`, each with the `.txnindex` of the aborted
+/// transactions it contains. Appends go to the newest segment.
+#[derive(Debug)]
+pub(crate) struct PartitionLog {
+    dir: PathBuf,
+    /// Every segment, oldest first. There is always at least one.
+    segments: Vec<Segment>,
+    log_end_offset: i64,
+    /// First offset of the open transaction of each producer.
+    ongoing_transactions: HashMap<i64, i64>,
+}
+
+impl PartitionLog {
+    pub(crate) fn open(log_dir: &Path, topic: &str, partition: i32) -> std::io::Result<Self> {
+        let dir = log_dir.join(format!("{}-{}", topic, partition));
+        fs::create_dir_all(&dir)?;
+
+        let mut base_offsets = segment_base_offsets(&dir)?;
+        if base_offsets.is_empty() {
+            base_offsets.push(0);
+        }
+
+        let mut log = Self {
+            dir,
+            segments: Vec::new(),
+            log_end_offset: base_offsets[0],
+            ongoing_transactions: HashMap::new(),
+        };
+
+        // The segments are the source of truth, so each index is rebuilt from
+        // its segment in case the broker stopped between writing a marker and
+        // its entry. Transactions may span segments, so they are replayed in
+        // order.
+        for base_offset in base_offsets {
+            let txn_index_path = log.dir.join(txn_index_file_name(base_offset));
+            log.log_end_offset = log.log_end_offset.max(base_offset);
+            log.segments.push(Segment {
+                base_offset,
+                path: log.dir.join(segment_file_name(base_offset)),
+                batches: Vec::new(),
+                txn_index: TxnIndex::empty(&txn_index_path),
+            });
+
+            let aborted = log.recover()?;
+            log.active_segment().txn_index = TxnIndex::create(&txn_index_path, aborted)?;
+        }
+
+        Ok(log)
+    }
+
+    /// Assigns the next offsets to `batch`, appends it to the newest segment
+    /// and returns its base offset.
+    pub(crate) fn append<V: ToBytes>(&mut self, mut batch: Batch<V>) -> std::io::Result<i64> {
+        let base_offset = self.log_end_offset;
+        batch.set_base_offset(base_offset);
+        let encoded = batch.to_be_bytes();
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.active_segment().path)?;
+        let position = file.metadata()?.len() as usize;
+        file.write_all(&encoded)?;
+        file.sync_data()?;
+
+        let batch = Batch::<Bytes>::try_from(&mut encoded.clone())?;
+        if let Some(aborted) = self.observe_batch(&batch, position, encoded.len()) {
+            self.active_segment().txn_index.append(aborted)?;
+        }
+
+        Ok(base_offset)
+    }
+
+    /// Picks up the batches another writer appended to the segments since
+    /// they were last read, returning whether there were any. A batch still
+    /// being written is left for a later call.
+    pub(crate) fn catch_up(&mut self) -> std::io::Result<bool> {
+        let log_end_offset = self.log_end_offset;
+        let active_base_offset = self.active_segment().base_offset;
+        let mut newer_segments = segment_base_offsets(&self.dir)?
+            .into_iter()
+            .filter(|base_offset| *base_offset > active_base_offset);
+
+        loop {
+            let segment = self.active_segment();
+            let read = segment
+                .batches
+                .last()
+                .map_or(0, |batch| batch.position + batch.size);
+            let contents = segment.read_all()?;
+            let mut bytes = contents.slice(read.min(contents.len())..);
+
+            while holds_whole_batch(&bytes)? {
+                let position = contents.len() - bytes.remaining();
+                let batch = Batch::<Bytes>::try_from(&mut bytes)?;
+                let size = contents.len() - bytes.remaining() - position;
+                if let Some(aborted) = self.observe_batch(&batch, position, size) {
+                    self.active_segment().txn_index.append(aborted)?;
+                }
+            }
+
+            let Some(base_offset) = newer_segments.next() else {
+                break;
+            };
+            self.log_end_offset = self.log_end_offset.max(base_offset);
+            self.segments.push(Segment {
+                base_offset,
+                path: self.dir.join(segment_file_name(base_offset)),
+                batches: Vec::new(),
+                txn_index: TxnIndex::empty(&self.dir.join(txn_index_file_name(base_offset))),
+            });
+        }
+
+        Ok(self.log_end_offset != log_end_offset)
+    }
+
+    /// Starts a new, empty segment at the log end offset that later appends go
+    /// to. Does nothing when the current segment is still empty.
+    pub(crate) fn roll(&mut self) -> std::io::Result<()> {
+        if self.active_segment().batches.is_empty() {
+            return Ok(());
+        }
+
+        let base_offset = self.log_end_offset;
+        let path = self.dir.join(segment_file_name(base_offset));
+        File::create(&path)?;
+        let txn_index =
+            TxnIndex::create(&self.dir.join(txn_index_file_name(base_offset)), Vec::new())?;
+        self.segments.push(Segment {
+            base_offset,
+            path,
+            batches: Vec::new(),
+            txn_index,
+        });
+
+        Ok(())
+    }
+
+    /// Deletes the segments, other than the one being written, holding only
+    /// offsets below `offset`.
+    pub(crate) fn delete_segments_before(&mut self, offset: i64) -> std::io::Result<()> {
+        while self.segments.len() > 1 && self.segments[1].base_offset <= offset {
+            let base_offset = self.segments[0].base_offset;
+            for path in [
+                self.dir.join(segment_file_name(base_offset)),
+                self.dir.join(txn_index_file_name(base_offset)),
+            ] {
+                match fs::remove_file(path) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+                    _ => {}
+                }
+            }
+            self.segments.remove(0);
+        }
+
+        Ok(())
+    }
+
+    /// Reads the batches containing offsets at or after `fetch_offset` from
+    /// the segment holding it, or the first segment after it, up to
+    /// `max_bytes`. With `min_one_batch` the first batch is returned even when
+    /// it is larger, so a consumer can get past it. With `read_committed` the
+    /// read stops at the last stable offset and the aborted transactions in
+    /// range are returned alongside the records.
+    pub(crate) fn read(
+        &self,
+        fetch_offset: i64,
+        isolation_level: IsolationLevel,
+        max_bytes: usize,
+        min_one_batch: bool,
+    ) -> std::io::Result<FetchData> {
+        let last_stable_offset = self.last_stable_offset();
+        let upper_bound = match isolation_level {
+            IsolationLevel::ReadCommitted => last_stable_offset,
+            IsolationLevel::ReadUncommitted => self.log_end_offset,
+        };
+
+        let mut records = Bytes::new();
+        if let Some(segment) = self.segments.iter().find(|segment| {
+            segment
+                .batches
+                .last()
+                .is_some_and(|batch| batch.last_offset >= fetch_offset)
+        }) {
+            let start = segment
+                .batches
+                .partition_point(|batch| batch.last_offset < fetch_offset);
+            let upper = segment
+                .batches
+                .partition_point(|batch| batch.last_offset < upper_bound);
+            let mut end = start;
+            while end < upper {
+                let batch = segment.batches[end];
+                let read_bytes = batch.position + batch.size - segment.batches[start].position;
+                if read_bytes > max_bytes && !(min_one_batch && end == start) {
+                    break;
+                }
+                end += 1;
+            }
+            if start < end {
+                let first = segment.batches[start];
+                let last = segment.batches[end - 1];
+                records = segment
+                    .read_range(first.position, last.position + last.size - first.position)?;
+            }
+        }
+
+        let aborted_transactions = match isolation_level {
+            IsolationLevel::ReadCommitted => self
+                .segments
+                .iter()
+                .flat_map(|segment| segment.txn_index.collect_aborted(fetch_offset, upper_bound))
+                .collect(),
+            IsolationLevel::ReadUncommitted => Vec::new(),
+        };
+
+        Ok(FetchData {
+            records,
+            high_watermark: self.log_end_offset,
+            last_stable_offset,
+            log_start_offset: self.log_start_offset(),
+            aborted_transactions,
+        })
+    }
+
+    /// The first offset of the oldest open transaction, or the log end offset
+    /// when no transaction is open.
+    pub(crate) fn last_stable_offset(&self) -> i64 {
+        self.ongoing_transactions
+            .values()
+            .copied()
+            .min()
+            .unwrap_or(self.log_end_offset)
+    }
+
+    pub(crate) fn log_start_offset(&self) -> i64 {
+        self.segments[0].base_offset
+    }
+
+    /// Returns the raw contents of every segment, oldest first.
+    pub(crate) fn read_all(&self) -> std::io::Result<Bytes> {
+        let mut buf = BytesMut::new();
+        for segment in &self.segments {
+            buf.extend_from_slice(&segment.read_all()?);
+        }
+
+        Ok(buf.freeze())
+    }
+
+    fn active_segment(&mut self) -> &mut Segment {
+        self.segments
+            .last_mut()
+            .expect("a partition log has a segment")
+    }
+
+    /// Rebuilds the batch positions and producer state from the newest
+    /// segment and returns the aborted transactions found in it.
+    fn recover(&mut self) -> std::io::Result<Vec<AbortedTxn>> {
+        let segment = self.active_segment().read_all()?;
+        let mut bytes = segment.clone();
+        let mut aborted = Vec::new();
+
+        while bytes.has_remaining() {
+            let position = segment.len() - bytes.remaining();
+            // A partially written batch at the tail is not part of the log. It
+            // is cut off so the next append lands right after the last whole
+            // batch.
+            if !holds_whole_batch(&bytes)? {
+                let file = OpenOptions::new()
+                    .write(true)
+                    .open(&self.active_segment().path)?;
+                file.set_len(position as u64)?;
+                file.sync_data()?;
+                break;
+            }
+
+            let batch = Batch::<Bytes>::try_from(&mut bytes)?;
+            let size = segment.len() - bytes.remaining() - position;
+
+            aborted.extend(self.observe_batch(&batch, position, size));
+        }
+
+        Ok(aborted)
+    }
+
+    /// Updates the in-memory state for a batch of the newest segment that is
+    /// now part of the log, returning the aborted transaction it completes,
+    /// if any.
+    fn observe_batch(
+        &mut self,
+        batch: &Batch<Bytes>,
+        position: usize,
+        size: usize,
+    ) -> Option<AbortedTxn> {
+        self.active_segment().batches.push(BatchPosition {
+            last_offset: batch.last_offset(),
+            position,
+            size,
+        });
+        self.log_end_offset = batch.last_offset() + 1;
+
+        if !batch.is_transactional() {
+            return None;
+        }
+
+        if !batch.is_control() {
+            self.ongoing_transactions
+                .entry(batch.producer_id())
+                .or_insert(batch.base_offset());
+            return None;
+        }
+
+        let first_offset = self.ongoing_transactions.remove(&batch.producer_id())?;
+        let is_abort = batch
+            .control_records()
+            .first()
+            .map(|record| matches!(record.value(), ControlRecordValue::Abort(_)))
+            .unwrap_or(false);
+
+        is_abort.then(|| AbortedTxn {
+            producer_id: batch.producer_id(),
+            first_offset,
+            last_offset: batch.base_offset(),
+            last_stable_offset: self.last_stable_offset(),
+        })
+    }
+}
+
+/// Whether `bytes` starts with a batch whose length prefix fits in it. A
+/// negative length can't come from a torn write and is an error.
+fn holds_whole_batch(bytes: &Bytes) -> std::io::Result<bool> {
+    if bytes.remaining() < BATCH_HEADER_SIZE {
+        return Ok(false);
+    }
+
+    let batch_length = i32::from_be_bytes(bytes[8..12].try_into().expect("4 bytes"));
+    if batch_length < 0 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("negative batch length {}", batch_length),
+        ));
+    }
+
+    Ok(bytes.remaining() - BATCH_HEADER_SIZE >= batch_length as usize)
+}
+
+pub(crate) fn segment_file_name(base_offset: i64) -> String {
+    format!("{:020}.log", base_offset)
+}
+
+fn txn_index_file_name(base_offset: i64) -> String {
+    format!("{:020}.txnindex", base_offset)
+}
+
+/// Base offsets of the segments in the partition directory `dir`, in order.
+pub(crate) fn segment_base_offsets(dir: &Path) -> std::io::Result<Vec<i64>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut base_offsets = Vec::new();
+    for entry in entries {
+        let name = entry?.file_name();
+        if let Some(base_offset) = name
+            .to_str()
+            .and_then(|name| name.strip_suffix(".log"))
+            .and_then(|base_offset| base_offset.parse::<i64>().ok())
+        {
+            base_offsets.push(base_offset);
+        }
+    }
+    base_offsets.sort_unstable();
+
+    Ok(base_offsets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{
+        cluster_metadata::{Record, TRANSACTIONAL_ATTRIBUTE},
+        control_record::ControlRecord,
+    };
+
+    fn batch(value: &'static [u8]) -> Batch<Bytes> {
+        Batch::new(
+            0,
+            -1,
+            -1,
+            -1,
+            0,
+            vec![Record::new(0, None, Bytes::from_static(value))],
+        )
+    }
+
+    fn transactional_batch(producer_id: i64) -> Batch<Bytes> {
+        Batch::new(
+            TRANSACTIONAL_ATTRIBUTE,
+            producer_id,
+            0,
+            0,
+            0,
+            vec![Record::new(0, None, Bytes::from_static(b"txn"))],
+        )
+    }
+
+    fn abort_marker(producer_id: i64) -> Batch<Bytes> {
+        Batch::control(
+            producer_id,
+            0,
+            0,
+            vec![ControlRecord::end_txn_marker(false, 0)],
+        )
+    }
+
+    #[test]
+    fn reads_every_segment_after_a_roll() {
+        let log_dir = std::env::temp_dir().join(format!("partition-log-{}", uuid::Uuid::new_v4()));
+
+        let mut log = PartitionLog::open(&log_dir, "rolled", 0).unwrap();
+        log.append(batch(b"first")).unwrap();
+        log.roll().unwrap();
+        log.append(batch(b"second")).unwrap();
+        log.append(batch(b"third")).unwrap();
+
+        for log in [log, PartitionLog::open(&log_dir, "rolled", 0).unwrap()] {
+            assert_eq!(log.segments.len(), 2);
+            assert_eq!(log.log_start_offset(), 0);
+
+            let mut first = log
+                .read(0, IsolationLevel::ReadUncommitted, usize::MAX, true)
+                .unwrap()
+                .records;
+            let batch = Batch::<Bytes>::try_from(&mut first).unwrap();
+            assert_eq!(batch.base_offset(), 0);
+            assert!(!first.has_remaining());
+
+            let mut rest = log
+                .read(2, IsolationLevel::ReadUncommitted, usize::MAX, true)
+                .unwrap()
+                .records;
+            let batch = Batch::<Bytes>::try_from(&mut rest).unwrap();
+            assert_eq!(batch.base_offset(), 2);
+            assert!(!rest.has_remaining());
+
+            assert!(log
+                .read(3, IsolationLevel::ReadUncommitted, usize::MAX, true)
+                .unwrap()
+                .records
+                .is_empty());
+        }
+
+        fs::remove_dir_all(&log_dir).unwrap();
+    }
+
+    #[test]
+    fn transactions_spanning_a_roll_survive_a_reopen() {
+        let log_dir = std::env::temp_dir().join(format!("partition-log-{}", uuid::Uuid::new_v4()));
+
+        let mut log = PartitionLog::open(&log_dir, "txn", 0).unwrap();
+        log.append(transactional_batch(1)).unwrap();
+        log.append(transactional_batch(2)).unwrap();
+        log.roll().unwrap();
+        log.append(abort_marker(1)).unwrap();
+        log.append(batch(b"plain")).unwrap();
+
+        let log = PartitionLog::open(&log_dir, "txn", 0).unwrap();
+        assert_eq!(log.log_end_offset, 4);
+        assert_eq!(log.last_stable_offset(), 1);
+
+        let data = log
+            .read(0, IsolationLevel::ReadCommitted, usize::MAX, true)
+            .unwrap();
+        assert_eq!(
+            data.aborted_transactions,
+            vec![AbortedTxn {
+                producer_id: 1,
+                first_offset: 0,
+                last_offset: 2,
+                last_stable_offset: 1,
+            }]
+        );
+
+        fs::remove_dir_all(&log_dir).unwrap();
+    }
+
+    #[test]
+    fn recover_truncates_a_torn_last_batch() {
+        let log_dir = std::env::temp_dir().join(format!("partition-log-{}", uuid::Uuid::new_v4()));
+        let segment_path = log_dir.join("torn-0").join(segment_file_name(0));
+
+        let mut log = PartitionLog::open(&log_dir, "torn", 0).unwrap();
+        log.append(batch(b"first")).unwrap();
+        let whole = fs::metadata(&segment_path).unwrap().len();
+        log.append(batch(b"second")).unwrap();
+        OpenOptions::new()
+            .write(true)
+            .open(&segment_path)
+            .unwrap()
+            .set_len(whole + 10)
+            .unwrap();
+
+        let mut log = PartitionLog::open(&log_dir, "torn", 0).unwrap();
+        assert_eq!(fs::metadata(&segment_path).unwrap().len(), whole);
+        assert_eq!(log.append(batch(b"third")).unwrap(), 1);
+
+        let log = PartitionLog::open(&log_dir, "torn", 0).unwrap();
+        assert_eq!(log.log_end_offset, 2);
+        assert_eq!(log.segments[0].batches.len(), 2);
+
+        fs::remove_dir_all(&log_dir).unwrap();
+    }
+
+    #[test]
+    fn open_rejects_a_negative_batch_length() {
+        let log_dir = std::env::temp_dir().join(format!("partition-log-{}", uuid::Uuid::new_v4()));
+        let partition_dir = log_dir.join("negative-0");
+        fs::create_dir_all(&partition_dir).unwrap();
+        let mut header = 0i64.to_be_bytes().to_vec();
+        header.extend_from_slice(&(-1i32).to_be_bytes());
+        fs::write(partition_dir.join(segment_file_name(0)), header).unwrap();
+
+        let e = PartitionLog::open(&log_dir, "negative", 0).unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::InvalidData);
+
+        fs::remove_dir_all(&log_dir).unwrap();
+    }
+}
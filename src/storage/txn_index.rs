@@ -0,0 +1,90 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use bytes::{BufMut, BytesMut};
+
+const TXN_INDEX_ENTRY_VERSION: i16 = 0;
+
+// version (2) + producer id (8) + first offset (8) + last offset (8)
+// + last stable offset (8)
+const TXN_INDEX_ENTRY_SIZE: usize = 34;
+
+/// A transaction that was aborted in this segment, spanning
+/// `first_offset..=last_offset` where `last_offset` is the ABORT marker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct AbortedTxn {
+    pub(crate) producer_id: i64,
+    pub(crate) first_offset: i64,
+    pub(crate) last_offset: i64,
+    pub(crate) last_stable_offset: i64,
+}
+
+/// The `.txnindex` file kept next to a segment, in the same fixed-size entry
+/// format Kafka uses.
+#[derive(Debug)]
+pub(crate) struct TxnIndex {
+    path: PathBuf,
+    entries: Vec<AbortedTxn>,
+}
+
+impl TxnIndex {
+    pub(crate) fn empty(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            entries: Vec::new(),
+        }
+    }
+
+    /// Creates the index from scratch, replacing any existing file.
+    pub(crate) fn create(path: &Path, entries: Vec<AbortedTxn>) -> std::io::Result<Self> {
+        let mut buf = BytesMut::with_capacity(entries.len() * TXN_INDEX_ENTRY_SIZE);
+        for entry in &entries {
+            encode_entry(&mut buf, entry);
+        }
+        File::create(path)?.write_all(&buf)?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            entries,
+        })
+    }
+
+    pub(crate) fn append(&mut self, entry: AbortedTxn) -> std::io::Result<()> {
+        let mut buf = BytesMut::with_capacity(TXN_INDEX_ENTRY_SIZE);
+        encode_entry(&mut buf, &entry);
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(&buf)?;
+        file.sync_data()?;
+
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// Aborted transactions that overlap `fetch_offset..upper_bound_offset`.
+    pub(crate) fn collect_aborted(
+        &self,
+        fetch_offset: i64,
+        upper_bound_offset: i64,
+    ) -> Vec<AbortedTxn> {
+        self.entries
+            .iter()
+            .filter(|txn| txn.last_offset >= fetch_offset && txn.first_offset < upper_bound_offset)
+            .copied()
+            .collect()
+    }
+}
+
+fn encode_entry(buf: &mut BytesMut, entry: &AbortedTxn) {
+    buf.put_i16(TXN_INDEX_ENTRY_VERSION);
+    buf.put_i64(entry.producer_id);
+    buf.put_i64(entry.first_offset);
+    buf.put_i64(entry.last_offset);
+    buf.put_i64(entry.last_stable_offset);
+}
@@ -0,0 +1,825 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    ops::Range,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bytes::Bytes;
+
+use crate::{
+    metadata::Controller,
+    protocol::{
+        cluster_metadata::{Batch, Record, TRANSACTIONAL_ATTRIBUTE},
+        control_record::ControlRecord,
+        response::ErrorCode,
+    },
+    storage::LogManager,
+};
+
+use super::state_log::{
+    decode_transaction_key, decode_transaction_value, encode_offset_commit_key,
+    encode_offset_commit_value, encode_transaction_key, encode_transaction_value,
+    TransactionMetadata, TransactionState,
+};
+
+pub(crate) const TRANSACTION_STATE_TOPIC: &str = "__transaction_state";
+pub(crate) const CONSUMER_OFFSETS_TOPIC: &str = "__consumer_offsets";
+
+/// Whether `name` is one of the topics the coordinators keep their state in,
+/// which clients don't produce to, create or delete directly.
+pub(crate) fn is_internal_topic(name: &str) -> bool {
+    matches!(name, CONSUMER_OFFSETS_TOPIC | TRANSACTION_STATE_TOPIC)
+}
+
+/// Both internal topics are kept in a single partition on this broker.
+const INTERNAL_TOPIC_PARTITION: i32 = 0;
+
+/// This broker is the only coordinator, so its epoch never changes.
+const COORDINATOR_EPOCH: i32 = 0;
+
+/// The longest `transaction.timeout.ms` a producer may ask for, matching the
+/// broker's `transaction.max.timeout.ms` default.
+const MAX_TRANSACTION_TIMEOUT_MS: i32 = 900_000;
+
+const NO_PRODUCER_ID: i64 = -1;
+const NO_PRODUCER_EPOCH: i16 = -1;
+const NO_SEQUENCE: i32 = -1;
+
+/// The state of a transactional id, which is `None` until InitProducerId has
+/// persisted its first state.
+type TransactionEntry = Arc<Mutex<Option<TransactionMetadata>>>;
+
+#[derive(Debug)]
+pub(crate) struct OffsetCommit {
+    pub(crate) topic: String,
+    pub(crate) partition: i32,
+    pub(crate) offset: i64,
+    pub(crate) leader_epoch: i32,
+    pub(crate) metadata: String,
+}
+
+/// Tracks every transactional id, persists its state to `__transaction_state`
+/// and writes the COMMIT/ABORT markers that complete a transaction.
+///
+/// Each transactional id has a lock of its own, held while its state is
+/// written, so the map of ids is only locked to look one up.
+#[derive(Debug)]
+pub(crate) struct TransactionCoordinator {
+    logs: Arc<LogManager>,
+    controller: Arc<Controller>,
+    transactions: Mutex<HashMap<String, TransactionEntry>>,
+    /// What is left of the block of producer ids the controller handed out.
+    producer_ids: Mutex<Range<i64>>,
+}
+
+impl TransactionCoordinator {
+    /// Rebuilds the coordinator by replaying `__transaction_state`. Transactions
+    /// that were left in a prepare state are completed before returning.
+    pub(crate) fn load(logs: Arc<LogManager>, controller: Arc<Controller>) -> crate::Result<Self> {
+        let mut bytes =
+            logs.with_log(TRANSACTION_STATE_TOPIC, INTERNAL_TOPIC_PARTITION, |log| {
+                log.read_all()
+            })?;
+
+        let mut transactions = HashMap::new();
+        loop {
+            let batch = match Batch::<Bytes>::try_from(&mut bytes) {
+                Ok(batch) => batch,
+                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e.into()),
+            };
+
+            for record in batch.records() {
+                let Some(key) = record.key() else {
+                    continue;
+                };
+                let transactional_id = decode_transaction_key(key.clone())?;
+                let value = record.record_value();
+
+                if value.is_empty() {
+                    transactions.remove(&transactional_id);
+                } else {
+                    transactions.insert(transactional_id, decode_transaction_value(value.clone())?);
+                }
+            }
+        }
+
+        let coordinator = Self {
+            logs,
+            controller,
+            transactions: Mutex::new(HashMap::new()),
+            producer_ids: Mutex::new(0..0),
+        };
+
+        for (transactional_id, mut metadata) in transactions {
+            if matches!(
+                metadata.state,
+                TransactionState::PrepareCommit | TransactionState::PrepareAbort
+            ) {
+                coordinator.complete_transaction(&transactional_id, &mut metadata)?;
+            }
+
+            coordinator
+                .lock()
+                .insert(transactional_id, Arc::new(Mutex::new(Some(metadata))));
+        }
+
+        Ok(coordinator)
+    }
+
+    /// Hands out the producer id and epoch a producer writes with. A
+    /// transactional id keeps its producer id and gets the next epoch, which
+    /// fences the producers that had the earlier ones, and aborts the
+    /// transaction they left open.
+    ///
+    /// `producer_id` and `producer_epoch` are the ones the producer had, or -1
+    /// for a producer starting out; a producer that has since been fenced
+    /// can't get a new epoch with them.
+    pub(crate) fn init_producer_id(
+        &self,
+        transactional_id: Option<&str>,
+        timeout_ms: i32,
+        producer_id: i64,
+        producer_epoch: i16,
+    ) -> Result<(i64, i16), ErrorCode> {
+        let Some(transactional_id) = transactional_id else {
+            return Ok((self.next_producer_id()?, 0));
+        };
+
+        if timeout_ms <= 0 || timeout_ms > MAX_TRANSACTION_TIMEOUT_MS {
+            return Err(ErrorCode::InvalidTransactionTimeout);
+        }
+
+        let entry = Arc::clone(self.lock().entry(transactional_id.to_string()).or_default());
+        let mut current = lock_entry(&entry);
+
+        let mut updated = match current.as_mut() {
+            Some(metadata) => {
+                if producer_id != NO_PRODUCER_ID
+                    && (producer_id, producer_epoch)
+                        != (metadata.producer_id, metadata.producer_epoch)
+                {
+                    return Err(ErrorCode::ProducerFenced);
+                }
+
+                self.end_transaction_of(transactional_id, metadata, false, true)
+                    .map_err(|e| {
+                        eprintln!(
+                            "failed to abort the transaction of {}: {}",
+                            transactional_id, e
+                        );
+                        ErrorCode::CoordinatorNotAvailable
+                    })?;
+                metadata.clone()
+            }
+            None => TransactionMetadata {
+                producer_id: self.next_producer_id()?,
+                producer_epoch: NO_PRODUCER_EPOCH,
+                timeout_ms,
+                state: TransactionState::Empty,
+                partitions: BTreeSet::new(),
+                last_update_timestamp: now_ms(),
+                start_timestamp: -1,
+            },
+        };
+
+        // An epoch that can't grow any further moves on to a new producer id.
+        if updated.producer_epoch >= i16::MAX - 1 {
+            updated.producer_id = self.next_producer_id()?;
+            updated.producer_epoch = 0;
+        } else {
+            updated.producer_epoch += 1;
+        }
+        updated.timeout_ms = timeout_ms;
+        updated.state = TransactionState::Empty;
+        updated.last_update_timestamp = now_ms();
+
+        if let Err(e) = self.persist(transactional_id, &updated) {
+            eprintln!(
+                "failed to persist transaction state for {}: {}",
+                transactional_id, e
+            );
+            return Err(ErrorCode::CoordinatorNotAvailable);
+        }
+
+        let handed_out = (updated.producer_id, updated.producer_epoch);
+        *current = Some(updated);
+        Ok(handed_out)
+    }
+
+    pub(crate) fn add_partitions(
+        &self,
+        transactional_id: &str,
+        producer_id: i64,
+        producer_epoch: i16,
+        partitions: &[(String, i32)],
+    ) -> ErrorCode {
+        let Some(entry) = self.entry(transactional_id) else {
+            return ErrorCode::InvalidProducerIdMapping;
+        };
+        let mut current = lock_entry(&entry);
+        let Some(metadata) = current.as_mut() else {
+            return ErrorCode::InvalidProducerIdMapping;
+        };
+
+        if let Err(error_code) = check_producer(metadata, producer_id, producer_epoch) {
+            return error_code;
+        }
+
+        match metadata.state {
+            TransactionState::PrepareCommit | TransactionState::PrepareAbort => {
+                return ErrorCode::ConcurrentTransactions
+            }
+            TransactionState::Ongoing
+                if partitions.iter().all(|p| metadata.partitions.contains(p)) =>
+            {
+                return ErrorCode::None
+            }
+            _ => {}
+        }
+
+        let mut updated = metadata.clone();
+        let now = now_ms();
+        if updated.state != TransactionState::Ongoing {
+            updated.start_timestamp = now;
+        }
+        updated.state = TransactionState::Ongoing;
+        updated.partitions.extend(partitions.iter().cloned());
+        updated.last_update_timestamp = now;
+
+        if let Err(e) = self.persist(transactional_id, &updated) {
+            eprintln!(
+                "failed to persist transaction state for {}: {}",
+                transactional_id, e
+            );
+            return ErrorCode::CoordinatorNotAvailable;
+        }
+
+        *metadata = updated;
+        ErrorCode::None
+    }
+
+    pub(crate) fn add_offsets(
+        &self,
+        transactional_id: &str,
+        producer_id: i64,
+        producer_epoch: i16,
+    ) -> ErrorCode {
+        self.add_partitions(
+            transactional_id,
+            producer_id,
+            producer_epoch,
+            &[(CONSUMER_OFFSETS_TOPIC.to_string(), INTERNAL_TOPIC_PARTITION)],
+        )
+    }
+
+    /// Writes the offsets as transactional records to `__consumer_offsets`;
+    /// they become visible to the group once the transaction commits.
+    pub(crate) fn commit_offsets(
+        &self,
+        transactional_id: &str,
+        group_id: &str,
+        producer_id: i64,
+        producer_epoch: i16,
+        offsets: &[OffsetCommit],
+    ) -> ErrorCode {
+        let Some(entry) = self.entry(transactional_id) else {
+            return ErrorCode::InvalidProducerIdMapping;
+        };
+        let current = lock_entry(&entry);
+        let Some(metadata) = current.as_ref() else {
+            return ErrorCode::InvalidProducerIdMapping;
+        };
+
+        if let Err(error_code) = check_producer(metadata, producer_id, producer_epoch) {
+            return error_code;
+        }
+
+        let offsets_partition = (CONSUMER_OFFSETS_TOPIC.to_string(), INTERNAL_TOPIC_PARTITION);
+        if metadata.state != TransactionState::Ongoing
+            || !metadata.partitions.contains(&offsets_partition)
+        {
+            return ErrorCode::InvalidTxnState;
+        }
+
+        if offsets.is_empty() {
+            return ErrorCode::None;
+        }
+
+        let now = now_ms();
+        let records = offsets
+            .iter()
+            .enumerate()
+            .map(|(i, offset)| {
+                Record::new(
+                    i as i32,
+                    Some(encode_offset_commit_key(
+                        group_id,
+                        &offset.topic,
+                        offset.partition,
+                    )),
+                    encode_offset_commit_value(
+                        offset.offset,
+                        offset.leader_epoch,
+                        &offset.metadata,
+                        now,
+                    ),
+                )
+            })
+            .collect();
+        let batch = Batch::new(
+            TRANSACTIONAL_ATTRIBUTE,
+            producer_id,
+            producer_epoch,
+            NO_SEQUENCE,
+            now,
+            records,
+        );
+
+        match self
+            .logs
+            .with_log(CONSUMER_OFFSETS_TOPIC, INTERNAL_TOPIC_PARTITION, |log| {
+                log.append(batch)
+            }) {
+            Ok(_) => ErrorCode::None,
+            Err(e) => {
+                eprintln!(
+                    "failed to write transactional offsets for {}: {}",
+                    group_id, e
+                );
+                ErrorCode::KafkaStorageError
+            }
+        }
+    }
+
+    pub(crate) fn end_transaction(
+        &self,
+        transactional_id: &str,
+        producer_id: i64,
+        producer_epoch: i16,
+        commit: bool,
+    ) -> ErrorCode {
+        let Some(entry) = self.entry(transactional_id) else {
+            return ErrorCode::InvalidProducerIdMapping;
+        };
+        let mut current = lock_entry(&entry);
+        let Some(metadata) = current.as_mut() else {
+            return ErrorCode::InvalidProducerIdMapping;
+        };
+
+        if let Err(error_code) = check_producer(metadata, producer_id, producer_epoch) {
+            return error_code;
+        }
+
+        match (metadata.state, commit) {
+            // A retry after the markers failed to be written picks up where
+            // they stopped.
+            (TransactionState::Ongoing, _)
+            | (TransactionState::PrepareCommit, true)
+            | (TransactionState::PrepareAbort, false) => {}
+            // Retries of an EndTxn that already went through succeed.
+            (TransactionState::CompleteCommit, true) | (TransactionState::CompleteAbort, false) => {
+                return ErrorCode::None
+            }
+            _ => return ErrorCode::InvalidTxnState,
+        }
+
+        match self.end_transaction_of(transactional_id, metadata, commit, false) {
+            Ok(()) => ErrorCode::None,
+            Err(e) => {
+                eprintln!("failed to complete transaction {}: {}", transactional_id, e);
+                ErrorCode::CoordinatorNotAvailable
+            }
+        }
+    }
+
+    /// Aborts ongoing transactions that outlived their timeout. The producer
+    /// epoch is bumped so the hung producer is fenced if it comes back.
+    pub(crate) fn abort_timed_out_transactions(&self) {
+        let now = now_ms();
+        let timed_out = |metadata: &TransactionMetadata| {
+            metadata.state == TransactionState::Ongoing
+                && metadata.start_timestamp + metadata.timeout_ms as i64 <= now
+        };
+
+        // Ids busy with a request are left for the next round rather than
+        // waited on with every id locked.
+        let expired = self
+            .lock()
+            .iter()
+            .filter(|(_, entry)| {
+                entry
+                    .try_lock()
+                    .is_ok_and(|metadata| metadata.as_ref().is_some_and(timed_out))
+            })
+            .map(|(transactional_id, entry)| (transactional_id.clone(), Arc::clone(entry)))
+            .collect::<Vec<(String, TransactionEntry)>>();
+
+        for (transactional_id, entry) in expired {
+            let mut current = lock_entry(&entry);
+            let Some(metadata) = current.as_mut().filter(|metadata| timed_out(metadata)) else {
+                continue;
+            };
+
+            println!(
+                "aborting transaction {} after timeout of {} ms",
+                transactional_id, metadata.timeout_ms
+            );
+
+            if let Err(e) = self.end_transaction_of(&transactional_id, metadata, false, true) {
+                eprintln!(
+                    "failed to abort timed out transaction {}: {}",
+                    transactional_id, e
+                );
+            }
+        }
+    }
+
+    /// Ends the transaction `metadata` is in, if any: an ongoing one is
+    /// prepared to commit or abort, with the producer epoch bumped when
+    /// `fence` is set, and a prepared one is completed.
+    fn end_transaction_of(
+        &self,
+        transactional_id: &str,
+        metadata: &mut TransactionMetadata,
+        commit: bool,
+        fence: bool,
+    ) -> crate::Result<()> {
+        if metadata.state == TransactionState::Ongoing {
+            let mut prepared = metadata.clone();
+            prepared.state = if commit {
+                TransactionState::PrepareCommit
+            } else {
+                TransactionState::PrepareAbort
+            };
+            if fence {
+                prepared.producer_epoch = prepared.producer_epoch.saturating_add(1);
+            }
+            prepared.last_update_timestamp = now_ms();
+            self.persist(transactional_id, &prepared)?;
+            *metadata = prepared;
+        }
+
+        if matches!(
+            metadata.state,
+            TransactionState::PrepareCommit | TransactionState::PrepareAbort
+        ) {
+            self.complete_transaction(transactional_id, metadata)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes a marker to every partition of a prepared transaction that is
+    /// still waiting for one, then records the completed state.
+    ///
+    /// A partition is dropped from `metadata` once its marker is written, so
+    /// after a failure `metadata` stays prepared with only the partitions
+    /// left to do, and calling this again resumes from there.
+    fn complete_transaction(
+        &self,
+        transactional_id: &str,
+        metadata: &mut TransactionMetadata,
+    ) -> crate::Result<()> {
+        let commit = metadata.state == TransactionState::PrepareCommit;
+
+        while let Some((topic, partition)) = metadata.partitions.first().cloned() {
+            let marker: Batch<Bytes> = Batch::control(
+                metadata.producer_id,
+                metadata.producer_epoch,
+                now_ms(),
+                vec![ControlRecord::end_txn_marker(commit, COORDINATOR_EPOCH)],
+            );
+            self.logs
+                .with_log(&topic, partition, |log| log.append(marker))
+                .map_err(|e| {
+                    format!(
+                        "failed to write transaction marker to {}-{}: {}",
+                        topic, partition, e
+                    )
+                })?;
+            metadata.partitions.remove(&(topic, partition));
+        }
+
+        let mut completed = metadata.clone();
+        completed.state = if commit {
+            TransactionState::CompleteCommit
+        } else {
+            TransactionState::CompleteAbort
+        };
+        completed.last_update_timestamp = now_ms();
+        self.persist(transactional_id, &completed)?;
+        *metadata = completed;
+
+        Ok(())
+    }
+
+    fn persist(
+        &self,
+        transactional_id: &str,
+        metadata: &TransactionMetadata,
+    ) -> std::io::Result<()> {
+        let batch = Batch::new(
+            0,
+            NO_PRODUCER_ID,
+            NO_PRODUCER_EPOCH,
+            NO_SEQUENCE,
+            metadata.last_update_timestamp,
+            vec![Record::new(
+                0,
+                Some(encode_transaction_key(transactional_id)),
+                encode_transaction_value(metadata),
+            )],
+        );
+
+        self.logs
+            .with_log(TRANSACTION_STATE_TOPIC, INTERNAL_TOPIC_PARTITION, |log| {
+                log.append(batch)
+            })
+            .map(|_| ())
+    }
+
+    /// The next unused producer id, taking a new block from the controller
+    /// once the current one runs out.
+    fn next_producer_id(&self) -> Result<i64, ErrorCode> {
+        let mut producer_ids = self
+            .producer_ids
+            .lock()
+            .expect("producer ids lock poisoned");
+
+        if producer_ids.is_empty() {
+            *producer_ids = self.controller.allocate_producer_ids().map_err(|e| {
+                eprintln!("failed to allocate producer ids: {}", e.message);
+                e.error_code
+            })?;
+        }
+
+        Ok(producer_ids.next().expect("producer id block is not empty"))
+    }
+
+    fn entry(&self, transactional_id: &str) -> Option<TransactionEntry> {
+        self.lock().get(transactional_id).cloned()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, TransactionEntry>> {
+        self.transactions
+            .lock()
+            .expect("transaction coordinator lock poisoned")
+    }
+}
+
+fn lock_entry(entry: &TransactionEntry) -> MutexGuard<'_, Option<TransactionMetadata>> {
+    entry.lock().expect("transaction lock poisoned")
+}
+
+fn check_producer(
+    metadata: &TransactionMetadata,
+    producer_id: i64,
+    producer_epoch: i16,
+) -> Result<(), ErrorCode> {
+    if producer_id != metadata.producer_id {
+        return Err(ErrorCode::InvalidProducerIdMapping);
+    }
+
+    if producer_epoch != metadata.producer_epoch {
+        return Err(ErrorCode::ProducerFenced);
+    }
+
+    Ok(())
+}
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf, thread, time::Duration};
+
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{
+        config::ServerProperties, protocol::control_record::ControlRecordValue, storage::LogDir,
+    };
+
+    /// A coordinator over a fresh log directory, along with what it needs to
+    /// be loaded again.
+    fn coordinator() -> (TransactionCoordinator, PathBuf) {
+        let path = std::env::temp_dir().join(format!("coordinator-{}", Uuid::new_v4()));
+        fs::create_dir_all(&path).unwrap();
+        let logs = Arc::new(LogManager::new(vec![LogDir {
+            path: path.clone(),
+            id: Uuid::new_v4(),
+        }]));
+
+        (reload(&logs), path)
+    }
+
+    fn reload(logs: &Arc<LogManager>) -> TransactionCoordinator {
+        let controller = Controller::load(
+            Arc::clone(logs),
+            ServerProperties::default(),
+            "cluster".to_string(),
+        )
+        .unwrap();
+
+        TransactionCoordinator::load(Arc::clone(logs), Arc::new(controller)).unwrap()
+    }
+
+    /// Starts a transaction on `foo-0` and `bar-0`, returning its producer.
+    fn begin(coordinator: &TransactionCoordinator, timeout_ms: i32) -> (i64, i16) {
+        let (producer_id, producer_epoch) = coordinator
+            .init_producer_id(Some("txn"), timeout_ms, -1, -1)
+            .unwrap();
+        let partitions = [("foo".to_string(), 0), ("bar".to_string(), 0)];
+        assert_eq!(
+            coordinator.add_partitions("txn", producer_id, producer_epoch, &partitions),
+            ErrorCode::None
+        );
+
+        (producer_id, producer_epoch)
+    }
+
+    /// The end transaction markers in `topic`-0, true for a commit.
+    fn markers(coordinator: &TransactionCoordinator, topic: &str) -> Vec<bool> {
+        let mut bytes = coordinator
+            .logs
+            .with_log(topic, 0, |log| log.read_all())
+            .unwrap();
+
+        let mut markers = Vec::new();
+        while !bytes.is_empty() {
+            let batch = Batch::<Bytes>::try_from(&mut bytes).unwrap();
+            for record in batch.control_records() {
+                match record.value() {
+                    ControlRecordValue::Commit(_) => markers.push(true),
+                    ControlRecordValue::Abort(_) => markers.push(false),
+                    _ => {}
+                }
+            }
+        }
+
+        markers
+    }
+
+    fn state(coordinator: &TransactionCoordinator) -> TransactionMetadata {
+        lock_entry(&coordinator.entry("txn").unwrap())
+            .clone()
+            .unwrap()
+    }
+
+    #[test]
+    fn end_txn_writes_a_marker_to_every_partition_once() {
+        for commit in [true, false] {
+            let (coordinator, path) = coordinator();
+            let (producer_id, producer_epoch) = begin(&coordinator, 60_000);
+
+            for _ in 0..2 {
+                assert_eq!(
+                    coordinator.end_transaction("txn", producer_id, producer_epoch, commit),
+                    ErrorCode::None
+                );
+            }
+
+            assert_eq!(markers(&coordinator, "foo"), [commit]);
+            assert_eq!(markers(&coordinator, "bar"), [commit]);
+            assert!(state(&coordinator).partitions.is_empty());
+
+            fs::remove_dir_all(&path).unwrap();
+        }
+    }
+
+    #[test]
+    fn reload_restores_transactions_and_completes_prepared_ones() {
+        let (coordinator, path) = coordinator();
+        let (producer_id, producer_epoch) = begin(&coordinator, 60_000);
+
+        let coordinator = reload(&coordinator.logs);
+        let restored = state(&coordinator);
+        assert_eq!(restored.state, TransactionState::Ongoing);
+        assert_eq!(restored.timeout_ms, 60_000);
+        assert_eq!(
+            (restored.producer_id, restored.producer_epoch),
+            (producer_id, producer_epoch)
+        );
+
+        // Stop as if the broker went down right after persisting the prepare.
+        let mut prepared = restored.clone();
+        prepared.state = TransactionState::PrepareCommit;
+        coordinator.persist("txn", &prepared).unwrap();
+
+        let coordinator = reload(&coordinator.logs);
+        assert_eq!(state(&coordinator).state, TransactionState::CompleteCommit);
+        assert_eq!(markers(&coordinator, "foo"), [true]);
+        assert_eq!(markers(&coordinator, "bar"), [true]);
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn completion_resumes_with_the_partitions_left() {
+        let (coordinator, path) = coordinator();
+        let (producer_id, producer_epoch) = begin(&coordinator, 60_000);
+
+        // As if the marker to `bar-0` was written before the one to `foo-0`
+        // failed.
+        {
+            let entry = coordinator.entry("txn").unwrap();
+            let mut current = lock_entry(&entry);
+            let metadata = current.as_mut().unwrap();
+            metadata.state = TransactionState::PrepareAbort;
+            metadata.partitions.remove(&("bar".to_string(), 0));
+        }
+
+        assert_eq!(
+            coordinator.end_transaction("txn", producer_id, producer_epoch, true),
+            ErrorCode::InvalidTxnState
+        );
+        assert_eq!(
+            coordinator.end_transaction("txn", producer_id, producer_epoch, false),
+            ErrorCode::None
+        );
+        assert_eq!(markers(&coordinator, "foo"), [false]);
+        assert!(markers(&coordinator, "bar").is_empty());
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn older_epochs_are_fenced() {
+        let (coordinator, path) = coordinator();
+        let (producer_id, producer_epoch) = begin(&coordinator, 60_000);
+
+        // A new producer for the same id aborts the open transaction.
+        let (new_producer_id, new_epoch) = coordinator
+            .init_producer_id(Some("txn"), 60_000, -1, -1)
+            .unwrap();
+        assert_eq!(new_producer_id, producer_id);
+        assert!(new_epoch > producer_epoch);
+        assert_eq!(markers(&coordinator, "foo"), [false]);
+
+        assert_eq!(
+            coordinator.end_transaction("txn", producer_id, producer_epoch, true),
+            ErrorCode::ProducerFenced
+        );
+        assert_eq!(
+            coordinator.add_partitions("txn", producer_id, producer_epoch, &[]),
+            ErrorCode::ProducerFenced
+        );
+        assert_eq!(
+            coordinator.init_producer_id(Some("txn"), 60_000, producer_id, producer_epoch),
+            Err(ErrorCode::ProducerFenced)
+        );
+        assert_eq!(
+            coordinator.add_partitions("txn", producer_id + 1, new_epoch, &[]),
+            ErrorCode::InvalidProducerIdMapping
+        );
+
+        // Producer ids are never handed out twice, even after a restart.
+        let coordinator = reload(&coordinator.logs);
+        let (other_producer_id, _) = coordinator.init_producer_id(None, 0, -1, -1).unwrap();
+        assert_ne!(other_producer_id, producer_id);
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn timed_out_transactions_are_aborted_and_their_producer_fenced() {
+        let (coordinator, path) = coordinator();
+        let (producer_id, producer_epoch) = begin(&coordinator, 1);
+
+        thread::sleep(Duration::from_millis(10));
+        coordinator.abort_timed_out_transactions();
+
+        let aborted = state(&coordinator);
+        assert_eq!(aborted.state, TransactionState::CompleteAbort);
+        assert_eq!(aborted.producer_epoch, producer_epoch + 1);
+        assert_eq!(markers(&coordinator, "foo"), [false]);
+        assert_eq!(markers(&coordinator, "bar"), [false]);
+        assert_eq!(
+            coordinator.end_transaction("txn", producer_id, producer_epoch, true),
+            ErrorCode::ProducerFenced
+        );
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn transaction_timeouts_are_bounded() {
+        let (coordinator, path) = coordinator();
+
+        for timeout_ms in [0, MAX_TRANSACTION_TIMEOUT_MS + 1] {
+            assert_eq!(
+                coordinator.init_producer_id(Some("txn"), timeout_ms, -1, -1),
+                Err(ErrorCode::InvalidTransactionTimeout)
+            );
+        }
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
@@ -0,0 +1,5 @@
+pub(crate) mod coordinator;
+
+pub(crate) mod state_log;
+
+pub(crate) use coordinator::TransactionCoordinator;
@@ -0,0 +1,225 @@
+//! Encoding of the records kept in the `__transaction_state` and
+//! `__consumer_offsets` internal topics. Both use Kafka's classic
+//! (non-flexible) layouts: a version-prefixed key and value with `i16`-length
+//! strings and `i32`-length arrays.
+
+use std::collections::BTreeSet;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use crate::Result;
+
+const TRANSACTION_LOG_KEY_VERSION: i16 = 0;
+const TRANSACTION_LOG_VALUE_VERSION: i16 = 0;
+const OFFSET_COMMIT_KEY_VERSION: i16 = 1;
+const OFFSET_COMMIT_VALUE_VERSION: i16 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TransactionState {
+    Empty = 0,
+    Ongoing = 1,
+    PrepareCommit = 2,
+    PrepareAbort = 3,
+    CompleteCommit = 4,
+    CompleteAbort = 5,
+}
+
+impl TryFrom<i8> for TransactionState {
+    type Error = crate::Error;
+
+    fn try_from(value: i8) -> std::result::Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Empty),
+            1 => Ok(Self::Ongoing),
+            2 => Ok(Self::PrepareCommit),
+            3 => Ok(Self::PrepareAbort),
+            4 => Ok(Self::CompleteCommit),
+            5 => Ok(Self::CompleteAbort),
+            _ => Err(format!("unknown transaction state {}", value).into()),
+        }
+    }
+}
+
+/// Durable state of a single transactional id, stored as the value of a
+/// `__transaction_state` record keyed by that id.
+#[derive(Debug, Clone)]
+pub(crate) struct TransactionMetadata {
+    pub(crate) producer_id: i64,
+    pub(crate) producer_epoch: i16,
+    pub(crate) timeout_ms: i32,
+    pub(crate) state: TransactionState,
+    pub(crate) partitions: BTreeSet<(String, i32)>,
+    pub(crate) last_update_timestamp: i64,
+    pub(crate) start_timestamp: i64,
+}
+
+pub(crate) fn encode_transaction_key(transactional_id: &str) -> Bytes {
+    let mut buf = BytesMut::new();
+
+    buf.put_i16(TRANSACTION_LOG_KEY_VERSION);
+    put_string(&mut buf, transactional_id);
+
+    buf.freeze()
+}
+
+pub(crate) fn decode_transaction_key(mut buf: Bytes) -> Result<String> {
+    let _version = buf.try_get_i16()?;
+    get_string(&mut buf)
+}
+
+pub(crate) fn encode_transaction_value(metadata: &TransactionMetadata) -> Bytes {
+    let mut buf = BytesMut::new();
+
+    buf.put_i16(TRANSACTION_LOG_VALUE_VERSION);
+    buf.put_i64(metadata.producer_id);
+    buf.put_i16(metadata.producer_epoch);
+    buf.put_i32(metadata.timeout_ms);
+    buf.put_i8(metadata.state as i8);
+
+    let mut topics: Vec<(&str, Vec<i32>)> = Vec::new();
+    for (topic, partition) in &metadata.partitions {
+        match topics.last_mut() {
+            Some((last, partitions)) if last == topic => partitions.push(*partition),
+            _ => topics.push((topic, vec![*partition])),
+        }
+    }
+
+    buf.put_i32(topics.len() as i32);
+    for (topic, partitions) in topics {
+        put_string(&mut buf, topic);
+        buf.put_i32(partitions.len() as i32);
+        for partition in partitions {
+            buf.put_i32(partition);
+        }
+    }
+
+    buf.put_i64(metadata.last_update_timestamp);
+    buf.put_i64(metadata.start_timestamp);
+
+    buf.freeze()
+}
+
+pub(crate) fn decode_transaction_value(mut buf: Bytes) -> Result<TransactionMetadata> {
+    let _version = buf.try_get_i16()?;
+    let producer_id = buf.try_get_i64()?;
+    let producer_epoch = buf.try_get_i16()?;
+    let timeout_ms = buf.try_get_i32()?;
+    let state = TransactionState::try_from(buf.try_get_i8()?)?;
+
+    let mut partitions = BTreeSet::new();
+    let topic_count = buf.try_get_i32()?;
+    for _ in 0..topic_count.max(0) {
+        let topic = get_string(&mut buf)?;
+        let partition_count = buf.try_get_i32()?;
+        for _ in 0..partition_count.max(0) {
+            partitions.insert((topic.clone(), buf.try_get_i32()?));
+        }
+    }
+
+    let last_update_timestamp = buf.try_get_i64()?;
+    let start_timestamp = buf.try_get_i64()?;
+
+    Ok(TransactionMetadata {
+        producer_id,
+        producer_epoch,
+        timeout_ms,
+        state,
+        partitions,
+        last_update_timestamp,
+        start_timestamp,
+    })
+}
+
+pub(crate) fn encode_offset_commit_key(group_id: &str, topic: &str, partition: i32) -> Bytes {
+    let mut buf = BytesMut::new();
+
+    buf.put_i16(OFFSET_COMMIT_KEY_VERSION);
+    put_string(&mut buf, group_id);
+    put_string(&mut buf, topic);
+    buf.put_i32(partition);
+
+    buf.freeze()
+}
+
+pub(crate) fn encode_offset_commit_value(
+    offset: i64,
+    leader_epoch: i32,
+    metadata: &str,
+    commit_timestamp: i64,
+) -> Bytes {
+    let mut buf = BytesMut::new();
+
+    buf.put_i16(OFFSET_COMMIT_VALUE_VERSION);
+    buf.put_i64(offset);
+    buf.put_i32(leader_epoch);
+    put_string(&mut buf, metadata);
+    buf.put_i64(commit_timestamp);
+
+    buf.freeze()
+}
+
+fn put_string(buf: &mut BytesMut, value: &str) {
+    buf.put_i16(value.len() as i16);
+    buf.put_slice(value.as_bytes());
+}
+
+fn get_string(buf: &mut Bytes) -> Result<String> {
+    let len = buf.try_get_i16()?;
+    if len < 0 || buf.remaining() < len as usize {
+        return Err(format!("invalid string length {}", len).into());
+    }
+
+    Ok(String::from_utf8(buf.split_to(len as usize).to_vec())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transaction_key_and_value_round_trip() {
+        let metadata = TransactionMetadata {
+            producer_id: 1000,
+            producer_epoch: 3,
+            timeout_ms: 60_000,
+            state: TransactionState::Ongoing,
+            partitions: BTreeSet::from([
+                ("bar".to_string(), 1),
+                ("foo".to_string(), 0),
+                ("foo".to_string(), 2),
+            ]),
+            last_update_timestamp: 1_700_000_000_500,
+            start_timestamp: 1_700_000_000_000,
+        };
+
+        let key = decode_transaction_key(encode_transaction_key("txn")).unwrap();
+        assert_eq!(key, "txn");
+
+        let decoded = decode_transaction_value(encode_transaction_value(&metadata)).unwrap();
+        assert_eq!(decoded.producer_id, metadata.producer_id);
+        assert_eq!(decoded.producer_epoch, metadata.producer_epoch);
+        assert_eq!(decoded.timeout_ms, metadata.timeout_ms);
+        assert_eq!(decoded.state, metadata.state);
+        assert_eq!(decoded.partitions, metadata.partitions);
+        assert_eq!(
+            decoded.last_update_timestamp,
+            metadata.last_update_timestamp
+        );
+        assert_eq!(decoded.start_timestamp, metadata.start_timestamp);
+    }
+
+    #[test]
+    fn truncated_value_is_an_error() {
+        let value = encode_transaction_value(&TransactionMetadata {
+            producer_id: 1,
+            producer_epoch: 0,
+            timeout_ms: 1,
+            state: TransactionState::Empty,
+            partitions: BTreeSet::new(),
+            last_update_timestamp: 0,
+            start_timestamp: -1,
+        });
+
+        assert!(decode_transaction_value(value.slice(..value.len() - 1)).is_err());
+    }
+}